@@ -0,0 +1,138 @@
+//! Per-chunk cost of the provider stream parsers. Run with `cargo bench`, the allocation
+//! count per stream is printed before each group
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use mono_ai::core::StreamParser;
+use mono_ai::providers::anthropic::AnthropicStreamParser;
+use mono_ai::providers::ollama::OllamaChatStreamParser;
+use mono_ai::providers::openai::OpenAIStreamParser;
+use mono_ai::providers::openrouter::OpenRouterStreamParser;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const TOKENS: usize = 500;
+// Typical network chunk sizes: one token per read, a few tokens, and a large buffered read
+const CHUNK_SIZES: &[usize] = &[16, 256, 4096];
+
+fn openai_body() -> String {
+    let mut body = String::new();
+    for i in 0..TOKENS {
+        body.push_str(&format!(
+            "data: {{\"id\":\"chatcmpl-1\",\"object\":\"chat.completion.chunk\",\"created\":1,\"model\":\"gpt-4.1\",\"choices\":[{{\"index\":0,\"delta\":{{\"content\":\"tok{} \"}},\"finish_reason\":null}}]}}\n\n",
+            i
+        ));
+    }
+    body.push_str("data: {\"id\":\"chatcmpl-1\",\"object\":\"chat.completion.chunk\",\"created\":1,\"model\":\"gpt-4.1\",\"choices\":[],\"usage\":{\"prompt_tokens\":10,\"completion_tokens\":500,\"total_tokens\":510}}\n\n");
+    body.push_str("data: [DONE]\n\n");
+    body
+}
+
+fn openrouter_body() -> String {
+    let mut body = String::new();
+    for i in 0..TOKENS {
+        body.push_str(&format!(
+            "data: {{\"id\":\"gen-1\",\"object\":\"chat.completion.chunk\",\"created\":1,\"model\":\"openai/gpt-4.1\",\"choices\":[{{\"index\":0,\"delta\":{{\"role\":\"assistant\",\"content\":\"tok{} \"}},\"finish_reason\":null}}]}}\n\n",
+            i
+        ));
+        if i % 50 == 0 {
+            body.push_str(": OPENROUTER PROCESSING\n\n");
+        }
+    }
+    body.push_str("data: [DONE]\n\n");
+    body
+}
+
+fn anthropic_body() -> String {
+    let mut body = String::from(
+        "event: message_start\ndata: {\"type\":\"message_start\",\"message\":{\"id\":\"msg_1\",\"type\":\"message\",\"role\":\"assistant\",\"content\":[],\"model\":\"claude-sonnet-4-5\",\"stop_reason\":null,\"stop_sequence\":null,\"usage\":{\"input_tokens\":10,\"output_tokens\":1}}}\n\n",
+    );
+    body.push_str("event: content_block_start\ndata: {\"type\":\"content_block_start\",\"index\":0,\"content_block\":{\"type\":\"text\",\"text\":\"\"}}\n\n");
+    for i in 0..TOKENS {
+        body.push_str(&format!(
+            "event: content_block_delta\ndata: {{\"type\":\"content_block_delta\",\"index\":0,\"delta\":{{\"type\":\"text_delta\",\"text\":\"tok{} \"}}}}\n\n",
+            i
+        ));
+    }
+    body.push_str("event: content_block_stop\ndata: {\"type\":\"content_block_stop\",\"index\":0}\n\n");
+    body.push_str("event: message_delta\ndata: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"end_turn\"},\"usage\":{\"output_tokens\":500}}\n\n");
+    body.push_str("event: message_stop\ndata: {\"type\":\"message_stop\"}\n\n");
+    body
+}
+
+fn ollama_body() -> String {
+    let mut body = String::new();
+    for i in 0..TOKENS {
+        body.push_str(&format!(
+            "{{\"model\":\"llama3.2\",\"created_at\":\"2025-01-01T00:00:00Z\",\"message\":{{\"role\":\"assistant\",\"content\":\"tok{} \"}},\"done\":false}}\n",
+            i
+        ));
+    }
+    body.push_str("{\"model\":\"llama3.2\",\"created_at\":\"2025-01-01T00:00:00Z\",\"message\":{\"role\":\"assistant\",\"content\":\"\"},\"done\":true,\"total_duration\":1,\"eval_count\":500,\"prompt_eval_count\":10}\n");
+    body
+}
+
+fn run<P: StreamParser>(mut parser: P, body: &[u8], chunk_size: usize) -> usize {
+    let mut items = 0;
+    for chunk in body.chunks(chunk_size) {
+        items += black_box(parser.push(chunk)).len();
+    }
+    items + black_box(parser.finish()).len()
+}
+
+fn bench_parser<P: StreamParser>(c: &mut Criterion, name: &str, body: String, new_parser: impl Fn() -> P) {
+    let body = body.into_bytes();
+    let mut group = c.benchmark_group(name);
+    group.throughput(Throughput::Bytes(body.len() as u64));
+
+    for &chunk_size in CHUNK_SIZES {
+        let before = ALLOCATIONS.load(Ordering::Relaxed);
+        run(new_parser(), &body, chunk_size);
+        let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+        println!(
+            "{}/{}: {} allocations per stream, {:.1} per chunk",
+            name,
+            chunk_size,
+            allocations,
+            allocations as f64 / body.len().div_ceil(chunk_size) as f64
+        );
+
+        group.bench_with_input(BenchmarkId::from_parameter(chunk_size), &chunk_size, |b, &chunk_size| {
+            b.iter(|| run(new_parser(), &body, chunk_size))
+        });
+    }
+    group.finish();
+}
+
+fn stream_parsers(c: &mut Criterion) {
+    bench_parser(c, "openai", openai_body(), || OpenAIStreamParser::new("gpt-4.1".to_string()));
+    bench_parser(c, "openrouter", openrouter_body(), OpenRouterStreamParser::new);
+    bench_parser(c, "anthropic", anthropic_body(), || AnthropicStreamParser::new("claude-sonnet-4-5".to_string()));
+    bench_parser(c, "ollama", ollama_body(), || OllamaChatStreamParser::new(None, false));
+}
+
+criterion_group!(benches, stream_parsers);
+criterion_main!(benches);
@@ -160,9 +160,9 @@ async fn select_provider() -> Result<MonoAI, Box<dyn std::error::Error>> {
             select_cloud_vision_model("Anthropic", "ANTHROPIC_API_KEY", MonoAI::anthropic, anthropic_filter, None).await
         }
         3 => {
-            // OpenAI vision models: GPT-4 with vision or GPT-4o
+            // Vision support of OpenAI models comes from the library's model table
             let vision_filter = |m: &mono_ai::core::MonoModel| {
-                m.id.contains("gpt-4") && (m.id.contains("vision") || m.id.contains("gpt-4o"))
+                m.capabilities.as_ref().is_some_and(|capabilities| capabilities.supports_vision())
             };
             let fallback_filter = |m: &mono_ai::core::MonoModel| {
                 m.id.contains("gpt-4") || m.id.contains("o1")
@@ -170,13 +170,9 @@ async fn select_provider() -> Result<MonoAI, Box<dyn std::error::Error>> {
             select_cloud_vision_model("OpenAI", "OPENAI_API_KEY", MonoAI::openai, vision_filter, Some(fallback_filter)).await
         }
         4 => {
-            // OpenRouter vision models: GPT-4 vision, Claude, Gemini
+            // OpenRouter lists the input modalities of every model
             let vision_filter = |m: &mono_ai::core::MonoModel| {
-                let id_lower = m.id.to_lowercase();
-                (id_lower.contains("gpt-4") && (id_lower.contains("vision") || id_lower.contains("gpt-4o"))) ||
-                id_lower.contains("claude") ||
-                id_lower.contains("gemini") ||
-                m.id == "custom"
+                m.capabilities.as_ref().is_some_and(|capabilities| capabilities.supports_vision())
             };
             select_cloud_vision_model("OpenRouter", "OPENROUTER_API_KEY", MonoAI::openrouter, vision_filter, None).await
         }
@@ -214,7 +210,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             role: "user".to_string(),
             content: "What do you see in this image?".to_string(),
             images: Some(vec![encoded_image]),
-            tool_calls: None,
+            ..Default::default()
         }
     ];
 
@@ -263,8 +259,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     messages.push(Message {
         role: "assistant".to_string(),
         content: full_response,
-        images: None,
         tool_calls: tool_calls.clone(),
+        ..Default::default()
     });
 
     // Handle tool calls if any
@@ -290,12 +286,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
         
         // Add the final assistant response to conversation
-        messages.push(Message {
-            role: "assistant".to_string(),
-            content: final_response,
-            images: None,
-            tool_calls: None,
-        });
+        messages.push(Message::assistant(final_response));
     }
 
     println!();
@@ -316,12 +307,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             continue;
         }
 
-        messages.push(Message {
-            role: "user".to_string(),
-            content: input.to_string(),
-            images: None,
-            tool_calls: None,
-        });
+        messages.push(Message::user(input));
 
         print!("{}: ", client.model());
         io::stdout().flush()?;
@@ -352,8 +338,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         messages.push(Message {
             role: "assistant".to_string(),
             content: full_response,
-            images: None,
             tool_calls: tool_calls.clone(),
+            ..Default::default()
         });
 
         // Handle tool calls if any
@@ -379,12 +365,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             
             // Add the final assistant response to conversation
-            messages.push(Message {
-                role: "assistant".to_string(),
-                content: final_response,
-                images: None,
-                tool_calls: None,
-            });
+            messages.push(Message::assistant(final_response));
         }
 
         println!();
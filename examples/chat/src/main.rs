@@ -68,12 +68,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             continue;
         }
 
-        messages.push(Message {
-            role: "user".to_string(),
-            content: input.to_string(),
-            images: None,
-            tool_calls: None,
-        });
+        messages.push(Message::user(input));
 
         print!("{}: ", client.model());
         io::stdout().flush()?;
@@ -129,8 +124,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         messages.push(Message {
             role: "assistant".to_string(),
             content: full_response,
-            images: None,
             tool_calls: tool_calls.clone(), // Include tool calls in the conversation history
+            ..Default::default()
         });
 
         // Handle tool calls
@@ -144,19 +139,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             
             // Show tool results
             for (tool_call, response) in tc.iter().zip(tool_responses.iter()) {
-                // Extract clean result from encoded format for display
-                let clean_result = if response.content.starts_with("TOOL_RESULT:") {
-                    // Parse "TOOL_RESULT:tool_id:actual_result" and extract actual_result
-                    let parts: Vec<&str> = response.content.splitn(3, ':').collect();
-                    if parts.len() == 3 {
-                        parts[2]
-                    } else {
-                        &response.content
-                    }
-                } else {
-                    &response.content
-                };
-                println!("{}", format!("{} called, result: {}", tool_call.function.name, clean_result).green());
+                println!("{}", format!("{} called, result: {}", tool_call.function.name, response.content).green());
             }
             
             messages.extend(tool_responses);
@@ -203,12 +186,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             
             // Add the final assistant response to conversation
-            messages.push(Message {
-                role: "assistant".to_string(),
-                content: final_response,
-                images: None,
-                tool_calls: None,
-            });
+            messages.push(Message::assistant(final_response));
         }
 
         println!();
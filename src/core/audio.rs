@@ -0,0 +1,100 @@
+use base64::{Engine as _, engine::general_purpose};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::future::Future;
+use std::pin::Pin;
+
+/// Recording sent as part of a chat message. OpenAI audio models (gpt-4o-audio-preview) take
+/// wav and mp3, Gemini models through OpenRouter also take aac, ogg, flac and aiff
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Audio {
+    pub data: String, // Base64 encoded
+    pub format: String, // File extension, e.g. "wav"
+}
+
+impl Audio {
+    pub fn from_bytes(bytes: &[u8], format: &str) -> Self {
+        Self {
+            data: general_purpose::STANDARD.encode(bytes),
+            format: format.to_lowercase(),
+        }
+    }
+
+    /// The format comes from the file extension
+    pub fn from_file(path: &str) -> Result<Self, Box<dyn Error>> {
+        let format = std::path::Path::new(path)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_string())
+            .ok_or_else(|| format!("Cannot tell the audio format of {} without a file extension", path))?;
+        Ok(Self::from_bytes(&std::fs::read(path)?, &format))
+    }
+}
+
+/// Audio to transcribe. The file name's extension tells the provider the format
+/// (mp3, mp4, mpeg, mpga, m4a, wav, webm, ogg, flac)
+#[derive(Debug, Clone)]
+pub struct AudioInput {
+    pub data: Vec<u8>,
+    pub filename: String,
+}
+
+impl AudioInput {
+    pub fn from_file(path: &str) -> Result<Self, std::io::Error> {
+        let data = std::fs::read(path)?;
+        let filename = std::path::Path::new(path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| "audio.mp3".to_string());
+        Ok(Self { data, filename })
+    }
+
+    pub fn from_bytes(data: Vec<u8>, filename: &str) -> Self {
+        Self {
+            data,
+            filename: filename.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TranscriptionOptions {
+    pub model: Option<String>, // Provider default when None, e.g. whisper-1 on OpenAI
+    pub language: Option<String>, // ISO-639-1 code of the spoken language, improves accuracy and latency
+    pub prompt: Option<String>, // Spelling of names and terms, or the preceding transcript
+    pub temperature: Option<f32>,
+}
+
+impl TranscriptionOptions {
+    pub fn with_model(mut self, model: &str) -> Self {
+        self.model = Some(model.to_string());
+        self
+    }
+
+    pub fn with_language(mut self, language: &str) -> Self {
+        self.language = Some(language.to_string());
+        self
+    }
+
+    pub fn with_prompt(mut self, prompt: &str) -> Self {
+        self.prompt = Some(prompt.to_string());
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Transcription {
+    pub text: String,
+    pub language: Option<String>, // Detected language, when the provider reports it
+    pub duration: Option<f64>, // Seconds of audio
+}
+
+pub type TranscriptionFuture<'a> = Pin<Box<dyn Future<Output = Result<Transcription, Box<dyn Error>>> + Send + 'a>>;
+
+/// Speech to text, implemented by providers with an audio capability
+pub trait Transcriber {
+    /// Text of the audio in its spoken language
+    fn transcribe<'a>(&'a self, audio: &'a AudioInput, options: &'a TranscriptionOptions) -> TranscriptionFuture<'a>;
+
+    /// Text of the audio translated to English
+    fn translate_audio<'a>(&'a self, audio: &'a AudioInput, options: &'a TranscriptionOptions) -> TranscriptionFuture<'a>;
+}
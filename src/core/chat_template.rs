@@ -0,0 +1,151 @@
+use super::Message;
+
+/// Prompt format of a model family, to render a conversation into the single prompt string of
+/// raw completion endpoints (Ollama `raw` generate, llama.cpp `/completion`)
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChatTemplate {
+    /// `<|im_start|>role ... <|im_end|>`: Qwen, Hermes, Yi and most fine-tunes
+    ChatMl,
+    /// `<|start_header_id|>role<|end_header_id|> ... <|eot_id|>`: Llama 3
+    Llama3,
+    /// `[INST] ... [/INST]`: Llama 2 and Mistral
+    Llama2,
+    /// `<start_of_turn>role ... <end_of_turn>`: Gemma, which has no system role
+    Gemma,
+    /// `<|role|> ... <|end|>`: Phi-3
+    Phi3,
+    Custom(CustomTemplate),
+}
+
+/// Text around each turn of a format not covered by the built-in templates
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CustomTemplate {
+    pub bos: String, // Once at the start
+    pub system_prefix: String,
+    pub system_suffix: String,
+    pub user_prefix: String,
+    pub user_suffix: String,
+    pub assistant_prefix: String, // Also the generation prompt
+    pub assistant_suffix: String,
+}
+
+impl ChatTemplate {
+    /// Recognize the family of a template by its special tokens. Works on Ollama templates
+    /// (`ModelInfo.template`, Go syntax) and Hugging Face `chat_template` (Jinja) alike
+    pub fn detect(template: &str) -> Option<ChatTemplate> {
+        if template.contains("<|start_header_id|>") {
+            Some(ChatTemplate::Llama3)
+        } else if template.contains("<|im_start|>") {
+            Some(ChatTemplate::ChatMl)
+        } else if template.contains("<start_of_turn>") {
+            Some(ChatTemplate::Gemma)
+        } else if template.contains("<|user|>") && template.contains("<|end|>") {
+            Some(ChatTemplate::Phi3)
+        } else if template.contains("[INST]") {
+            Some(ChatTemplate::Llama2)
+        } else {
+            None
+        }
+    }
+
+    /// The conversation as one prompt. With `add_generation_prompt` it ends with the opening
+    /// of an assistant turn for the model to complete
+    pub fn render(&self, messages: &[Message], add_generation_prompt: bool) -> String {
+        match self {
+            // Llama 2 has no generation prompt, the model answers after [/INST]
+            ChatTemplate::Llama2 => return render_llama2(messages),
+            ChatTemplate::Gemma => return render_gemma(messages, add_generation_prompt),
+            _ => {}
+        }
+
+        let mut prompt = match self {
+            ChatTemplate::Llama3 => "<|begin_of_text|>".to_string(),
+            ChatTemplate::Custom(custom) => custom.bos.clone(),
+            _ => String::new(),
+        };
+        for message in messages {
+            let role = message.role.as_str();
+            let content = message.content.trim();
+            match self {
+                ChatTemplate::ChatMl => prompt.push_str(&format!("<|im_start|>{}\n{}<|im_end|>\n", role, content)),
+                ChatTemplate::Llama3 => {
+                    let role = if role == "tool" { "ipython" } else { role };
+                    prompt.push_str(&format!("<|start_header_id|>{}<|end_header_id|>\n\n{}<|eot_id|>", role, content));
+                }
+                ChatTemplate::Phi3 => {
+                    let role = if role == "tool" { "user" } else { role };
+                    prompt.push_str(&format!("<|{}|>\n{}<|end|>\n", role, content));
+                }
+                ChatTemplate::Custom(custom) => {
+                    let (prefix, suffix) = match role {
+                        "system" => (&custom.system_prefix, &custom.system_suffix),
+                        "assistant" => (&custom.assistant_prefix, &custom.assistant_suffix),
+                        _ => (&custom.user_prefix, &custom.user_suffix),
+                    };
+                    prompt.push_str(&format!("{}{}{}", prefix, content, suffix));
+                }
+                ChatTemplate::Llama2 | ChatTemplate::Gemma => unreachable!("rendered above"),
+            }
+        }
+
+        if add_generation_prompt {
+            match self {
+                ChatTemplate::ChatMl => prompt.push_str("<|im_start|>assistant\n"),
+                ChatTemplate::Llama3 => prompt.push_str("<|start_header_id|>assistant<|end_header_id|>\n\n"),
+                ChatTemplate::Phi3 => prompt.push_str("<|assistant|>\n"),
+                ChatTemplate::Custom(custom) => prompt.push_str(&custom.assistant_prefix),
+                ChatTemplate::Llama2 | ChatTemplate::Gemma => {}
+            }
+        }
+        prompt
+    }
+
+    /// Tokens that end the model's turn, to pass as stop sequences to raw endpoints
+    pub fn stop_sequences(&self) -> Vec<String> {
+        let stops: &[&str] = match self {
+            ChatTemplate::ChatMl => &["<|im_end|>", "<|im_start|>"],
+            ChatTemplate::Llama3 => &["<|eot_id|>", "<|start_header_id|>"],
+            ChatTemplate::Llama2 => &["</s>", "[INST]"],
+            ChatTemplate::Gemma => &["<end_of_turn>", "<start_of_turn>"],
+            ChatTemplate::Phi3 => &["<|end|>", "<|user|>"],
+            ChatTemplate::Custom(custom) => {
+                let stops = [&custom.assistant_suffix, &custom.user_prefix];
+                return stops.into_iter().filter(|stop| !stop.trim().is_empty()).cloned().collect();
+            }
+        };
+        stops.iter().map(|stop| stop.to_string()).collect()
+    }
+}
+
+// Instructions and replies alternate, the system prompt goes into the first instruction
+fn render_llama2(messages: &[Message]) -> String {
+    let system: Vec<&str> = messages.iter().filter(|m| m.role == "system").map(|m| m.content.trim()).collect();
+    let mut system = (!system.is_empty()).then(|| format!("<<SYS>>\n{}\n<</SYS>>\n\n", system.join("\n\n")));
+
+    let mut prompt = String::new();
+    for message in messages.iter().filter(|m| m.role != "system") {
+        match message.role.as_str() {
+            "assistant" => prompt.push_str(&format!(" {} </s>", message.content.trim())),
+            _ => prompt.push_str(&format!("<s>[INST] {}{} [/INST]", system.take().unwrap_or_default(), message.content.trim())),
+        }
+    }
+    prompt
+}
+
+// Gemma has no system role, the system prompt goes into the first user turn
+fn render_gemma(messages: &[Message], add_generation_prompt: bool) -> String {
+    let system: Vec<&str> = messages.iter().filter(|m| m.role == "system").map(|m| m.content.trim()).collect();
+    let mut system = (!system.is_empty()).then(|| format!("{}\n\n", system.join("\n\n")));
+
+    let mut prompt = "<bos>".to_string();
+    for message in messages.iter().filter(|m| m.role != "system") {
+        match message.role.as_str() {
+            "assistant" => prompt.push_str(&format!("<start_of_turn>model\n{}<end_of_turn>\n", message.content.trim())),
+            _ => prompt.push_str(&format!("<start_of_turn>user\n{}{}<end_of_turn>\n", system.take().unwrap_or_default(), message.content.trim())),
+        }
+    }
+    if add_generation_prompt {
+        prompt.push_str("<start_of_turn>model\n");
+    }
+    prompt
+}
@@ -0,0 +1,114 @@
+use std::error::Error;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::{ChatResponse, Conversation, Message, TokenUsage, ToolCall};
+
+// Bumped when the blob format changes incompatibly
+const CHECKPOINT_VERSION: u32 = 1;
+
+/// State of an agent's tool loop in one blob: the conversation, the tool calls the model asked
+/// for that have no result yet, and the usage so far. Saved after every step, it lets an agent
+/// resume after a crash without losing history or running finished tools again
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    version: u32,
+    pub conversation: Conversation,
+    pub pending_tool_calls: Vec<ToolCall>,
+    pub usage: UsageCounters,
+}
+
+/// Totals over the requests of a session
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageCounters {
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cost_usd: f64, // Of the requests that reported a cost
+}
+
+impl UsageCounters {
+    pub fn record(&mut self, usage: &TokenUsage) {
+        self.requests += 1;
+        self.prompt_tokens += usage.prompt_tokens.unwrap_or(0) as u64;
+        self.completion_tokens += usage.completion_tokens.unwrap_or(0) as u64;
+        self.cost_usd += usage.cost_usd.unwrap_or(0.0);
+    }
+}
+
+impl Default for Checkpoint {
+    fn default() -> Self {
+        Self::new(Conversation::new())
+    }
+}
+
+impl Checkpoint {
+    pub fn new(conversation: Conversation) -> Self {
+        Self {
+            version: CHECKPOINT_VERSION,
+            conversation,
+            pending_tool_calls: Vec::new(),
+            usage: UsageCounters::default(),
+        }
+    }
+
+    /// Add the model's reply to the conversation, its tool calls become pending
+    pub fn record_reply(&mut self, response: &ChatResponse) {
+        self.conversation.push(Message {
+            role: "assistant".to_string(),
+            content: response.text.clone(),
+            tool_calls: response.tool_calls.clone(),
+            ..Default::default()
+        });
+        self.pending_tool_calls.extend(response.tool_calls.iter().flatten().cloned());
+        if let Some(usage) = &response.usage {
+            self.usage.record(usage);
+        }
+    }
+
+    /// Add a tool result to the conversation, settling the pending call it answers: the one
+    /// with its `tool_call_id`, or the first of its tool for providers without call IDs
+    pub fn record_tool_result(&mut self, result: Message) {
+        let answered = match &result.tool_call_id {
+            Some(id) => self.pending_tool_calls.iter().position(|call| call.id.as_ref() == Some(id)),
+            None => self.pending_tool_calls.iter().position(|call| Some(&call.function.name) == result.name.as_ref()),
+        };
+        if let Some(index) = answered {
+            self.pending_tool_calls.remove(index);
+        }
+        self.conversation.push(result);
+    }
+
+    /// Whether the model is waiting for tool results before it can continue
+    pub fn has_pending_tool_calls(&self) -> bool {
+        !self.pending_tool_calls.is_empty()
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        Ok(serde_json::to_vec(self)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let checkpoint: Self = serde_json::from_slice(bytes)?;
+        if checkpoint.version > CHECKPOINT_VERSION {
+            return Err(format!("Checkpoint version {} is newer than the supported {}", checkpoint.version, CHECKPOINT_VERSION).into());
+        }
+        Ok(checkpoint)
+    }
+
+    /// Write to `path` through a temporary file, so a crash while saving leaves the previous
+    /// checkpoint intact
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        let path = path.as_ref();
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        std::fs::write(&temporary, self.to_bytes()?)?;
+        std::fs::rename(&temporary, path)?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+}
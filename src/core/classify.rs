@@ -0,0 +1,92 @@
+use std::error::Error;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::ResponseFormat;
+
+/// Label picked by `MonoAI::classify`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Classification {
+    pub label: String,
+    pub confidence: f32, // 0.0 - 1.0, as judged by the model
+    pub reasoning: String,
+}
+
+/// What `MonoAI::score` rates a text on, and the range of the score
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rubric {
+    pub criteria: String,
+    pub min: f64,
+    pub max: f64,
+}
+
+/// Score given by `MonoAI::score`, within the rubric's range
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Score {
+    pub score: f64,
+    pub confidence: f32,
+    pub reasoning: String,
+}
+
+impl Rubric {
+    pub fn new(criteria: &str, min: f64, max: f64) -> Self {
+        Self {
+            criteria: criteria.to_string(),
+            min,
+            max,
+        }
+    }
+
+    pub(crate) fn prompt(&self, text: &str) -> String {
+        format!(
+            "Score the text below from {} to {} on these criteria:\n{}\n\nAlso give your confidence in the score from 0 to 1 and a short reasoning.\n\nText:\n{}",
+            self.min, self.max, self.criteria, text
+        )
+    }
+
+    pub(crate) fn format(&self) -> ResponseFormat {
+        ResponseFormat::json_schema(
+            "score",
+            json!({
+                "type": "object",
+                "properties": {
+                    "reasoning": {"type": "string"},
+                    "score": {"type": "number", "minimum": self.min, "maximum": self.max},
+                    "confidence": {"type": "number", "minimum": 0, "maximum": 1}
+                },
+                "required": ["reasoning", "score", "confidence"],
+                "additionalProperties": false
+            }),
+        )
+    }
+}
+
+impl Classification {
+    pub(crate) fn prompt(text: &str, labels: &[&str]) -> String {
+        format!(
+            "Classify the text below with exactly one of these labels: {}.\n\nAlso give your confidence in the label from 0 to 1 and a short reasoning.\n\nText:\n{}",
+            labels.join(", "),
+            text
+        )
+    }
+
+    pub(crate) fn format(labels: &[&str]) -> Result<ResponseFormat, Box<dyn Error>> {
+        if labels.is_empty() {
+            return Err("At least one label is needed to classify".into());
+        }
+        Ok(ResponseFormat::json_schema(
+            "classification",
+            json!({
+                "type": "object",
+                "properties": {
+                    "reasoning": {"type": "string"},
+                    "label": {"type": "string", "enum": labels},
+                    "confidence": {"type": "number", "minimum": 0, "maximum": 1}
+                },
+                "required": ["reasoning", "label", "confidence"],
+                "additionalProperties": false
+            }),
+        ))
+    }
+}
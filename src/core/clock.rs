@@ -0,0 +1,155 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::time::{Duration, Instant, SystemTime};
+
+#[cfg(feature = "test-util")]
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "test-util")]
+use std::task::{Context, Poll, Waker};
+
+/// Source of time for retry backoff and rate limiting, see `MonoAI::set_clock`
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+
+    /// Wall clock time, for the reset times in rate limit headers
+    fn system_time(&self) -> SystemTime;
+
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>>;
+
+    fn sleep_until(&self, deadline: Instant) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        self.sleep(deadline.saturating_duration_since(self.now()))
+    }
+}
+
+/// The real time, sleeping with tokio. Default of every client
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn system_time(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+/// Clock for tests that only moves when told, so retries and throttling run without real
+/// waits. By default a sleep jumps the time to its end right away; `MockClock::manual()`
+/// keeps sleepers waiting until `advance` passes their deadline
+#[cfg(feature = "test-util")]
+#[derive(Clone)]
+pub struct MockClock {
+    state: Arc<Mutex<MockClockState>>,
+    auto_advance: bool,
+}
+
+#[cfg(feature = "test-util")]
+struct MockClockState {
+    start: Instant,
+    start_system: SystemTime,
+    elapsed: Duration,
+    sleeps: Vec<Duration>, // Every requested sleep that isn't zero, in order
+    waiting: Vec<(Duration, Waker)>, // Manual mode sleepers by the elapsed time they wait for
+}
+
+#[cfg(feature = "test-util")]
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "test-util")]
+impl MockClock {
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(MockClockState {
+                start: Instant::now(),
+                start_system: SystemTime::now(),
+                elapsed: Duration::ZERO,
+                sleeps: Vec::new(),
+                waiting: Vec::new(),
+            })),
+            auto_advance: true,
+        }
+    }
+
+    /// Clock whose sleeps wait for `advance`, e.g. to check what happens mid-backoff
+    pub fn manual() -> Self {
+        Self { auto_advance: false, ..Self::new() }
+    }
+
+    /// Move the time forward, waking the sleeps that end by then
+    pub fn advance(&self, duration: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.elapsed += duration;
+        let elapsed = state.elapsed;
+        let (due, waiting) = std::mem::take(&mut state.waiting).into_iter().partition(|(until, _)| *until <= elapsed);
+        state.waiting = waiting;
+        drop(state);
+        due.into_iter().for_each(|(_, waker): (Duration, Waker)| waker.wake());
+    }
+
+    /// Time passed since the clock was created
+    pub fn elapsed(&self) -> Duration {
+        self.state.lock().unwrap().elapsed
+    }
+
+    /// Durations of the sleeps requested so far, leaving out zero ones, e.g. to assert a
+    /// backoff schedule
+    pub fn sleeps(&self) -> Vec<Duration> {
+        self.state.lock().unwrap().sleeps.clone()
+    }
+}
+
+#[cfg(feature = "test-util")]
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        let state = self.state.lock().unwrap();
+        state.start + state.elapsed
+    }
+
+    fn system_time(&self) -> SystemTime {
+        let state = self.state.lock().unwrap();
+        state.start_system + state.elapsed
+    }
+
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        let mut state = self.state.lock().unwrap();
+        if !duration.is_zero() {
+            state.sleeps.push(duration);
+        }
+        let until = state.elapsed + duration;
+        if self.auto_advance {
+            // Concurrent sleeps all end, the time ends up at the latest deadline
+            state.elapsed = state.elapsed.max(until);
+        }
+        Box::pin(MockSleep { state: self.state.clone(), until })
+    }
+}
+
+#[cfg(feature = "test-util")]
+struct MockSleep {
+    state: Arc<Mutex<MockClockState>>,
+    until: Duration,
+}
+
+#[cfg(feature = "test-util")]
+impl Future for MockSleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.elapsed >= self.until {
+            return Poll::Ready(());
+        }
+        state.waiting.push((self.until, cx.waker().clone()));
+        Poll::Pending
+    }
+}
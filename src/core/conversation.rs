@@ -0,0 +1,284 @@
+use std::error::Error;
+
+use serde::{Deserialize, Serialize};
+
+use super::{GenerationOptions, Message, Persona, TokenUsage, TruncationStrategy, estimate_tokens, truncate_with_counts};
+
+/// Chat history as a tree of messages. Editing a message or regenerating a reply starts a new
+/// branch and keeps the old one as an alternative, like the retry and edit buttons of chat UIs.
+/// The active branch is what `messages` returns and what gets sent to the model
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Conversation {
+    nodes: Vec<Node>,
+    roots: Vec<usize>,
+    selected_root: Option<usize>, // Index into `roots`
+    #[serde(default)]
+    persona: Option<Persona>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    model: Option<String>,
+    #[serde(default)]
+    options: GenerationOptions,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Node {
+    message: Message,
+    children: Vec<usize>,
+    selected: Option<usize>, // Index into `children`, the branch continuing the active path
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    usage: Option<MessageUsage>,
+}
+
+/// Tokens and cost of one message of a `Conversation`, see `Conversation::push_with_usage`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct MessageUsage {
+    pub tokens: u32, // Completion tokens of a reply, the share of the prompt for other messages
+    pub cost_usd: Option<f64>, // Set on replies, the cost of the request that produced them
+    pub estimated: bool, // Estimated from the text or split between messages rather than reported
+}
+
+impl Conversation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start from an existing history, as a single branch
+    pub fn from_messages(messages: impl IntoIterator<Item = Message>) -> Self {
+        let mut conversation = Self::new();
+        for message in messages {
+            conversation.push(message);
+        }
+        conversation
+    }
+
+    /// Append to the end of the active branch
+    pub fn push(&mut self, message: Message) {
+        let id = self.nodes.len();
+        self.nodes.push(Node { message, children: Vec::new(), selected: None, usage: None });
+        match self.path().last().copied() {
+            Some(leaf) => {
+                let leaf = &mut self.nodes[leaf];
+                leaf.children.push(id);
+                leaf.selected = Some(leaf.children.len() - 1);
+            }
+            None => {
+                self.roots.push(id);
+                self.selected_root = Some(self.roots.len() - 1);
+            }
+        }
+    }
+
+    /// Append a reply with the usage of the request that produced it. The reply gets the
+    /// completion tokens and the cost, the prompt tokens not accounted for by earlier messages
+    /// are split between the messages added since the last annotated one. The first of them
+    /// also carry system prompts and tool definitions the client sends
+    pub fn push_with_usage(&mut self, message: Message, usage: &TokenUsage) {
+        let path = self.path();
+        if let Some(prompt_tokens) = usage.prompt_tokens {
+            let counted: u32 = path.iter().filter_map(|&id| self.nodes[id].usage).map(|usage| usage.tokens).sum();
+            let unannotated: Vec<usize> = path.iter().copied().filter(|&id| self.nodes[id].usage.is_none()).collect();
+            let estimates: Vec<u32> = unannotated.iter().map(|&id| estimate_tokens(std::slice::from_ref(&self.nodes[id].message))).collect();
+            let estimated_total: u32 = estimates.iter().sum();
+
+            // In proportion to their estimated size, the last one takes the rounding remainder
+            let unaccounted = prompt_tokens.saturating_sub(counted);
+            let mut remaining = unaccounted;
+            for (i, (&id, &estimate)) in unannotated.iter().zip(&estimates).enumerate() {
+                let tokens = if i + 1 == unannotated.len() {
+                    remaining
+                } else {
+                    (unaccounted as u64 * estimate as u64 / estimated_total.max(1) as u64) as u32
+                };
+                remaining -= tokens;
+                self.nodes[id].usage = Some(MessageUsage { tokens, cost_usd: None, estimated: unannotated.len() > 1 });
+            }
+        }
+
+        let usage = MessageUsage {
+            tokens: usage.completion_tokens.unwrap_or_else(|| estimate_tokens(std::slice::from_ref(&message))),
+            cost_usd: usage.cost_usd,
+            estimated: usage.completion_tokens.is_none(),
+        };
+        self.push(message);
+        let id = self.nodes.len() - 1;
+        self.nodes[id].usage = Some(usage);
+    }
+
+    /// Usage recorded for the message at `index` of the active branch
+    pub fn usage(&self, index: usize) -> Option<MessageUsage> {
+        self.path().get(index).and_then(|&id| self.nodes[id].usage)
+    }
+
+    /// Record the usage of the message at `index` of the active branch, e.g. tokens counted
+    /// with the provider's tokenizer
+    pub fn set_usage(&mut self, index: usize, usage: MessageUsage) -> Result<(), Box<dyn Error>> {
+        let id = *self.path().get(index).ok_or_else(|| format!("No message at index {}", index))?;
+        self.nodes[id].usage = Some(usage);
+        Ok(())
+    }
+
+    /// Tokens of each message of the active branch, as recorded or else estimated at about 4
+    /// characters per token
+    pub fn token_counts(&self) -> Vec<u32> {
+        self.path()
+            .into_iter()
+            .map(|id| match self.nodes[id].usage {
+                Some(usage) => usage.tokens,
+                None => estimate_tokens(std::slice::from_ref(&self.nodes[id].message)),
+            })
+            .collect()
+    }
+
+    /// Tokens of the active branch, about what the next request's prompt takes
+    pub fn total_tokens(&self) -> u32 {
+        self.token_counts().iter().sum()
+    }
+
+    /// Cost of the replies on the active branch in USD
+    pub fn cost_usd(&self) -> f64 {
+        self.path().into_iter().filter_map(|id| self.nodes[id].usage?.cost_usd).sum()
+    }
+
+    /// Cost of every reply in USD, including regenerated ones kept as alternatives
+    pub fn total_cost_usd(&self) -> f64 {
+        self.nodes.iter().filter_map(|node| node.usage?.cost_usd).sum()
+    }
+
+    /// Active branch shortened to `max_tokens` like `truncate_history`, using the recorded
+    /// token counts where there are some
+    pub fn truncated_messages(&self, max_tokens: u32, strategy: TruncationStrategy) -> Vec<Message> {
+        truncate_with_counts(&self.messages(), &self.token_counts(), max_tokens, strategy)
+    }
+
+    /// Messages of the active branch, to send to the model
+    pub fn messages(&self) -> Vec<Message> {
+        self.path().into_iter().map(|id| self.nodes[id].message.clone()).collect()
+    }
+
+    /// Persona for requests sent with `MonoAI::send_conversation`, instead of the client's
+    pub fn set_persona(&mut self, persona: Option<Persona>) {
+        self.persona = persona;
+    }
+
+    pub fn persona(&self) -> Option<&Persona> {
+        self.persona.as_ref()
+    }
+
+    /// Model of the client's provider for requests sent with `MonoAI::send_conversation`,
+    /// instead of the persona's or the client's. Saved with the conversation
+    pub fn set_model(&mut self, model: Option<String>) {
+        self.model = model;
+    }
+
+    pub fn model(&self) -> Option<&str> {
+        self.model.as_deref()
+    }
+
+    /// Defaults for requests sent with `MonoAI::send_conversation`. Fields set on a request win,
+    /// fields left unset here fall back to the persona's
+    pub fn set_options(&mut self, options: GenerationOptions) {
+        self.options = options;
+    }
+
+    pub fn options(&self) -> &GenerationOptions {
+        &self.options
+    }
+
+    pub fn len(&self) -> usize {
+        self.path().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.selected_root.is_none()
+    }
+
+    /// Keep the first `index` messages of the active branch, the next `push` starts a new branch
+    /// there. The messages from `index` on stay available through `alternatives`
+    pub fn branch_at(&mut self, index: usize) -> Result<(), Box<dyn Error>> {
+        let path = self.path();
+        if index > path.len() {
+            return Err(format!("Cannot branch at message {}, the conversation has {}", index, path.len()).into());
+        }
+        match index {
+            0 => self.selected_root = None,
+            _ => self.nodes[path[index - 1]].selected = None,
+        }
+        Ok(())
+    }
+
+    /// Replace the message at `index` with an edited version on a new branch. The model's
+    /// reply to it has to be generated again
+    pub fn edit(&mut self, index: usize, content: &str) -> Result<(), Box<dyn Error>> {
+        let mut message = self.messages().get(index).cloned().ok_or_else(|| format!("No message at index {}", index))?;
+        message.content = content.to_string();
+        self.branch_at(index)?;
+        self.push(message);
+        Ok(())
+    }
+
+    /// Branch off before the last assistant reply, keeping it as an alternative, and return the
+    /// messages to send again. Push the new reply to complete the branch. When the last message
+    /// isn't an assistant reply the history is returned as is
+    pub fn regenerate_last(&mut self) -> Vec<Message> {
+        let len = self.len();
+        let last_is_reply = self.path().last().is_some_and(|&id| self.nodes[id].message.role == "assistant");
+        if last_is_reply {
+            // Within bounds, so this can't fail
+            let _ = self.branch_at(len - 1);
+        }
+        self.messages()
+    }
+
+    /// Versions of the message at `index` of the active branch, including the active one.
+    /// Empty when `index` is past the end
+    pub fn alternatives(&self, index: usize) -> Vec<&Message> {
+        self.siblings(index).iter().map(|&id| &self.nodes[id].message).collect()
+    }
+
+    /// Position of the active version among the `alternatives` of the message at `index`
+    pub fn selected_alternative(&self, index: usize) -> Option<usize> {
+        match index {
+            0 => self.selected_root,
+            _ => self.path().get(index - 1).and_then(|&parent| self.nodes[parent].selected),
+        }
+    }
+
+    /// Make another version of the message at `index` active, along with the branch that
+    /// followed it when it was last active
+    pub fn select_alternative(&mut self, index: usize, alternative: usize) -> Result<(), Box<dyn Error>> {
+        let count = self.siblings(index).len();
+        if alternative >= count {
+            return Err(format!("Message {} has {} alternatives, not {}", index, count, alternative + 1).into());
+        }
+        match index {
+            0 => self.selected_root = Some(alternative),
+            _ => {
+                let parent = self.path()[index - 1];
+                self.nodes[parent].selected = Some(alternative);
+            }
+        }
+        Ok(())
+    }
+
+    fn siblings(&self, index: usize) -> &[usize] {
+        match index {
+            0 => &self.roots,
+            _ => match self.path().get(index - 1) {
+                Some(&parent) => &self.nodes[parent].children,
+                None => &[],
+            },
+        }
+    }
+
+    // Node IDs of the active branch from the first message
+    fn path(&self) -> Vec<usize> {
+        let mut path = Vec::new();
+        let mut next = self.selected_root.map(|selected| self.roots[selected]);
+        while let Some(id) = next {
+            path.push(id);
+            let node = &self.nodes[id];
+            next = node.selected.map(|selected| node.children[selected]);
+        }
+        path
+    }
+}
@@ -0,0 +1,183 @@
+use std::fmt;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+
+use super::{quota_from_headers, Quota, RequestTags, Utf8Decoder};
+
+// Headers whose values are replaced before logging
+const SECRET_HEADERS: &[&str] = &["authorization", "x-api-key", "api-key", "proxy-authorization", "cookie"];
+
+/// What a client sent and received, reported while debug logging is on
+#[derive(Debug, Clone)]
+pub enum DebugEvent {
+    /// The exact request, with API keys redacted. Streaming bodies such as file uploads have no `body`
+    Request { provider: String, method: String, url: String, headers: Vec<(String, String)>, body: Option<String>, tags: Option<RequestTags> },
+    Response { provider: String, url: String, status: u16, tags: Option<RequestTags> },
+    /// A raw chunk of a streamed response as it arrived
+    StreamChunk { provider: String, data: String },
+    /// Model output searched for fallback tool calls
+    FallbackOutput { provider: String, content: String },
+    /// A line of a response that couldn't be parsed and was skipped
+    ParseError { provider: String, data: String, error: String },
+}
+
+impl fmt::Display for DebugEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DebugEvent::Request { provider, method, url, headers, body, tags } => {
+                writeln!(f, "[{}] {} {}", provider, method, url)?;
+                if let Some(tags) = tags {
+                    writeln!(f, "  tags: {}", tags)?;
+                }
+                for (name, value) in headers {
+                    writeln!(f, "  {}: {}", name, value)?;
+                }
+                write!(f, "  {}", body.as_deref().unwrap_or("<streamed body>"))
+            }
+            DebugEvent::Response { provider, url, status, tags } => {
+                write!(f, "[{}] {} <- {}", provider, status, url)?;
+                match tags {
+                    Some(tags) => write!(f, " ({})", tags.correlation_id),
+                    None => Ok(()),
+                }
+            }
+            DebugEvent::StreamChunk { provider, data } => write!(f, "[{}] chunk {:?}", provider, data),
+            DebugEvent::FallbackOutput { provider, content } => write!(f, "[{}] fallback output {:?}", provider, content),
+            DebugEvent::ParseError { provider, data, error } => write!(f, "[{}] skipped {:?}: {}", provider, data, error),
+        }
+    }
+}
+
+pub type DebugCallback = Arc<dyn Fn(&DebugEvent) + Send + Sync>;
+
+/// Writes each event to stderr, what `set_debug_mode(true)` installs
+pub fn stderr_debug_callback() -> DebugCallback {
+    Arc::new(|event: &DebugEvent| eprintln!("{}", event))
+}
+
+/// Debug logging of one provider client, off unless a callback is installed
+#[derive(Clone)]
+pub struct DebugLog {
+    provider: &'static str,
+    callback: Option<DebugCallback>,
+    tags: Option<RequestTags>,
+    quota: Arc<Mutex<Option<Quota>>>, // Rate limits from the latest response reporting them
+}
+
+impl DebugLog {
+    pub(crate) fn new(provider: &'static str) -> Self {
+        Self { provider, callback: None, tags: None, quota: Arc::new(Mutex::new(None)) }
+    }
+
+    /// A copy that reports `tags` with the requests it logs
+    pub(crate) fn with_tags(&self, tags: Option<RequestTags>) -> Self {
+        Self { tags, ..self.clone() }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.callback.is_some()
+    }
+
+    pub(crate) fn set_callback(&mut self, callback: Option<DebugCallback>) {
+        self.callback = callback;
+    }
+
+    /// Rate limits the latest response reported, empty before the first one
+    pub(crate) fn last_quota(&self) -> Quota {
+        self.quota.lock().unwrap().clone().unwrap_or_default()
+    }
+
+    pub(crate) fn log(&self, event: impl FnOnce(String) -> DebugEvent) {
+        if let Some(callback) = &self.callback {
+            callback(&event(self.provider.to_string()));
+        }
+    }
+
+    pub(crate) fn fallback_output(&self, content: &str) {
+        self.log(|provider| DebugEvent::FallbackOutput { provider, content: content.to_string() });
+    }
+
+    pub(crate) fn parse_error(&self, data: &str, error: impl fmt::Display) {
+        self.log(|provider| DebugEvent::ParseError { provider, data: data.to_string(), error: error.to_string() });
+    }
+
+    // Building a copy of the request is the only way to see the headers and serialized body
+    fn request(&self, builder: &reqwest::RequestBuilder) {
+        if !self.is_enabled() {
+            return;
+        }
+        let Some(request) = builder.try_clone().and_then(|builder| builder.build().ok()) else {
+            return;
+        };
+        let headers = request
+            .headers()
+            .iter()
+            .map(|(name, value)| {
+                let value = if SECRET_HEADERS.contains(&name.as_str()) {
+                    "[redacted]".to_string()
+                } else {
+                    String::from_utf8_lossy(value.as_bytes()).into_owned()
+                };
+                (name.to_string(), value)
+            })
+            .collect();
+        let body = request.body().and_then(|body| body.as_bytes()).map(|bytes| String::from_utf8_lossy(bytes).into_owned());
+
+        self.log(|provider| DebugEvent::Request {
+            provider,
+            method: request.method().to_string(),
+            url: request.url().to_string(),
+            headers,
+            body,
+            tags: self.tags.clone(),
+        });
+    }
+
+    /// Log every chunk of a response body as it passes through
+    pub(crate) fn inspect_stream<S>(&self, stream: S) -> Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>
+    where
+        S: Stream<Item = Result<Bytes, reqwest::Error>> + Send + 'static,
+    {
+        match self.callback.clone() {
+            None => Box::pin(stream),
+            Some(callback) => {
+                let provider = self.provider.to_string();
+                // Characters split across chunks are logged whole with the chunk completing them
+                let mut decoder = Utf8Decoder::new();
+                Box::pin(stream.inspect(move |chunk| {
+                    if let Ok(chunk) = chunk {
+                        let data = decoder.decode(chunk);
+                        if !data.is_empty() {
+                            callback(&DebugEvent::StreamChunk { provider: provider.clone(), data });
+                        }
+                    }
+                }))
+            }
+        }
+    }
+}
+
+/// `send()` that reports the request and the response status to a client's `DebugLog`
+pub(crate) trait SendLogged {
+    async fn send_logged(self, debug: &DebugLog) -> reqwest::Result<reqwest::Response>;
+}
+
+impl SendLogged for reqwest::RequestBuilder {
+    async fn send_logged(self, debug: &DebugLog) -> reqwest::Result<reqwest::Response> {
+        debug.request(&self);
+        let response = self.send().await?;
+        if let Some(quota) = quota_from_headers(response.headers()) {
+            *debug.quota.lock().unwrap() = Some(quota);
+        }
+        debug.log(|provider| DebugEvent::Response {
+            provider,
+            url: response.url().to_string(),
+            status: response.status().as_u16(),
+            tags: debug.tags.clone(),
+        });
+        Ok(response)
+    }
+}
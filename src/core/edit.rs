@@ -0,0 +1,250 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use super::{AIRequestError, Validator};
+
+/// How a model writes its changes to a source file, see `MonoAI::request_edit`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditFormat {
+    /// Hunks of a unified diff. Hunks are located by their context lines, the line numbers
+    /// in the headers only pick between equal matches
+    UnifiedDiff,
+    /// SEARCH/REPLACE blocks, each search text has to match the source exactly once
+    SearchReplace,
+}
+
+impl EditFormat {
+    /// System prompt telling the model to reply with edits in this format
+    pub fn instructions(&self) -> &'static str {
+        match self {
+            EditFormat::UnifiedDiff => {
+                "You edit source files. Reply only with a unified diff of your changes in a ```diff block, \
+                 with hunks starting at @@ -start,count +start,count @@. Context lines start with a space, \
+                 removed lines with -, added lines with +. Copy context and removed lines exactly from the \
+                 source, including indentation, with a few unchanged lines of context around each change."
+            }
+            EditFormat::SearchReplace => {
+                "You edit source files. Reply only with SEARCH/REPLACE blocks:\n\
+                 <<<<<<< SEARCH\n\
+                 lines copied exactly from the source\n\
+                 =======\n\
+                 the lines replacing them\n\
+                 >>>>>>> REPLACE\n\
+                 Each SEARCH section has to match exactly one place in the source, including indentation. \
+                 Use one block per change, in the order they appear in the file."
+            }
+        }
+    }
+}
+
+/// Apply the edits in a model's reply to `source`. Text around the edits is ignored. Fails
+/// with a message for the model when an edit doesn't apply
+pub fn apply_edit(source: &str, reply: &str, format: EditFormat) -> Result<String, AIRequestError> {
+    // Edits are applied to `\n` lines and the source's line endings restored
+    let crlf = source.contains("\r\n");
+    let text = source.replace("\r\n", "\n");
+    let reply = reply.replace("\r\n", "\n");
+    let edited = match format {
+        EditFormat::UnifiedDiff => apply_diff(&text, &reply)?,
+        EditFormat::SearchReplace => apply_search_replace(&text, &reply)?,
+    };
+    Ok(if crlf { edited.replace('\n', "\r\n") } else { edited })
+}
+
+fn apply_search_replace(source: &str, reply: &str) -> Result<String, AIRequestError> {
+    let blocks = parse_search_replace(reply)?;
+    if blocks.is_empty() {
+        return Err(AIRequestError::Other("The reply has no SEARCH/REPLACE blocks.".to_string()));
+    }
+
+    let mut text = source.to_string();
+    for (index, (search, replace)) in blocks.iter().enumerate() {
+        let number = index + 1;
+        if search.is_empty() {
+            if !text.is_empty() {
+                return Err(AIRequestError::Other(format!(
+                    "Block {} has an empty SEARCH section, copy the lines to replace from the source.",
+                    number
+                )));
+            }
+            text = replace.clone();
+            continue;
+        }
+        match text.matches(search.as_str()).count() {
+            1 => text = text.replacen(search.as_str(), replace, 1),
+            0 => {
+                return Err(AIRequestError::Other(format!(
+                    "The SEARCH section of block {} doesn't match the source, it has to repeat the lines exactly, including indentation:\n{}",
+                    number, search
+                )));
+            }
+            matches => {
+                return Err(AIRequestError::Other(format!(
+                    "The SEARCH section of block {} matches {} places in the source, include more lines to make it unique:\n{}",
+                    number, matches, search
+                )));
+            }
+        }
+    }
+    Ok(text)
+}
+
+// Search and replace text of each block, lines joined with `\n`
+fn parse_search_replace(reply: &str) -> Result<Vec<(String, String)>, AIRequestError> {
+    let mut blocks = Vec::new();
+    let mut lines = reply.lines();
+    while let Some(line) = lines.next() {
+        if !line.trim_start().starts_with("<<<<<<<") {
+            continue;
+        }
+        let unterminated = || AIRequestError::Other(format!("Block {} is not closed with ======= and >>>>>>> REPLACE.", blocks.len() + 1));
+        let mut search = Vec::new();
+        loop {
+            match lines.next() {
+                Some(line) if line.trim() == "=======" => break,
+                Some(line) => search.push(line),
+                None => return Err(unterminated()),
+            }
+        }
+        let mut replace = Vec::new();
+        loop {
+            match lines.next() {
+                Some(line) if line.trim_start().starts_with(">>>>>>>") => break,
+                Some(line) => replace.push(line),
+                None => return Err(unterminated()),
+            }
+        }
+        blocks.push((search.join("\n"), replace.join("\n")));
+    }
+    Ok(blocks)
+}
+
+static HUNK_HEADER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^@@ -(\d+)(?:,\d+)? \+\d+(?:,\d+)? @@").expect("hunk header pattern is valid"));
+
+// Lines a hunk expects in the source and the lines replacing them, with the line the header names
+struct Hunk<'a> {
+    start: Option<usize>, // 1-based
+    old: Vec<&'a str>,
+    new: Vec<&'a str>,
+}
+
+fn apply_diff(source: &str, reply: &str) -> Result<String, AIRequestError> {
+    let hunks = parse_hunks(reply);
+    if hunks.is_empty() {
+        return Err(AIRequestError::Other("The reply has no diff hunks starting with @@.".to_string()));
+    }
+
+    let trailing_newline = source.is_empty() || source.ends_with('\n');
+    let mut lines: Vec<&str> = source.lines().collect();
+    let mut searched_to = 0; // Hunks are looked for after the previous one first
+    let mut shift: isize = 0; // Lines added minus removed so far, for the header line numbers
+    for (index, hunk) in hunks.iter().enumerate() {
+        let expected = hunk.start.map(|start| (start as isize - 1 + shift).max(0) as usize);
+        let position = if hunk.old.is_empty() {
+            // Pure insertion, after the line the header names
+            expected.map_or(lines.len(), |line| (line + 1).min(lines.len())).max(searched_to)
+        } else {
+            let matches_from = |from: usize| -> Vec<usize> {
+                (from..=lines.len().saturating_sub(hunk.old.len()))
+                    .filter(|&at| {
+                        lines.len() >= at + hunk.old.len()
+                            && lines[at..at + hunk.old.len()].iter().zip(&hunk.old).all(|(line, old)| line.trim_end() == old.trim_end())
+                    })
+                    .collect()
+            };
+            // Hunks out of order are still found
+            let mut matches = matches_from(searched_to);
+            if matches.is_empty() {
+                matches = matches_from(0);
+            }
+            let nearest = matches.iter().min_by_key(|&&at| expected.map_or(0, |line| at.abs_diff(line)));
+            *nearest.ok_or_else(|| {
+                AIRequestError::Other(format!(
+                    "Hunk {} doesn't match the source, its context and removed lines have to repeat the source lines exactly:\n{}",
+                    index + 1,
+                    hunk.old.join("\n")
+                ))
+            })?
+        };
+        lines.splice(position..position + hunk.old.len(), hunk.new.iter().copied());
+        searched_to = position + hunk.new.len();
+        shift += hunk.new.len() as isize - hunk.old.len() as isize;
+    }
+
+    let mut edited = lines.join("\n");
+    if trailing_newline && !edited.is_empty() {
+        edited.push('\n');
+    }
+    Ok(edited)
+}
+
+fn parse_hunks(reply: &str) -> Vec<Hunk<'_>> {
+    let mut hunks: Vec<Hunk> = Vec::new();
+    let mut in_hunk = false;
+    // Blank context lines often lose their leading space, they count once more lines of the hunk follow
+    let mut blank_lines = 0;
+    let mut lines = reply.lines().peekable();
+    while let Some(line) = lines.next() {
+        if line.starts_with("@@") {
+            let start = HUNK_HEADER.captures(line).and_then(|captures| captures[1].parse().ok());
+            hunks.push(Hunk { start, old: Vec::new(), new: Vec::new() });
+            in_hunk = true;
+            blank_lines = 0;
+            continue;
+        }
+        // File headers, fences and prose end a hunk
+        let file_header = line.starts_with("--- ") && lines.peek().is_some_and(|next| next.starts_with("+++ "));
+        if !in_hunk || file_header || line.starts_with("```") || line.starts_with("diff ") {
+            in_hunk = false;
+            continue;
+        }
+        let Some(hunk) = hunks.last_mut() else { continue };
+        let (marker, content) = match line.chars().next() {
+            Some(marker @ (' ' | '-' | '+')) => (marker, &line[1..]),
+            None => {
+                blank_lines += 1;
+                continue;
+            }
+            Some('\\') => continue, // "\ No newline at end of file"
+            Some(_) => {
+                in_hunk = false;
+                continue;
+            }
+        };
+        for _ in 0..std::mem::take(&mut blank_lines) {
+            hunk.old.push("");
+            hunk.new.push("");
+        }
+        if marker != '+' {
+            hunk.old.push(content);
+        }
+        if marker != '-' {
+            hunk.new.push(content);
+        }
+    }
+    hunks
+}
+
+/// Reply has to hold edits in a format that apply to a source, for `send_chat_request_validated`
+#[derive(Debug, Clone)]
+pub struct EditValidator {
+    source: String,
+    format: EditFormat,
+}
+
+impl EditValidator {
+    pub fn new(source: &str, format: EditFormat) -> Self {
+        Self { source: source.to_string(), format }
+    }
+}
+
+impl Validator for EditValidator {
+    fn validate(&self, response: &str) -> Result<(), String> {
+        apply_edit(&self.source, response, self.format).map(|_| ()).map_err(|e| match e {
+            AIRequestError::Other(message) => message,
+            e => e.to_string(),
+        })
+    }
+}
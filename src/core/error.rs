@@ -1,10 +1,20 @@
+use std::error::Error;
 use std::fmt;
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde_json::Value;
 
 #[derive(Debug)]
 pub enum AIRequestError {
     Network(reqwest::Error),
     Json(serde_json::Error),
     IO(std::io::Error),
+    /// The request doesn't fit the model's context window, with the token counts when the
+    /// provider reports them. `MonoAI::set_context_truncation` retries with a shorter history
+    ContextLengthExceeded { limit: Option<u32>, requested: Option<u32> },
+    /// An error response from a provider's API, see `as_provider_error`
+    Provider(Box<ProviderError>),
     Other(String),
 }
 
@@ -14,6 +24,12 @@ impl fmt::Display for AIRequestError {
             AIRequestError::Network(e) => write!(f, "Network error: {}", e),
             AIRequestError::Json(e) => write!(f, "JSON error: {}", e),
             AIRequestError::IO(e) => write!(f, "IO error: {}", e),
+            AIRequestError::ContextLengthExceeded { limit, requested } => match (limit, requested) {
+                (Some(limit), Some(requested)) => write!(f, "Context length exceeded: {} tokens requested, the model takes {}", requested, limit),
+                (Some(limit), None) => write!(f, "Context length exceeded: the model takes {} tokens", limit),
+                _ => write!(f, "Context length exceeded"),
+            },
+            AIRequestError::Provider(e) => write!(f, "{}", e),
             AIRequestError::Other(msg) => write!(f, "Error: {}", msg),
         }
     }
@@ -37,4 +53,118 @@ impl From<std::io::Error> for AIRequestError {
     fn from(err: std::io::Error) -> Self {
         AIRequestError::IO(err)
     }
-}
\ No newline at end of file
+}
+
+/// Fields of a provider's error response. OpenAI, Anthropic and OpenRouter send JSON error bodies
+/// with a type or code to branch on, e.g. `insufficient_quota` vs `invalid_api_key`. Bodies that
+/// aren't JSON become the message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProviderError {
+    pub provider: String,
+    pub status: Option<u16>, // HTTP status
+    pub error_type: Option<String>, // e.g. "invalid_request_error", "rate_limit_error"
+    pub code: Option<String>, // e.g. "insufficient_quota", "invalid_api_key", OpenRouter's numeric codes as text
+    pub message: String,
+    pub param: Option<String>, // Request parameter the error is about
+}
+
+impl ProviderError {
+    /// Read an error body: `{"error": {"type", "code", "message", "param"}}` as OpenAI and
+    /// OpenRouter send it, Anthropic's `{"type": "error", "error": {"type", "message"}}`, or
+    /// Ollama's `{"error": "message"}`
+    pub fn parse(provider: &str, status: Option<u16>, body: &str) -> Self {
+        let json: Option<Value> = serde_json::from_str(body).ok();
+        let error = json.as_ref().map(|json| json.get("error").unwrap_or(json));
+        let text = |value: Option<&Value>| match value? {
+            Value::String(text) => Some(text.clone()),
+            Value::Number(number) => Some(number.to_string()),
+            _ => None,
+        };
+        let mut parsed = Self {
+            provider: provider.to_string(),
+            status,
+            error_type: None,
+            code: None,
+            message: body.trim().to_string(),
+            param: None,
+        };
+        match error {
+            Some(Value::Object(fields)) => {
+                parsed.error_type = text(fields.get("type"));
+                parsed.code = text(fields.get("code"));
+                parsed.param = text(fields.get("param"));
+                if let Some(message) = text(fields.get("message")) {
+                    parsed.message = message;
+                }
+            }
+            Some(Value::String(message)) => parsed.message = message.clone(),
+            _ => {}
+        }
+        parsed
+    }
+}
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} API error: {}", self.provider, self.message)?;
+        match self.code.as_ref().or(self.error_type.as_ref()) {
+            Some(code) => write!(f, " ({})", code),
+            None => Ok(()),
+        }
+    }
+}
+
+// Shapes of context errors: the limit and the requested tokens as named groups, either may be missing
+static CONTEXT_LENGTH_PATTERNS: LazyLock<Vec<Regex>> = LazyLock::new(|| {
+    [
+        // OpenAI and OpenRouter: "maximum context length is 8192 tokens. However, you requested about 9000 tokens"
+        r"(?s)maximum context length is (?P<limit>\d+) tokens.*?(?:resulted in|requested(?: about)?) (?P<requested>\d+) tokens",
+        // OpenAI: "Input tokens exceed the configured limit of 272000 tokens. Your messages resulted in 300000 tokens"
+        r"(?s)limit of (?P<limit>\d+) tokens.*?resulted in (?P<requested>\d+) tokens",
+        // Anthropic: "prompt is too long: 210000 tokens > 200000 maximum"
+        r"prompt is too long: (?P<requested>\d+) tokens > (?P<limit>\d+) maximum",
+        // llama.cpp: "n_prompt_tokens": 9000, "n_ctx": 8192
+        r#"(?s)"n_prompt_tokens":\s*(?P<requested>\d+).*?"n_ctx":\s*(?P<limit>\d+)"#,
+        // Ollama and error codes without counts
+        r"context_length_exceeded|exceeds? the context (?:length|window|size)|exceed_context_size_error|context window exceeds",
+    ]
+    .iter()
+    .map(|pattern| Regex::new(pattern).expect("context length patterns are valid"))
+    .collect()
+});
+
+impl AIRequestError {
+    /// `ContextLengthExceeded` when a provider's error body reports a request too long for the model
+    pub fn context_length_exceeded(error_text: &str) -> Option<Self> {
+        let captures = CONTEXT_LENGTH_PATTERNS.iter().find_map(|pattern| pattern.captures(error_text))?;
+        let count = |name: &str| captures.name(name).and_then(|count| count.as_str().parse().ok());
+        Some(AIRequestError::ContextLengthExceeded { limit: count("limit"), requested: count("requested") })
+    }
+
+    /// The context error inside a boxed error, as returned by the chat methods
+    pub fn as_context_length_exceeded(error: &(dyn Error + 'static)) -> Option<(Option<u32>, Option<u32>)> {
+        match error.downcast_ref::<AIRequestError>()? {
+            AIRequestError::ContextLengthExceeded { limit, requested } => Some((*limit, *requested)),
+            _ => None,
+        }
+    }
+
+    /// The provider error inside a boxed error, as returned by the client methods
+    pub fn as_provider_error<'a>(error: &'a (dyn Error + 'static)) -> Option<&'a ProviderError> {
+        match error.downcast_ref::<AIRequestError>()? {
+            AIRequestError::Provider(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Error for an unsuccessful response with the given status and body
+pub(crate) fn api_error(provider: &str, status: u16, error_text: &str) -> AIRequestError {
+    AIRequestError::Provider(Box::new(ProviderError::parse(provider, Some(status), error_text)))
+}
+
+/// Error of a failed chat request: `ContextLengthExceeded` when the body says so, otherwise the
+/// provider's error
+pub(crate) fn chat_api_error(provider: &str, status: u16, error_text: &str) -> Box<dyn Error> {
+    AIRequestError::context_length_exceeded(error_text).unwrap_or_else(|| api_error(provider, status, error_text)).into()
+}
@@ -1,51 +1,89 @@
 use regex::Regex;
+use serde_json::{json, Value};
+use std::sync::Arc;
 use crate::core::{Tool, ToolCall, Function};
 
+/// How tool calls are requested from models without native tool support
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FallbackStrategy {
+    /// `<tool_call>{...}</tool_call>` tags inside the reply
+    #[default]
+    Xml,
+    /// Fenced ```tool_call blocks containing JSON, easier for small models than XML tags
+    JsonBlock,
+    /// The whole reply is one JSON object, enforced with `format: json` on Ollama.
+    /// Text is emitted once the reply completes since partial JSON is not readable
+    ConstrainedJson,
+}
+
 pub struct FallbackToolHandler;
 
 impl FallbackToolHandler {
-    pub fn generate_tool_context(tools: &[Tool]) -> String {
+    pub fn generate_tool_context(tools: &[Arc<Tool>]) -> String {
+        Self::generate_tool_context_with_strategy(tools, FallbackStrategy::Xml)
+    }
+
+    pub fn generate_tool_context_with_strategy(tools: &[Arc<Tool>], strategy: FallbackStrategy) -> String {
         if tools.is_empty() {
             return String::new();
         }
 
-        let mut context = String::from("\n\nYou have access to the following tools. When you need to use a tool, respond with:\n\n<tool_call>\n{\"function\": {\"name\": \"function_name\", \"arguments\": {\"param1\": \"value1\", \"param2\": \"value2\"}}}\n</tool_call>\n\nAvailable tools:\n\n");
-        
+        let mut context = match strategy {
+            FallbackStrategy::Xml => String::from("\n\nYou have access to the following tools. When you need to use a tool, respond with:\n\n<tool_call>\n{\"function\": {\"name\": \"function_name\", \"arguments\": {\"param1\": \"value1\", \"param2\": \"value2\"}}}\n</tool_call>\n\nAvailable tools:\n\n"),
+            FallbackStrategy::JsonBlock => String::from("\n\nYou have access to the following tools. When you need to use a tool, respond with a fenced code block tagged tool_call:\n\n```tool_call\n{\"name\": \"function_name\", \"arguments\": {\"param1\": \"value1\", \"param2\": \"value2\"}}\n```\n\nAvailable tools:\n\n"),
+            FallbackStrategy::ConstrainedJson => String::from("\n\nYou must reply with a single JSON object and nothing else. To answer the user, reply with:\n\n{\"response\": \"your answer\"}\n\nTo use one or more tools, reply with:\n\n{\"tool_calls\": [{\"name\": \"function_name\", \"arguments\": {\"param1\": \"value1\"}}]}\n\nAvailable tools:\n\n"),
+        };
+
         for tool in tools {
             context.push_str(&format!("{}: {}\n", tool.name, tool.description));
             context.push_str(&format!("Parameters schema: {}\n\n", serde_json::to_string_pretty(&tool.parameters).unwrap_or_default()));
         }
-        
-        context.push_str("When using tools, wrap the JSON in <tool_call></tool_call> tags as shown above. Don't feel obligated to use tool calls if it doesn't make sense to do so or you weren't instructed. Normally you'll want to present your results to the user after making a tool call, as the user doesn't know the result, unless explicitly told otherwise (example: the user wants many consecutive tool calls).\n");
+
+        match strategy {
+            FallbackStrategy::Xml => context.push_str("When using tools, wrap the JSON in <tool_call></tool_call> tags as shown above. "),
+            FallbackStrategy::JsonBlock => context.push_str("When using tools, put the JSON in a ```tool_call block as shown above. "),
+            FallbackStrategy::ConstrainedJson => context.push_str("Always reply with exactly one JSON object in one of the two shapes shown above. "),
+        }
+        context.push_str("Don't feel obligated to use tool calls if it doesn't make sense to do so or you weren't instructed. Normally you'll want to present your results to the user after making a tool call, as the user doesn't know the result, unless explicitly told otherwise (example: the user wants many consecutive tool calls).\n");
         context
     }
 
     pub fn parse_fallback_tool_calls(content: &str) -> Option<Vec<ToolCall>> {
-        let xml_regex = Regex::new(r"(?s)<tool_call>(.*?)</tool_call>").ok()?;
-        
+        Self::parse_fallback_tool_calls_with_strategy(content, FallbackStrategy::Xml)
+    }
+
+    /// Extract tool calls, recovering calls whose closing tag or braces were cut off
+    pub fn parse_fallback_tool_calls_with_strategy(content: &str, strategy: FallbackStrategy) -> Option<Vec<ToolCall>> {
         let mut all_tool_calls = Vec::new();
-        
-        for caps in xml_regex.captures_iter(content) {
-            if let Some(json_str) = caps.get(1) {
-                let json_content = json_str.as_str().trim();
-                
-                if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(json_content) {
-                    if let (Some(name), Some(arguments)) = (
-                        parsed.get("function").and_then(|f| f.get("name")).and_then(|n| n.as_str()),
-                        parsed.get("function").and_then(|f| f.get("arguments"))
-                    ) {
-                        all_tool_calls.push(ToolCall {
-                            id: None, // Fallback mode doesn't have tool IDs
-                            function: Function {
-                                name: name.to_string(),
-                                arguments: arguments.clone(),
-                            }
-                        });
+
+        match strategy {
+            FallbackStrategy::Xml => {
+                for block in marked_blocks(content, "<tool_call>", "</tool_call>") {
+                    if let Some(value) = extract_json_object(block) {
+                        all_tool_calls.extend(tool_call_from_value(&value));
+                    }
+                }
+            }
+            FallbackStrategy::JsonBlock => {
+                for marker in ["```tool_call", "```json"] {
+                    for block in marked_blocks(content, marker, "```") {
+                        if let Some(value) = extract_json_object(block) {
+                            all_tool_calls.extend(tool_call_from_value(&value));
+                        }
+                    }
+                }
+            }
+            FallbackStrategy::ConstrainedJson => {
+                if let Some(value) = extract_json_object(content) {
+                    match value.get("tool_calls").or_else(|| value.get("tool_call")) {
+                        Some(Value::Array(calls)) => all_tool_calls.extend(calls.iter().filter_map(tool_call_from_value)),
+                        Some(call) => all_tool_calls.extend(tool_call_from_value(call)),
+                        None => all_tool_calls.extend(tool_call_from_value(&value)),
                     }
                 }
             }
         }
-        
+
         if !all_tool_calls.is_empty() {
             Some(all_tool_calls)
         } else {
@@ -54,25 +92,259 @@ impl FallbackToolHandler {
     }
 
     pub fn process_fallback_response(content: &str) -> (String, Option<Vec<ToolCall>>) {
-        if let Some(tool_calls) = Self::parse_fallback_tool_calls(content) {
-            // Remove the tool call XML from the content
-            let xml_regex = Regex::new(r"(?s)<tool_call>.*?</tool_call>").unwrap();
-            let cleaned_content = xml_regex.replace_all(content, "").trim().to_string();
-            
-            // If cleaned content is empty or very short, indicate tool usage
-            let final_content = if cleaned_content.len() < 10 {
-                "I'll help you with that.".to_string()
+        Self::process_fallback_response_with_strategy(content, FallbackStrategy::Xml)
+    }
+
+    pub fn process_fallback_response_with_strategy(content: &str, strategy: FallbackStrategy) -> (String, Option<Vec<ToolCall>>) {
+        let tool_calls = Self::parse_fallback_tool_calls_with_strategy(content, strategy);
+
+        let cleaned_content = match strategy {
+            // Remove complete and cut-off tool call blocks from the content
+            FallbackStrategy::Xml => {
+                let xml_regex = Regex::new(r"(?s)<tool_call>.*?(</tool_call>|$)").unwrap();
+                xml_regex.replace_all(content, "").trim().to_string()
+            }
+            FallbackStrategy::JsonBlock => {
+                let block_regex = Regex::new(r"(?s)```tool_call.*?(```|$)").unwrap();
+                let cleaned = block_regex.replace_all(content, "").to_string();
+                let json_regex = Regex::new(r"(?s)```json(.*?)(```|$)").unwrap();
+                json_regex
+                    .replace_all(&cleaned, |caps: &regex::Captures| {
+                        let is_call = extract_json_object(&caps[1]).and_then(|v| tool_call_from_value(&v)).is_some();
+                        if is_call { String::new() } else { caps[0].to_string() }
+                    })
+                    .trim()
+                    .to_string()
+            }
+            FallbackStrategy::ConstrainedJson => constrained_response_text(content),
+        };
+
+        match tool_calls {
+            Some(tool_calls) => {
+                // If cleaned content is empty or very short, indicate tool usage
+                let final_content = if cleaned_content.len() < 10 {
+                    "I'll help you with that.".to_string()
+                } else {
+                    cleaned_content
+                };
+                (final_content, Some(tool_calls))
+            }
+            None => (cleaned_content, None),
+        }
+    }
+}
+
+/// Hides fallback tool call markup from streamed content, including markers split across chunks.
+/// With `JsonBlock`, ```json blocks are held back until they close and only hidden when they
+/// hold a tool call, like in `process_fallback_response`
+pub struct FallbackStreamFilter {
+    strategy: FallbackStrategy,
+    inside_tool_call: Option<&'static str>, // Start marker of the open block
+    block: String, // Content of the open block so far
+    pending: String,
+}
+
+impl FallbackStreamFilter {
+    pub fn new(strategy: FallbackStrategy) -> Self {
+        Self {
+            strategy,
+            inside_tool_call: None,
+            block: String::new(),
+            pending: String::new(),
+        }
+    }
+
+    pub fn process_chunk(&mut self, chunk: &str) -> String {
+        let (starts, end): (&[&'static str], &str) = match self.strategy {
+            FallbackStrategy::Xml => (&["<tool_call>"], "</tool_call>"),
+            FallbackStrategy::JsonBlock => (&["```tool_call", "```json"], "```"),
+            // Nothing is readable until the JSON object is complete, see `finish`
+            FallbackStrategy::ConstrainedJson => return String::new(),
+        };
+
+        let mut buffer = std::mem::take(&mut self.pending);
+        buffer.push_str(chunk);
+        let mut output = String::new();
+
+        loop {
+            if let Some(start) = self.inside_tool_call {
+                match buffer.find(end) {
+                    Some(pos) => {
+                        self.block.push_str(&buffer[..pos]);
+                        output.push_str(&self.close_block(start, end));
+                        buffer = buffer[pos + end.len()..].to_string();
+                    }
+                    None => {
+                        self.pending = keep_partial_marker(&buffer, end);
+                        self.block.push_str(&buffer[..buffer.len() - self.pending.len()]);
+                        return output;
+                    }
+                }
             } else {
-                cleaned_content
-            };
-            
-            (final_content, Some(tool_calls))
-        } else {
-            // Check for incomplete tool calls and remove them
-            let incomplete_regex = Regex::new(r"<tool_call>\s*$").unwrap();
-            let cleaned_content = incomplete_regex.replace_all(content, "").trim().to_string();
-            
-            (cleaned_content, None)
+                let found = starts
+                    .iter()
+                    .filter_map(|start| buffer.find(start).map(|pos| (pos, *start)))
+                    .min_by_key(|(pos, _)| *pos);
+                match found {
+                    Some((pos, start)) => {
+                        output.push_str(&buffer[..pos]);
+                        self.inside_tool_call = Some(start);
+                        buffer = buffer[pos + start.len()..].to_string();
+                    }
+                    None => {
+                        self.pending = starts
+                            .iter()
+                            .map(|start| keep_partial_marker(&buffer, start))
+                            .max_by_key(|partial| partial.len())
+                            .unwrap_or_default();
+                        output.push_str(&buffer[..buffer.len() - self.pending.len()]);
+                        return output;
+                    }
+                }
+            }
+        }
+    }
+
+    // The text a finished block shows, empty for tool calls. A block cut off by the end of the
+    // stream has no `end`
+    fn close_block(&mut self, start: &str, end: &str) -> String {
+        self.inside_tool_call = None;
+        let block = std::mem::take(&mut self.block);
+        // Only ```json blocks can be ordinary code, the other markers always mean a call
+        let is_call = start != "```json" || extract_json_object(&block).and_then(|value| tool_call_from_value(&value)).is_some();
+        if is_call { String::new() } else { format!("{}{}{}", start, block, end) }
+    }
+
+    /// Flush held back text once the stream ends, `full_content` is the complete raw reply
+    pub fn finish(&mut self, full_content: &str) -> String {
+        match self.strategy {
+            FallbackStrategy::ConstrainedJson => constrained_response_text(full_content),
+            _ => match self.inside_tool_call {
+                Some(start) => {
+                    let pending = std::mem::take(&mut self.pending);
+                    self.block.push_str(&pending);
+                    self.close_block(start, "")
+                }
+                None => std::mem::take(&mut self.pending),
+            },
+        }
+    }
+
+    pub fn is_inside_tool_call(&self) -> bool {
+        self.inside_tool_call.is_some()
+    }
+}
+
+// Longest suffix of `buffer` that could be the beginning of `marker`
+fn keep_partial_marker(buffer: &str, marker: &str) -> String {
+    (1..marker.len())
+        .rev()
+        .filter(|&len| len <= buffer.len() && buffer.is_char_boundary(buffer.len() - len))
+        .map(|len| &buffer[buffer.len() - len..])
+        .find(|suffix| marker.starts_with(suffix))
+        .unwrap_or("")
+        .to_string()
+}
+
+// Text following each `start` marker, up to `end` or the end of content when the block was cut off
+fn marked_blocks<'a>(content: &'a str, start: &str, end: &str) -> Vec<&'a str> {
+    let mut blocks = Vec::new();
+    let mut rest = content;
+    while let Some(pos) = rest.find(start) {
+        let after = &rest[pos + start.len()..];
+        match after.find(end) {
+            Some(close) => {
+                blocks.push(&after[..close]);
+                rest = &after[close + end.len()..];
+            }
+            None => {
+                blocks.push(after);
+                break;
+            }
         }
     }
-}
\ No newline at end of file
+    blocks
+}
+
+fn constrained_response_text(content: &str) -> String {
+    match extract_json_object(content) {
+        Some(value) => value
+            .get("response")
+            .and_then(|r| r.as_str())
+            .unwrap_or("")
+            .trim()
+            .to_string(),
+        None => content.trim().to_string(),
+    }
+}
+
+/// Parse the first JSON object in `text`. Objects cut off mid-way are repaired by closing
+/// open strings, arrays and objects
+fn extract_json_object(text: &str) -> Option<Value> {
+    let start = text.find('{')?;
+    let mut closers = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (offset, c) in text[start..].char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' => closers.push('}'),
+            '[' => closers.push(']'),
+            '}' | ']' => {
+                closers.pop();
+                if closers.is_empty() {
+                    return serde_json::from_str(&text[start..start + offset + 1]).ok();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Unterminated, try to close what is open
+    let mut repaired = text[start..].trim_end().to_string();
+    if in_string {
+        if escaped {
+            repaired.pop();
+        }
+        repaired.push('"');
+    }
+    let trimmed = repaired.trim_end();
+    if let Some(without_comma) = trimmed.strip_suffix(',') {
+        repaired = without_comma.to_string();
+    } else if trimmed.ends_with(':') {
+        repaired.push_str("null");
+    }
+    while let Some(closer) = closers.pop() {
+        repaired.push(closer);
+    }
+    serde_json::from_str(&repaired).ok()
+}
+
+fn tool_call_from_value(value: &Value) -> Option<ToolCall> {
+    let call = value.get("function").filter(|f| f.is_object()).unwrap_or(value);
+    let name = call.get("name").or_else(|| call.get("tool")).and_then(|n| n.as_str())?;
+    let arguments = match call.get("arguments").or_else(|| call.get("parameters")) {
+        // Some models send arguments as a JSON encoded string
+        Some(Value::String(raw)) => extract_json_object(raw).unwrap_or_else(|| json!({})),
+        Some(arguments) => arguments.clone(),
+        None => json!({}),
+    };
+
+    Some(ToolCall {
+        id: None, // Fallback mode doesn't have tool IDs
+        function: Function {
+            name: name.to_string(),
+            arguments,
+        },
+    })
+}
@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::core::{encode_image_file, AIRequestError};
+
+/// Content for a moderation check
+#[derive(Debug, Clone)]
+pub enum ModerationInput {
+    Text(String),
+    ImageUrl(String), // https URL or data URL
+}
+
+impl ModerationInput {
+    /// Image file sent as a data URL, the media type is read from the file's contents
+    pub fn image_file(path: &str) -> Result<Self, AIRequestError> {
+        Ok(ModerationInput::ImageUrl(encode_image_file(path)?.data_url()))
+    }
+}
+
+impl From<&str> for ModerationInput {
+    fn from(text: &str) -> Self {
+        ModerationInput::Text(text.to_string())
+    }
+}
+
+impl From<String> for ModerationInput {
+    fn from(text: String) -> Self {
+        ModerationInput::Text(text)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ModerationResult {
+    pub flagged: bool,
+    pub categories: Vec<String>, // Flagged categories, e.g. "harassment" or "violence/graphic"
+    pub category_scores: HashMap<String, f64>, // 0.0 - 1.0 per category
+}
+
+/// Which side of the exchange a guardrail is looking at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GuardrailStage {
+    Input,      // Latest user message, before it is sent
+    ToolResult, // Tool output the model hasn't seen yet, before it is sent
+    Output,     // Complete model reply
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GuardrailVerdict {
+    Allow,
+    Flag(String),  // Let it through, the reason is reported on the final stream item
+    Block(String), // Stop the exchange with this reason as the error
+}
+
+pub type GuardrailFuture<'a> = Pin<Box<dyn Future<Output = GuardrailVerdict> + Send + 'a>>;
+
+/// Content check run by `MonoAI` before sending and after receiving, for every provider.
+/// Closures `Fn(GuardrailStage, &str) -> GuardrailVerdict` implement it
+pub trait Guardrail: Send + Sync {
+    fn check<'a>(&'a self, stage: GuardrailStage, text: &'a str) -> GuardrailFuture<'a>;
+}
+
+impl<F> Guardrail for F
+where
+    F: Fn(GuardrailStage, &str) -> GuardrailVerdict + Send + Sync,
+{
+    fn check<'a>(&'a self, stage: GuardrailStage, text: &'a str) -> GuardrailFuture<'a> {
+        let verdict = self(stage, text);
+        Box::pin(async move { verdict })
+    }
+}
@@ -0,0 +1,190 @@
+use std::borrow::Cow;
+
+use super::{AIRequestError, Message, ToolCall, ToolOutput};
+
+/// What a provider's API rejects in a conversation history, see `normalize_history`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HistoryRules {
+    pub merge_consecutive: bool, // Consecutive user or assistant messages become one
+    pub pair_tool_calls: bool, // Every tool call needs a result before the next turn, every result a call
+}
+
+impl HistoryRules {
+    pub const OPENAI: HistoryRules = HistoryRules { merge_consecutive: false, pair_tool_calls: true };
+    pub const ANTHROPIC: HistoryRules = HistoryRules { merge_consecutive: true, pair_tool_calls: true };
+    pub const OPENROUTER: HistoryRules = HistoryRules { merge_consecutive: false, pair_tool_calls: true };
+    // Tool calls in prompt-based fallback mode are answered with user messages
+    pub const OLLAMA: HistoryRules = HistoryRules { merge_consecutive: false, pair_tool_calls: false };
+}
+
+/// Repair the parts of a history the provider would reject with a 400, before converting it.
+/// Borrows the messages when nothing needs repair:
+/// - User and assistant messages with nothing in them are dropped, whitespace-only text is cleared
+/// - With `merge_consecutive`, consecutive user or assistant messages are merged into one
+/// - With `pair_tool_calls`, tool calls left without a result when the conversation moved on
+///   (e.g. an interrupted agent) get an error result
+///
+/// Tool results answering no earlier call, and a history ending in unanswered tool calls,
+/// can't be repaired and fail with an error naming the problem
+pub fn normalize_history(messages: &[Message], rules: HistoryRules) -> Result<Cow<'_, [Message]>, AIRequestError> {
+    let mut normalized: Vec<Message> = Vec::with_capacity(messages.len());
+    let mut changed = false;
+    let mut pending: Vec<ToolCall> = Vec::new();
+
+    for (index, message) in messages.iter().enumerate() {
+        if message.role == "tool" {
+            if rules.pair_tool_calls {
+                let answered = match &message.tool_call_id {
+                    Some(id) => pending.iter().position(|call| call.id.as_ref() == Some(id)),
+                    None => pending.iter().position(|call| Some(&call.function.name) == message.name.as_ref()),
+                };
+                match answered {
+                    Some(answered) => {
+                        pending.remove(answered);
+                    }
+                    None => return Err(orphan_result_error(index, message)),
+                }
+            }
+            normalized.push(message.clone());
+            continue;
+        }
+
+        // The conversation moved on, calls without a result were interrupted
+        if !pending.is_empty() {
+            for call in pending.drain(..) {
+                normalized.push(Message::tool_result(&call, ToolOutput::error("The tool call was interrupted before it returned a result")));
+            }
+            changed = true;
+        }
+
+        let conversational = message.role == "user" || message.role == "assistant";
+        if conversational && is_blank(message) {
+            changed = true;
+            continue;
+        }
+        let mut message = message.clone();
+        if !message.content.is_empty() && message.content.trim().is_empty() {
+            message.content.clear();
+            changed = true;
+        }
+        if rules.pair_tool_calls {
+            pending.extend(message.tool_calls.iter().flatten().cloned());
+        }
+
+        match normalized.last_mut() {
+            Some(previous) if rules.merge_consecutive && conversational && previous.role == message.role => {
+                merge(previous, message);
+                changed = true;
+            }
+            _ => normalized.push(message),
+        }
+    }
+
+    if !pending.is_empty() {
+        let names: Vec<&str> = pending.iter().map(|call| call.function.name.as_str()).collect();
+        return Err(AIRequestError::Other(format!(
+            "The last assistant message calls {} without a result, send the tool results (see handle_tool_calls) before requesting a reply",
+            names.join(", ")
+        )));
+    }
+    Ok(if changed { Cow::Owned(normalized) } else { Cow::Borrowed(messages) })
+}
+
+fn orphan_result_error(index: usize, message: &Message) -> AIRequestError {
+    let call = match (&message.tool_call_id, &message.name) {
+        (Some(id), _) => format!("call {}", id),
+        (None, Some(name)) => format!("a call of {}", name),
+        (None, None) => "a call without ID or name".to_string(),
+    };
+    AIRequestError::Other(format!(
+        "Message {} is a tool result for {}, which no earlier assistant message made",
+        index, call
+    ))
+}
+
+// Nothing the provider could send, only whitespace text
+fn is_blank(message: &Message) -> bool {
+    message.content.trim().is_empty()
+        && message.tool_calls.as_ref().is_none_or(|calls| calls.is_empty())
+        && message.thinking.as_ref().is_none_or(|thinking| thinking.is_empty())
+        && message.images.as_ref().is_none_or(|images| images.is_empty())
+        && message.image_urls.as_ref().is_none_or(|urls| urls.is_empty())
+        && message.documents.as_ref().is_none_or(|documents| documents.is_empty())
+        && message.audio.as_ref().is_none_or(|audio| audio.is_empty())
+}
+
+fn merge(previous: &mut Message, message: Message) {
+    if !message.content.is_empty() {
+        if !previous.content.is_empty() {
+            previous.content.push_str("\n\n");
+        }
+        previous.content.push_str(&message.content);
+    }
+    fn append<T>(into: &mut Option<Vec<T>>, items: Option<Vec<T>>) {
+        if let Some(items) = items {
+            into.get_or_insert_with(Vec::new).extend(items);
+        }
+    }
+    append(&mut previous.images, message.images);
+    append(&mut previous.image_urls, message.image_urls);
+    append(&mut previous.documents, message.documents);
+    append(&mut previous.audio, message.audio);
+    append(&mut previous.tool_calls, message.tool_calls);
+    append(&mut previous.thinking, message.thinking);
+    previous.cache = message.cache.or(previous.cache);
+}
+
+/// Which messages `truncate_history` keeps when a history exceeds the model's context. Leading
+/// system messages and the latest turn are always kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TruncationStrategy {
+    /// Drop the oldest turns first
+    DropOldest,
+    /// Drop the oldest turns after the first, e.g. when the first user message states the task
+    KeepFirstTurn,
+}
+
+/// Shorten a history to about `max_tokens` (estimated at 4 characters per token) by dropping
+/// whole turns, a user message with the replies and tool results following it, so tool calls
+/// keep their results. Returns what is left when even the kept messages exceed the budget
+pub fn truncate_history(messages: &[Message], max_tokens: u32, strategy: TruncationStrategy) -> Vec<Message> {
+    let counts: Vec<u32> = messages.iter().map(|message| estimate_tokens(std::slice::from_ref(message))).collect();
+    truncate_with_counts(messages, &counts, max_tokens, strategy)
+}
+
+// `truncate_history` with the token count of each message given, e.g. as reported by the provider
+pub(crate) fn truncate_with_counts(messages: &[Message], counts: &[u32], max_tokens: u32, strategy: TruncationStrategy) -> Vec<Message> {
+    let leading = messages.iter().take_while(|message| message.role == "system").count();
+    let system: Vec<usize> = (0..leading).collect();
+    let mut turns: Vec<Vec<usize>> = Vec::new();
+    for (index, message) in messages.iter().enumerate().skip(leading) {
+        match turns.last_mut() {
+            Some(turn) if message.role != "user" => turn.push(index),
+            _ => turns.push(vec![index]),
+        }
+    }
+
+    let tokens = |turn: &[usize]| turn.iter().map(|&index| counts[index]).sum::<u32>();
+    let mut total: u32 = tokens(&system) + turns.iter().map(|turn| tokens(turn)).sum::<u32>();
+    let first = match strategy {
+        TruncationStrategy::DropOldest => 0,
+        TruncationStrategy::KeepFirstTurn => 1,
+    };
+    while total > max_tokens && turns.len() > first + 1 {
+        total -= tokens(&turns.remove(first));
+    }
+
+    system.into_iter().chain(turns.into_iter().flatten()).map(|index| messages[index].clone()).collect()
+}
+
+// Roughly 4 characters per token for English text, plus a few tokens of framing per message
+pub(crate) fn estimate_tokens(messages: &[Message]) -> u32 {
+    messages
+        .iter()
+        .map(|message| {
+            let text_len = message.content.len()
+                + message.tool_calls.iter().flatten().map(|call| call.function.arguments.to_string().len()).sum::<usize>();
+            (text_len as u32).div_ceil(4) + 4
+        })
+        .sum()
+}
@@ -0,0 +1,66 @@
+use base64::{Engine as _, engine::general_purpose};
+
+use crate::core::TokenUsage;
+
+#[derive(Debug, Clone, Default)]
+pub struct ImageGenerationOptions {
+    pub model: Option<String>, // Provider default when None, gpt-image-1 on OpenAI
+    pub size: Option<String>, // e.g. "1024x1024", "1536x1024" or "auto"
+    pub quality: Option<String>, // "low", "medium", "high" for gpt-image-1, "standard" or "hd" for dall-e-3
+    pub format: Option<String>, // "png", "jpeg" or "webp", gpt-image-1 only
+    pub n: Option<u32>, // Number of images, dall-e-3 only makes one
+}
+
+impl ImageGenerationOptions {
+    pub fn with_model(mut self, model: &str) -> Self {
+        self.model = Some(model.to_string());
+        self
+    }
+
+    pub fn with_size(mut self, size: &str) -> Self {
+        self.size = Some(size.to_string());
+        self
+    }
+
+    pub fn with_quality(mut self, quality: &str) -> Self {
+        self.quality = Some(quality.to_string());
+        self
+    }
+
+    pub fn with_format(mut self, format: &str) -> Self {
+        self.format = Some(format.to_string());
+        self
+    }
+
+    pub fn with_count(mut self, n: u32) -> Self {
+        self.n = Some(n);
+        self
+    }
+}
+
+/// One generated image, as base64 data or a temporary URL depending on the model
+#[derive(Debug, Clone)]
+pub struct GeneratedImage {
+    pub b64_data: Option<String>,
+    pub url: Option<String>, // dall-e URLs expire after an hour
+    pub revised_prompt: Option<String>, // Prompt the model actually used, dall-e-3 rewrites it
+}
+
+impl GeneratedImage {
+    /// Decoded image bytes, None for URL results
+    pub fn bytes(&self) -> Option<Vec<u8>> {
+        self.b64_data.as_ref().and_then(|data| general_purpose::STANDARD.decode(data).ok())
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let bytes = self.bytes().ok_or("Image has no inline data, download it from its url")?;
+        std::fs::write(path, bytes)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ImageGeneration {
+    pub images: Vec<GeneratedImage>,
+    pub usage: Option<TokenUsage>, // Token counts and estimated cost, when the provider prices per token or reports it
+}
@@ -0,0 +1,161 @@
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use futures_util::task::AtomicWaker;
+use futures_util::{Stream, StreamExt};
+
+/// How far a long-running operation is
+#[derive(Debug, Clone)]
+pub struct JobProgress {
+    pub message: String,
+    pub completed: Option<u64>, // Units of work done: bytes, texts, items
+    pub total: Option<u64>,
+    pub percent: Option<f32>, // 0.0 - 100.0 when the total is known
+    pub eta: Option<Duration>, // Extrapolated from the rate so far
+    pub elapsed: Duration,
+}
+
+/// Event of a `Job`. `Done`, `Failed` and `Cancelled` are always the last event
+#[derive(Debug, Clone)]
+pub enum JobEvent<T> {
+    Progress(JobProgress),
+    Done(T),
+    Failed(String),
+    Cancelled,
+}
+
+/// Measures elapsed time from the job start, for the percentage and ETA of its progress
+#[derive(Debug, Clone, Copy)]
+pub struct JobClock {
+    started: Instant,
+}
+
+impl Default for JobClock {
+    fn default() -> Self {
+        Self::start()
+    }
+}
+
+impl JobClock {
+    pub fn start() -> Self {
+        Self { started: Instant::now() }
+    }
+
+    pub fn progress(&self, message: &str, completed: Option<u64>, total: Option<u64>) -> JobProgress {
+        let elapsed = self.started.elapsed();
+        let fraction = match (completed, total) {
+            (Some(completed), Some(total)) if total > 0 => Some((completed as f64 / total as f64).min(1.0)),
+            _ => None,
+        };
+        let eta = fraction.filter(|fraction| *fraction > 0.0).map(|fraction| elapsed.mul_f64((1.0 - fraction) / fraction));
+
+        JobProgress {
+            message: message.to_string(),
+            completed,
+            total,
+            percent: fraction.map(|fraction| (fraction * 100.0) as f32),
+            eta,
+            elapsed,
+        }
+    }
+}
+
+/// Cancels a `Job` from anywhere, e.g. a UI button or another task
+#[derive(Debug, Clone, Default)]
+pub struct JobCanceller {
+    state: Arc<CancelState>,
+}
+
+#[derive(Debug, Default)]
+struct CancelState {
+    cancelled: AtomicBool,
+    waker: AtomicWaker, // Wakes a job waiting on its request so it sees the cancellation
+}
+
+impl JobCanceller {
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, Ordering::SeqCst);
+        self.state.waker.wake();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+/// A long-running operation (model pull, embedding thousands of texts, ...) as a stream of
+/// `JobEvent`s. Cancelling stops it at the next poll and drops the request in flight, as
+/// does dropping the job
+pub struct Job<'a, T> {
+    events: Option<Pin<Box<dyn Stream<Item = JobEvent<T>> + Send + 'a>>>, // None once finished
+    canceller: JobCanceller,
+}
+
+impl<'a, T: Send + 'a> Job<'a, T> {
+    /// Job from a stream of events, it finishes with the first `Done`, `Failed` or `Cancelled`.
+    /// A stream ending without one fails the job
+    pub fn new(events: impl Stream<Item = JobEvent<T>> + Send + 'a) -> Self {
+        Self {
+            events: Some(Box::pin(events)),
+            canceller: JobCanceller::default(),
+        }
+    }
+
+    /// Job that failed before it started
+    pub fn failed(error: impl Into<String>) -> Self {
+        Self::new(futures_util::stream::iter([JobEvent::Failed(error.into())]))
+    }
+
+    pub fn canceller(&self) -> JobCanceller {
+        self.canceller.clone()
+    }
+
+    pub fn cancel(&self) {
+        self.canceller.cancel();
+    }
+
+    /// Run to the end, passing each progress update to `on_progress`
+    pub async fn wait_with_progress(mut self, mut on_progress: impl FnMut(&JobProgress)) -> Result<T, String> {
+        while let Some(event) = self.next().await {
+            match event {
+                JobEvent::Progress(progress) => on_progress(&progress),
+                JobEvent::Done(output) => return Ok(output),
+                JobEvent::Failed(error) => return Err(error),
+                JobEvent::Cancelled => return Err("Job cancelled".to_string()),
+            }
+        }
+        Err("Job ended without a result".to_string())
+    }
+
+    pub async fn wait(self) -> Result<T, String> {
+        self.wait_with_progress(|_| {}).await
+    }
+}
+
+impl<T> Stream for Job<'_, T> {
+    type Item = JobEvent<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<JobEvent<T>>> {
+        let this = self.get_mut();
+        let Some(events) = this.events.as_mut() else {
+            return Poll::Ready(None);
+        };
+        this.canceller.state.waker.register(cx.waker());
+        if this.canceller.is_cancelled() {
+            this.events = None;
+            return Poll::Ready(Some(JobEvent::Cancelled));
+        }
+
+        let event = match events.poll_next_unpin(cx) {
+            Poll::Ready(Some(JobEvent::Progress(progress))) => return Poll::Ready(Some(JobEvent::Progress(progress))),
+            Poll::Ready(Some(event)) => event,
+            Poll::Ready(None) => JobEvent::Failed("Job ended without a result".to_string()),
+            Poll::Pending => return Poll::Pending,
+        };
+        this.events = None;
+        Poll::Ready(Some(event))
+    }
+}
@@ -0,0 +1,84 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::ResponseFormat;
+
+/// Language found by `MonoAI::detect_language`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LanguageDetection {
+    pub language: String, // English name, e.g. "German"
+    pub code: String, // ISO 639-1, e.g. "de"
+    pub confidence: f32, // 0.0 - 1.0, as judged by the model
+}
+
+/// Result of `MonoAI::translate`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Translation {
+    pub text: String,
+    pub source_language: String, // ISO 639-1 code of the detected language of the input
+    pub target_language: String, // As requested
+}
+
+impl LanguageDetection {
+    pub(crate) fn prompt(text: &str) -> String {
+        format!(
+            "Detect the language of the text below. Give its English name, its ISO 639-1 code and your confidence from 0 to 1. For text mixing languages, give the main one.\n\nText:\n{}",
+            text
+        )
+    }
+
+    pub(crate) fn format() -> ResponseFormat {
+        ResponseFormat::json_schema(
+            "language",
+            json!({
+                "type": "object",
+                "properties": {
+                    "language": {"type": "string"},
+                    "code": {"type": "string", "pattern": "^[a-z]{2}$"},
+                    "confidence": {"type": "number", "minimum": 0, "maximum": 1}
+                },
+                "required": ["language", "code", "confidence"],
+                "additionalProperties": false
+            }),
+        )
+    }
+}
+
+impl Translation {
+    pub(crate) fn prompt(text: &str, target_language: &str) -> String {
+        format!(
+            "Translate the text below into {}. Keep the meaning, tone and formatting, and leave code, URLs and placeholders such as {{name}} unchanged. Text already in {} is returned as it is. Also give the ISO 639-1 code of the language the text is written in.\n\nText:\n{}",
+            target_language, target_language, text
+        )
+    }
+
+    pub(crate) fn format() -> ResponseFormat {
+        ResponseFormat::json_schema(
+            "translation",
+            json!({
+                "type": "object",
+                "properties": {
+                    "source_language": {"type": "string"},
+                    "translation": {"type": "string"}
+                },
+                "required": ["source_language", "translation"],
+                "additionalProperties": false
+            }),
+        )
+    }
+
+    // The structured reply with the requested target
+    pub(crate) fn from_reply(reply: serde_json::Value, target_language: &str) -> Result<Self, serde_json::Error> {
+        #[derive(Deserialize)]
+        struct Reply {
+            source_language: String,
+            translation: String,
+        }
+        let reply: Reply = serde_json::from_value(reply)?;
+        Ok(Self {
+            text: reply.translation,
+            source_language: reply.source_language,
+            target_language: target_language.to_string(),
+        })
+    }
+}
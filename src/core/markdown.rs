@@ -0,0 +1,347 @@
+use std::pin::Pin;
+use std::sync::LazyLock;
+
+use futures_util::{Stream, StreamExt};
+use regex::Regex;
+
+use super::ChatStream;
+
+/// Line-level element of streamed markdown
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MarkdownBlock {
+    /// A line of text, consecutive ones are lines of the same paragraph
+    Paragraph,
+    Heading(u8),
+    /// `ordered` holds the item's number in numbered lists, `indent` the spaces before the marker
+    ListItem { ordered: Option<u64>, indent: usize },
+    Quote,
+    /// Fenced code, its lines arrive as raw `Text` including their newlines
+    CodeBlock { language: Option<String> },
+}
+
+/// Inline formatting inside a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InlineStyle {
+    Bold,
+    Italic,
+    Strikethrough,
+    Code,
+}
+
+/// What a `StreamRenderer` reports, in document order. Every start has a matching end
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenderEvent {
+    BlockStart(MarkdownBlock),
+    BlockEnd(MarkdownBlock),
+    StyleStart(InlineStyle),
+    StyleEnd(InlineStyle),
+    /// Text in the open block and styles, markers removed
+    Text(String),
+    /// An empty line between paragraphs
+    BlankLine,
+    /// A horizontal rule, `---`
+    Rule,
+}
+
+// Line prefixes that decide the block before the line is complete
+static HEADING: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^ {0,3}(#{1,6}) $").expect("valid pattern"));
+static BULLET: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^( *)[-*+] $").expect("valid pattern"));
+static ORDERED: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^( *)(\d{1,9})[.)] $").expect("valid pattern"));
+static QUOTE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^ {0,3}> ?").expect("valid pattern"));
+// Prefixes that could still become any of those, a fence or a rule
+static UNDECIDED: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?: *| {0,3}#{1,6}| *[-*+]| *\d{1,9}[.)]?| {0,3}>| *(?:`{1,2}|~{1,2})| *(?:```|~~~).*| *[-*_](?: *[-*_])* *)$").expect("valid pattern")
+});
+// Complete lines
+static FENCE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^ *(`{3,}|~{3,}) *([^`\s]*)").expect("valid pattern"));
+static RULE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^ {0,3}(?:(?:- *){3,}|(?:\* *){3,}|(?:_ *){3,})$").expect("valid pattern"));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LineState {
+    Start, // Collecting the line's prefix until its block is known
+    Inline, // Parsing formatted text
+    Code, // Raw text of a code block line
+}
+
+/// Incremental markdown parser for streamed replies. Feed it text as it arrives and render
+/// the events, without re-parsing partial markdown. Covers headings, lists, quotes, rules,
+/// fenced code, bold, italic, strikethrough and inline code. Inline styles end with their line
+#[derive(Debug)]
+pub struct StreamRenderer {
+    state: LineState,
+    pending: String, // Line prefix not yet classified
+    block: Option<MarkdownBlock>, // MarkdownBlock of the current line
+    fence: Option<(char, usize)>, // Inside a code block: fence character and length
+    styles: Vec<InlineStyle>,
+    text: String, // Text not yet reported
+    held: Option<char>, // Marker waiting for the next character: `*`, `_`, `~` or `\`
+    previous: Option<char>, // Last text character, for `_` word boundaries
+}
+
+impl Default for StreamRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StreamRenderer {
+    pub fn new() -> Self {
+        Self {
+            state: LineState::Start,
+            pending: String::new(),
+            block: None,
+            fence: None,
+            styles: Vec::new(),
+            text: String::new(),
+            held: None,
+            previous: None,
+        }
+    }
+
+    /// Feed the next piece of text, returns the events it completed
+    pub fn push(&mut self, text: &str) -> Vec<RenderEvent> {
+        let mut events = Vec::new();
+        for c in text.chars() {
+            match self.state {
+                LineState::Start => self.start_char(c, &mut events),
+                LineState::Inline => self.inline_char(c, &mut events),
+                LineState::Code if c == '\n' => {
+                    self.text.push(c);
+                    self.state = LineState::Start;
+                }
+                LineState::Code => self.text.push(c),
+            }
+        }
+        self.flush_text(&mut events);
+        events
+    }
+
+    /// The reply ended, closes whatever is open
+    pub fn finish(&mut self) -> Vec<RenderEvent> {
+        let mut events = Vec::new();
+        match self.state {
+            // A code line without its newline stays as it is
+            LineState::Start if self.fence.is_some() && !self.is_closing_fence(&self.pending) => {
+                let pending = std::mem::take(&mut self.pending);
+                self.text.push_str(&pending);
+            }
+            LineState::Start if self.pending.is_empty() => {}
+            LineState::Code => {}
+            _ => self.end_line(&mut events),
+        }
+        self.flush_text(&mut events);
+        if self.fence.take().is_some() {
+            events.extend(self.block.take().map(RenderEvent::BlockEnd));
+        }
+        self.state = LineState::Start;
+        events
+    }
+
+    fn start_char(&mut self, c: char, events: &mut Vec<RenderEvent>) {
+        if c == '\n' {
+            self.end_line(events);
+            return;
+        }
+        self.pending.push(c);
+
+        if let Some((fence_char, length)) = self.fence {
+            // Only a closing fence needs the whole line
+            let trimmed = self.pending.trim_start();
+            let fence = trimmed.trim_end();
+            let closing = fence.chars().all(|c| c == fence_char) && (fence.len() == trimmed.len() || fence.len() >= length);
+            if !closing {
+                self.text.push_str(&std::mem::take(&mut self.pending));
+                self.state = LineState::Code;
+            }
+            return;
+        }
+
+        let pending = self.pending.as_str();
+        let (block, rest) = if let Some(captures) = HEADING.captures(pending) {
+            (MarkdownBlock::Heading(captures[1].len() as u8), String::new())
+        } else if let Some(captures) = BULLET.captures(pending) {
+            (MarkdownBlock::ListItem { ordered: None, indent: captures[1].len() }, String::new())
+        } else if let Some(captures) = ORDERED.captures(pending) {
+            (MarkdownBlock::ListItem { ordered: captures[2].parse().ok(), indent: captures[1].len() }, String::new())
+        } else if pending.trim_start().starts_with('>') && pending.trim_start().len() > 1 {
+            let marker = QUOTE.find(pending).map_or(0, |marker| marker.end());
+            (MarkdownBlock::Quote, pending[marker..].to_string())
+        } else if UNDECIDED.is_match(pending) {
+            return;
+        } else {
+            (MarkdownBlock::Paragraph, pending.to_string())
+        };
+
+        self.pending.clear();
+        self.start_block(block, events);
+        self.state = LineState::Inline;
+        for c in rest.chars() {
+            self.inline_char(c, events);
+        }
+    }
+
+    fn start_block(&mut self, block: MarkdownBlock, events: &mut Vec<RenderEvent>) {
+        events.push(RenderEvent::BlockStart(block.clone()));
+        self.block = Some(block);
+        self.previous = None;
+    }
+
+    // A newline: finishes the line's block, or classifies a line that is complete only now
+    fn end_line(&mut self, events: &mut Vec<RenderEvent>) {
+        if self.state == LineState::Inline {
+            self.resolve_held(None, events);
+            self.flush_text(events);
+            while let Some(style) = self.styles.pop() {
+                events.push(RenderEvent::StyleEnd(style));
+            }
+            events.extend(self.block.take().map(RenderEvent::BlockEnd));
+            self.state = LineState::Start;
+            return;
+        }
+
+        let line = std::mem::take(&mut self.pending);
+        if self.fence.is_some() {
+            if self.is_closing_fence(&line) {
+                self.flush_text(events);
+                self.fence = None;
+                events.extend(self.block.take().map(RenderEvent::BlockEnd));
+            } else {
+                self.text.push_str(&line);
+                self.text.push('\n');
+            }
+            return;
+        }
+
+        if line.trim().is_empty() {
+            events.push(RenderEvent::BlankLine);
+        } else if let Some(captures) = FENCE.captures(&line) {
+            let fence = &captures[1];
+            let language = Some(captures[2].to_string()).filter(|language| !language.is_empty());
+            self.fence = Some((fence.chars().next().unwrap_or('`'), fence.len()));
+            self.start_block(MarkdownBlock::CodeBlock { language }, events);
+        } else if RULE.is_match(&line) {
+            events.push(RenderEvent::Rule);
+        } else {
+            // Undecided until now, e.g. a lone `#`, `>` or `-`
+            let (block, text) = match line.trim() {
+                heading if heading.len() <= 6 && heading.chars().all(|c| c == '#') => (MarkdownBlock::Heading(heading.len() as u8), ""),
+                ">" => (MarkdownBlock::Quote, ""),
+                _ => (MarkdownBlock::Paragraph, line.as_str()),
+            };
+            self.start_block(block, events);
+            self.state = LineState::Inline;
+            for c in text.chars() {
+                self.inline_char(c, events);
+            }
+            self.end_line(events);
+        }
+    }
+
+    fn is_closing_fence(&self, line: &str) -> bool {
+        let line = line.trim();
+        self.fence.is_some_and(|(fence_char, length)| line.len() >= length && line.chars().all(|c| c == fence_char))
+    }
+
+    fn inline_char(&mut self, c: char, events: &mut Vec<RenderEvent>) {
+        if c == '\n' {
+            self.end_line(events);
+            return;
+        }
+        if self.styles.last() == Some(&InlineStyle::Code) {
+            match c {
+                '`' => self.toggle(InlineStyle::Code, events),
+                c => self.text.push(c),
+            }
+            return;
+        }
+        if self.held.is_some() && self.resolve_held(Some(c), events) {
+            return;
+        }
+        match c {
+            '*' | '_' | '~' | '\\' => self.held = Some(c),
+            '`' => self.toggle(InlineStyle::Code, events),
+            c => self.push_text(c),
+        }
+    }
+
+    // Decides what a held marker means now that `next` follows it (None at the end of the line).
+    // True when `next` was consumed with it
+    fn resolve_held(&mut self, next: Option<char>, events: &mut Vec<RenderEvent>) -> bool {
+        let Some(marker) = self.held.take() else { return false };
+        let next_is_space = next.is_none_or(char::is_whitespace);
+        match (marker, next) {
+            ('\\', Some(next)) => {
+                self.push_text(next);
+                return true;
+            }
+            ('*', Some('*')) | ('_', Some('_')) => {
+                self.toggle(InlineStyle::Bold, events);
+                return true;
+            }
+            ('~', Some('~')) => {
+                self.toggle(InlineStyle::Strikethrough, events);
+                return true;
+            }
+            ('*', _) | ('_', _) => {
+                let open = self.styles.contains(&InlineStyle::Italic);
+                let word_before = self.previous.is_some_and(char::is_alphanumeric);
+                let word_after = next.is_some_and(char::is_alphanumeric);
+                let toggles = match (open, marker) {
+                    (true, '*') => true,
+                    (true, _) => !word_after,
+                    (false, '*') => !next_is_space,
+                    (false, _) => !next_is_space && !word_before,
+                };
+                if toggles {
+                    self.toggle(InlineStyle::Italic, events);
+                } else {
+                    self.push_text(marker);
+                }
+            }
+            (marker, _) => self.push_text(marker),
+        }
+        false
+    }
+
+    // Opens the style, or closes it with the styles opened after it
+    fn toggle(&mut self, style: InlineStyle, events: &mut Vec<RenderEvent>) {
+        self.flush_text(events);
+        match self.styles.iter().rposition(|open| *open == style) {
+            Some(index) => {
+                for style in self.styles.drain(index..).rev() {
+                    events.push(RenderEvent::StyleEnd(style));
+                }
+            }
+            None => {
+                self.styles.push(style);
+                events.push(RenderEvent::StyleStart(style));
+            }
+        }
+    }
+
+    fn push_text(&mut self, c: char) {
+        self.text.push(c);
+        self.previous = Some(c);
+    }
+
+    fn flush_text(&mut self, events: &mut Vec<RenderEvent>) {
+        if !self.text.is_empty() {
+            events.push(RenderEvent::Text(std::mem::take(&mut self.text)));
+        }
+    }
+}
+
+/// Render events for the text of a chat stream's first completion, other items are dropped
+pub fn render_stream(stream: ChatStream) -> Pin<Box<dyn Stream<Item = Result<RenderEvent, String>> + Send>> {
+    let events = futures_util::stream::unfold(Some((stream, StreamRenderer::new())), |state| async move {
+        let (mut stream, mut renderer) = state?;
+        let events: Vec<Result<RenderEvent, String>> = match stream.next().await {
+            Some(Ok(item)) if item.choice_index.is_some() => Vec::new(),
+            Some(Ok(item)) => renderer.push(&item.content).into_iter().map(Ok).collect(),
+            Some(Err(e)) => vec![Err(e)],
+            None => return Some((renderer.finish().into_iter().map(Ok).collect(), None)),
+        };
+        Some((events, Some((stream, renderer))))
+    });
+    Box::pin(events.flat_map(futures_util::stream::iter))
+}
@@ -0,0 +1,301 @@
+use base64::{Engine as _, engine::general_purpose};
+use serde::{Deserialize, Serialize};
+
+use crate::core::{AIRequestError, Audio, Message};
+
+/// Image formats vision models accept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Gif,
+    Webp,
+}
+
+impl ImageFormat {
+    /// Detect the format from the file's magic bytes
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [0x89, b'P', b'N', b'G', ..] => Some(ImageFormat::Png),
+            [0xFF, 0xD8, 0xFF, ..] => Some(ImageFormat::Jpeg),
+            [b'G', b'I', b'F', b'8', ..] => Some(ImageFormat::Gif),
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some(ImageFormat::Webp),
+            _ => None,
+        }
+    }
+
+    /// Detect the format of base64 encoded image data
+    pub fn from_base64(data: &str) -> Option<Self> {
+        Self::from_bytes(&decode_header(data))
+    }
+
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "image/png",
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Gif => "image/gif",
+            ImageFormat::Webp => "image/webp",
+        }
+    }
+}
+
+// First 12 bytes of base64 data, enough for every magic number checked
+fn decode_header(data: &str) -> Vec<u8> {
+    let prefix: String = data.chars().take(16).collect();
+    general_purpose::STANDARD.decode(prefix).unwrap_or_default()
+}
+
+/// Base64 image data ready for `Message.images`, with the format sniffed from its bytes
+#[derive(Debug, Clone)]
+pub struct EncodedImage {
+    pub data: String,
+    pub format: ImageFormat,
+}
+
+impl EncodedImage {
+    pub fn media_type(&self) -> &'static str {
+        self.format.media_type()
+    }
+
+    pub fn data_url(&self) -> String {
+        format!("data:{};base64,{}", self.media_type(), self.data)
+    }
+}
+
+impl From<EncodedImage> for String {
+    fn from(image: EncodedImage) -> Self {
+        image.data
+    }
+}
+
+/// Base64 encode image bytes, failing for formats vision models don't accept
+pub fn encode_image_bytes(bytes: &[u8]) -> Result<EncodedImage, AIRequestError> {
+    let data = general_purpose::STANDARD.encode(bytes);
+    match ImageFormat::from_bytes(bytes) {
+        Some(format) => Ok(EncodedImage { data, format }),
+        None => Err(unsupported_image_error(&data)),
+    }
+}
+
+/// Read and base64 encode an image file, see `encode_image_bytes`
+pub fn encode_image_file(path: &str) -> Result<EncodedImage, AIRequestError> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| AIRequestError::Other(format!("Failed to read image file {}: {}", path, e)))?;
+    encode_image_bytes(&bytes)
+}
+
+/// Media type of base64 image data, JPEG when the format isn't recognized
+pub fn image_media_type(data: &str) -> &'static str {
+    ImageFormat::from_base64(data).unwrap_or(ImageFormat::Jpeg).media_type()
+}
+
+/// Data URL for base64 image data, as OpenAI-style APIs expect
+pub fn image_data_url(data: &str) -> String {
+    format!("data:{};base64,{}", image_media_type(data), data)
+}
+
+/// OpenAI-style `image_url` content part
+pub fn image_url_content(data: &str, detail: Option<ImageDetail>) -> serde_json::Value {
+    remote_image_content(&image_data_url(data), detail)
+}
+
+/// OpenAI-style `image_url` content part for an http(s) or data URL, passed through as is
+pub fn remote_image_content(url: &str, detail: Option<ImageDetail>) -> serde_json::Value {
+    let mut image_url = serde_json::json!({ "url": url });
+    if let Some(detail) = detail {
+        image_url["detail"] = serde_json::json!(detail);
+    }
+    serde_json::json!({ "type": "image_url", "image_url": image_url })
+}
+
+/// OpenAI-style `input_audio` content part
+pub fn input_audio_content(audio: &Audio) -> serde_json::Value {
+    serde_json::json!({ "type": "input_audio", "input_audio": { "data": audio.data, "format": audio.format } })
+}
+
+/// Error for providers without audio input when a message carries audio, rather than
+/// dropping it without the model hearing it
+pub fn reject_audio(messages: &[Message], provider: &str) -> Result<(), AIRequestError> {
+    match messages.iter().any(|message| message.audio.as_ref().is_some_and(|audio| !audio.is_empty())) {
+        true => Err(AIRequestError::Other(format!("Audio input is not supported for {} provider", provider))),
+        false => Ok(()),
+    }
+}
+
+/// Check that every image in the messages is PNG, JPEG, GIF or WebP, so unsupported files
+/// fail before the request instead of with an opaque provider error
+pub fn validate_images(messages: &[Message]) -> Result<(), AIRequestError> {
+    for image in messages.iter().filter_map(|message| message.images.as_ref()).flatten() {
+        if ImageFormat::from_base64(image).is_none() {
+            return Err(unsupported_image_error(image));
+        }
+    }
+    Ok(())
+}
+
+// Error naming the format of base64 image data that isn't PNG, JPEG, GIF or WebP
+fn unsupported_image_error(data: &str) -> AIRequestError {
+    let format = match decode_header(data).as_slice() {
+        [_, _, _, _, b'f', b't', b'y', b'p', b'h', b'e', b'i', ..] | [_, _, _, _, b'f', b't', b'y', b'p', b'm', b'i', b'f', ..] => "HEIC",
+        [b'B', b'M', ..] => "BMP",
+        [b'I', b'I', 0x2A, 0x00, ..] | [b'M', b'M', 0x00, 0x2A, ..] => "TIFF",
+        [b'%', b'P', b'D', b'F', ..] => "PDF, attach it as a Document instead",
+        [b'<', ..] => "SVG",
+        _ => "unknown",
+    };
+    AIRequestError::Other(format!(
+        "Unsupported image format ({}), vision models accept PNG, JPEG, GIF and WebP",
+        format
+    ))
+}
+
+/// Largest image a provider accepts. With the `image` feature, images over the limits are
+/// downscaled and recompressed before sending instead of failing with a 4xx
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageLimits {
+    pub max_bytes: usize, // Decoded size
+    pub max_dimension: u32, // Longest side in pixels
+}
+
+impl ImageLimits {
+    pub const ANTHROPIC: ImageLimits = ImageLimits { max_bytes: 5 * 1024 * 1024, max_dimension: 8000 };
+    // OpenAI scales images down to fit 2048x2048 itself, larger ones only cost upload time
+    pub const OPENAI: ImageLimits = ImageLimits { max_bytes: 20 * 1024 * 1024, max_dimension: 2048 };
+    pub const OPENROUTER: ImageLimits = ImageLimits { max_bytes: 20 * 1024 * 1024, max_dimension: 8000 };
+}
+
+/// Shrink image bytes to fit `limits`. Images already within them are returned unchanged,
+/// others are resized and re-encoded, as JPEG unless a smaller PNG fits
+#[cfg(feature = "image")]
+pub fn fit_image(data: &[u8], limits: ImageLimits) -> Result<Vec<u8>, AIRequestError> {
+    use ::image::{DynamicImage, GenericImageView, ImageReader, codecs::jpeg::JpegEncoder, imageops::FilterType};
+    use std::io::Cursor;
+
+    let decode_error = |e: ::image::ImageError| AIRequestError::Other(format!("Failed to process image: {}", e));
+    let reader = ImageReader::new(Cursor::new(data)).with_guessed_format().map_err(AIRequestError::IO)?;
+    let (width, height) = reader.into_dimensions().map_err(decode_error)?;
+    if data.len() <= limits.max_bytes && width.max(height) <= limits.max_dimension {
+        return Ok(data.to_vec());
+    }
+
+    let mut image = ::image::load_from_memory(data).map_err(decode_error)?;
+    if image.width().max(image.height()) > limits.max_dimension {
+        image = image.resize(limits.max_dimension, limits.max_dimension, FilterType::Triangle);
+    }
+
+    if ImageFormat::from_bytes(data) == Some(ImageFormat::Png) {
+        let mut png = Vec::new();
+        image.write_to(&mut Cursor::new(&mut png), ::image::ImageFormat::Png).map_err(decode_error)?;
+        if png.len() <= limits.max_bytes {
+            return Ok(png);
+        }
+    }
+
+    // Lower the JPEG quality first, then halve the resolution until the image fits
+    let mut image = DynamicImage::ImageRgb8(image.to_rgb8());
+    loop {
+        for quality in [85, 70, 55, 40] {
+            let mut jpeg = Vec::new();
+            image
+                .write_with_encoder(JpegEncoder::new_with_quality(&mut jpeg, quality))
+                .map_err(decode_error)?;
+            if jpeg.len() <= limits.max_bytes {
+                return Ok(jpeg);
+            }
+        }
+        let (width, height) = image.dimensions();
+        if width.max(height) <= 64 {
+            return Err(AIRequestError::Other(format!(
+                "Image can't be compressed below {} bytes",
+                limits.max_bytes
+            )));
+        }
+        image = image.resize(width / 2, height / 2, FilterType::Triangle);
+    }
+}
+
+/// Copy of the messages with every image shrunk to fit `limits`, see `fit_image`.
+/// Borrows the messages when all images already fit
+#[cfg(feature = "image")]
+pub fn fit_images(messages: &[Message], limits: ImageLimits) -> Result<std::borrow::Cow<'_, [Message]>, AIRequestError> {
+    let mut resolved: Option<Vec<Message>> = None;
+    for (index, message) in messages.iter().enumerate() {
+        for (image_index, image) in message.images.iter().flatten().enumerate() {
+            let data = general_purpose::STANDARD.decode(image).map_err(|e| AIRequestError::Other(format!("Invalid base64 image: {}", e)))?;
+            let fitted = fit_image(&data, limits)?;
+            if fitted != data {
+                let resolved = resolved.get_or_insert_with(|| messages.to_vec());
+                if let Some(images) = resolved[index].images.as_mut() {
+                    images[image_index] = general_purpose::STANDARD.encode(fitted);
+                }
+            }
+        }
+    }
+    Ok(resolved.map_or(std::borrow::Cow::Borrowed(messages), std::borrow::Cow::Owned))
+}
+
+/// Without the `image` feature images are sent as is
+#[cfg(not(feature = "image"))]
+pub fn fit_images(messages: &[Message], _limits: ImageLimits) -> Result<std::borrow::Cow<'_, [Message]>, AIRequestError> {
+    Ok(std::borrow::Cow::Borrowed(messages))
+}
+
+/// Largest remote image downloaded for providers without URL support, 20 MB
+pub const MAX_IMAGE_DOWNLOAD_BYTES: usize = 20 * 1024 * 1024;
+
+/// Download an image and return it base64 encoded. Fails when the response isn't successful
+/// or the body grows past `max_bytes`, without reading the rest of it
+pub async fn download_image(url: &str, max_bytes: usize) -> Result<String, AIRequestError> {
+    let too_large = || AIRequestError::Other(format!("Image at {} is larger than {} bytes", url, max_bytes));
+    let request_error = |e: reqwest::Error| AIRequestError::Other(format!("Failed to download image {}: {}", url, e));
+
+    let mut response = reqwest::get(url).await.map_err(request_error)?;
+    if !response.status().is_success() {
+        return Err(AIRequestError::Other(format!(
+            "Failed to download image {}: status {}",
+            url,
+            response.status()
+        )));
+    }
+    if response.content_length().is_some_and(|length| length as usize > max_bytes) {
+        return Err(too_large());
+    }
+
+    let mut bytes = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(request_error)? {
+        if bytes.len() + chunk.len() > max_bytes {
+            return Err(too_large());
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(general_purpose::STANDARD.encode(bytes))
+}
+
+/// Copy of the messages with every `image_urls` entry downloaded into `images`, for
+/// providers that only accept inline image data. Borrows the messages when none has URLs
+pub async fn resolve_image_urls(messages: &[Message]) -> Result<std::borrow::Cow<'_, [Message]>, AIRequestError> {
+    if messages.iter().all(|message| message.image_urls.is_none()) {
+        return Ok(std::borrow::Cow::Borrowed(messages));
+    }
+
+    let mut resolved = messages.to_vec();
+    for message in &mut resolved {
+        let Some(urls) = message.image_urls.take() else {
+            continue;
+        };
+        let images = message.images.get_or_insert_with(Vec::new);
+        for url in urls {
+            images.push(download_image(&url, MAX_IMAGE_DOWNLOAD_BYTES).await?);
+        }
+    }
+    Ok(std::borrow::Cow::Owned(resolved))
+}
+
+/// Resolution OpenAI-style vision models look at an image with. Low is cheaper and faster
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageDetail {
+    Auto,
+    Low,
+    High,
+}
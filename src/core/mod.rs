@@ -1,9 +1,73 @@
 pub mod types;
 pub mod tool;
+pub mod tool_schema;
 pub mod error;
 pub mod fallback;
+pub mod registry;
+pub mod schema;
+pub mod progress;
+pub mod structured;
+pub mod audio;
+pub mod image;
+pub mod media;
+pub mod guardrail;
+pub mod options;
+pub mod pricing;
+pub mod stream;
+pub mod debug;
+pub mod tags;
+pub mod job;
+pub mod conversation;
+pub mod classify;
+pub mod language;
+pub mod redaction;
+pub mod validator;
+pub mod persona;
+pub mod chat_template;
+pub mod checkpoint;
+pub mod history;
+pub mod edit;
+pub mod pacing;
+pub mod markdown;
+pub mod quota;
+pub mod sink;
+pub mod clock;
+#[cfg(feature = "video")]
+pub mod video;
 
 pub use types::*;
 pub use tool::*;
+pub use tool_schema::*;
 pub use error::*;
-pub use fallback::*;
\ No newline at end of file
+pub use fallback::*;
+pub use registry::*;
+pub use schema::*;
+pub use progress::*;
+pub use structured::*;
+pub use audio::*;
+pub use image::*;
+pub use media::*;
+pub use guardrail::*;
+pub use options::*;
+pub use pricing::*;
+pub use stream::*;
+pub use debug::*;
+pub use tags::*;
+pub use job::*;
+pub use conversation::*;
+pub use classify::*;
+pub use language::*;
+pub use redaction::*;
+pub use validator::*;
+pub use persona::*;
+pub use chat_template::*;
+pub use checkpoint::*;
+pub use history::*;
+pub use edit::*;
+pub use pacing::*;
+pub use markdown::*;
+pub use quota::*;
+pub use sink::*;
+pub use clock::*;
+#[cfg(feature = "video")]
+pub use video::*;
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::RequestTags;
+
+/// Sampling and output settings understood by every provider. Each provider ignores the
+/// fields it doesn't support, `MonoAI::unsupported_options` lists them
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GenerationOptions {
+    pub temperature: Option<f32>,
+    pub max_tokens: Option<u32>,
+    pub seed: Option<i64>,
+    pub n: Option<u32>, // Completions to generate, items of the extra ones carry `ChatStreamItem.choice_index`
+    pub presence_penalty: Option<f32>, // -2.0 - 2.0
+    pub frequency_penalty: Option<f32>, // -2.0 - 2.0
+    pub logit_bias: Option<HashMap<u32, f32>>, // Token ID to bias, -100 - 100
+    pub user: Option<String>, // End-user identifier for the provider's abuse monitoring
+    pub tags: Option<RequestTags>, // Metadata for usage records and debug logs, understood by every provider
+    pub extensions: Option<Map<String, Value>>, // Provider-specific body fields, merged into the request as-is
+}
+
+/// Seed of the `GenerationOptions::deterministic` preset
+pub const DETERMINISTIC_SEED: i64 = 42;
+
+impl GenerationOptions {
+    /// Preset for reproducible runs, e.g. tests and evals: temperature 0 and a fixed seed.
+    /// `MonoAI::determinism` tells which of them the provider honors
+    pub fn deterministic() -> Self {
+        Self::default().with_greedy().with_seed(DETERMINISTIC_SEED)
+    }
+
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    /// Temperature 0, the model always picks its most likely token
+    pub fn with_greedy(self) -> Self {
+        self.with_temperature(0.0)
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    pub fn with_seed(mut self, seed: i64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Number of completions to generate. More than one is refused while tools are registered
+    pub fn with_n(mut self, n: u32) -> Self {
+        self.n = Some(n);
+        self
+    }
+
+    pub fn with_presence_penalty(mut self, penalty: f32) -> Self {
+        self.presence_penalty = Some(penalty);
+        self
+    }
+
+    pub fn with_frequency_penalty(mut self, penalty: f32) -> Self {
+        self.frequency_penalty = Some(penalty);
+        self
+    }
+
+    pub fn with_logit_bias(mut self, logit_bias: HashMap<u32, f32>) -> Self {
+        self.logit_bias = Some(logit_bias);
+        self
+    }
+
+    pub fn with_user(mut self, user: &str) -> Self {
+        self.user = Some(user.to_string());
+        self
+    }
+
+    pub fn with_tags(mut self, tags: RequestTags) -> Self {
+        self.tags = Some(tags);
+        self
+    }
+
+    /// Provider-specific request field, e.g. OpenRouter's `transforms`. Objects are merged into
+    /// what the crate sends, so `{"options": {"num_keep": 5}}` keeps Ollama's other options
+    pub fn with_extension(mut self, key: &str, value: Value) -> Self {
+        self.extensions.get_or_insert_with(Map::new).insert(key.to_string(), value);
+        self
+    }
+
+    pub fn with_extensions(mut self, extensions: Map<String, Value>) -> Self {
+        self.extensions = Some(extensions);
+        self
+    }
+
+    /// `user`, or else the user ID tag
+    pub fn end_user(&self) -> Option<String> {
+        self.user.clone().or_else(|| self.tags.as_ref().and_then(|tags| tags.user_id()).map(str::to_string))
+    }
+
+    /// These options with unset fields taken from `defaults`
+    pub fn or_defaults(&self, defaults: &GenerationOptions) -> GenerationOptions {
+        GenerationOptions {
+            temperature: self.temperature.or(defaults.temperature),
+            max_tokens: self.max_tokens.or(defaults.max_tokens),
+            seed: self.seed.or(defaults.seed),
+            n: self.n.or(defaults.n),
+            presence_penalty: self.presence_penalty.or(defaults.presence_penalty),
+            frequency_penalty: self.frequency_penalty.or(defaults.frequency_penalty),
+            logit_bias: self.logit_bias.clone().or_else(|| defaults.logit_bias.clone()),
+            user: self.user.clone().or_else(|| defaults.user.clone()),
+            tags: self.tags.clone().or_else(|| defaults.tags.clone()),
+            extensions: match (&self.extensions, &defaults.extensions) {
+                (Some(extensions), Some(defaults)) => {
+                    let mut merged = Value::Object(defaults.clone());
+                    merge_extensions(&mut merged, Some(extensions));
+                    merged.as_object().cloned()
+                }
+                (extensions, defaults) => extensions.clone().or_else(|| defaults.clone()),
+            },
+        }
+    }
+
+    /// Names of the fields that are set
+    pub fn set_fields(&self) -> Vec<&'static str> {
+        let fields = [
+            ("temperature", self.temperature.is_some()),
+            ("max_tokens", self.max_tokens.is_some()),
+            ("seed", self.seed.is_some()),
+            ("n", self.n.is_some()),
+            ("presence_penalty", self.presence_penalty.is_some()),
+            ("frequency_penalty", self.frequency_penalty.is_some()),
+            ("logit_bias", self.logit_bias.is_some()),
+            ("user", self.user.is_some()),
+        ];
+        fields.into_iter().filter(|(_, set)| *set).map(|(name, _)| name).collect()
+    }
+
+    /// Set fields missing from a provider's `SUPPORTED_OPTIONS`
+    pub fn unsupported(&self, supported: &[&str]) -> Vec<&'static str> {
+        self.set_fields().into_iter().filter(|field| !supported.contains(field)).collect()
+    }
+
+    /// Which of the set sampling knobs a provider with these `SUPPORTED_OPTIONS` honors
+    pub fn determinism(&self, supported: &[&str], reproducibility: Reproducibility) -> DeterminismReport {
+        let (honored, ignored): (Vec<_>, Vec<_>) = self
+            .set_fields()
+            .into_iter()
+            .filter(|field| SAMPLING_FIELDS.contains(field))
+            .partition(|field| supported.contains(field));
+        let greedy = self.temperature == Some(0.0) && honored.contains(&"temperature");
+        DeterminismReport { honored, ignored, greedy, reproducibility }
+    }
+}
+
+/// Merges `GenerationOptions::extensions` into a request body: objects key by key, anything
+/// else replaces the value the crate set
+pub(crate) fn merge_extensions(body: &mut Value, extensions: Option<&Map<String, Value>>) {
+    let (Some(body), Some(extensions)) = (body.as_object_mut(), extensions) else {
+        return;
+    };
+    for (key, value) in extensions {
+        match (body.get_mut(key), value) {
+            (Some(existing @ Value::Object(_)), Value::Object(fields)) => merge_extensions(existing, Some(fields)),
+            _ => {
+                body.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// A typed request body with the extensions merged in
+pub(crate) fn request_body<T: Serialize>(request: &T, extensions: Option<&Map<String, Value>>) -> Result<Value, serde_json::Error> {
+    let mut body = serde_json::to_value(request)?;
+    merge_extensions(&mut body, extensions);
+    Ok(body)
+}
+
+// Fields that change which tokens are sampled
+const SAMPLING_FIELDS: &[&str] = &["temperature", "seed", "presence_penalty", "frequency_penalty", "logit_bias"];
+
+/// How far a provider reproduces a reply to the same request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reproducibility {
+    /// Replies don't depend on sampling, as with the scripted mock
+    Always,
+    /// The same seed and prompt give the same reply on the same model and hardware
+    Exact,
+    /// The seed is honored, but replies may still change, e.g. when the backend is updated
+    BestEffort,
+    /// No seed, temperature 0 only makes matching replies likely
+    Unseeded,
+}
+
+/// Sampling knobs a provider honors for a request, see `MonoAI::determinism`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeterminismReport {
+    pub honored: Vec<&'static str>, // Set sampling fields sent to the provider
+    pub ignored: Vec<&'static str>, // Set sampling fields the provider drops
+    pub greedy: bool, // Temperature 0 reaches the provider
+    pub reproducibility: Reproducibility,
+}
+
+impl DeterminismReport {
+    /// Replies can be expected to repeat: greedy or seeded sampling on a provider that honors it
+    pub fn is_reproducible(&self) -> bool {
+        match self.reproducibility {
+            Reproducibility::Always => true,
+            Reproducibility::Exact | Reproducibility::BestEffort => self.greedy || self.honored.contains(&"seed"),
+            Reproducibility::Unseeded => false,
+        }
+    }
+}
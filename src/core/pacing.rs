@@ -0,0 +1,398 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use futures_util::{FutureExt, StreamExt};
+use tokio::time::Instant;
+
+use super::{ChatStream, ChatStreamItem};
+
+/// Piece of text a `typewriter` stream emits at a time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypewriterUnit {
+    /// A user-perceived character. Combining marks, emoji ZWJ sequences and flags stay together
+    Grapheme,
+    /// A word with the whitespace before it
+    Word,
+}
+
+/// Pacing of a `typewriter` stream
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypewriterOptions {
+    pub unit: TypewriterUnit,
+    pub interval: Duration, // Between units
+    pub max_backlog: usize, // Units waiting beyond this are emitted together, so the text never falls far behind
+}
+
+impl Default for TypewriterOptions {
+    /// A grapheme every 10 ms, catching up beyond 200 waiting
+    fn default() -> Self {
+        Self {
+            unit: TypewriterUnit::Grapheme,
+            interval: Duration::from_millis(10),
+            max_backlog: 200,
+        }
+    }
+}
+
+impl TypewriterOptions {
+    /// A word every `interval`, catching up beyond 40 waiting
+    pub fn words(interval: Duration) -> Self {
+        Self { unit: TypewriterUnit::Word, interval, max_backlog: 40 }
+    }
+
+    pub fn with_unit(mut self, unit: TypewriterUnit) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn with_max_backlog(mut self, max_backlog: usize) -> Self {
+        self.max_backlog = max_backlog;
+        self
+    }
+}
+
+// What the typewriter emits next: a unit of text, or an item passed on without its content
+enum Paced {
+    Text(String),
+    Item(Box<Result<ChatStreamItem, String>>),
+}
+
+struct Typewriter {
+    source: Option<ChatStream>, // None once it ended
+    options: TypewriterOptions,
+    queue: VecDeque<Paced>,
+    units: usize, // Text entries in the queue
+    partial: String, // Trailing text that may continue in the next item
+    next_at: Instant,
+}
+
+impl Typewriter {
+    fn push(&mut self, item: Result<ChatStreamItem, String>) {
+        let mut item = match item {
+            // Extra completions and errors aren't paced
+            Ok(item) if item.choice_index.is_none() => item,
+            item => {
+                self.flush_partial();
+                self.queue.push_back(Paced::Item(Box::new(item)));
+                return;
+            }
+        };
+
+        self.partial.push_str(&std::mem::take(&mut item.content));
+        let text = std::mem::take(&mut self.partial);
+        let mut units = match self.options.unit {
+            TypewriterUnit::Grapheme => split_graphemes(&text),
+            TypewriterUnit::Word => split_words(&text),
+        };
+        // The last unit may still grow, e.g. a word or a grapheme with combining marks
+        if let Some(last) = units.pop() {
+            self.partial = last.to_string();
+        }
+        for unit in units {
+            self.push_text(unit.to_string());
+        }
+
+        let content_only = item.thinking.is_none()
+            && item.thinking_blocks.is_none()
+            && item.citations.is_none()
+            && item.server_tool_event.is_none()
+            && item.tool_calls.is_none()
+            && item.usage.is_none()
+            && item.timing.is_none()
+            && item.guardrail_flags.is_none()
+            && item.finish_reason.is_none()
+            && !item.done;
+        if !content_only {
+            self.flush_partial();
+            self.queue.push_back(Paced::Item(Box::new(Ok(item))));
+        }
+    }
+
+    fn push_text(&mut self, text: String) {
+        self.queue.push_back(Paced::Text(text));
+        self.units += 1;
+    }
+
+    fn flush_partial(&mut self) {
+        if !self.partial.is_empty() {
+            let partial = std::mem::take(&mut self.partial);
+            self.push_text(partial);
+        }
+    }
+
+    // Items the source has ready, without waiting for more
+    fn pull_ready(&mut self) {
+        while let Some(source) = &mut self.source {
+            match source.next().now_or_never() {
+                Some(Some(item)) => self.push(item),
+                Some(None) => {
+                    self.source = None;
+                    self.flush_partial();
+                }
+                None => break,
+            }
+        }
+    }
+
+    async fn next(&mut self) -> Option<Result<ChatStreamItem, String>> {
+        loop {
+            self.pull_ready();
+            match self.queue.pop_front() {
+                Some(Paced::Item(item)) => return Some(*item),
+                Some(Paced::Text(text)) => {
+                    self.units -= 1;
+                    tokio::time::sleep_until(self.next_at).await;
+                    self.pull_ready();
+                    return Some(Ok(self.emit_text(text)));
+                }
+                None => {
+                    match self.source.as_mut()?.next().await {
+                        Some(item) => self.push(item),
+                        None => {
+                            self.source = None;
+                            self.flush_partial();
+                        }
+                    }
+                    // Text arriving after a pause starts right away
+                    self.next_at = self.next_at.max(Instant::now());
+                }
+            }
+        }
+    }
+
+    // The unit, with the units waiting beyond the backlog limit joined to it
+    fn emit_text(&mut self, mut content: String) -> ChatStreamItem {
+        for _ in 0..self.units.saturating_sub(self.options.max_backlog) {
+            match self.queue.pop_front() {
+                Some(Paced::Text(text)) => {
+                    content.push_str(&text);
+                    self.units -= 1;
+                }
+                Some(item) => {
+                    self.queue.push_front(item);
+                    break;
+                }
+                None => break,
+            }
+        }
+        self.next_at = Instant::now().max(self.next_at) + self.options.interval;
+        text_item(content)
+    }
+}
+
+/// Re-chunk a chat stream into evenly paced graphemes or words, for typewriter output that looks
+/// the same whatever chunk sizes the provider sends. Items carrying more than text (tool calls,
+/// usage, the done item) follow the text before them, extra completions and errors pass unpaced
+pub fn typewriter(stream: ChatStream, options: TypewriterOptions) -> ChatStream {
+    let typewriter = Typewriter {
+        source: Some(stream),
+        options,
+        queue: VecDeque::new(),
+        units: 0,
+        partial: String::new(),
+        next_at: Instant::now(),
+    };
+    Box::pin(futures_util::stream::unfold(typewriter, |mut typewriter| async move {
+        let item = typewriter.next().await?;
+        Some((item, typewriter))
+    }))
+}
+
+/// When a `coalesce` stream flushes the deltas it batched
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoalesceOptions {
+    pub interval: Duration, // After the first delta of a batch
+    pub max_chars: usize, // Text in a batch that flushes it right away
+}
+
+impl Default for CoalesceOptions {
+    /// Every 50 ms or 512 characters
+    fn default() -> Self {
+        Self { interval: Duration::from_millis(50), max_chars: 512 }
+    }
+}
+
+impl CoalesceOptions {
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn with_max_chars(mut self, max_chars: usize) -> Self {
+        self.max_chars = max_chars;
+        self
+    }
+}
+
+struct Coalescer {
+    source: Option<ChatStream>, // None once it ended
+    options: CoalesceOptions,
+    content: String,
+    thinking: String,
+    chars: usize,
+    deadline: Option<Instant>, // Set while a batch is open
+    next: Option<Result<ChatStreamItem, String>>, // Passed on after the batch it flushed
+}
+
+impl Coalescer {
+    async fn next(&mut self) -> Option<Result<ChatStreamItem, String>> {
+        loop {
+            if let Some(item) = self.next.take() {
+                return Some(item);
+            }
+            let source = match &mut self.source {
+                Some(source) => source,
+                None => return self.deadline.is_some().then(|| Ok(self.flush())),
+            };
+            let item = match self.deadline {
+                Some(deadline) => match tokio::time::timeout_at(deadline, source.next()).await {
+                    Ok(item) => item,
+                    Err(_) => return Some(Ok(self.flush())),
+                },
+                None => source.next().await,
+            };
+
+            match item {
+                None => self.source = None,
+                Some(Ok(mut item)) if is_delta(&item) => {
+                    let content = std::mem::take(&mut item.content);
+                    let thinking = item.thinking.unwrap_or_default();
+                    self.chars += content.chars().count() + thinking.chars().count();
+                    self.content.push_str(&content);
+                    self.thinking.push_str(&thinking);
+                    self.deadline.get_or_insert_with(|| Instant::now() + self.options.interval);
+                    if self.chars >= self.options.max_chars {
+                        return Some(Ok(self.flush()));
+                    }
+                }
+                // Everything else flushes the batch before it
+                Some(item) if self.deadline.is_some() => {
+                    self.next = Some(item);
+                    return Some(Ok(self.flush()));
+                }
+                Some(item) => return Some(item),
+            }
+        }
+    }
+
+    fn flush(&mut self) -> ChatStreamItem {
+        self.deadline = None;
+        self.chars = 0;
+        let thinking = std::mem::take(&mut self.thinking);
+        ChatStreamItem {
+            thinking: Some(thinking).filter(|thinking| !thinking.is_empty()),
+            ..text_item(std::mem::take(&mut self.content))
+        }
+    }
+}
+
+// Content or thinking text of the first completion and nothing else
+fn is_delta(item: &ChatStreamItem) -> bool {
+    item.choice_index.is_none()
+        && item.thinking_blocks.is_none()
+        && item.citations.is_none()
+        && item.server_tool_event.is_none()
+        && item.tool_calls.is_none()
+        && item.usage.is_none()
+        && item.timing.is_none()
+        && item.guardrail_flags.is_none()
+        && item.finish_reason.is_none()
+        && !item.done
+}
+
+/// Batch a chat stream's small content and thinking deltas into one item per `interval` or
+/// `max_chars`, to cut per-message overhead when forwarding to websockets. Tool calls, usage,
+/// the done item, extra completions and errors flush the batch and pass on right away
+pub fn coalesce(stream: ChatStream, options: CoalesceOptions) -> ChatStream {
+    let coalescer = Coalescer {
+        source: Some(stream),
+        options,
+        content: String::new(),
+        thinking: String::new(),
+        chars: 0,
+        deadline: None,
+        next: None,
+    };
+    Box::pin(futures_util::stream::unfold(coalescer, |mut coalescer| async move {
+        let item = coalescer.next().await?;
+        Some((item, coalescer))
+    }))
+}
+
+fn text_item(content: String) -> ChatStreamItem {
+    ChatStreamItem {
+        content,
+        ..Default::default()
+    }
+}
+
+// Words with the whitespace before them, trailing whitespace is its own unit
+fn split_words(text: &str) -> Vec<&str> {
+    let mut units = Vec::new();
+    let mut start = 0;
+    let mut in_word = false;
+    for (index, c) in text.char_indices() {
+        if c.is_whitespace() && in_word {
+            units.push(&text[start..index]);
+            start = index;
+            in_word = false;
+        } else if !c.is_whitespace() {
+            in_word = true;
+        }
+    }
+    if start < text.len() {
+        units.push(&text[start..]);
+    }
+    units
+}
+
+// Approximate grapheme clusters: a character with the combining marks, variation selectors and
+// skin tones after it, characters joined by ZWJ, and regional indicator pairs
+fn split_graphemes(text: &str) -> Vec<&str> {
+    let mut units = Vec::new();
+    let mut start = 0;
+    let mut previous: Option<char> = None;
+    let mut regional_run = 0;
+    for (index, c) in text.char_indices() {
+        let joins = match previous {
+            None => false,
+            Some('\u{200D}') => true,
+            Some(previous) if is_regional_indicator(previous) && is_regional_indicator(c) => regional_run % 2 == 1,
+            Some(_) => is_extending(c),
+        };
+        if !joins && index > 0 {
+            units.push(&text[start..index]);
+            start = index;
+        }
+        regional_run = if is_regional_indicator(c) { regional_run + 1 } else { 0 };
+        previous = Some(c);
+    }
+    if start < text.len() {
+        units.push(&text[start..]);
+    }
+    units
+}
+
+fn is_extending(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036F}' // Combining diacritical marks
+        | '\u{1AB0}'..='\u{1AFF}'
+        | '\u{1DC0}'..='\u{1DFF}'
+        | '\u{20D0}'..='\u{20FF}'
+        | '\u{FE20}'..='\u{FE2F}'
+        | '\u{FE00}'..='\u{FE0F}' // Variation selectors
+        | '\u{E0100}'..='\u{E01EF}'
+        | '\u{1F3FB}'..='\u{1F3FF}' // Skin tones
+        | '\u{E0020}'..='\u{E007F}' // Emoji tag sequences
+        | '\u{200D}'
+    )
+}
+
+fn is_regional_indicator(c: char) -> bool {
+    ('\u{1F1E6}'..='\u{1F1FF}').contains(&c)
+}
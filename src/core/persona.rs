@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+
+use super::{GenerationOptions, Message};
+
+/// An assistant profile: system prompt, default options, preferred model and the tools it may
+/// use. Attached to a `MonoAI` client or a `Conversation`, it applies to every request sent
+/// with it and can be swapped between requests
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Persona {
+    pub name: String,
+    pub system_prompt: String,
+    pub options: GenerationOptions, // Defaults, fields set on a request win
+    pub model: Option<String>, // Same provider, None for the client's model
+    pub tools: Option<Vec<String>>, // Registry patterns (`name`, `namespace.*`, ...), None for every tool
+}
+
+impl Persona {
+    pub fn new(name: &str, system_prompt: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            system_prompt: system_prompt.to_string(),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_options(mut self, options: GenerationOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn with_model(mut self, model: &str) -> Self {
+        self.model = Some(model.to_string());
+        self
+    }
+
+    /// Only offer the tools matching these registry patterns
+    pub fn with_tools(mut self, patterns: &[&str]) -> Self {
+        self.tools = Some(patterns.iter().map(|pattern| pattern.to_string()).collect());
+        self
+    }
+
+    /// The conversation with the persona's system prompt first. A system message at the start
+    /// of the conversation is kept after it
+    pub fn apply(&self, messages: &[Message]) -> Vec<Message> {
+        let mut messages = messages.to_vec();
+        if self.system_prompt.is_empty() {
+            return messages;
+        }
+        match messages.first_mut().filter(|message| message.role == "system") {
+            Some(system) => system.content = format!("{}\n\n{}", self.system_prompt, system.content),
+            None => messages.insert(0, Message::system(self.system_prompt.clone())),
+        }
+        messages
+    }
+}
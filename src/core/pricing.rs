@@ -0,0 +1,132 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, LazyLock, RwLock};
+
+/// Price of a model in USD per token
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ModelPricing {
+    pub input: f64,
+    pub output: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached_input: Option<f64>, // Prompt tokens read from the prompt cache, `input` when unknown
+}
+
+impl ModelPricing {
+    pub fn per_million(input: f64, output: f64) -> Self {
+        Self {
+            input: input / 1_000_000.0,
+            output: output / 1_000_000.0,
+            cached_input: None,
+        }
+    }
+
+    pub fn with_cached_input_per_million(mut self, cached_input: f64) -> Self {
+        self.cached_input = Some(cached_input / 1_000_000.0);
+        self
+    }
+
+    pub fn cost(&self, prompt_tokens: u32, completion_tokens: u32) -> f64 {
+        prompt_tokens as f64 * self.input + completion_tokens as f64 * self.output
+    }
+
+    /// Cost with the `cached_prompt_tokens` part of the prompt at the cached input rate
+    pub fn cost_with_cache(&self, prompt_tokens: u32, cached_prompt_tokens: u32, completion_tokens: u32) -> f64 {
+        let cached_prompt_tokens = cached_prompt_tokens.min(prompt_tokens);
+        let cached_input = self.cached_input.unwrap_or(self.input);
+        self.cost(prompt_tokens - cached_prompt_tokens, completion_tokens) + cached_prompt_tokens as f64 * cached_input
+    }
+}
+
+/// Prices consulted for cost tracking before the snapshot bundled with each provider
+pub trait PricingSource: Send + Sync {
+    fn pricing(&self, model: &str) -> Option<ModelPricing>;
+}
+
+/// Fixed prices by model name, e.g. loaded from a JSON file or URL
+#[derive(Debug, Clone, Default)]
+pub struct PricingSnapshot {
+    pub models: HashMap<String, ModelPricing>,
+}
+
+// Per-million prices as written in pricing JSON
+#[derive(Deserialize)]
+struct PricingEntry {
+    input: f64,
+    output: f64,
+    #[serde(default)]
+    cached_input: Option<f64>,
+}
+
+impl PricingSnapshot {
+    /// Parse `{"gpt-4o": {"input": 2.5, "output": 10.0, "cached_input": 1.25}, ...}` with USD per
+    /// million tokens, `cached_input` being optional
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        let entries: HashMap<String, PricingEntry> = serde_json::from_str(json)?;
+        Ok(Self {
+            models: entries
+                .into_iter()
+                .map(|(model, entry)| {
+                    let pricing = ModelPricing::per_million(entry.input, entry.output);
+                    (model, entry.cached_input.map_or(pricing, |cached_input| pricing.with_cached_input_per_million(cached_input)))
+                })
+                .collect(),
+        })
+    }
+
+    pub async fn fetch(url: &str) -> Result<Self, Box<dyn Error>> {
+        let response = reqwest::get(url).await?;
+        if !response.status().is_success() {
+            return Err(format!("Pricing fetch failed with status {}", response.status()).into());
+        }
+        Ok(Self::from_json(&response.text().await?)?)
+    }
+}
+
+impl PricingSource for PricingSnapshot {
+    fn pricing(&self, model: &str) -> Option<ModelPricing> {
+        self.models.get(model).copied()
+    }
+}
+
+#[derive(Default)]
+struct PricingState {
+    overrides: HashMap<String, ModelPricing>,
+    source: Option<Arc<dyn PricingSource>>,
+}
+
+static PRICING: LazyLock<RwLock<PricingState>> = LazyLock::new(|| RwLock::new(PricingState::default()));
+
+/// Override the price of a model in USD per million tokens, takes precedence over every source
+pub fn set_pricing(model: &str, input_per_million: f64, output_per_million: f64) {
+    let mut state = PRICING.write().unwrap_or_else(|e| e.into_inner());
+    state.overrides.insert(model.to_string(), ModelPricing::per_million(input_per_million, output_per_million));
+}
+
+pub fn clear_pricing_overrides() {
+    PRICING.write().unwrap_or_else(|e| e.into_inner()).overrides.clear();
+}
+
+/// Consult `source` after the overrides and before the bundled snapshot, replacing any previous source
+pub fn set_pricing_source(source: impl PricingSource + 'static) {
+    PRICING.write().unwrap_or_else(|e| e.into_inner()).source = Some(Arc::new(source));
+}
+
+/// Load prices from a JSON document at `url`, see `PricingSnapshot::from_json` for the format.
+/// Returns the number of models priced
+pub async fn refresh_pricing(url: &str) -> Result<usize, Box<dyn Error>> {
+    let snapshot = PricingSnapshot::fetch(url).await?;
+    let count = snapshot.models.len();
+    set_pricing_source(snapshot);
+    Ok(count)
+}
+
+/// Price from the overrides or the configured source, None when neither knows the model
+pub fn lookup_pricing(model: &str) -> Option<ModelPricing> {
+    let state = PRICING.read().unwrap_or_else(|e| e.into_inner());
+    state
+        .overrides
+        .get(model)
+        .copied()
+        .or_else(|| state.source.as_ref().and_then(|source| source.pricing(model)))
+}
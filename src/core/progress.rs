@@ -0,0 +1,58 @@
+use std::cell::RefCell;
+use std::sync::Arc;
+
+use crate::core::ToolProgress;
+
+thread_local! {
+    static CURRENT_REPORTER: RefCell<Option<ProgressReporter>> = const { RefCell::new(None) };
+}
+
+/// Handle a running tool uses to report progress, clone it to report from other threads
+#[derive(Clone)]
+pub struct ProgressReporter {
+    tool_call_id: Option<String>,
+    tool_name: String,
+    sink: Arc<dyn Fn(ToolProgress) + Send + Sync>,
+}
+
+impl ProgressReporter {
+    pub fn new(tool_call_id: Option<String>, tool_name: &str, sink: Arc<dyn Fn(ToolProgress) + Send + Sync>) -> Self {
+        Self {
+            tool_call_id,
+            tool_name: tool_name.to_string(),
+            sink,
+        }
+    }
+
+    pub fn report(&self, message: &str, fraction: Option<f32>) {
+        (self.sink)(ToolProgress {
+            tool_call_id: self.tool_call_id.clone(),
+            tool_name: self.tool_name.clone(),
+            message: message.to_string(),
+            fraction: fraction.map(|f| f.clamp(0.0, 1.0)),
+        });
+    }
+
+    /// Run `f` with this reporter installed for the current thread
+    pub fn scope<R>(&self, f: impl FnOnce() -> R) -> R {
+        let previous = CURRENT_REPORTER.with(|current| current.replace(Some(self.clone())));
+        let result = f();
+        CURRENT_REPORTER.with(|current| *current.borrow_mut() = previous);
+        result
+    }
+}
+
+/// Report progress from inside a tool function. Does nothing when the tool is not
+/// executed through `handle_tool_calls_with_progress`
+pub fn report_progress(message: &str, fraction: Option<f32>) {
+    CURRENT_REPORTER.with(|current| {
+        if let Some(reporter) = current.borrow().as_ref() {
+            reporter.report(message, fraction);
+        }
+    });
+}
+
+/// Reporter for the tool running on this thread, for tools that hand work to other threads
+pub fn current_progress_reporter() -> Option<ProgressReporter> {
+    CURRENT_REPORTER.with(|current| current.borrow().clone())
+}
@@ -0,0 +1,162 @@
+use std::time::{Duration, SystemTime};
+
+use reqwest::header::HeaderMap;
+
+/// One rate limit window of a provider
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RateLimit {
+    pub limit: Option<u64>,
+    pub remaining: Option<u64>,
+    pub reset: Option<SystemTime>, // When `remaining` is back at `limit`
+}
+
+/// Remaining requests, tokens and credits, see `MonoAI::get_quota`. What a provider doesn't
+/// report stays None
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Quota {
+    pub requests: Option<RateLimit>,
+    pub tokens: Option<RateLimit>,
+    pub input_tokens: Option<RateLimit>, // Anthropic
+    pub output_tokens: Option<RateLimit>, // Anthropic
+    pub credits_remaining: Option<f64>, // USD, OpenRouter keys with a credit limit
+    pub credits_used: Option<f64>, // USD, OpenRouter
+    pub observed_at: Option<SystemTime>, // When the response reporting it arrived
+}
+
+impl Quota {
+    /// When every used-up window has reset, before which requests would be rejected
+    pub fn exhausted_until(&self) -> Option<SystemTime> {
+        [self.requests, self.tokens, self.input_tokens, self.output_tokens]
+            .into_iter()
+            .flatten()
+            .filter(|limit| limit.remaining == Some(0))
+            .filter_map(|limit| limit.reset)
+            .max()
+    }
+
+    /// How long to hold off before the next request to avoid a 429: until the reset when a window
+    /// is used up, and a growing share of the time to the reset once less than `headroom` (a
+    /// fraction of the limit) remains. None while every window has room or the resets passed
+    pub fn throttle_delay(&self, headroom: f64) -> Option<Duration> {
+        self.throttle_delay_at(headroom, SystemTime::now())
+    }
+
+    /// `throttle_delay` as of `now`, e.g. the time of a `Clock`
+    pub fn throttle_delay_at(&self, headroom: f64, now: SystemTime) -> Option<Duration> {
+        [self.requests, self.tokens, self.input_tokens, self.output_tokens]
+            .into_iter()
+            .flatten()
+            .filter_map(|window| {
+                let until_reset = window.reset?.duration_since(now).ok()?;
+                let remaining = window.remaining? as f64;
+                let threshold = window.limit.map_or(0.0, |limit| limit as f64 * headroom);
+                if remaining == 0.0 {
+                    Some(until_reset)
+                } else if remaining < threshold {
+                    Some(until_reset.mul_f64(1.0 - remaining / threshold))
+                } else {
+                    None
+                }
+            })
+            .max()
+    }
+}
+
+/// Rate limits in a response's headers: OpenAI's `x-ratelimit-*` or Anthropic's
+/// `anthropic-ratelimit-*`. None when the response has none
+pub(crate) fn quota_from_headers(headers: &HeaderMap) -> Option<Quota> {
+    let now = SystemTime::now();
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+    let number = |name: &str| header(name).and_then(|value| value.trim().parse().ok());
+
+    // OpenAI: x-ratelimit-remaining-requests, resets as durations such as "6m0s"
+    let openai = |kind: &str| {
+        let limit = RateLimit {
+            limit: number(&format!("x-ratelimit-limit-{}", kind)),
+            remaining: number(&format!("x-ratelimit-remaining-{}", kind)),
+            reset: header(&format!("x-ratelimit-reset-{}", kind)).and_then(parse_duration).map(|reset| now + reset),
+        };
+        Some(limit).filter(|limit| *limit != RateLimit::default())
+    };
+    // Anthropic: anthropic-ratelimit-requests-remaining, resets as RFC 3339 timestamps
+    let anthropic = |kind: &str| {
+        let limit = RateLimit {
+            limit: number(&format!("anthropic-ratelimit-{}-limit", kind)),
+            remaining: number(&format!("anthropic-ratelimit-{}-remaining", kind)),
+            reset: header(&format!("anthropic-ratelimit-{}-reset", kind)).and_then(parse_timestamp),
+        };
+        Some(limit).filter(|limit| *limit != RateLimit::default())
+    };
+
+    let quota = Quota {
+        requests: openai("requests").or_else(|| anthropic("requests")),
+        tokens: openai("tokens").or_else(|| anthropic("tokens")),
+        input_tokens: anthropic("input-tokens"),
+        output_tokens: anthropic("output-tokens"),
+        ..Quota::default()
+    };
+    (quota != Quota::default()).then_some(Quota { observed_at: Some(now), ..quota })
+}
+
+// Go-style durations as OpenAI sends them: "1s", "6m0s", "1h2m3.5s", "20ms"
+fn parse_duration(text: &str) -> Option<Duration> {
+    let mut total = 0.0;
+    let mut rest = text.trim();
+    if rest.is_empty() {
+        return None;
+    }
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit() && c != '.')?;
+        let value: f64 = rest[..digits].parse().ok()?;
+        rest = &rest[digits..];
+        let unit = rest.find(|c: char| c.is_ascii_digit()).unwrap_or(rest.len());
+        total += value
+            * match &rest[..unit] {
+                "ms" => 0.001,
+                "s" => 1.0,
+                "m" => 60.0,
+                "h" => 3600.0,
+                _ => return None,
+            };
+        rest = &rest[unit..];
+    }
+    Some(Duration::from_secs_f64(total))
+}
+
+// RFC 3339 timestamps as Anthropic sends them: "2025-01-01T12:00:00Z", with optional
+// fractional seconds and UTC offset
+fn parse_timestamp(text: &str) -> Option<SystemTime> {
+    let text = text.trim();
+    let number = |range: std::ops::Range<usize>| -> Option<i64> { text.get(range)?.parse().ok() };
+    let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+    let (hour, minute, second) = (number(11..13)?, number(14..16)?, number(17..19)?);
+
+    let mut rest = &text[19..];
+    let mut fraction = 0.0;
+    if let Some(digits) = rest.strip_prefix('.') {
+        let end = digits.find(|c: char| !c.is_ascii_digit()).unwrap_or(digits.len());
+        fraction = format!("0.{}", &digits[..end]).parse().ok()?;
+        rest = &digits[end..];
+    }
+    let offset = match rest {
+        "Z" | "z" | "" => 0,
+        offset => {
+            let sign = if offset.starts_with('-') { -1 } else { 1 };
+            let hours: i64 = offset.get(1..3)?.parse().ok()?;
+            let minutes: i64 = offset.get(4..6)?.parse().ok()?;
+            sign * (hours * 3600 + minutes * 60)
+        }
+    };
+
+    // Days since the epoch from the civil date
+    let (y, m) = if month <= 2 { (year - 1, month + 9) } else { (year, month - 3) };
+    let era = y.div_euclid(400);
+    let year_of_era = y - era * 400;
+    let day_of_year = (153 * m + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    let seconds = days * 86_400 + hour * 3600 + minute * 60 + second - offset;
+    let seconds = u64::try_from(seconds).ok()?;
+    Some(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds) + Duration::from_secs_f64(fraction))
+}
@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+use std::error::Error;
+
+use regex::Regex;
+use serde_json::Value;
+
+use super::Message;
+
+// Longest placeholder held back while a streamed reply may be in the middle of one
+const MAX_PLACEHOLDER_LEN: usize = 40;
+
+/// Masks personal data in messages before they leave for the provider, replacing each value
+/// with a placeholder such as `[EMAIL_1]`. The same value gets the same placeholder throughout
+/// a request, so the model can still refer to it, and replies can have the values restored
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    rules: Vec<RedactionRule>,
+    restore: bool,
+}
+
+#[derive(Debug, Clone)]
+struct RedactionRule {
+    kind: String, // Placeholder prefix, e.g. "EMAIL"
+    regex: Regex,
+    validate: Option<fn(&str) -> bool>, // Rejects lookalikes, e.g. card numbers failing the checksum
+}
+
+/// Placeholders given out while redacting one request, to put the values back in the reply
+#[derive(Debug, Clone, Default)]
+pub struct Redactions {
+    by_value: HashMap<String, String>,
+    values: Vec<(String, String)>, // (placeholder, value)
+    counts: HashMap<String, usize>, // Per kind
+}
+
+impl Default for Redactor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Redactor {
+    /// Email addresses, credit card numbers and phone numbers, restoring them in replies
+    pub fn new() -> Self {
+        Self::empty()
+            .with_rule("EMAIL", r"[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}", None)
+            .with_rule("CARD", r"\b(?:\d[ -]?){12,18}\d\b", Some(luhn_valid))
+            .with_rule("PHONE", r"(?:\+\d{1,3}[\s-]?)?(?:\(\d{1,4}\)\s?|\b\d{2,4}[\s-])\d{3,4}[\s-]?\d{3,4}\b", None)
+    }
+
+    /// No rules, for only custom patterns
+    pub fn empty() -> Self {
+        Self { rules: Vec::new(), restore: true }
+    }
+
+    /// Also mask matches of `pattern` as `[KIND_n]`. Rules added earlier win on overlapping matches
+    pub fn with_pattern(mut self, kind: &str, pattern: &str) -> Result<Self, Box<dyn Error>> {
+        self.rules.push(RedactionRule { kind: kind.to_uppercase(), regex: Regex::new(pattern)?, validate: None });
+        Ok(self)
+    }
+
+    /// Whether replies get the original values back, on by default. Off, the caller only ever
+    /// sees the placeholders
+    pub fn with_restore(mut self, restore: bool) -> Self {
+        self.restore = restore;
+        self
+    }
+
+    pub fn restores(&self) -> bool {
+        self.restore
+    }
+
+    fn with_rule(mut self, kind: &str, pattern: &str, validate: Option<fn(&str) -> bool>) -> Self {
+        let regex = Regex::new(pattern).expect("redaction patterns are valid");
+        self.rules.push(RedactionRule { kind: kind.to_string(), regex, validate });
+        self
+    }
+
+    /// Mask `text`, adding the placeholders it needs to `redactions`
+    pub fn redact(&self, text: &str, redactions: &mut Redactions) -> String {
+        let mut spans: Vec<(usize, usize, &str)> = Vec::new();
+        for rule in &self.rules {
+            for found in rule.regex.find_iter(text) {
+                let overlaps = spans.iter().any(|(start, end, _)| found.start() < *end && *start < found.end());
+                let valid = rule.validate.is_none_or(|validate| validate(found.as_str()));
+                if !overlaps && valid {
+                    spans.push((found.start(), found.end(), &rule.kind));
+                }
+            }
+        }
+        spans.sort_by_key(|(start, _, _)| *start);
+
+        let mut redacted = String::with_capacity(text.len());
+        let mut last = 0;
+        for (start, end, kind) in spans {
+            redacted.push_str(&text[last..start]);
+            redacted.push_str(&redactions.placeholder(kind, &text[start..end]));
+            last = end;
+        }
+        redacted.push_str(&text[last..]);
+        redacted
+    }
+
+    /// Copy of the conversation with message contents and tool call arguments masked
+    pub fn redact_messages(&self, messages: &[Message]) -> (Vec<Message>, Redactions) {
+        let mut redactions = Redactions::default();
+        let messages = messages
+            .iter()
+            .map(|message| {
+                let mut message = message.clone();
+                message.content = self.redact(&message.content, &mut redactions);
+                for tool_call in message.tool_calls.iter_mut().flatten() {
+                    map_strings(&mut tool_call.function.arguments, &mut |text| self.redact(text, &mut redactions));
+                }
+                message
+            })
+            .collect();
+        (messages, redactions)
+    }
+}
+
+impl Redactions {
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Placeholders and the values they stand for, in the order they were given out
+    pub fn values(&self) -> &[(String, String)] {
+        &self.values
+    }
+
+    fn placeholder(&mut self, kind: &str, value: &str) -> String {
+        if let Some(placeholder) = self.by_value.get(value) {
+            return placeholder.clone();
+        }
+        let count = self.counts.entry(kind.to_string()).or_insert(0);
+        *count += 1;
+        let placeholder = format!("[{}_{}]", kind, count);
+        self.by_value.insert(value.to_string(), placeholder.clone());
+        self.values.push((placeholder.clone(), value.to_string()));
+        placeholder
+    }
+
+    /// Put the original values back in place of the placeholders
+    pub fn restore(&self, text: &str) -> String {
+        let mut restored = text.to_string();
+        // Longest first, so [EMAIL_1] doesn't replace the start of [EMAIL_10]
+        let mut values: Vec<&(String, String)> = self.values.iter().collect();
+        values.sort_by_key(|(placeholder, _)| std::cmp::Reverse(placeholder.len()));
+        for (placeholder, value) in values {
+            restored = restored.replace(placeholder.as_str(), value);
+        }
+        restored
+    }
+
+    /// Restore the strings in tool call arguments
+    pub fn restore_json(&self, value: &mut Value) {
+        map_strings(value, &mut |text| self.restore(text));
+    }
+
+    /// Split streamed text into the part that can be restored now and the tail that may be
+    /// the start of a placeholder continuing in the next chunk
+    pub(crate) fn split_pending(text: &str) -> (&str, &str) {
+        match text.rfind('[') {
+            Some(start) if text.len() - start < MAX_PLACEHOLDER_LEN && !text[start..].contains(']') => text.split_at(start),
+            _ => (text, ""),
+        }
+    }
+}
+
+fn map_strings(value: &mut Value, map: &mut impl FnMut(&str) -> String) {
+    match value {
+        Value::String(text) => *text = map(text),
+        Value::Array(items) => items.iter_mut().for_each(|item| map_strings(item, map)),
+        Value::Object(fields) => fields.values_mut().for_each(|field| map_strings(field, map)),
+        _ => {}
+    }
+}
+
+// Checksum of card numbers, rules out most other long digit runs
+fn luhn_valid(number: &str) -> bool {
+    let digits: Vec<u32> = number.chars().filter_map(|c| c.to_digit(10)).collect();
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &digit)| match i % 2 {
+            1 if digit * 2 > 9 => digit * 2 - 9,
+            1 => digit * 2,
+            _ => digit,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
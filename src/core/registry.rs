@@ -0,0 +1,212 @@
+use std::sync::{Arc, RwLock};
+
+use crate::core::{AIRequestError, CacheHint, Tool};
+
+struct RegisteredTool {
+    namespace: Option<String>,
+    qualified_name: String,
+    enabled: bool,
+    tool: Arc<Tool>,
+}
+
+/// Summary of a registered tool as returned by `ToolRegistry::list`
+#[derive(Debug, Clone)]
+pub struct ToolInfo {
+    pub name: String,           // Name the model sees and calls (namespace_tool)
+    pub qualified_name: String, // Registry name used for enable/disable (namespace.tool)
+    pub namespace: Option<String>,
+    pub description: String,
+    pub enabled: bool,
+}
+
+/// Shared set of tools that can be attached to one or more clients.
+/// Cloning the registry clones the handle, so every clone sees the same tools.
+#[derive(Clone, Default)]
+pub struct ToolRegistry {
+    entries: Arc<RwLock<Vec<RegisteredTool>>>,
+    cache: Arc<RwLock<Option<CacheHint>>>,
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a tool without a namespace
+    pub fn register(&self, tool: Tool) -> Result<(), AIRequestError> {
+        let qualified_name = tool.name.clone();
+        self.insert(None, qualified_name, tool)
+    }
+
+    /// Register a tool under a namespace. The model sees it as `namespace_name`,
+    /// while enable/disable patterns address it as `namespace.name`
+    pub fn register_in(&self, namespace: &str, mut tool: Tool) -> Result<(), AIRequestError> {
+        let qualified_name = format!("{}.{}", namespace, tool.name);
+        tool.name = format!("{}_{}", namespace, tool.name);
+        self.insert(Some(namespace.to_string()), qualified_name, tool)
+    }
+
+    fn insert(&self, namespace: Option<String>, qualified_name: String, tool: Tool) -> Result<(), AIRequestError> {
+        let mut entries = self.entries.write().unwrap();
+        if let Some(existing) = entries
+            .iter()
+            .find(|e| e.tool.name == tool.name || e.qualified_name == qualified_name)
+        {
+            return Err(AIRequestError::Other(format!(
+                "Tool '{}' conflicts with already registered tool '{}'",
+                qualified_name, existing.qualified_name
+            )));
+        }
+
+        entries.push(RegisteredTool {
+            namespace,
+            qualified_name,
+            enabled: true,
+            tool: Arc::new(tool),
+        });
+        Ok(())
+    }
+
+    /// Remove a tool by model-facing or qualified name, returns whether it existed
+    pub fn unregister(&self, name: &str) -> bool {
+        let mut entries = self.entries.write().unwrap();
+        let before = entries.len();
+        entries.retain(|e| e.tool.name != name && e.qualified_name != name);
+        entries.len() != before
+    }
+
+    /// Enable tools matching a pattern (`name`, `namespace.name`, `namespace.*` or `*`), returns match count
+    pub fn enable(&self, pattern: &str) -> usize {
+        self.set_enabled(pattern, true)
+    }
+
+    /// Disable tools matching a pattern (`name`, `namespace.name`, `namespace.*` or `*`), returns match count
+    pub fn disable(&self, pattern: &str) -> usize {
+        self.set_enabled(pattern, false)
+    }
+
+    fn set_enabled(&self, pattern: &str, enabled: bool) -> usize {
+        let mut entries = self.entries.write().unwrap();
+        let mut count = 0;
+        for entry in entries.iter_mut().filter(|e| Self::matches(e, pattern)) {
+            entry.enabled = enabled;
+            count += 1;
+        }
+        count
+    }
+
+    fn matches(entry: &RegisteredTool, pattern: &str) -> bool {
+        if pattern == "*" {
+            return true;
+        }
+        if let Some(namespace) = pattern.strip_suffix(".*") {
+            return entry.namespace.as_deref() == Some(namespace);
+        }
+        entry.tool.name == pattern || entry.qualified_name == pattern
+    }
+
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.entries
+            .read()
+            .unwrap()
+            .iter()
+            .any(|e| e.enabled && (e.tool.name == name || e.qualified_name == name))
+    }
+
+    /// Look up an enabled tool by the name the model used to call it
+    pub fn get(&self, name: &str) -> Option<Arc<Tool>> {
+        self.entries
+            .read()
+            .unwrap()
+            .iter()
+            .find(|e| e.enabled && e.tool.name == name)
+            .map(|e| e.tool.clone())
+    }
+
+    /// Tools currently offered to the model
+    pub fn enabled_tools(&self) -> Vec<Arc<Tool>> {
+        self.entries
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|e| e.enabled)
+            .map(|e| e.tool.clone())
+            .collect()
+    }
+
+    /// Every registered tool, including disabled ones
+    pub(crate) fn all_tools(&self) -> Vec<Arc<Tool>> {
+        self.entries.read().unwrap().iter().map(|e| e.tool.clone()).collect()
+    }
+
+    /// List all registered tools, including disabled ones
+    pub fn list(&self) -> Vec<ToolInfo> {
+        self.entries
+            .read()
+            .unwrap()
+            .iter()
+            .map(|e| ToolInfo {
+                name: e.tool.name.clone(),
+                qualified_name: e.qualified_name.clone(),
+                namespace: e.namespace.clone(),
+                description: e.tool.description.clone(),
+                enabled: e.enabled,
+            })
+            .collect()
+    }
+
+    /// Distinct namespaces in registration order
+    pub fn namespaces(&self) -> Vec<String> {
+        let mut namespaces: Vec<String> = Vec::new();
+        for entry in self.entries.read().unwrap().iter() {
+            if let Some(ns) = &entry.namespace && !namespaces.contains(ns) {
+                namespaces.push(ns.clone());
+            }
+        }
+        namespaces
+    }
+
+    /// Separate registry with the enabled tools matching any of the patterns, for offering a
+    /// narrower set to some requests without changing this one
+    pub fn subset(&self, patterns: &[String]) -> ToolRegistry {
+        let entries = self
+            .entries
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|e| e.enabled && patterns.iter().any(|pattern| Self::matches(e, pattern)))
+            .map(|e| RegisteredTool {
+                namespace: e.namespace.clone(),
+                qualified_name: e.qualified_name.clone(),
+                enabled: true,
+                tool: e.tool.clone(),
+            })
+            .collect();
+        ToolRegistry {
+            entries: Arc::new(RwLock::new(entries)),
+            cache: Arc::new(RwLock::new(self.cache_hint())),
+        }
+    }
+
+    /// Cache the tool definitions, which come before the messages in every request. See `CacheHint`
+    pub fn set_cache_hint(&self, hint: Option<CacheHint>) {
+        *self.cache.write().unwrap() = hint;
+    }
+
+    pub fn cache_hint(&self) -> Option<CacheHint> {
+        *self.cache.read().unwrap()
+    }
+
+    /// True when at least one tool is offered to the model
+    pub fn has_enabled_tools(&self) -> bool {
+        self.entries.read().unwrap().iter().any(|e| e.enabled)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.read().unwrap().is_empty()
+    }
+}
@@ -0,0 +1,429 @@
+use serde_json::Value;
+
+/// Validate a value against the subset of JSON Schema used for tool parameters
+/// (type, properties, required, additionalProperties, enum, items, min/max bounds).
+/// Returns one human readable message per violation, phrased so a model can correct itself.
+pub fn validate_against_schema(schema: &Value, value: &Value) -> Result<(), Vec<String>> {
+    let mut errors = Vec::new();
+    validate_node(schema, value, "", &mut errors);
+    if errors.is_empty() { Ok(()) } else { Err(errors) }
+}
+
+fn validate_node(schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+    let schema = match schema.as_object() {
+        Some(obj) => obj,
+        None => return, // `true`/missing schemas accept anything
+    };
+    let label = if path.is_empty() { "arguments".to_string() } else { format!("argument {}", path) };
+
+    if let Some(expected) = schema.get("type") {
+        let allowed: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(types) => types.iter().filter_map(|t| t.as_str()).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|t| matches_type(t, value)) {
+            errors.push(format!("{} must be {}, got {}", label, describe_types(&allowed), type_name(value)));
+            return;
+        }
+    }
+
+    if let Some(Value::Array(options)) = schema.get("enum") && !options.contains(value) {
+        let options: Vec<String> = options.iter().map(|o| o.to_string()).collect();
+        errors.push(format!("{} must be one of [{}], got {}", label, options.join(", "), value));
+    }
+
+    if let Some(number) = value.as_f64() {
+        if let Some(min) = schema.get("minimum").and_then(|m| m.as_f64()) && number < min {
+            errors.push(format!("{} must be >= {}, got {}", label, min, number));
+        }
+        if let Some(max) = schema.get("maximum").and_then(|m| m.as_f64()) && number > max {
+            errors.push(format!("{} must be <= {}, got {}", label, max, number));
+        }
+    }
+
+    if let Some(text) = value.as_str() {
+        let length = text.chars().count() as u64;
+        if let Some(min) = schema.get("minLength").and_then(|m| m.as_u64()) && length < min {
+            errors.push(format!("{} must be at least {} characters long", label, min));
+        }
+        if let Some(max) = schema.get("maxLength").and_then(|m| m.as_u64()) && length > max {
+            errors.push(format!("{} must be at most {} characters long", label, max));
+        }
+    }
+
+    if let Some(object) = value.as_object() {
+        let properties = schema.get("properties").and_then(|p| p.as_object());
+
+        if let Some(Value::Array(required)) = schema.get("required") {
+            for name in required.iter().filter_map(|r| r.as_str()) {
+                if !object.contains_key(name) {
+                    errors.push(format!("missing required argument {}", join_path(path, name)));
+                }
+            }
+        }
+
+        for (key, child) in object {
+            match properties.and_then(|p| p.get(key)) {
+                Some(child_schema) => validate_node(child_schema, child, &join_path(path, key), errors),
+                None => match schema.get("additionalProperties") {
+                    Some(Value::Bool(false)) => {
+                        errors.push(format!("unexpected argument {}", join_path(path, key)));
+                    }
+                    Some(extra_schema @ Value::Object(_)) => {
+                        validate_node(extra_schema, child, &join_path(path, key), errors);
+                    }
+                    _ => {}
+                },
+            }
+        }
+    }
+
+    if let Some(items) = value.as_array() && let Some(item_schema) = schema.get("items") {
+        for (i, item) in items.iter().enumerate() {
+            validate_node(item_schema, item, &format!("{}[{}]", path, i), errors);
+        }
+    }
+}
+
+fn matches_type(expected: &str, value: &Value) -> bool {
+    match expected {
+        "string" => value.is_string(),
+        // JSON Schema counts whole-number floats such as 3.0 as integers
+        "integer" => value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|f| f.fract() == 0.0),
+        "number" => value.is_number(),
+        "boolean" => value.is_boolean(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        "null" => value.is_null(),
+        _ => true, // Unknown type keywords are not enforced
+    }
+}
+
+fn describe_types(types: &[&str]) -> String {
+    let described: Vec<String> = types
+        .iter()
+        .map(|t| match *t {
+            "integer" | "array" | "object" => format!("an {}", t),
+            "null" => "null".to_string(),
+            other => format!("a {}", other),
+        })
+        .collect();
+    described.join(" or ")
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(n) if n.is_f64() => "a number",
+        Value::Number(_) => "an integer",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+fn join_path(path: &str, key: &str) -> String {
+    if path.is_empty() { key.to_string() } else { format!("{}.{}", path, key) }
+}
+
+const SCHEMA_TYPES: &[&str] = &["string", "integer", "number", "boolean", "array", "object", "null"];
+
+/// Problems with a schema itself, as `(path, message)` with JSON pointer paths such as
+/// `#/properties/city`. `strict` adds OpenAI's strict mode rules: every object closed with
+/// `additionalProperties: false` and listing all of its properties in `required`
+pub(crate) fn check_schema(schema: &Value, strict: bool) -> Vec<(String, String)> {
+    let mut issues = Vec::new();
+    check_node(schema, "#", strict, &mut issues);
+    if strict && schema.get("type").and_then(|t| t.as_str()) != Some("object") {
+        issues.push(("#".to_string(), "the root must be an object schema in strict mode".to_string()));
+    }
+    issues
+}
+
+fn check_node(schema: &Value, path: &str, strict: bool, issues: &mut Vec<(String, String)>) {
+    let mut issue = |path: &str, message: String| issues.push((path.to_string(), message));
+    let schema = match schema {
+        Value::Object(obj) => obj,
+        Value::Bool(_) => return,
+        other => return issue(path, format!("a schema must be an object or a boolean, got {}", type_name(other))),
+    };
+
+    match schema.get("type") {
+        None => {}
+        Some(Value::String(t)) if SCHEMA_TYPES.contains(&t.as_str()) => {}
+        Some(Value::Array(types)) if types.iter().all(|t| t.as_str().is_some_and(|t| SCHEMA_TYPES.contains(&t))) => {}
+        Some(other) => issue(&format!("{}/type", path), format!("type must be one of {} or an array of them, got {}", SCHEMA_TYPES.join(", "), other)),
+    }
+
+    if let Some(options) = schema.get("enum") && options.as_array().is_none_or(|options| options.is_empty()) {
+        issue(&format!("{}/enum", path), "enum must be a non-empty array".to_string());
+    }
+
+    for keyword in ["minimum", "maximum", "exclusiveMinimum", "exclusiveMaximum", "multipleOf"] {
+        // Draft 4 exclusive bounds are booleans
+        if schema.get(keyword).is_some_and(|bound| !(bound.is_number() || keyword.starts_with("exclusive") && bound.is_boolean())) {
+            issue(&format!("{}/{}", path, keyword), format!("{} must be a number", keyword));
+        }
+    }
+    for keyword in ["minLength", "maxLength", "minItems", "maxItems", "minProperties", "maxProperties"] {
+        if schema.get(keyword).is_some_and(|bound| !bound.is_u64()) {
+            issue(&format!("{}/{}", path, keyword), format!("{} must be a non-negative integer", keyword));
+        }
+    }
+
+    if let Some(pattern) = schema.get("pattern") {
+        match pattern.as_str() {
+            Some(pattern) => {
+                if let Err(e) = regex::Regex::new(pattern) {
+                    issue(&format!("{}/pattern", path), format!("pattern is not a valid regular expression ({})", e.to_string().lines().last().unwrap_or_default().trim_start_matches("error: ")));
+                }
+            }
+            None => issue(&format!("{}/pattern", path), "pattern must be a string".to_string()),
+        }
+    }
+
+    if schema.get("$ref").is_some_and(|reference| !reference.is_string()) {
+        issue(&format!("{}/$ref", path), "$ref must be a string".to_string());
+    }
+
+    let properties = match schema.get("properties") {
+        None => None,
+        Some(Value::Object(properties)) => Some(properties),
+        Some(_) => {
+            issue(&format!("{}/properties", path), "properties must be an object of schemas".to_string());
+            None
+        }
+    };
+
+    let mut required_names = Vec::new();
+    match schema.get("required") {
+        None => {}
+        Some(Value::Array(required)) => {
+            for (i, name) in required.iter().enumerate() {
+                let Some(name) = name.as_str() else {
+                    issue(&format!("{}/required/{}", path, i), format!("required must only hold property names, got {}", name));
+                    continue;
+                };
+                if required_names.contains(&name) {
+                    issue(&format!("{}/required/{}", path, i), format!("'{}' is listed twice in required", name));
+                } else if properties.is_some_and(|properties| !properties.contains_key(name)) {
+                    issue(&format!("{}/required/{}", path, i), format!("required property '{}' is not defined in properties", name));
+                }
+                required_names.push(name);
+            }
+        }
+        // Draft 3 style `required: true` on a property is a common mistake
+        Some(other) => issue(&format!("{}/required", path), format!("required must be an array of property names, got {}", other)),
+    }
+
+    let is_object = schema.get("type").is_some_and(|t| t == "object") || properties.is_some();
+    if strict && is_object {
+        if schema.get("additionalProperties") != Some(&Value::Bool(false)) {
+            issue(path, "strict mode needs additionalProperties: false on every object".to_string());
+        }
+        for name in properties.into_iter().flat_map(|properties| properties.keys()) {
+            if !required_names.contains(&name.as_str()) {
+                issue(path, format!("strict mode needs every property in required, '{}' is missing (make it nullable to keep it optional)", name));
+            }
+        }
+    }
+
+    if let Some(properties) = properties {
+        for (name, child) in properties {
+            check_node(child, &format!("{}/properties/{}", path, name), strict, issues);
+        }
+    }
+
+    match schema.get("additionalProperties") {
+        Some(child @ Value::Object(_)) => check_node(child, &format!("{}/additionalProperties", path), strict, issues),
+        Some(Value::Bool(_)) | None => {}
+        Some(other) => issues.push((format!("{}/additionalProperties", path), format!("additionalProperties must be a boolean or a schema, got {}", type_name(other)))),
+    }
+
+    match schema.get("items") {
+        Some(Value::Array(items)) => {
+            for (i, child) in items.iter().enumerate() {
+                check_node(child, &format!("{}/items/{}", path, i), strict, issues);
+            }
+        }
+        Some(child) => check_node(child, &format!("{}/items", path), strict, issues),
+        None => {}
+    }
+
+    for keyword in ["anyOf", "oneOf", "allOf"] {
+        match schema.get(keyword) {
+            Some(Value::Array(children)) if !children.is_empty() => {
+                for (i, child) in children.iter().enumerate() {
+                    check_node(child, &format!("{}/{}/{}", path, keyword, i), strict, issues);
+                }
+            }
+            Some(_) => issues.push((format!("{}/{}", path, keyword), format!("{} must be a non-empty array of schemas", keyword))),
+            None => {}
+        }
+    }
+    if let Some(child) = schema.get("not") {
+        check_node(child, &format!("{}/not", path), strict, issues);
+    }
+
+    for keyword in ["$defs", "definitions"] {
+        if let Some(definitions) = schema.get(keyword).and_then(|d| d.as_object()) {
+            for (name, child) in definitions {
+                check_node(child, &format!("{}/{}/{}", path, keyword, name), strict, issues);
+            }
+        }
+    }
+}
+
+// Keywords OpenAI's strict mode rejects
+const STRICT_UNSUPPORTED: &[&str] = &[
+    "allOf", "oneOf", "not", "if", "then", "else", "patternProperties", "propertyNames", "unevaluatedProperties",
+    "dependentRequired", "dependentSchemas", "minProperties", "maxProperties", "minLength", "maxLength",
+    "uniqueItems", "contains", "minContains", "maxContains", "unevaluatedItems",
+];
+
+/// The schema rewritten for OpenAI's strict mode: objects closed with `additionalProperties:
+/// false` and optional properties made required but nullable. None when strict mode can't
+/// express it, e.g. open objects or unsupported keywords
+pub(crate) fn strict_schema(schema: &Value) -> Option<Value> {
+    if schema.get("type").and_then(|t| t.as_str()) != Some("object") {
+        return None;
+    }
+    let mut strict = schema.clone();
+    strict_node(&mut strict)?;
+    check_schema(&strict, true).is_empty().then_some(strict)
+}
+
+fn strict_node(schema: &mut Value) -> Option<()> {
+    let schema = schema.as_object_mut()?;
+    if STRICT_UNSUPPORTED.iter().any(|keyword| schema.contains_key(*keyword)) {
+        return None;
+    }
+
+    let is_object = schema.get("type").is_some_and(|t| t == "object") || schema.contains_key("properties");
+    if is_object {
+        match schema.get("additionalProperties") {
+            None | Some(Value::Bool(false)) => {}
+            Some(_) => return None, // Maps with arbitrary keys
+        }
+        schema.insert("additionalProperties".to_string(), Value::Bool(false));
+
+        let required: Vec<String> = schema
+            .get("required")
+            .and_then(|r| r.as_array())
+            .map(|r| r.iter().filter_map(|name| name.as_str().map(str::to_string)).collect())
+            .unwrap_or_default();
+        let mut all_required = required.clone();
+        if let Some(Value::Object(properties)) = schema.get_mut("properties") {
+            for (name, property) in properties.iter_mut() {
+                strict_node(property)?;
+                if !required.contains(name) {
+                    make_nullable(property)?;
+                    all_required.push(name.clone());
+                }
+            }
+        }
+        schema.insert("required".to_string(), Value::from(all_required));
+    }
+
+    match schema.get_mut("items") {
+        Some(items @ Value::Object(_)) => strict_node(items)?,
+        Some(_) => return None, // Tuples
+        None => {}
+    }
+    if let Some(Value::Array(options)) = schema.get_mut("anyOf") {
+        for option in options {
+            strict_node(option)?;
+        }
+    }
+    for keyword in ["$defs", "definitions"] {
+        if let Some(Value::Object(definitions)) = schema.get_mut(keyword) {
+            for definition in definitions.values_mut() {
+                strict_node(definition)?;
+            }
+        }
+    }
+    Some(())
+}
+
+// Let the model pass null for an optional property it leaves out
+fn make_nullable(schema: &mut Value) -> Option<()> {
+    let schema = schema.as_object_mut()?;
+    if let Some(Value::Array(options)) = schema.get_mut("enum") && !options.contains(&Value::Null) {
+        options.push(Value::Null);
+    }
+    match schema.get_mut("type") {
+        Some(Value::String(t)) => {
+            let t = t.clone();
+            if t != "null" {
+                schema.insert("type".to_string(), Value::from(vec![t, "null".to_string()]));
+            }
+        }
+        Some(Value::Array(types)) => {
+            if !types.contains(&Value::from("null")) {
+                types.push(Value::from("null"));
+            }
+        }
+        Some(_) => return None,
+        None => match schema.get_mut("anyOf") {
+            Some(Value::Array(options)) => options.push(serde_json::json!({"type": "null"})),
+            _ => return None,
+        },
+    }
+    Some(())
+}
+
+/// Remove nulls given for optional properties whose schema doesn't take null, as models do
+/// with the nullable properties of strict mode schemas
+pub(crate) fn drop_optional_nulls(schema: &Value, value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            let required = schema.get("required").and_then(|r| r.as_array());
+            let properties = schema.get("properties").and_then(|p| p.as_object());
+            object.retain(|name, child| {
+                !child.is_null()
+                    || required.is_some_and(|r| r.iter().any(|n| n == name))
+                    || properties.and_then(|p| p.get(name)).is_none_or(accepts_null)
+            });
+            for (name, child) in object.iter_mut() {
+                if let Some(child_schema) = properties.and_then(|p| p.get(name)) {
+                    drop_optional_nulls(child_schema, child);
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(item_schema) = schema.get("items") {
+                for item in items {
+                    drop_optional_nulls(item_schema, item);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn accepts_null(schema: &Value) -> bool {
+    match schema.get("type") {
+        Some(Value::String(t)) => t == "null",
+        Some(Value::Array(types)) => types.iter().any(|t| t == "null"),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn integers_accept_whole_number_floats() {
+        let schema = json!({ "type": "object", "properties": { "count": { "type": "integer" } } });
+        assert!(validate_against_schema(&schema, &json!({ "count": 3 })).is_ok());
+        assert!(validate_against_schema(&schema, &json!({ "count": 3.0 })).is_ok());
+        assert!(validate_against_schema(&schema, &json!({ "count": -2.0 })).is_ok());
+
+        let errors = validate_against_schema(&schema, &json!({ "count": 3.5 })).unwrap_err();
+        assert_eq!(errors, vec!["argument count must be an integer, got a number"]);
+    }
+}
@@ -0,0 +1,116 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::{GenerationOptions, Message, RequestTags, TokenUsage, ToolCall, UsageRecord};
+
+/// What a client sent and received, passed to the `Sink` set with `MonoAI::set_sink`. Requests
+/// and their responses share an `id`, `timestamp` is in milliseconds since the Unix epoch
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SinkRecord {
+    /// A chat request as sent, after redaction
+    Request {
+        id: String,
+        timestamp: u64,
+        provider: String,
+        model: String,
+        messages: Vec<Message>,
+        options: Option<GenerationOptions>,
+    },
+    /// The complete reply to a request, or the error that ended it
+    Response {
+        id: String,
+        timestamp: u64,
+        provider: String,
+        model: String,
+        text: String,
+        tool_calls: Option<Vec<ToolCall>>,
+        finish_reason: Option<String>,
+        usage: Option<TokenUsage>,
+        latency_ms: u64,
+        error: Option<String>,
+    },
+    /// A tool run by `handle_tool_calls`, `id` is the tool call's
+    ToolCall {
+        id: Option<String>,
+        timestamp: u64,
+        name: String,
+        arguments: Value,
+        result: String,
+        is_error: bool,
+    },
+    /// What the usage callback receives
+    Usage {
+        timestamp: u64,
+        provider: String,
+        model: String,
+        usage: TokenUsage,
+        tags: Option<RequestTags>,
+    },
+}
+
+impl From<&UsageRecord> for SinkRecord {
+    fn from(record: &UsageRecord) -> Self {
+        SinkRecord::Usage {
+            timestamp: now_ms(),
+            provider: record.provider.clone(),
+            model: record.model.clone(),
+            usage: record.usage.clone(),
+            tags: record.tags.clone(),
+        }
+    }
+}
+
+/// Destination for `SinkRecord`s, e.g. a database or a queue. Called on the task making the
+/// request, so slow sinks should hand records off
+pub trait Sink: Send + Sync {
+    fn record(&self, record: &SinkRecord);
+
+    /// Write out buffered records, called by `MonoAI::shutdown`
+    fn flush(&self) {}
+}
+
+impl<F: Fn(&SinkRecord) + Send + Sync> Sink for F {
+    fn record(&self, record: &SinkRecord) {
+        self(record)
+    }
+}
+
+/// Appends every record to a file as one JSON object per line, for offline analysis and
+/// building datasets. Write errors are ignored so logging never fails a request
+pub struct JsonlSink {
+    writer: Mutex<BufWriter<File>>,
+}
+
+impl JsonlSink {
+    /// Append to `path`, created if missing
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { writer: Mutex::new(BufWriter::new(file)) })
+    }
+}
+
+impl Sink for JsonlSink {
+    fn record(&self, record: &SinkRecord) {
+        let mut writer = self.writer.lock().unwrap();
+        // Flushed per record so a crash loses nothing written so far
+        let _ = serde_json::to_writer(&mut *writer, record)
+            .map_err(io::Error::from)
+            .and_then(|_| writer.write_all(b"\n"))
+            .and_then(|_| writer.flush());
+    }
+
+    fn flush(&self) {
+        let _ = self.writer.lock().unwrap().flush();
+    }
+}
+
+pub(crate) fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
@@ -0,0 +1,418 @@
+use std::collections::VecDeque;
+use std::pin::Pin;
+
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+use tokio::sync::mpsc;
+
+use super::ChatStreamItem;
+
+/// Turns the byte chunks of a streaming response into items. Chunks may split lines, events
+/// and UTF-8 characters anywhere, parsers buffer until they have a complete unit
+pub trait StreamParser {
+    type Item;
+
+    /// Feed the next chunk, returns the items it completed
+    fn push(&mut self, chunk: &[u8]) -> Vec<Self::Item>;
+
+    /// The response ended, returns whatever the buffered remainder completes
+    fn finish(&mut self) -> Vec<Self::Item> {
+        Vec::new()
+    }
+}
+
+/// Drive a parser with a response body. Network errors are yielded as `Err` and the stream
+/// continues with the next chunk
+pub fn parse_stream<S, P>(stream: S, parser: P) -> Pin<Box<dyn Stream<Item = Result<P::Item, String>> + Send>>
+where
+    S: Stream<Item = Result<Bytes, reqwest::Error>> + Send + 'static,
+    P: StreamParser + Send + 'static,
+    P::Item: Send + 'static,
+{
+    Box::pin(futures_util::stream::unfold(
+        (Box::pin(stream), parser, VecDeque::new(), false),
+        |(mut stream, mut parser, mut pending, mut finished)| async move {
+            loop {
+                if let Some(item) = pending.pop_front() {
+                    return Some((Ok(item), (stream, parser, pending, finished)));
+                }
+                if finished {
+                    return None;
+                }
+                match stream.next().await {
+                    Some(Ok(chunk)) => pending.extend(parser.push(&chunk)),
+                    Some(Err(e)) => return Some((Err(e.to_string()), (stream, parser, pending, finished))),
+                    None => {
+                        pending.extend(parser.finish());
+                        finished = true;
+                    }
+                }
+            }
+        },
+    ))
+}
+
+pub(crate) type ChatStream = Pin<Box<dyn Stream<Item = Result<ChatStreamItem, String>> + Send>>;
+
+/// Split a chat stream with `n` completions (see `GenerationOptions::with_n`) into one stream
+/// per completion, in index order. Each stream ends with its completion's done item, the first
+/// one's carries the usage and tool calls. Errors are sent to every stream
+pub fn split_choices(stream: ChatStream, n: usize) -> Vec<ChatStream> {
+    let (senders, receivers): (Vec<_>, Vec<_>) = (0..n.max(1)).map(|_| mpsc::unbounded_channel()).unzip();
+    // Routes items until the source ends or every split stream is dropped
+    tokio::spawn(async move {
+        let mut stream = stream;
+        while let Some(item) = stream.next().await {
+            match item {
+                Ok(item) => {
+                    if let Some(sender) = senders.get(item.choice_index.unwrap_or(0)) {
+                        let _ = sender.send(Ok(item));
+                    }
+                }
+                Err(e) => {
+                    for sender in &senders {
+                        let _ = sender.send(Err(e.clone()));
+                    }
+                }
+            }
+            if senders.iter().all(|sender| sender.is_closed()) {
+                return;
+            }
+        }
+    });
+
+    receivers
+        .into_iter()
+        .map(|receiver| {
+            Box::pin(futures_util::stream::unfold(receiver, |mut receiver| async move {
+                receiver.recv().await.map(|item| (item, receiver))
+            })) as ChatStream
+        })
+        .collect()
+}
+
+/// Copy a stream, e.g. a chat stream, to `n` consumers that each get every item as it arrives,
+/// such as the UI, a logger and a moderation check. Items wait in a queue per copy, so a slow
+/// consumer doesn't hold up the others. Dropped copies are skipped, the source is dropped
+/// once all of them are
+pub fn tee<T: Clone + Send + 'static>(
+    stream: Pin<Box<dyn Stream<Item = T> + Send>>,
+    n: usize,
+) -> Vec<Pin<Box<dyn Stream<Item = T> + Send>>> {
+    let (senders, receivers): (Vec<_>, Vec<_>) = (0..n.max(1)).map(|_| mpsc::unbounded_channel()).unzip();
+    tokio::spawn(async move {
+        let mut stream = stream;
+        loop {
+            // Also wakes when the last copy is dropped, rather than only at the next item
+            let item = tokio::select! {
+                item = stream.next() => item,
+                _ = futures_util::future::join_all(senders.iter().map(|sender| sender.closed())) => return,
+            };
+            let Some(item) = item else {
+                return;
+            };
+            for sender in &senders {
+                let _ = sender.send(item.clone());
+            }
+        }
+    });
+
+    receivers
+        .into_iter()
+        .map(|receiver| {
+            Box::pin(futures_util::stream::unfold(receiver, |mut receiver| async move {
+                receiver.recv().await.map(|item| (item, receiver))
+            })) as Pin<Box<dyn Stream<Item = T> + Send>>
+        })
+        .collect()
+}
+
+/// One server-sent event
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SseEvent {
+    pub event: Option<String>,
+    pub data: String, // `data:` lines joined with newlines
+}
+
+/// Server-sent events parser, as used by the OpenAI, Anthropic and OpenRouter streams.
+/// Accepts `\n` and `\r\n` line endings and skips `:` comment lines
+#[derive(Debug, Default)]
+pub struct SseParser {
+    lines: LineBuffer,
+    event: Option<String>,
+    data: Vec<String>,
+}
+
+impl SseParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn line(event: &mut Option<String>, data: &mut Vec<String>, line: &str, events: &mut Vec<SseEvent>) {
+        if line.is_empty() {
+            Self::dispatch(event, data, events);
+            return;
+        }
+        if line.starts_with(':') {
+            return;
+        }
+
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "data" => data.push(value.to_string()),
+            "event" => *event = Some(value.to_string()),
+            _ => {} // id and retry don't matter for completions
+        }
+    }
+
+    fn dispatch(event: &mut Option<String>, data: &mut Vec<String>, events: &mut Vec<SseEvent>) {
+        let event = event.take();
+        // Nearly every event has a single data line, which is moved rather than joined
+        let data = match data.len() {
+            0 => return,
+            1 => data.pop().unwrap_or_default(),
+            _ => std::mem::take(data).join("\n"),
+        };
+        events.push(SseEvent { event, data });
+    }
+}
+
+impl StreamParser for SseParser {
+    type Item = SseEvent;
+
+    fn push(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        let mut events = Vec::new();
+        let Self { lines, event, data } = self;
+        lines.push(chunk, |line| Self::line(event, data, line, &mut events));
+        events
+    }
+
+    /// A stream cut off without the closing blank line still delivers its last event
+    fn finish(&mut self) -> Vec<SseEvent> {
+        let mut events = Vec::new();
+        if let Some(line) = self.lines.finish() {
+            Self::line(&mut self.event, &mut self.data, &line, &mut events);
+        }
+        Self::dispatch(&mut self.event, &mut self.data, &mut events);
+        events
+    }
+}
+
+/// Newline-delimited JSON parser, as used by the Ollama streams. Yields each non-empty line
+#[derive(Debug, Default)]
+pub struct NdjsonParser {
+    lines: LineBuffer,
+}
+
+impl NdjsonParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl StreamParser for NdjsonParser {
+    type Item = String;
+
+    fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        let mut lines = Vec::new();
+        self.lines.push(chunk, |line| {
+            if !line.trim().is_empty() {
+                lines.push(line.to_string());
+            }
+        });
+        lines
+    }
+
+    fn finish(&mut self) -> Vec<String> {
+        self.lines.finish().into_iter().filter(|line| !line.trim().is_empty()).collect()
+    }
+}
+
+/// Decodes UTF-8 text from chunks split anywhere. The bytes of a character cut off at the end
+/// of a chunk wait for the rest, invalid bytes become U+FFFD
+#[derive(Debug, Default)]
+pub struct Utf8Decoder {
+    partial: Vec<u8>,
+}
+
+impl Utf8Decoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Text of the chunk, from the character the previous chunk cut off up to the one this cuts off
+    pub fn decode(&mut self, chunk: &[u8]) -> String {
+        self.partial.extend_from_slice(chunk);
+        let complete = self.partial.len() - incomplete_tail(&self.partial);
+        let text = String::from_utf8_lossy(&self.partial[..complete]).into_owned();
+        self.partial.drain(..complete);
+        text
+    }
+}
+
+impl StreamParser for Utf8Decoder {
+    type Item = String;
+
+    fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        let text = self.decode(chunk);
+        if text.is_empty() { Vec::new() } else { vec![text] }
+    }
+
+    /// A character still cut off when the stream ends is invalid
+    fn finish(&mut self) -> Vec<String> {
+        let partial = std::mem::take(&mut self.partial);
+        if partial.is_empty() { Vec::new() } else { vec![String::from_utf8_lossy(&partial).into_owned()] }
+    }
+}
+
+// Length of the character started but not finished at the end of the bytes
+fn incomplete_tail(bytes: &[u8]) -> usize {
+    for back in 1..=bytes.len().min(3) {
+        let byte = bytes[bytes.len() - back];
+        // Continuation bytes are 10xxxxxx, the lead byte gives the character's length
+        if byte & 0xC0 != 0x80 {
+            let length = match byte {
+                0xC2..=0xDF => 2,
+                0xE0..=0xEF => 3,
+                0xF0..=0xF4 => 4,
+                _ => 1,
+            };
+            return if length > back { back } else { 0 };
+        }
+    }
+    0
+}
+
+// Bytes of an unfinished line, decoded only once the line is complete so multi-byte
+// characters split across chunks survive
+#[derive(Debug, Default)]
+struct LineBuffer {
+    buffer: Vec<u8>,
+}
+
+impl LineBuffer {
+    // Calls `on_line` with each line the chunk completes. Lines that are valid UTF-8 are
+    // borrowed from the buffer, and the consumed bytes are drained in place
+    fn push(&mut self, chunk: &[u8], mut on_line: impl FnMut(&str)) {
+        let start = self.buffer.len();
+        self.buffer.extend_from_slice(chunk);
+        // Only the new bytes can hold the newline completing a line
+        let Some(last_newline) = self.buffer[start..].iter().rposition(|&b| b == b'\n').map(|i| start + i) else {
+            return;
+        };
+
+        for line in self.buffer[..last_newline].split(|&b| b == b'\n') {
+            on_line(&String::from_utf8_lossy(line.strip_suffix(b"\r").unwrap_or(line)));
+        }
+        self.buffer.drain(..=last_newline);
+    }
+
+    fn finish(&mut self) -> Option<String> {
+        if self.buffer.is_empty() {
+            return None;
+        }
+        let line = std::mem::take(&mut self.buffer);
+        Some(String::from_utf8_lossy(line.strip_suffix(b"\r").unwrap_or(&line)).into_owned())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn parse_chunks<P: StreamParser>(mut parser: P, chunks: &[&[u8]]) -> Vec<P::Item> {
+        let mut items = Vec::new();
+        for chunk in chunks {
+            items.extend(parser.push(chunk));
+        }
+        items.extend(parser.finish());
+        items
+    }
+
+    // Parse `body` whole, then split in two at every byte offset and one byte per chunk, checking
+    // each gives the same items after `normalize`. Returns the items of the whole body
+    pub(crate) fn parse_every_split<P, T>(body: &[u8], new_parser: impl Fn() -> P, normalize: impl Fn(Vec<P::Item>) -> T) -> T
+    where
+        P: StreamParser,
+        T: PartialEq + std::fmt::Debug,
+    {
+        let whole = normalize(parse_chunks(new_parser(), &[body]));
+        for offset in 0..=body.len() {
+            let (head, tail) = body.split_at(offset);
+            assert_eq!(normalize(parse_chunks(new_parser(), &[head, tail])), whole, "split at byte {}", offset);
+        }
+        let bytes: Vec<&[u8]> = body.chunks(1).collect();
+        assert_eq!(normalize(parse_chunks(new_parser(), &bytes)), whole, "one byte per chunk");
+        whole
+    }
+
+    fn event(event: Option<&str>, data: &str) -> SseEvent {
+        SseEvent { event: event.map(str::to_string), data: data.to_string() }
+    }
+
+    #[test]
+    fn sse_events_survive_every_split() {
+        let body = ": keep-alive\r\nevent: message\r\ndata: héllo 世界 🦀\r\n\r\ndata:first\ndata: second\n\n: comment\nid: 7\ndata: {\"done\": true}\n\ndata: last without blank line";
+        let events = parse_every_split(body.as_bytes(), SseParser::new, |events| events);
+        assert_eq!(
+            events,
+            vec![
+                event(Some("message"), "héllo 世界 🦀"),
+                event(None, "first\nsecond"),
+                event(None, "{\"done\": true}"),
+                event(None, "last without blank line"),
+            ]
+        );
+    }
+
+    #[test]
+    fn sse_skips_events_without_data() {
+        let body = ": only a comment\n\nevent: ping\n\n";
+        assert_eq!(parse_every_split(body.as_bytes(), SseParser::new, |events| events), vec![]);
+    }
+
+    #[test]
+    fn ndjson_lines_survive_every_split() {
+        let body = "{\"a\":\"ä\"}\r\n\n  \n{\"b\":\"日本\"}\n{\"c\":\"🦀\"}";
+        let lines = parse_every_split(body.as_bytes(), NdjsonParser::new, |lines| lines);
+        assert_eq!(lines, vec!["{\"a\":\"ä\"}", "{\"b\":\"日本\"}", "{\"c\":\"🦀\"}"]);
+    }
+
+    #[test]
+    fn utf8_text_survives_every_split() {
+        let body = "ascii, é, 世界, 🦀🦀";
+        let text = parse_every_split(body.as_bytes(), Utf8Decoder::new, |parts| parts.concat());
+        assert_eq!(text, body);
+    }
+
+    #[test]
+    fn utf8_invalid_and_cut_off_bytes_become_replacement_characters() {
+        let body = b"ok \xFF then \xE4\xB8 cut \xF0\x9F";
+        let text = parse_every_split(body, Utf8Decoder::new, |parts| parts.concat());
+        assert_eq!(text, "ok \u{FFFD} then \u{FFFD} cut \u{FFFD}");
+    }
+
+    #[test]
+    fn line_buffer_survives_every_split() {
+        let body = "first\r\nsecond ü\n\nthird 🦀";
+        let split_lines = |chunks: &[&[u8]]| {
+            let mut buffer = LineBuffer::default();
+            let mut lines = Vec::new();
+            for chunk in chunks {
+                buffer.push(chunk, |line| lines.push(line.to_string()));
+            }
+            lines.extend(buffer.finish());
+            lines
+        };
+
+        let expected = vec!["first", "second ü", "", "third 🦀"];
+        for offset in 0..=body.len() {
+            let (head, tail) = body.as_bytes().split_at(offset);
+            assert_eq!(split_lines(&[head, tail]), expected, "split at byte {}", offset);
+        }
+        let bytes: Vec<&[u8]> = body.as_bytes().chunks(1).collect();
+        assert_eq!(split_lines(&bytes), expected);
+        assert_eq!(split_lines(&[b"ends with newline\r\n"]), vec!["ends with newline"]);
+    }
+}
@@ -0,0 +1,89 @@
+use serde_json::Value;
+
+use crate::core::{validate_against_schema, AIRequestError, Message};
+
+/// Requested shape of a structured (JSON) reply
+#[derive(Debug, Clone)]
+pub enum ResponseFormat {
+    /// Any JSON object
+    Json,
+    /// JSON matching a schema, enforced server-side where the provider supports it
+    JsonSchema { name: String, schema: Value },
+}
+
+impl ResponseFormat {
+    pub fn json_schema(name: &str, schema: Value) -> Self {
+        ResponseFormat::JsonSchema {
+            name: name.to_string(),
+            schema,
+        }
+    }
+
+    pub fn schema(&self) -> Option<&Value> {
+        match self {
+            ResponseFormat::Json => None,
+            ResponseFormat::JsonSchema { schema, .. } => Some(schema),
+        }
+    }
+
+    /// Prompt text asking for the reply format, for providers without server-side enforcement
+    pub fn instructions(&self) -> String {
+        match self {
+            ResponseFormat::Json => "\n\nRespond only with a valid JSON object, without any other text.".to_string(),
+            ResponseFormat::JsonSchema { schema, .. } => format!(
+                "\n\nRespond only with a valid JSON object matching this JSON schema, without any other text:\n{}",
+                serde_json::to_string_pretty(schema).unwrap_or_default()
+            ),
+        }
+    }
+
+    /// Copy of the conversation with the format instructions added to the system message
+    pub fn apply_instructions(&self, messages: &[Message]) -> Vec<Message> {
+        let mut messages = messages.to_vec();
+        let instructions = self.instructions();
+
+        if let Some(system_msg) = messages.iter_mut().find(|msg| msg.role == "system") {
+            system_msg.content.push_str(&instructions);
+        } else {
+            messages.insert(0, Message::system(format!("You are a helpful assistant.{}", instructions)));
+        }
+        messages
+    }
+
+    /// Parse a reply as JSON (tolerating code fences and surrounding text) and check it against the schema
+    pub fn parse(&self, content: &str) -> Result<Value, AIRequestError> {
+        let value = parse_json_reply(content)
+            .ok_or_else(|| AIRequestError::Other(format!("Response is not valid JSON: {}", content)))?;
+
+        if let Some(schema) = self.schema() {
+            validate_against_schema(schema, &value).map_err(|errors| {
+                AIRequestError::Other(format!("Response does not match the schema: {}", errors.join("; ")))
+            })?;
+        }
+        Ok(value)
+    }
+}
+
+fn parse_json_reply(content: &str) -> Option<Value> {
+    let trimmed = content.trim();
+    if let Ok(value) = serde_json::from_str(trimmed) {
+        return Some(value);
+    }
+
+    // Strip a ```json fence, or fall back to the outermost object in the text
+    let unfenced = trimmed
+        .strip_prefix("```json")
+        .or_else(|| trimmed.strip_prefix("```"))
+        .and_then(|rest| rest.trim_end().strip_suffix("```"))
+        .map(|inner| inner.trim());
+    if let Some(inner) = unfenced && let Ok(value) = serde_json::from_str(inner) {
+        return Some(value);
+    }
+
+    let start = trimmed.find('{')?;
+    let end = trimmed.rfind('}')?;
+    if end <= start {
+        return None;
+    }
+    serde_json::from_str(&trimmed[start..=end]).ok()
+}
@@ -0,0 +1,89 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use super::TokenUsage;
+
+/// Metadata attached to a request, carried into the provider's user fields, usage records and
+/// debug logs. Every set of tags gets its own correlation ID
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RequestTags {
+    pub correlation_id: String,
+    pub values: BTreeMap<String, String>,
+}
+
+impl Default for RequestTags {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RequestTags {
+    pub const USER_ID: &'static str = "user_id";
+    pub const SESSION_ID: &'static str = "session_id";
+    pub const FEATURE: &'static str = "feature";
+    pub const TIER: &'static str = "tier"; // Set by `ModelTier`
+
+    pub fn new() -> Self {
+        Self {
+            correlation_id: uuid::Uuid::new_v4().to_string(),
+            values: BTreeMap::new(),
+        }
+    }
+
+    /// Use an ID from the caller, e.g. the ID of the incoming HTTP request
+    pub fn with_correlation_id(mut self, correlation_id: &str) -> Self {
+        self.correlation_id = correlation_id.to_string();
+        self
+    }
+
+    pub fn with(mut self, key: &str, value: &str) -> Self {
+        self.values.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Also sent as the provider's end-user identifier (OpenAI and OpenRouter `user`,
+    /// Anthropic `metadata.user_id`) unless `GenerationOptions.user` is set
+    pub fn with_user_id(self, user_id: &str) -> Self {
+        self.with(Self::USER_ID, user_id)
+    }
+
+    pub fn with_session_id(self, session_id: &str) -> Self {
+        self.with(Self::SESSION_ID, session_id)
+    }
+
+    pub fn with_feature(self, feature: &str) -> Self {
+        self.with(Self::FEATURE, feature)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    pub fn user_id(&self) -> Option<&str> {
+        self.get(Self::USER_ID)
+    }
+}
+
+impl fmt::Display for RequestTags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "correlation_id={}", self.correlation_id)?;
+        for (key, value) in &self.values {
+            write!(f, " {}={}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Usage of one completed request, reported to the callback set with `MonoAI::set_usage_callback`
+#[derive(Debug, Clone)]
+pub struct UsageRecord {
+    pub provider: String,
+    pub model: String,
+    pub usage: TokenUsage,
+    pub tags: Option<RequestTags>,
+}
+
+pub type UsageCallback = Arc<dyn Fn(&UsageRecord) + Send + Sync>;
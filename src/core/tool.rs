@@ -1,8 +1,93 @@
+use std::cell::RefCell;
+
+use base64::{Engine as _, engine::general_purpose};
 use serde_json::Value;
 
+use crate::core::{validate_against_schema, drop_optional_nulls};
+
+thread_local! {
+    static ATTACHED_IMAGES: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
+/// Attach an image (PNG, JPEG, GIF or WebP bytes) to the result of the tool currently running
+/// on this thread, e.g. a rendered chart. Vision-capable models receive it as an image block
+pub fn attach_image(bytes: &[u8]) {
+    ATTACHED_IMAGES.with(|images| {
+        if let Some(images) = images.borrow_mut().as_mut() {
+            images.push(general_purpose::STANDARD.encode(bytes));
+        }
+    });
+}
+
+/// Text and images produced by one tool call
+#[derive(Debug, Clone, Default)]
+pub struct ToolOutput {
+    pub content: String,
+    pub images: Vec<String>, // Base64 encoded
+    pub is_error: bool, // The call failed, `content` describes the error
+}
+
+impl ToolOutput {
+    pub fn error(content: impl Into<String>) -> Self {
+        Self { content: content.into(), images: Vec::new(), is_error: true }
+    }
+}
+
+impl From<String> for ToolOutput {
+    fn from(content: String) -> Self {
+        Self { content, images: Vec::new(), is_error: false }
+    }
+}
+
+impl From<&str> for ToolOutput {
+    fn from(content: &str) -> Self {
+        content.to_string().into()
+    }
+}
+
 pub struct Tool {
     pub name: String,
     pub description: String,
     pub parameters: Value,
     pub function: Box<dyn Fn(serde_json::Value) -> String + Send + Sync>,
-}
\ No newline at end of file
+}
+
+impl Tool {
+    /// Check model-provided arguments against the tool's parameter schema
+    pub fn validate_arguments(&self, arguments: &Value) -> Result<(), Vec<String>> {
+        validate_against_schema(&self.parameters, arguments)
+    }
+
+    /// Validate arguments and run the tool. Nulls for optional arguments are dropped first.
+    /// Invalid arguments are not passed to the function, instead a corrective result is
+    /// returned so the model can retry the call
+    pub fn invoke(&self, mut arguments: Value) -> String {
+        drop_optional_nulls(&self.parameters, &mut arguments);
+        match self.validate_arguments(&arguments) {
+            Ok(()) => (self.function)(arguments),
+            Err(errors) => self.invalid_arguments_message(&errors),
+        }
+    }
+
+    /// Like `invoke`, also collecting images the tool attached with `attach_image`.
+    /// Invalid arguments produce an output flagged as an error
+    pub fn invoke_with_attachments(&self, mut arguments: Value) -> ToolOutput {
+        drop_optional_nulls(&self.parameters, &mut arguments);
+        if let Err(errors) = self.validate_arguments(&arguments) {
+            return ToolOutput::error(self.invalid_arguments_message(&errors));
+        }
+
+        let previous = ATTACHED_IMAGES.with(|images| images.replace(Some(Vec::new())));
+        let content = (self.function)(arguments);
+        let images = ATTACHED_IMAGES.with(|images| images.replace(previous)).unwrap_or_default();
+        ToolOutput { content, images, is_error: false }
+    }
+
+    fn invalid_arguments_message(&self, errors: &[String]) -> String {
+        format!(
+            "Error: invalid arguments for tool '{}': {}. Fix the arguments and call the tool again.",
+            self.name,
+            errors.join("; ")
+        )
+    }
+}
@@ -0,0 +1,176 @@
+use std::fmt;
+use std::sync::Arc;
+
+use regex::Regex;
+use serde_json::{json, Value};
+
+use crate::core::{AIRequestError, Tool, ToolRegistry, check_schema, strict_schema};
+
+/// API whose tool definition format to use, see `Tool::to_provider_schema`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProviderKind {
+    Ollama,
+    Anthropic,
+    OpenAI,
+    OpenRouter,
+}
+
+/// A problem with a tool definition that the provider would reject, see `ToolRegistry::validate`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolSchemaIssue {
+    pub tool: String,
+    pub path: String, // JSON pointer into the parameters, e.g. `#/properties/city`
+    pub message: String,
+}
+
+impl fmt::Display for ToolSchemaIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "tool '{}' at {}: {}", self.tool, self.path, self.message)
+    }
+}
+
+impl Tool {
+    /// Check the name and parameter schema against JSON Schema and what every provider accepts:
+    /// names of 1-64 letters, digits, `_` or `-`, and an object schema at the root
+    pub fn validate_schema(&self) -> Result<(), Vec<ToolSchemaIssue>> {
+        self.schema_issues(false)
+    }
+
+    /// Like `validate_schema`, also applying OpenAI's strict mode rules
+    pub fn validate_strict_schema(&self) -> Result<(), Vec<ToolSchemaIssue>> {
+        self.schema_issues(true)
+    }
+
+    fn schema_issues(&self, strict: bool) -> Result<(), Vec<ToolSchemaIssue>> {
+        let mut issues = check_schema(&self.parameters, strict);
+        if !Regex::new("^[a-zA-Z0-9_-]{1,64}$").unwrap().is_match(&self.name) {
+            issues.insert(0, ("name".to_string(), "names must be 1-64 letters, digits, '_' or '-'".to_string()));
+        }
+        if !strict && self.parameters.get("type").is_some_and(|t| t != "object") {
+            issues.push(("#/type".to_string(), "parameters must be an object schema".to_string()));
+        }
+
+        if issues.is_empty() {
+            return Ok(());
+        }
+        Err(issues
+            .into_iter()
+            .map(|(path, message)| ToolSchemaIssue { tool: self.name.clone(), path, message })
+            .collect())
+    }
+
+    /// The parameters as OpenAI's strict mode takes them: every object closed and every property
+    /// required, the optional ones nullable. None when the schema can't be made strict
+    pub fn strict_parameters(&self) -> Option<Value> {
+        strict_schema(&self.parameters)
+    }
+
+    /// The definition as the provider's API takes it: `{"type": "function", "function": {..}}`
+    /// for Ollama, OpenAI and OpenRouter, `{"name", "description", "input_schema"}` for Anthropic
+    pub fn to_provider_schema(&self, provider: ProviderKind) -> Value {
+        match provider {
+            ProviderKind::Anthropic => json!({
+                "name": self.name,
+                "description": self.description,
+                "input_schema": self.parameters,
+            }),
+            ProviderKind::OpenAI => {
+                // OpenAI rejects arguments outside the schema only when told so
+                let mut parameters = self.parameters.clone();
+                if let Some(obj) = parameters.as_object_mut() {
+                    obj.insert("additionalProperties".to_string(), Value::Bool(false));
+                }
+                json!({
+                    "type": "function",
+                    "function": {
+                        "name": self.name,
+                        "description": self.description,
+                        "parameters": parameters,
+                    }
+                })
+            }
+            ProviderKind::Ollama | ProviderKind::OpenRouter => json!({
+                "type": "function",
+                "function": {
+                    "name": self.name,
+                    "description": self.description,
+                    "parameters": self.parameters,
+                }
+            }),
+        }
+    }
+
+    /// A tool from a definition in any provider's format, or plain `{"name", "description",
+    /// "parameters"}`, that runs `function` when called
+    pub fn from_provider_schema(
+        definition: &Value,
+        function: impl Fn(Value) -> String + Send + Sync + 'static,
+    ) -> Result<Tool, AIRequestError> {
+        let definition = definition.get("function").unwrap_or(definition);
+        let name = definition
+            .get("name")
+            .and_then(|name| name.as_str())
+            .ok_or_else(|| AIRequestError::Other(format!("Tool definition without a name: {}", definition)))?;
+        let parameters = definition
+            .get("parameters")
+            .or_else(|| definition.get("input_schema"))
+            .cloned()
+            .unwrap_or_else(|| json!({"type": "object", "properties": {}}));
+
+        Ok(Tool {
+            name: name.to_string(),
+            description: definition.get("description").and_then(|d| d.as_str()).unwrap_or_default().to_string(),
+            parameters,
+            function: Box::new(function),
+        })
+    }
+}
+
+impl ToolRegistry {
+    /// Definitions of the enabled tools in the provider's format, e.g. for a config file or a
+    /// service in another language
+    pub fn to_provider_schema(&self, provider: ProviderKind) -> Value {
+        Value::Array(self.enabled_tools().iter().map(|tool| tool.to_provider_schema(provider)).collect())
+    }
+
+    /// Check every registered tool, including disabled ones, with `Tool::validate_schema`, e.g.
+    /// at startup rather than finding out from a 400 on the first request
+    pub fn validate(&self) -> Result<(), Vec<ToolSchemaIssue>> {
+        self.collect_issues(Tool::validate_schema)
+    }
+
+    /// Like `validate`, also applying OpenAI's strict mode rules
+    pub fn validate_strict(&self) -> Result<(), Vec<ToolSchemaIssue>> {
+        self.collect_issues(Tool::validate_strict_schema)
+    }
+
+    fn collect_issues(&self, validate: fn(&Tool) -> Result<(), Vec<ToolSchemaIssue>>) -> Result<(), Vec<ToolSchemaIssue>> {
+        let issues: Vec<ToolSchemaIssue> = self.all_tools().iter().filter_map(|tool| validate(tool).err()).flatten().collect();
+        if issues.is_empty() { Ok(()) } else { Err(issues) }
+    }
+
+    /// Registry of the tools in a JSON array of definitions (or `{"tools": [..]}`), in any format
+    /// `Tool::from_provider_schema` reads. Every call goes to `handler` with the tool name and the
+    /// arguments, e.g. to forward it to the service that implements the tools
+    pub fn from_json(
+        json: &str,
+        handler: impl Fn(&str, Value) -> String + Send + Sync + 'static,
+    ) -> Result<ToolRegistry, AIRequestError> {
+        let document: Value = serde_json::from_str(json)?;
+        let definitions = document
+            .get("tools")
+            .unwrap_or(&document)
+            .as_array()
+            .ok_or_else(|| AIRequestError::Other("Expected an array of tool definitions".to_string()))?;
+
+        let handler = Arc::new(handler);
+        let registry = ToolRegistry::new();
+        for definition in definitions {
+            let mut tool = Tool::from_provider_schema(definition, |_| String::new())?;
+            let (handler, name) = (handler.clone(), tool.name.clone());
+            tool.function = Box::new(move |args| handler(&name, args));
+            registry.register(tool)?;
+        }
+        Ok(registry)
+    }
+}
@@ -1,6 +1,13 @@
+use base64::{Engine as _, engine::general_purpose};
+use futures_util::{Stream, StreamExt};
 use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::pin::Pin;
+use std::time::{Duration, Instant};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+use crate::core::{Audio, ModelPricing, ToolOutput};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Message {
     pub role: String,
     pub content: String,
@@ -8,6 +15,202 @@ pub struct Message {
     pub images: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_calls: Option<Vec<ToolCall>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>, // Set on role "tool" messages, the call this result answers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>, // Tool name for role "tool" messages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<Vec<ThinkingBlock>>, // Reasoning of assistant messages, sent back on later turns
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_error: Option<bool>, // Set on role "tool" messages whose call failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub documents: Option<Vec<Document>>, // PDFs and text documents attached to user messages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_urls: Option<Vec<String>>, // Remote images, downloaded for providers that only take inline data
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache: Option<CacheHint>, // Ends a prefix worth caching, see `CacheHint`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio: Option<Vec<Audio>>, // Recordings attached to user messages, for models that listen to audio
+}
+
+/// Marks the end of a prompt prefix that later requests will repeat, such as a long system
+/// prompt or documents, so providers can cache it. Anthropic and OpenRouter (for models with
+/// explicit caching) cache up to the marked message, OpenAI and Ollama cache prefixes on their
+/// own and ignore the hint. Cache hits are reported in `TokenUsage.cached_prompt_tokens`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheHint {
+    /// Kept for about 5 minutes after the last use
+    Ephemeral,
+    /// Kept for about an hour, at a higher write price where the provider charges one
+    Extended,
+}
+
+/// A document the model can read and cite. Anthropic receives it as a document block,
+/// other providers get text documents inlined into the message and don't support PDFs
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Document {
+    pub source: DocumentSource,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub citations: bool, // Ask the model to cite passages it relies on
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum DocumentSource {
+    Pdf(String), // Base64 encoded
+    Text(String),
+}
+
+impl Document {
+    pub fn pdf(bytes: &[u8]) -> Self {
+        Self {
+            source: DocumentSource::Pdf(general_purpose::STANDARD.encode(bytes)),
+            title: None,
+            citations: false,
+        }
+    }
+
+    pub fn pdf_file(path: &str) -> Result<Self, std::io::Error> {
+        let mut document = Self::pdf(&std::fs::read(path)?);
+        document.title = std::path::Path::new(path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string());
+        Ok(document)
+    }
+
+    pub fn text(text: &str) -> Self {
+        Self {
+            source: DocumentSource::Text(text.to_string()),
+            title: None,
+            citations: false,
+        }
+    }
+
+    pub fn with_title(mut self, title: &str) -> Self {
+        self.title = Some(title.to_string());
+        self
+    }
+
+    pub fn with_citations(mut self, enabled: bool) -> Self {
+        self.citations = enabled;
+        self
+    }
+}
+
+impl Message {
+    /// A text message, other fields are set with struct update syntax:
+    /// `Message { images: Some(images), ..Message::user("What is this?") }`
+    pub fn new(role: &str, content: impl Into<String>) -> Self {
+        Self {
+            role: role.to_string(),
+            content: content.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Self::new("user", content)
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self::new("assistant", content)
+    }
+
+    pub fn system(content: impl Into<String>) -> Self {
+        Self::new("system", content)
+    }
+
+    /// Cache the conversation up to and including this message
+    pub fn with_cache(mut self, hint: CacheHint) -> Self {
+        self.cache = Some(hint);
+        self
+    }
+
+    /// Attach a recording for the model to listen to, instead of transcribing it first
+    pub fn with_audio(mut self, audio: Audio) -> Self {
+        self.audio.get_or_insert_with(Vec::new).push(audio);
+        self
+    }
+
+    /// Copy of the message with text documents prepended to the content, for providers
+    /// without document support
+    pub fn with_inlined_documents(&self) -> Message {
+        let mut message = self.clone();
+        let Some(documents) = message.documents.take() else {
+            return message;
+        };
+
+        let mut content = String::new();
+        for document in &documents {
+            if let DocumentSource::Text(text) = &document.source {
+                match &document.title {
+                    Some(title) => content.push_str(&format!("<document title=\"{}\">\n{}\n</document>\n\n", title, text)),
+                    None => content.push_str(&format!("<document>\n{}\n</document>\n\n", text)),
+                }
+            }
+        }
+        content.push_str(&message.content);
+        message.content = content;
+        message
+    }
+}
+
+/// Activity of a tool the provider runs itself, such as Anthropic web search. Reported for
+/// display, the provider already fed the result to the model
+#[derive(Debug, Clone)]
+pub enum ServerToolEvent {
+    Call { id: String, name: String, input: serde_json::Value },
+    Result { tool_use_id: String, content: serde_json::Value },
+}
+
+/// A passage of a source the model relied on, for showing grounded references
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Citation {
+    pub cited_text: String,
+    pub document_index: Option<usize>, // Position among the documents of the request
+    pub document_title: Option<String>,
+    pub location: CitationLocation,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum CitationLocation {
+    /// Character range in a text document, end exclusive
+    Chars { start: usize, end: usize },
+    /// Page range in a PDF, 1-based with end exclusive
+    Pages { start: usize, end: usize },
+    /// Block range in a custom content document
+    Blocks { start: usize, end: usize },
+    /// Web page found by a search tool
+    Url { url: String, title: Option<String> },
+}
+
+/// Reasoning produced with an assistant reply. Anthropic requires it unchanged, signature
+/// included, when a conversation continues after tool use with extended thinking
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ThinkingBlock {
+    pub thinking: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub redacted: bool, // Encrypted reasoning, `thinking` holds the opaque data
+}
+
+impl Message {
+    /// Result of a tool call, providers serialize it in their native tool result format.
+    /// Images in the output are forwarded to vision-capable models
+    pub fn tool_result(tool_call: &ToolCall, output: impl Into<ToolOutput>) -> Self {
+        let output = output.into();
+        Self {
+            images: if output.images.is_empty() { None } else { Some(output.images) },
+            tool_call_id: tool_call.id.clone(),
+            name: Some(tool_call.function.name.clone()),
+            is_error: if output.is_error { Some(true) } else { None },
+            ..Self::new("tool", output.content)
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -30,14 +233,101 @@ pub struct MonoModel {
     pub provider: String,
     pub size: Option<u64>, // Size in bytes for local models
     pub created: Option<u64>, // Creation timestamp for cloud models
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capabilities: Option<ModelCapabilities>, // From provider metadata, None when the provider lists no details
 }
 
-#[derive(Debug, Clone)]
+/// What a model accepts and supports, as reported by the provider
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ModelCapabilities {
+    pub context_length: Option<u32>, // Tokens
+    pub max_output_tokens: Option<u32>,
+    pub input_modalities: Vec<String>, // e.g. "text", "image", "file", "audio"
+    pub output_modalities: Vec<String>,
+    pub supported_parameters: Vec<String>, // Request parameters such as "tools", "reasoning", "response_format", "stream"
+    pub pricing: Option<ModelPricing>, // USD per token
+}
+
+impl ModelCapabilities {
+    pub fn supports_vision(&self) -> bool {
+        self.input_modalities.iter().any(|modality| modality == "image")
+    }
+
+    pub fn supports_tools(&self) -> bool {
+        self.supports_parameter("tools")
+    }
+
+    pub fn supports_reasoning(&self) -> bool {
+        self.supports_parameter("reasoning")
+    }
+
+    /// JSON output through `ResponseFormat`
+    pub fn supports_json_mode(&self) -> bool {
+        self.supports_parameter("response_format") || self.supports_parameter("structured_outputs")
+    }
+
+    pub fn supports_streaming(&self) -> bool {
+        self.supports_parameter("stream")
+    }
+
+    pub fn supports_image_output(&self) -> bool {
+        self.output_modalities.iter().any(|modality| modality == "image")
+    }
+
+    pub fn supports_parameter(&self, parameter: &str) -> bool {
+        self.supported_parameters.iter().any(|supported| supported == parameter)
+    }
+}
+
+/// Requirements for `get_available_models_filtered`. Models whose capabilities are unknown
+/// only match a filter without requirements
+#[derive(Debug, Clone, Default)]
+pub struct ModelFilter {
+    pub vision: bool,
+    pub tools: bool,
+    pub reasoning: bool,
+    pub image_output: bool,
+    pub min_context: Option<u32>, // Tokens
+    pub max_input_price: Option<f64>, // USD per million input tokens, models without a known price don't match
+}
+
+impl ModelFilter {
+    pub fn matches(&self, capabilities: Option<&ModelCapabilities>) -> bool {
+        let Some(capabilities) = capabilities else {
+            return !self.vision
+                && !self.tools
+                && !self.reasoning
+                && !self.image_output
+                && self.min_context.is_none()
+                && self.max_input_price.is_none();
+        };
+
+        (!self.vision || capabilities.supports_vision())
+            && (!self.tools || capabilities.supports_tools())
+            && (!self.reasoning || capabilities.supports_reasoning())
+            && (!self.image_output || capabilities.supports_image_output())
+            && self.min_context.is_none_or(|min| capabilities.context_length.is_some_and(|context| context >= min))
+            && self.max_input_price.is_none_or(|max| {
+                capabilities.pricing.is_some_and(|pricing| pricing.input * 1_000_000.0 <= max)
+            })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TokenUsage {
     pub prompt_tokens: Option<u32>,
     pub completion_tokens: Option<u32>,
     pub total_tokens: Option<u32>,
     pub cost_usd: Option<f64>, // Estimated cost in USD
+    pub cached_prompt_tokens: Option<u32>, // Part of the prompt tokens read from the provider's prompt cache
+    pub reasoning_tokens: Option<u32>, // Part of the completion tokens spent on hidden reasoning
+    pub audio_tokens: Option<u32>, // Part of the prompt and completion tokens that were audio
+}
+
+impl Default for TokenUsage {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TokenUsage {
@@ -47,6 +337,9 @@ impl TokenUsage {
             completion_tokens: None,
             total_tokens: None,
             cost_usd: None,
+            cached_prompt_tokens: None,
+            reasoning_tokens: None,
+            audio_tokens: None,
         }
     }
 
@@ -56,16 +349,112 @@ impl TokenUsage {
             completion_tokens: Some(completion),
             total_tokens: Some(prompt + completion),
             cost_usd: None,
+            cached_prompt_tokens: None,
+            reasoning_tokens: None,
+            audio_tokens: None,
         }
     }
 }
 
-#[derive(Debug)]
+/// Generation timings reported by local servers such as Ollama
+#[derive(Debug, Clone, Default)]
+pub struct GenerationTiming {
+    pub total_duration: Option<Duration>,
+    pub load_duration: Option<Duration>, // Time spent loading the model into memory
+    pub prompt_eval_duration: Option<Duration>,
+    pub eval_duration: Option<Duration>,
+    pub prompt_tokens_per_second: Option<f64>,
+    pub tokens_per_second: Option<f64>, // Generation speed
+}
+
+impl GenerationTiming {
+    /// Build from nanosecond durations and token counts as reported by Ollama
+    pub fn from_nanos(
+        total_duration: Option<u64>,
+        load_duration: Option<u64>,
+        prompt_eval_duration: Option<u64>,
+        eval_duration: Option<u64>,
+        prompt_eval_count: Option<u32>,
+        eval_count: Option<u32>,
+    ) -> Self {
+        let rate = |count: Option<u32>, nanos: Option<u64>| match (count, nanos) {
+            (Some(count), Some(nanos)) if nanos > 0 => Some(count as f64 / (nanos as f64 / 1_000_000_000.0)),
+            _ => None,
+        };
+
+        Self {
+            total_duration: total_duration.map(Duration::from_nanos),
+            load_duration: load_duration.map(Duration::from_nanos),
+            prompt_eval_duration: prompt_eval_duration.map(Duration::from_nanos),
+            eval_duration: eval_duration.map(Duration::from_nanos),
+            prompt_tokens_per_second: rate(prompt_eval_count, prompt_eval_duration),
+            tokens_per_second: rate(eval_count, eval_duration),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct ChatStreamItem {
     pub content: String,
+    pub thinking: Option<String>, // Reasoning from models that expose it, kept out of `content`
+    pub thinking_blocks: Option<Vec<ThinkingBlock>>, // Complete reasoning on the final item, for `Message.thinking`
+    pub citations: Option<Vec<Citation>>, // Sources backing the text streamed around this item
+    pub server_tool_event: Option<ServerToolEvent>,
     pub tool_calls: Option<Vec<ToolCall>>,
     pub done: bool,
     pub usage: Option<TokenUsage>,
+    pub timing: Option<GenerationTiming>, // Only on the final item, for providers reporting it
+    pub guardrail_flags: Option<Vec<String>>, // Reasons a guardrail flagged the exchange, on the final item
+    pub choice_index: Option<usize>, // Set on items of the extra completions requested with `n`, None for the first
+    pub finish_reason: Option<String>, // Why the model stopped, in the provider's terms ("stop", "end_turn", "length", ...), on the final item
+}
+
+/// Complete reply of a non-streaming request
+#[derive(Debug, Clone, Default)]
+pub struct ChatResponse {
+    pub text: String,
+    pub tool_calls: Option<Vec<ToolCall>>,
+    pub usage: Option<TokenUsage>,
+    pub finish_reason: Option<String>, // In the provider's terms, see `ChatStreamItem.finish_reason`
+    pub latency: Duration, // From sending the request to the end of the reply
+    pub choices: Vec<String>, // Texts of the extra completions requested with `n`, in index order
+}
+
+impl ChatResponse {
+    /// Drain a chat stream into one response, texts of extra completions go to `choices`.
+    /// `started` is when the request was sent
+    pub async fn from_stream(
+        mut stream: Pin<Box<dyn Stream<Item = Result<ChatStreamItem, String>> + Send>>,
+        started: Instant,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut response = Self::default();
+        while let Some(item) = stream.next().await {
+            let item = item.map_err(|e| format!("Stream error: {}", e))?;
+            // Index 0 is the first completion, the same as no index
+            if let Some(index) = item.choice_index.filter(|&index| index > 0) {
+                if index > response.choices.len() {
+                    response.choices.resize(index, String::new());
+                }
+                response.choices[index - 1].push_str(&item.content);
+                continue;
+            }
+            response.text.push_str(&item.content);
+            if item.tool_calls.is_some() {
+                response.tool_calls = item.tool_calls;
+            }
+            if item.usage.is_some() {
+                response.usage = item.usage;
+            }
+            if item.finish_reason.is_some() {
+                response.finish_reason = item.finish_reason;
+            }
+            if item.done {
+                break;
+            }
+        }
+        response.latency = started.elapsed();
+        Ok(response)
+    }
 }
 
 #[derive(Debug)]
@@ -76,10 +465,71 @@ pub struct PullProgress {
     pub completed: Option<u64>,
 }
 
+#[derive(Debug, Clone)]
+pub struct ToolProgress {
+    pub tool_call_id: Option<String>,
+    pub tool_name: String,
+    pub message: String,
+    pub fraction: Option<f32>, // 0.0 - 1.0 when the tool knows how far along it is
+}
+
+#[derive(Debug)]
+pub enum ToolEvent {
+    Progress(ToolProgress),
+    Response(Message), // Formatted tool result, ready to append to the conversation
+}
+
 #[derive(Deserialize, Debug)]
 pub struct ModelInfo {
     pub license: String,
     pub modelfile: String,
     pub parameters: String,
     pub template: String,
+    #[serde(default)]
+    pub capabilities: Vec<String>, // e.g. "completion", "vision", "tools", "thinking", reported since Ollama 0.6.4
+    #[serde(default)]
+    pub model_info: serde_json::Map<String, serde_json::Value>, // Architecture details keyed like "llama.context_length"
+}
+
+impl ModelInfo {
+    /// Maximum context the model was trained for, Ollama runs it with a smaller `num_ctx` by default
+    pub fn context_length(&self) -> Option<u32> {
+        self.model_info
+            .iter()
+            .find(|(key, _)| key.ends_with(".context_length"))
+            .and_then(|(_, value)| value.as_u64())
+            .map(|length| length as u32)
+    }
+
+    pub fn model_capabilities(&self) -> ModelCapabilities {
+        let has = |capability: &str| self.capabilities.iter().any(|c| c == capability);
+
+        let mut input_modalities = vec!["text".to_string()];
+        if has("vision") {
+            input_modalities.push("image".to_string());
+        }
+
+        let mut supported_parameters = Vec::new();
+        // Embedding-only models report just "embedding" and can't chat
+        if self.capabilities.is_empty() || has("completion") {
+            supported_parameters.push("response_format".to_string());
+            supported_parameters.push("stream".to_string());
+        }
+        // Older servers don't report capabilities, tool support shows in the template
+        if has("tools") || self.template.contains(".Tools") || self.template.contains(".tools") {
+            supported_parameters.push("tools".to_string());
+        }
+        if has("thinking") {
+            supported_parameters.push("reasoning".to_string());
+        }
+
+        ModelCapabilities {
+            context_length: self.context_length(),
+            max_output_tokens: None,
+            input_modalities,
+            output_modalities: vec!["text".to_string()],
+            supported_parameters,
+            pricing: None,
+        }
+    }
 }
\ No newline at end of file
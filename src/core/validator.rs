@@ -0,0 +1,105 @@
+use std::error::Error;
+
+use regex::Regex;
+use serde_json::Value;
+
+use super::{AIRequestError, ResponseFormat};
+
+/// Check run on a completed reply by `MonoAI::send_chat_request_validated`. The error is shown
+/// to the model when asking it to repair the reply, so it should say what is wrong.
+/// Closures `Fn(&str) -> Result<(), String>` implement it
+pub trait Validator: Send + Sync {
+    fn validate(&self, response: &str) -> Result<(), String>;
+}
+
+impl<F> Validator for F
+where
+    F: Fn(&str) -> Result<(), String> + Send + Sync,
+{
+    fn validate(&self, response: &str) -> Result<(), String> {
+        self(response)
+    }
+}
+
+/// Every validator has to pass, the errors of those failing are combined
+impl Validator for Vec<Box<dyn Validator>> {
+    fn validate(&self, response: &str) -> Result<(), String> {
+        let errors: Vec<String> = self.iter().filter_map(|validator| validator.validate(response).err()).collect();
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors.join("\n")),
+        }
+    }
+}
+
+/// Reply has to match a regex somewhere
+#[derive(Debug, Clone)]
+pub struct RegexValidator {
+    regex: Regex,
+}
+
+impl RegexValidator {
+    pub fn new(pattern: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(Self { regex: Regex::new(pattern)? })
+    }
+}
+
+impl Validator for RegexValidator {
+    fn validate(&self, response: &str) -> Result<(), String> {
+        match self.regex.is_match(response) {
+            true => Ok(()),
+            false => Err(format!("The reply does not match the pattern /{}/.", self.regex.as_str())),
+        }
+    }
+}
+
+/// Reply has to be JSON matching a schema, code fences and text around the JSON are tolerated
+#[derive(Debug, Clone)]
+pub struct SchemaValidator {
+    format: ResponseFormat,
+}
+
+impl SchemaValidator {
+    pub fn new(schema: Value) -> Self {
+        Self { format: ResponseFormat::json_schema("reply", schema) }
+    }
+}
+
+impl Validator for SchemaValidator {
+    fn validate(&self, response: &str) -> Result<(), String> {
+        self.format.parse(response).map(|_| ()).map_err(|e| match e {
+            AIRequestError::Other(message) => message,
+            e => e.to_string(),
+        })
+    }
+}
+
+/// Reply length in characters, after trimming whitespace
+#[derive(Debug, Clone, Copy)]
+pub struct LengthValidator {
+    pub min: usize,
+    pub max: usize,
+}
+
+impl LengthValidator {
+    pub fn max(max: usize) -> Self {
+        Self { min: 0, max }
+    }
+
+    pub fn between(min: usize, max: usize) -> Self {
+        Self { min, max }
+    }
+}
+
+impl Validator for LengthValidator {
+    fn validate(&self, response: &str) -> Result<(), String> {
+        let length = response.trim().chars().count();
+        if length < self.min {
+            Err(format!("The reply has {} characters, it needs at least {}.", length, self.min))
+        } else if length > self.max {
+            Err(format!("The reply has {} characters, it must not exceed {}.", length, self.max))
+        } else {
+            Ok(())
+        }
+    }
+}
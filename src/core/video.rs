@@ -0,0 +1,109 @@
+use std::error::Error;
+use std::path::Path;
+
+use base64::{Engine as _, engine::general_purpose};
+use tokio::process::Command;
+
+use super::{EncodedImage, ImageFormat, Message};
+
+/// How `extract_video_frames` samples a clip
+#[derive(Debug, Clone, PartialEq)]
+pub struct VideoFrameOptions {
+    pub fps: f32, // Frames per second of video
+    pub max_dimension: u32, // Longer side of each frame in pixels, smaller frames are kept as is
+    pub max_frames: Option<usize>, // Frames after this many are dropped
+}
+
+impl Default for VideoFrameOptions {
+    /// One frame per second at 768px, up to 20 frames
+    fn default() -> Self {
+        Self {
+            fps: 1.0,
+            max_dimension: 768,
+            max_frames: Some(20),
+        }
+    }
+}
+
+impl VideoFrameOptions {
+    pub fn with_fps(mut self, fps: f32) -> Self {
+        self.fps = fps;
+        self
+    }
+
+    pub fn with_max_dimension(mut self, max_dimension: u32) -> Self {
+        self.max_dimension = max_dimension;
+        self
+    }
+
+    pub fn with_max_frames(mut self, max_frames: Option<usize>) -> Self {
+        self.max_frames = max_frames;
+        self
+    }
+}
+
+/// Sample frames from the video at `path` as JPEG images, in playback order. Decoding is left
+/// to the `ffmpeg` binary, which has to be on PATH
+pub async fn extract_video_frames(path: impl AsRef<Path>, options: &VideoFrameOptions) -> Result<Vec<EncodedImage>, Box<dyn Error>> {
+    if options.fps.is_nan() || options.fps <= 0.0 {
+        return Err(format!("Frame rate must be positive, got {}", options.fps).into());
+    }
+    let directory = std::env::temp_dir().join(format!("mono-ai-frames-{}", uuid::Uuid::new_v4()));
+    tokio::fs::create_dir(&directory).await?;
+    let frames = run_ffmpeg(path.as_ref(), &directory, options).await;
+    let _ = tokio::fs::remove_dir_all(&directory).await;
+    frames
+}
+
+// Writes the frames into `directory` as numbered files and reads them back in order
+async fn run_ffmpeg(path: &Path, directory: &Path, options: &VideoFrameOptions) -> Result<Vec<EncodedImage>, Box<dyn Error>> {
+    // Fit within a max_dimension square without upscaling, keeping the aspect ratio
+    let filter = format!(
+        "fps={},scale='min({size},iw)':'min({size},ih)':force_original_aspect_ratio=decrease",
+        options.fps,
+        size = options.max_dimension
+    );
+    let mut command = Command::new("ffmpeg");
+    command.args(["-hide_banner", "-loglevel", "error", "-i"]).arg(path).args(["-vf", &filter, "-q:v", "3"]);
+    if let Some(max_frames) = options.max_frames {
+        command.args(["-frames:v", &max_frames.to_string()]);
+    }
+    command.arg(directory.join("frame_%05d.jpg"));
+
+    let output = command.output().await.map_err(|e| -> Box<dyn Error> {
+        match e.kind() {
+            std::io::ErrorKind::NotFound => "ffmpeg was not found on PATH, it is needed to extract video frames".into(),
+            _ => e.into(),
+        }
+    })?;
+    if !output.status.success() {
+        return Err(format!("ffmpeg failed on {}: {}", path.display(), String::from_utf8_lossy(&output.stderr).trim()).into());
+    }
+
+    let mut files = Vec::new();
+    let mut entries = tokio::fs::read_dir(directory).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        files.push(entry.path());
+    }
+    files.sort();
+
+    let mut frames = Vec::with_capacity(files.len());
+    for file in files {
+        let data = tokio::fs::read(&file).await?;
+        frames.push(EncodedImage { data: general_purpose::STANDARD.encode(data), format: ImageFormat::Jpeg });
+    }
+    if frames.is_empty() {
+        return Err(format!("No frames could be extracted from {}", path.display()).into());
+    }
+    Ok(frames)
+}
+
+impl Message {
+    /// Attach frames sampled from the video at `path` as images, for "describe this clip"
+    /// requests to vision models
+    pub async fn with_video_frames(mut self, path: impl AsRef<Path>, options: &VideoFrameOptions) -> Result<Self, Box<dyn Error>> {
+        let frames = extract_video_frames(path, options).await?;
+        self.images.get_or_insert_with(Vec::new).extend(frames.into_iter().map(String::from));
+        Ok(self)
+    }
+}
@@ -0,0 +1,132 @@
+use std::fmt;
+use std::sync::Arc;
+
+use regex::Regex;
+use serde_json::Value;
+
+use crate::core::{validate_against_schema, Message};
+
+/// A prompt and the properties its reply must have
+#[derive(Debug, Clone)]
+pub struct EvalCase {
+    pub name: String,
+    pub messages: Vec<Message>,
+    pub checks: Vec<Check>,
+}
+
+/// Property of a reply. `Judge` needs a judge model on the `Evaluation`, the others are
+/// checked in code
+#[derive(Clone)]
+pub enum Check {
+    Contains(String),
+    NotContains(String),
+    Matches(String), // Regex
+    MaxChars(usize),
+    /// Reply is JSON, matching the schema when there is one
+    Json(Option<Value>),
+    /// Judge scores the reply on the criteria from 0 to 10, passing at `min_score` or above
+    Judge { criteria: String, min_score: f64 },
+    /// Named check in code, returning why the reply fails
+    Custom(String, CheckFn),
+}
+
+pub type CheckFn = Arc<dyn Fn(&str) -> Result<(), String> + Send + Sync>;
+
+impl EvalCase {
+    /// Case sending `prompt` as a single user message
+    pub fn new(name: &str, prompt: &str) -> Self {
+        Self::from_messages(name, vec![Message::user(prompt)])
+    }
+
+    pub fn from_messages(name: &str, messages: Vec<Message>) -> Self {
+        Self {
+            name: name.to_string(),
+            messages,
+            checks: Vec::new(),
+        }
+    }
+
+    pub fn expect(mut self, check: Check) -> Self {
+        self.checks.push(check);
+        self
+    }
+}
+
+impl Check {
+    pub fn contains(text: &str) -> Self {
+        Check::Contains(text.to_string())
+    }
+
+    pub fn not_contains(text: &str) -> Self {
+        Check::NotContains(text.to_string())
+    }
+
+    pub fn matches(pattern: &str) -> Self {
+        Check::Matches(pattern.to_string())
+    }
+
+    pub fn judge(criteria: &str, min_score: f64) -> Self {
+        Check::Judge {
+            criteria: criteria.to_string(),
+            min_score,
+        }
+    }
+
+    pub fn custom(name: &str, check: impl Fn(&str) -> Result<(), String> + Send + Sync + 'static) -> Self {
+        Check::Custom(name.to_string(), Arc::new(check))
+    }
+
+    /// Run a check that doesn't need a judge, None for `Judge`
+    pub(crate) fn run_local(&self, output: &str) -> Option<Result<(), String>> {
+        let result = match self {
+            Check::Contains(text) => match output.contains(text.as_str()) {
+                true => Ok(()),
+                false => Err(format!("Reply does not contain {:?}", text)),
+            },
+            Check::NotContains(text) => match output.contains(text.as_str()) {
+                true => Err(format!("Reply contains {:?}", text)),
+                false => Ok(()),
+            },
+            Check::Matches(pattern) => match Regex::new(pattern) {
+                Ok(regex) if regex.is_match(output) => Ok(()),
+                Ok(_) => Err(format!("Reply does not match /{}/", pattern)),
+                Err(e) => Err(format!("Invalid pattern /{}/: {}", pattern, e)),
+            },
+            Check::MaxChars(max) => match output.chars().count() {
+                count if count <= *max => Ok(()),
+                count => Err(format!("Reply has {} characters, more than {}", count, max)),
+            },
+            Check::Json(schema) => match serde_json::from_str::<Value>(output.trim()) {
+                Ok(value) => match schema {
+                    Some(schema) => validate_against_schema(schema, &value).map_err(|errors| errors.join("; ")),
+                    None => Ok(()),
+                },
+                Err(e) => Err(format!("Reply is not valid JSON: {}", e)),
+            },
+            Check::Custom(_, check) => check(output),
+            Check::Judge { .. } => return None,
+        };
+        Some(result)
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Check::Contains(text) => write!(f, "contains {:?}", text),
+            Check::NotContains(text) => write!(f, "does not contain {:?}", text),
+            Check::Matches(pattern) => write!(f, "matches /{}/", pattern),
+            Check::MaxChars(max) => write!(f, "at most {} characters", max),
+            Check::Json(Some(_)) => write!(f, "JSON matching the schema"),
+            Check::Json(None) => write!(f, "JSON"),
+            Check::Judge { criteria, min_score } => write!(f, "judge scores {:?} at least {}", criteria, min_score),
+            Check::Custom(name, _) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl fmt::Debug for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Check({})", self)
+    }
+}
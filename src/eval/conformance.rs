@@ -0,0 +1,310 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use futures_util::StreamExt;
+use futures_util::future::join_all;
+use serde::Serialize;
+use serde_json::json;
+
+use crate::core::{validate_against_schema, Message, Tool};
+use crate::mono::MonoAI;
+
+// 64x64 PNG filled with red, for the vision check
+const RED_SQUARE: &str = "iVBORw0KGgoAAAANSUhEUgAAAEAAAABACAIAAAAlC+aJAAAAT0lEQVR42u3PQQkAAAgEsAtx/ZMZxgi+hcEKLNO+FgEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQGBywKqxUDxqh7TUQAAAABJRU5ErkJggg==";
+
+/// Behavior every provider should share, checked by `ConformanceSuite`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ConformanceCheck {
+    /// Text streams in items and the stream ends with one done item
+    StreamingText,
+    /// A registered tool is called with arguments matching its schema
+    ToolCall,
+    /// An attached image is seen. Skipped for models without vision
+    Vision,
+    /// Replies report their prompt and completion tokens
+    Usage,
+    /// A request for a model that doesn't exist fails with an error instead of a reply
+    ErrorHandling,
+}
+
+impl ConformanceCheck {
+    pub const ALL: [ConformanceCheck; 5] = [
+        ConformanceCheck::StreamingText,
+        ConformanceCheck::ToolCall,
+        ConformanceCheck::Vision,
+        ConformanceCheck::Usage,
+        ConformanceCheck::ErrorHandling,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConformanceCheck::StreamingText => "streaming",
+            ConformanceCheck::ToolCall => "tool call",
+            ConformanceCheck::Vision => "vision",
+            ConformanceCheck::Usage => "usage",
+            ConformanceCheck::ErrorHandling => "errors",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum ConformanceOutcome {
+    Passed,
+    Failed(String),
+    Skipped(String), // The model or client can't take part, e.g. no vision
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConformanceResult {
+    pub provider: String,
+    pub model: String,
+    pub check: ConformanceCheck,
+    pub outcome: ConformanceOutcome,
+    pub duration: Duration,
+}
+
+/// Outcome of every check on every client, printed as a table of clients by checks
+#[derive(Debug, Clone, Serialize)]
+pub struct ConformanceMatrix {
+    pub results: Vec<ConformanceResult>,
+}
+
+/// Runs a shared battery of live requests against several clients at once, to keep providers
+/// behaving the same as they evolve. Every check is a real, small request
+pub struct ConformanceSuite {
+    checks: Vec<ConformanceCheck>,
+    timeout: Duration,
+}
+
+impl Default for ConformanceSuite {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConformanceSuite {
+    /// Every check, each failing after 2 minutes
+    pub fn new() -> Self {
+        Self { checks: ConformanceCheck::ALL.to_vec(), timeout: Duration::from_secs(120) }
+    }
+
+    pub fn with_checks(mut self, checks: &[ConformanceCheck]) -> Self {
+        self.checks = checks.to_vec();
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Run the checks on all clients in parallel, each client's checks one after the other
+    pub async fn run(&self, clients: &[&MonoAI]) -> ConformanceMatrix {
+        let runs = clients.iter().map(|client| async move {
+            let mut results = Vec::new();
+            for check in &self.checks {
+                results.push(self.run_check(client, *check).await);
+            }
+            results
+        });
+        ConformanceMatrix { results: join_all(runs).await.into_iter().flatten().collect() }
+    }
+
+    async fn run_check(&self, client: &MonoAI, check: ConformanceCheck) -> ConformanceResult {
+        let started = Instant::now();
+        let run = async {
+            match check {
+                ConformanceCheck::StreamingText => check_streaming(client).await,
+                ConformanceCheck::ToolCall => check_tool_call(client).await,
+                ConformanceCheck::Vision => check_vision(client).await,
+                ConformanceCheck::Usage => check_usage(client).await,
+                ConformanceCheck::ErrorHandling => check_errors(client).await,
+            }
+        };
+        let outcome = match tokio::time::timeout(self.timeout, run).await {
+            Ok(Ok(outcome)) => outcome,
+            Ok(Err(e)) => ConformanceOutcome::Failed(e),
+            Err(_) => ConformanceOutcome::Failed(format!("No result after {}s", self.timeout.as_secs())),
+        };
+        ConformanceResult {
+            provider: client.provider_name().to_string(),
+            model: client.model().to_string(),
+            check,
+            outcome,
+            duration: started.elapsed(),
+        }
+    }
+}
+
+async fn check_streaming(client: &MonoAI) -> Result<ConformanceOutcome, String> {
+    let messages = [Message::user("Reply with exactly these words and nothing else: conformance ok")];
+    let mut stream = client.send_chat_request(&messages).await.map_err(|e| e.to_string())?;
+    let mut text = String::new();
+    let mut done = false;
+    while let Some(item) = stream.next().await {
+        let item = item?;
+        if done {
+            return Err("Items arrived after the done item".to_string());
+        }
+        text.push_str(&item.content);
+        done = item.done;
+    }
+    if !done {
+        return Err("The stream ended without a done item".to_string());
+    }
+    if !text.to_lowercase().contains("conformance ok") {
+        return Err(format!("Unexpected reply: {}", text));
+    }
+    Ok(ConformanceOutcome::Passed)
+}
+
+async fn check_tool_call(client: &MonoAI) -> Result<ConformanceOutcome, String> {
+    // A copy without the caller's tools
+    let Some(mut client) = client.for_model(client.model()) else {
+        return Ok(ConformanceOutcome::Skipped("The client answers from a script".to_string()));
+    };
+    let parameters = json!({
+        "type": "object",
+        "properties": { "city": { "type": "string" } },
+        "required": ["city"]
+    });
+    let tool = Tool {
+        name: "get_weather".to_string(),
+        description: "Current weather in a city".to_string(),
+        parameters: parameters.clone(),
+        function: Box::new(|_| "18°C and sunny".to_string()),
+    };
+    client.add_tool(tool).await.map_err(|e| e.to_string())?;
+
+    let messages = [Message::user("What is the weather in Paris right now? Use the get_weather tool.")];
+    let response = client.send_chat_request_no_stream(&messages).await.map_err(|e| e.to_string())?;
+    let calls = response.tool_calls.unwrap_or_default();
+    let call = calls
+        .iter()
+        .find(|call| call.function.name == "get_weather")
+        .ok_or_else(|| format!("No get_weather call, replied: {}", response.text))?;
+    validate_against_schema(&parameters, &call.function.arguments)
+        .map_err(|errors| format!("Arguments {} don't match the schema: {}", call.function.arguments, errors.join(", ")))?;
+    if !call.function.arguments["city"].as_str().is_some_and(|city| city.to_lowercase().contains("paris")) {
+        return Err(format!("Called with the wrong city: {}", call.function.arguments));
+    }
+    Ok(ConformanceOutcome::Passed)
+}
+
+async fn check_vision(client: &MonoAI) -> Result<ConformanceOutcome, String> {
+    // Capabilities are only known for some models, the others are tried
+    if let Ok(capabilities) = client.capabilities(client.model()).await && !capabilities.supports_vision() {
+        return Ok(ConformanceOutcome::Skipped("The model doesn't take images".to_string()));
+    }
+    let mut message = Message::user("What color fills this image? Answer with one word.");
+    message.images = Some(vec![RED_SQUARE.to_string()]);
+    let response = client.send_chat_request_no_stream(&[message]).await.map_err(|e| e.to_string())?;
+    if !response.text.to_lowercase().contains("red") {
+        return Err(format!("Expected red, replied: {}", response.text));
+    }
+    Ok(ConformanceOutcome::Passed)
+}
+
+async fn check_usage(client: &MonoAI) -> Result<ConformanceOutcome, String> {
+    let messages = [Message::user("Say hello.")];
+    let response = client.send_chat_request_no_stream(&messages).await.map_err(|e| e.to_string())?;
+    let usage = response.usage.ok_or("The reply reports no usage")?;
+    match (usage.prompt_tokens, usage.completion_tokens) {
+        (Some(prompt), Some(completion)) if prompt > 0 && completion > 0 => Ok(ConformanceOutcome::Passed),
+        _ => Err(format!("Incomplete usage: {:?}", usage)),
+    }
+}
+
+async fn check_errors(client: &MonoAI) -> Result<ConformanceOutcome, String> {
+    let Some(missing) = client.for_model("mono-ai-conformance-missing-model") else {
+        return Ok(ConformanceOutcome::Skipped("The client answers from a script".to_string()));
+    };
+    let messages = [Message::user("Hello")];
+    match missing.send_chat_request_no_stream(&messages).await {
+        Ok(response) => Err(format!("A model that doesn't exist replied: {}", response.text)),
+        Err(e) if e.to_string().trim().is_empty() => Err("The error has no message".to_string()),
+        Err(_) => Ok(ConformanceOutcome::Passed),
+    }
+}
+
+/// A client for each provider configured in the environment: `OPENAI_API_KEY`,
+/// `ANTHROPIC_API_KEY`, `OPENROUTER_API_KEY` and `OLLAMA_MODEL` (with `OLLAMA_ENDPOINT`,
+/// `http://localhost:11434` by default). `OPENAI_MODEL`, `ANTHROPIC_MODEL` and
+/// `OPENROUTER_MODEL` pick other models than the defaults
+pub fn clients_from_env() -> Vec<MonoAI> {
+    let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+    let model = |name: &str, default: &str| var(name).unwrap_or_else(|| default.to_string());
+    let mut clients = Vec::new();
+    if let Some(key) = var("OPENAI_API_KEY") {
+        clients.push(MonoAI::openai(key, model("OPENAI_MODEL", "gpt-4.1-mini")));
+    }
+    if let Some(key) = var("ANTHROPIC_API_KEY") {
+        clients.push(MonoAI::anthropic(key, model("ANTHROPIC_MODEL", "claude-sonnet-4-20250514")));
+    }
+    if let Some(key) = var("OPENROUTER_API_KEY") {
+        clients.push(MonoAI::openrouter(key, model("OPENROUTER_MODEL", "openai/gpt-4.1-mini")));
+    }
+    if let Some(ollama_model) = var("OLLAMA_MODEL") {
+        clients.push(MonoAI::ollama(model("OLLAMA_ENDPOINT", "http://localhost:11434"), ollama_model));
+    }
+    clients
+}
+
+impl ConformanceMatrix {
+    /// No check failed, skipped ones count as passed
+    pub fn all_passed(&self) -> bool {
+        self.failures().is_empty()
+    }
+
+    pub fn failures(&self) -> Vec<&ConformanceResult> {
+        self.results.iter().filter(|result| matches!(result.outcome, ConformanceOutcome::Failed(_))).collect()
+    }
+}
+
+/// Table of pass, FAIL and skip per client and check, followed by why checks failed
+impl fmt::Display for ConformanceMatrix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // A row per client run, clients with the same model stay apart
+        let mut rows: Vec<Vec<&ConformanceResult>> = Vec::new();
+        let mut checks: Vec<ConformanceCheck> = Vec::new();
+        for result in &self.results {
+            match rows.last_mut() {
+                Some(row) if row[0].provider == result.provider && row[0].model == result.model && row.iter().all(|r| r.check != result.check) => {
+                    row.push(result)
+                }
+                _ => rows.push(vec![result]),
+            }
+            if !checks.contains(&result.check) {
+                checks.push(result.check);
+            }
+        }
+
+        let name = |row: &[&ConformanceResult]| format!("{}/{}", row[0].provider, row[0].model);
+        let width = rows.iter().map(|row| name(row).len()).max().unwrap_or(0);
+        write!(f, "{:width$}", "")?;
+        for check in &checks {
+            write!(f, "  {:>9}", check.name())?;
+        }
+        writeln!(f)?;
+        for row in &rows {
+            write!(f, "{:width$}", name(row))?;
+            for check in &checks {
+                let cell = match row.iter().find(|result| result.check == *check).map(|result| &result.outcome) {
+                    Some(ConformanceOutcome::Passed) => "pass",
+                    Some(ConformanceOutcome::Failed(_)) => "FAIL",
+                    Some(ConformanceOutcome::Skipped(_)) => "skip",
+                    None => "-",
+                };
+                write!(f, "  {:>9}", cell)?;
+            }
+            writeln!(f)?;
+        }
+
+        for result in self.failures() {
+            if let ConformanceOutcome::Failed(reason) = &result.outcome {
+                writeln!(f, "{}/{} {}: {}", result.provider, result.model, result.check.name(), reason)?;
+            }
+        }
+        Ok(())
+    }
+}
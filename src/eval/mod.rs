@@ -0,0 +1,11 @@
+pub mod case;
+pub mod runner;
+pub mod transcript;
+#[cfg(feature = "conformance")]
+pub mod conformance;
+
+pub use case::*;
+pub use runner::*;
+pub use transcript::*;
+#[cfg(feature = "conformance")]
+pub use conformance::*;
@@ -0,0 +1,183 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use futures_util::{StreamExt, stream};
+use serde::Serialize;
+
+use crate::core::Rubric;
+use crate::mono::MonoAI;
+use super::{Check, EvalCase};
+
+/// Runs eval cases against one or more models, for catching prompt regressions locally or in CI
+pub struct Evaluation<'a> {
+    cases: Vec<EvalCase>,
+    judge: Option<&'a MonoAI>,
+    concurrency: usize,
+}
+
+/// Outcome of every case on every model
+#[derive(Debug, Clone, Serialize)]
+pub struct EvalReport {
+    pub results: Vec<CaseResult>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CaseResult {
+    pub case: String,
+    pub provider: String,
+    pub model: String,
+    pub output: Result<String, String>,
+    pub checks: Vec<CheckResult>,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckResult {
+    pub check: String,
+    pub passed: bool,
+    pub detail: Option<String>, // Why it failed, or the judge's reasoning
+    pub score: Option<f64>, // Judge checks only
+}
+
+impl<'a> Evaluation<'a> {
+    pub fn new(cases: Vec<EvalCase>) -> Self {
+        Self { cases, judge: None, concurrency: 4 }
+    }
+
+    /// Model grading `Check::Judge`, without one those checks fail
+    pub fn with_judge(mut self, judge: &'a MonoAI) -> Self {
+        self.judge = Some(judge);
+        self
+    }
+
+    /// Cases run at the same time per model
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Run every case on every model, one model after the other
+    pub async fn run(&self, models: &[&MonoAI]) -> EvalReport {
+        let mut results = Vec::new();
+        for client in models {
+            let cases = stream::iter(&self.cases).map(|case| self.run_case(client, case)).buffered(self.concurrency);
+            results.extend(cases.collect::<Vec<_>>().await);
+        }
+        EvalReport { results }
+    }
+
+    async fn run_case(&self, client: &MonoAI, case: &EvalCase) -> CaseResult {
+        let started = Instant::now();
+        let output = client.send_chat_request_no_stream(&case.messages).await.map(|response| response.text).map_err(|e| e.to_string());
+        let duration = started.elapsed();
+
+        let mut checks = Vec::new();
+        for check in &case.checks {
+            let result = match &output {
+                Ok(text) => self.run_check(check, case, text).await,
+                Err(_) => CheckResult::failed(check, "No reply to check".to_string()),
+            };
+            checks.push(result);
+        }
+
+        CaseResult {
+            case: case.name.clone(),
+            provider: client.provider_name().to_string(),
+            model: client.model().to_string(),
+            output,
+            checks,
+            duration,
+        }
+    }
+
+    async fn run_check(&self, check: &Check, case: &EvalCase, output: &str) -> CheckResult {
+        if let Some(result) = check.run_local(output) {
+            return match result {
+                Ok(()) => CheckResult { check: check.to_string(), passed: true, detail: None, score: None },
+                Err(e) => CheckResult::failed(check, e),
+            };
+        }
+        let Check::Judge { criteria, min_score } = check else {
+            unreachable!("only judge checks need the judge");
+        };
+        let Some(judge) = self.judge else {
+            return CheckResult::failed(check, "No judge model set".to_string());
+        };
+
+        let request = case.messages.iter().rev().find(|message| message.role == "user").map(|message| message.content.as_str()).unwrap_or_default();
+        let text = format!("Request:\n{}\n\nResponse:\n{}", request, output);
+        match judge.score(&text, &Rubric::new(criteria, 0.0, 10.0)).await {
+            Ok(score) => CheckResult {
+                check: check.to_string(),
+                passed: score.score >= *min_score,
+                detail: Some(score.reasoning),
+                score: Some(score.score),
+            },
+            Err(e) => CheckResult::failed(check, format!("Judge failed: {}", e)),
+        }
+    }
+}
+
+impl CheckResult {
+    fn failed(check: &Check, detail: String) -> Self {
+        Self { check: check.to_string(), passed: false, detail: Some(detail), score: None }
+    }
+}
+
+impl CaseResult {
+    /// The model replied and every check passed
+    pub fn passed(&self) -> bool {
+        self.output.is_ok() && self.checks.iter().all(|check| check.passed)
+    }
+}
+
+impl EvalReport {
+    pub fn all_passed(&self) -> bool {
+        self.results.iter().all(CaseResult::passed)
+    }
+
+    pub fn failures(&self) -> Vec<&CaseResult> {
+        self.results.iter().filter(|result| !result.passed()).collect()
+    }
+
+    /// Share of passed cases per model, in the order the models ran
+    pub fn pass_rates(&self) -> Vec<(String, f64)> {
+        let mut rates: Vec<(String, usize, usize)> = Vec::new();
+        for result in &self.results {
+            let model = format!("{}/{}", result.provider, result.model);
+            let index = match rates.iter().position(|(name, _, _)| *name == model) {
+                Some(index) => index,
+                None => {
+                    rates.push((model, 0, 0));
+                    rates.len() - 1
+                }
+            };
+            rates[index].1 += result.passed() as usize;
+            rates[index].2 += 1;
+        }
+        rates.into_iter().map(|(model, passed, total)| (model, passed as f64 / total as f64)).collect()
+    }
+}
+
+/// Plain-text report listing failed checks, with the pass rate of each model
+impl fmt::Display for EvalReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for result in &self.results {
+            let status = if result.passed() { "PASS" } else { "FAIL" };
+            writeln!(f, "{} {} [{}/{}] {:.1}s", status, result.case, result.provider, result.model, result.duration.as_secs_f32())?;
+            if let Err(e) = &result.output {
+                writeln!(f, "    error: {}", e)?;
+            }
+            for check in result.checks.iter().filter(|check| !check.passed) {
+                match check.score {
+                    Some(score) => writeln!(f, "    {}: scored {}, {}", check.check, score, check.detail.as_deref().unwrap_or_default())?,
+                    None => writeln!(f, "    {}: {}", check.check, check.detail.as_deref().unwrap_or_default())?,
+                }
+            }
+        }
+        for (model, rate) in self.pass_rates() {
+            writeln!(f, "{}: {:.0}% passed", model, rate * 100.0)?;
+        }
+        Ok(())
+    }
+}
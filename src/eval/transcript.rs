@@ -0,0 +1,206 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::core::{GenerationOptions, Message};
+
+/// Settings of `MonoAI::replay`
+#[derive(Debug, Clone, Default)]
+pub struct ReplayOptions {
+    pub options: GenerationOptions,
+    pub own_history: bool, // Later turns see the replayed replies instead of the recorded ones
+    pub width: Option<usize>, // Characters per column of the side-by-side diff, 50 by default
+}
+
+impl ReplayOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_options(mut self, options: GenerationOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Continue each turn from the replayed replies, as a full re-run of the conversation,
+    /// rather than from the recorded ones so every turn is compared in the same context
+    pub fn with_own_history(mut self, own_history: bool) -> Self {
+        self.own_history = own_history;
+        self
+    }
+
+    pub fn with_width(mut self, width: usize) -> Self {
+        self.width = Some(width.max(10));
+        self
+    }
+}
+
+/// A user turn of a replayed conversation with both replies
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayTurn {
+    pub index: usize, // Position of the user message in the conversation
+    pub prompt: String,
+    pub recorded: String, // The assistant messages that followed, tool calls as `-> name(arguments)`
+    pub replayed: Result<String, String>,
+    pub similarity: f64, // Shared words, 0 - 1, 0 when the replay failed
+}
+
+/// The outcome of `MonoAI::replay`, displayed as a side-by-side diff of every turn
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayReport {
+    pub provider: String,
+    pub model: String,
+    pub turns: Vec<ReplayTurn>,
+    #[serde(skip)]
+    pub(crate) width: usize,
+}
+
+impl ReplayReport {
+    /// Average similarity over the turns, 1 for a conversation without user turns
+    pub fn mean_similarity(&self) -> f64 {
+        if self.turns.is_empty() {
+            return 1.0;
+        }
+        self.turns.iter().map(|turn| turn.similarity).sum::<f64>() / self.turns.len() as f64
+    }
+
+    /// Turns whose replies share less than `threshold` of their words, or failed
+    pub fn divergent(&self, threshold: f64) -> Vec<&ReplayTurn> {
+        self.turns.iter().filter(|turn| turn.similarity < threshold).collect()
+    }
+}
+
+impl fmt::Display for ReplayReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self.width;
+        for turn in &self.turns {
+            writeln!(f, "Turn {}: {}", turn.index, truncate(&turn.prompt, width * 2))?;
+            writeln!(f, "{:<width$}   {}/{}", "recorded", self.provider, self.model)?;
+            match &turn.replayed {
+                Ok(replayed) => {
+                    for (marker, left, right) in side_by_side(&turn.recorded, replayed) {
+                        write_row(f, marker, left, right, width)?;
+                    }
+                }
+                Err(e) => write_row(f, '|', &turn.recorded, &format!("error: {}", e), width)?,
+            }
+            writeln!(f, "similarity {:.2}\n", turn.similarity)?;
+        }
+        write!(f, "{}/{}: mean similarity {:.2}", self.provider, self.model, self.mean_similarity())
+    }
+}
+
+// One diff row, wrapped to the column width. `|` marks changed lines, `<` and `>` lines only
+// one side has, as `diff --side-by-side` does
+fn write_row(f: &mut fmt::Formatter<'_>, marker: char, left: &str, right: &str, width: usize) -> fmt::Result {
+    let (left, right) = (wrap(left, width), wrap(right, width));
+    for row in 0..left.len().max(right.len()) {
+        let marker = if row == 0 { marker } else { ' ' };
+        let left = left.get(row).map_or("", String::as_str);
+        let right = right.get(row).map_or("", String::as_str);
+        writeln!(f, "{}", format!("{:<width$} {} {}", left, marker, right).trim_end())?;
+    }
+    Ok(())
+}
+
+/// The reply an assistant message gives, with its tool calls
+pub(crate) fn reply_text(message: &Message) -> String {
+    let mut text = message.content.trim().to_string();
+    for call in message.tool_calls.iter().flatten() {
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        text.push_str(&format!("-> {}({})", call.function.name, call.function.arguments));
+    }
+    text
+}
+
+/// Dice coefficient of the word sequences: twice the longest common subsequence over the total
+pub(crate) fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b): (Vec<&str>, Vec<&str>) = (a.split_whitespace().collect(), b.split_whitespace().collect());
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    2.0 * common_subsequence(&a, &b).len() as f64 / (a.len() + b.len()) as f64
+}
+
+// Index pairs of a longest common subsequence
+fn common_subsequence<T: PartialEq>(a: &[T], b: &[T]) -> Vec<(usize, usize)> {
+    let mut lengths = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i][j] = if a[i] == b[j] { lengths[i + 1][j + 1] + 1 } else { lengths[i + 1][j].max(lengths[i][j + 1]) };
+        }
+    }
+    let (mut i, mut j, mut pairs) = (0, 0, Vec::new());
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
+
+// Lines of both replies aligned on the ones they share, changed lines side by side
+fn side_by_side<'a>(left: &'a str, right: &'a str) -> Vec<(char, &'a str, &'a str)> {
+    let (left, right): (Vec<&str>, Vec<&str>) = (left.lines().collect(), right.lines().collect());
+    let mut rows = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    let anchors = common_subsequence(&left, &right).into_iter().chain([(left.len(), right.len())]);
+    for (next_i, next_j) in anchors {
+        // Lines between two shared ones are paired up, the longer side's rest stands alone
+        while i < next_i || j < next_j {
+            match (i < next_i, j < next_j) {
+                (true, true) => rows.push(('|', left[i], right[j])),
+                (true, false) => rows.push(('<', left[i], "")),
+                _ => rows.push(('>', "", right[j])),
+            }
+            i = (i + 1).min(next_i);
+            j = (j + 1).min(next_j);
+        }
+        if next_i < left.len() {
+            rows.push((' ', left[next_i], right[next_j]));
+            i = next_i + 1;
+            j = next_j + 1;
+        }
+    }
+    rows
+}
+
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+        // Words longer than a line are broken up
+        while line.chars().count() > width {
+            let split = line.char_indices().nth(width).map_or(line.len(), |(index, _)| index);
+            let rest = line.split_off(split);
+            lines.push(std::mem::replace(&mut line, rest));
+        }
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match text.char_indices().nth(max) {
+        Some((index, _)) => format!("{}...", &text[..index]),
+        None => text,
+    }
+}
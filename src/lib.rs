@@ -1,9 +1,26 @@
 pub mod core;
 pub mod providers;
 pub mod mono;
+pub mod mcp;
+pub mod openapi;
+pub mod eval;
+#[cfg(feature = "test-util")]
+pub mod replay;
+#[cfg(feature = "axum")]
+pub mod web;
+#[cfg(feature = "realtime")]
+pub mod voice;
 
 // Re-export core types
-pub use core::{Message, ToolCall, Function, ChatStreamItem, PullProgress, ModelInfo, Tool, FallbackToolHandler, AIRequestError, MonoModel};
+pub use core::{Message, ToolCall, Function, ChatStreamItem, ChatResponse, CacheHint, TokenUsage, GenerationTiming, ThinkingBlock, Document, DocumentSource, Citation, CitationLocation, ServerToolEvent, PullProgress, ModelInfo, ModelCapabilities, ModelFilter, Tool, ToolRegistry, ProviderKind, ToolSchemaIssue, FallbackToolHandler, FallbackStrategy, AIRequestError, ProviderError, Quota, RateLimit, MonoModel, ToolEvent, ToolProgress, report_progress, ToolOutput, attach_image, ResponseFormat, Audio, AudioInput, TranscriptionOptions, Transcription, Transcriber, ImageGenerationOptions, ImageGeneration, GeneratedImage, ImageFormat, ImageDetail, EncodedImage, encode_image_file, encode_image_bytes, ModerationInput, ModerationResult, Guardrail, GuardrailStage, GuardrailVerdict, GenerationOptions, ModelPricing, PricingSource, PricingSnapshot, set_pricing, clear_pricing_overrides, set_pricing_source, refresh_pricing, DebugEvent, RequestTags, UsageRecord, Sink, SinkRecord, JsonlSink, Clock, SystemClock, Job, JobEvent, JobProgress, Conversation, MessageUsage, Classification, Rubric, Score, LanguageDetection, Translation, Redactor, Redactions, Validator, RegexValidator, SchemaValidator, LengthValidator, EditFormat, EditValidator, apply_edit, Persona, ChatTemplate, CustomTemplate, Checkpoint, UsageCounters, HistoryRules, normalize_history, TruncationStrategy, truncate_history, split_choices, tee, typewriter, TypewriterOptions, TypewriterUnit, coalesce, CoalesceOptions, StreamRenderer, RenderEvent, MarkdownBlock, InlineStyle, render_stream, DeterminismReport, Reproducibility, DETERMINISTIC_SEED};
+#[cfg(feature = "test-util")]
+pub use core::MockClock;
+#[cfg(feature = "video")]
+pub use core::{VideoFrameOptions, extract_video_frames};
+#[cfg(feature = "realtime")]
+pub use voice::{VoiceSession, VoiceSender, VoiceEvent, VoiceConfig};
 
 // Main interface
-pub use mono::MonoAI;
\ No newline at end of file
+pub use mono::{MonoAI, ShutdownReport, ModelTier, Tier, BatchRunner, BatchItem, BatchReport, Ensemble, Candidate, EnsembleResult, InjectionGuard};
+pub use mcp::McpServer;
+pub use openapi::OpenApiToolGenerator;
\ No newline at end of file
@@ -0,0 +1,310 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+use crate::core::ToolRegistry;
+
+const PROTOCOL_VERSION: &str = "2024-11-05";
+const DEFAULT_MAX_BODY_SIZE: usize = 4 * 1024 * 1024;
+
+/// Serves the tools of a `ToolRegistry` to MCP hosts (Claude Desktop, IDEs, ...)
+/// over stdio or HTTP+SSE using JSON-RPC 2.0
+#[derive(Clone)]
+pub struct McpServer {
+    registry: ToolRegistry,
+    name: String,
+    version: String,
+    max_body_size: usize,
+    allowed_origins: Vec<String>,
+}
+
+impl McpServer {
+    pub fn new(registry: ToolRegistry) -> Self {
+        Self {
+            registry,
+            name: "mono-ai".to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+            allowed_origins: Vec::new(),
+        }
+    }
+
+    /// Set the server name and version reported to hosts during initialization
+    pub fn with_server_info(mut self, name: &str, version: &str) -> Self {
+        self.name = name.to_string();
+        self.version = version.to_string();
+        self
+    }
+
+    /// Largest POST body the HTTP transport reads, larger ones get a 413 (default 4 MiB)
+    pub fn with_max_body_size(mut self, bytes: usize) -> Self {
+        self.max_body_size = bytes;
+        self
+    }
+
+    /// Accept HTTP requests from a browser origin such as `https://app.example.com`. Requests
+    /// without an `Origin` header and from localhost origins are always accepted, any other
+    /// origin gets a 403 so a web page can't reach a local server through DNS rebinding
+    pub fn with_allowed_origin(mut self, origin: &str) -> Self {
+        self.allowed_origins.push(origin.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// Handle one raw JSON-RPC message, returns the serialized response (None for notifications)
+    pub fn handle_message(&self, raw: &str) -> Option<String> {
+        let response = match serde_json::from_str::<Value>(raw) {
+            Ok(Value::Array(batch)) => {
+                let responses: Vec<Value> = batch.iter().filter_map(|m| self.handle_request(m)).collect();
+                if responses.is_empty() {
+                    None
+                } else {
+                    Some(Value::Array(responses))
+                }
+            }
+            Ok(message) => self.handle_request(&message),
+            Err(e) => Some(error_response(Value::Null, -32700, &format!("Parse error: {}", e))),
+        };
+        response.map(|r| r.to_string())
+    }
+
+    /// Handle one parsed JSON-RPC request, returns None for notifications
+    pub fn handle_request(&self, request: &Value) -> Option<Value> {
+        let id = request.get("id").cloned();
+        let method = request.get("method").and_then(|m| m.as_str()).unwrap_or("");
+        let params = request.get("params").cloned().unwrap_or(Value::Null);
+
+        // Notifications carry no id and never get a response
+        let id = id?;
+
+        let result = match method {
+            // The only version implemented, hosts asking for another one decide whether to go on
+            "initialize" => Ok(json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": { "tools": { "listChanged": false } },
+                "serverInfo": { "name": self.name, "version": self.version },
+            })),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(self.list_tools()),
+            "tools/call" => self.call_tool(&params),
+            _ => Err((-32601, format!("Method not found: {}", method))),
+        };
+
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => error_response(id, code, &message),
+        })
+    }
+
+    fn list_tools(&self) -> Value {
+        let tools: Vec<Value> = self
+            .registry
+            .enabled_tools()
+            .iter()
+            .map(|tool| json!({
+                "name": tool.name,
+                "description": tool.description,
+                "inputSchema": tool.parameters,
+            }))
+            .collect();
+        json!({ "tools": tools })
+    }
+
+    fn call_tool(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let name = params
+            .get("name")
+            .and_then(|n| n.as_str())
+            .ok_or((-32602, "Missing tool name".to_string()))?;
+        let arguments = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
+
+        let tool = self
+            .registry
+            .get(name)
+            .ok_or((-32602, format!("Unknown tool: {}", name)))?;
+
+        let (text, is_error) = match tool.validate_arguments(&arguments) {
+            Ok(()) => ((tool.function)(arguments), false),
+            Err(errors) => (format!("Invalid arguments: {}", errors.join("; ")), true),
+        };
+
+        Ok(json!({
+            "content": [{ "type": "text", "text": text }],
+            "isError": is_error,
+        }))
+    }
+
+    /// Serve over stdin/stdout with newline-delimited JSON messages until stdin closes
+    pub async fn serve_stdio(&self) -> Result<(), Box<dyn Error>> {
+        let mut lines = BufReader::new(tokio::io::stdin()).lines();
+        let mut stdout = tokio::io::stdout();
+
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(response) = self.handle_message(&line) {
+                stdout.write_all(response.as_bytes()).await?;
+                stdout.write_all(b"\n").await?;
+                stdout.flush().await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Serve the HTTP+SSE transport: hosts open `GET /sse`, receive an `endpoint` event
+    /// and POST JSON-RPC messages to it, responses are delivered on the event stream
+    pub async fn serve_sse(&self, addr: &str) -> Result<(), Box<dyn Error>> {
+        let listener = TcpListener::bind(addr).await?;
+        let sessions: Arc<Mutex<HashMap<String, mpsc::UnboundedSender<String>>>> = Arc::new(Mutex::new(HashMap::new()));
+
+        loop {
+            let (socket, _) = listener.accept().await?;
+            let server = self.clone();
+            let sessions = sessions.clone();
+            tokio::spawn(async move {
+                let _ = server.handle_connection(socket, sessions).await;
+            });
+        }
+    }
+
+    async fn handle_connection(
+        &self,
+        socket: TcpStream,
+        sessions: Arc<Mutex<HashMap<String, mpsc::UnboundedSender<String>>>>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut reader = BufReader::new(socket);
+
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).await?;
+        let mut parts = request_line.split_whitespace();
+        let method = parts.next().unwrap_or("").to_string();
+        let target = parts.next().unwrap_or("").to_string();
+
+        let mut content_length = 0usize;
+        let mut origin = None;
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header).await? == 0 || header.trim().is_empty() {
+                break;
+            }
+            if let Some((key, value)) = header.split_once(':') {
+                let key = key.trim();
+                if key.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().unwrap_or(0);
+                } else if key.eq_ignore_ascii_case("origin") {
+                    origin = Some(value.trim().to_string());
+                }
+            }
+        }
+
+        if origin.as_deref().is_some_and(|origin| !self.origin_allowed(origin)) {
+            return respond(reader.into_inner(), "403 Forbidden").await;
+        }
+
+        let (path, query) = target.split_once('?').unwrap_or((target.as_str(), ""));
+
+        match (method.as_str(), path) {
+            ("GET", "/sse") => {
+                let session_id = uuid::Uuid::new_v4().to_string();
+                let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+                sessions.lock().unwrap().insert(session_id.clone(), tx);
+
+                // The session ends however the stream does, including the host disconnecting
+                let result = stream_events(reader.into_inner(), &session_id, &mut rx).await;
+                sessions.lock().unwrap().remove(&session_id);
+                result
+            }
+            ("POST", "/messages") => {
+                // Refused before reading, a client can't make the server allocate what it announces
+                if content_length > self.max_body_size {
+                    return respond(reader.into_inner(), "413 Payload Too Large").await;
+                }
+                let mut body = vec![0u8; content_length];
+                reader.read_exact(&mut body).await?;
+                let socket = reader.into_inner();
+
+                let session_id = query
+                    .split('&')
+                    .find_map(|pair| pair.strip_prefix("sessionId="))
+                    .unwrap_or("")
+                    .to_string();
+                let sender = sessions.lock().unwrap().get(&session_id).cloned();
+
+                let status = match sender {
+                    Some(sender) => {
+                        if let Some(response) = self.handle_message(&String::from_utf8_lossy(&body)) {
+                            let _ = sender.send(response);
+                        }
+                        "202 Accepted"
+                    }
+                    None => "404 Not Found",
+                };
+                respond(socket, status).await
+            }
+            _ => respond(reader.into_inner(), "404 Not Found").await,
+        }
+    }
+
+    fn origin_allowed(&self, origin: &str) -> bool {
+        let origin = origin.trim_end_matches('/');
+        if self.allowed_origins.iter().any(|allowed| allowed == origin) {
+            return true;
+        }
+        let host = origin.split_once("://").map_or(origin, |(_, rest)| rest);
+        let host = match host.strip_prefix('[') {
+            Some(ipv6) => ipv6.split(']').next().unwrap_or(""),
+            None => host.split(':').next().unwrap_or(""),
+        };
+        matches!(host, "localhost" | "127.0.0.1" | "::1")
+    }
+}
+
+// Send the event stream of one SSE session until the host disconnects or the session closes
+async fn stream_events(
+    mut socket: TcpStream,
+    session_id: &str,
+    rx: &mut mpsc::UnboundedReceiver<String>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (mut read_half, mut write_half) = socket.split();
+    let head = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n";
+    write_half.write_all(head.as_bytes()).await?;
+    let endpoint = format!("event: endpoint\ndata: /messages?sessionId={}\n\n", session_id);
+    write_half.write_all(endpoint.as_bytes()).await?;
+    write_half.flush().await?;
+
+    // Hosts send nothing on the stream, so the read side only ends when they disconnect
+    let mut ignored = [0u8; 256];
+    loop {
+        tokio::select! {
+            message = rx.recv() => {
+                let Some(message) = message else { return Ok(()) };
+                let event = format!("event: message\ndata: {}\n\n", message);
+                write_half.write_all(event.as_bytes()).await?;
+                write_half.flush().await?;
+            }
+            read = read_half.read(&mut ignored) => {
+                if read? == 0 {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+async fn respond(mut socket: TcpStream, status: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let reply = format!("HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+    socket.write_all(reply.as_bytes()).await?;
+    Ok(())
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
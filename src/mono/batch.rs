@@ -0,0 +1,170 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures_util::{Stream, StreamExt};
+use tokio::sync::Mutex;
+
+use crate::core::{JobClock, JobProgress, Message};
+use super::client::MonoAI;
+
+/// Placeholder in a `BatchRunner` template replaced by each input
+pub const BATCH_INPUT: &str = "{input}";
+
+/// Runs one prompt template over many inputs with bounded concurrency, a request rate budget
+/// and retries, for large offline jobs such as labeling or extracting data from documents
+pub struct BatchRunner<'a> {
+    client: &'a MonoAI,
+    template: String,
+    concurrency: usize,
+    requests_per_minute: Option<u32>,
+    throttle_headroom: f64, // See `Quota::throttle_delay`
+    max_retries: u32,
+    retry_delay: Duration, // Doubles after every failed attempt
+}
+
+/// Outcome of one input, in completion order
+#[derive(Debug, Clone)]
+pub struct BatchItem {
+    pub index: usize, // Position of the input
+    pub input: String,
+    pub output: Result<String, String>,
+    pub attempts: u32,
+    pub progress: JobProgress, // Of the whole batch, after this item
+}
+
+/// All outcomes of a batch, ordered by input
+#[derive(Debug, Clone, Default)]
+pub struct BatchReport {
+    pub items: Vec<BatchItem>,
+}
+
+impl BatchReport {
+    /// Replies of the inputs that succeeded, by input index
+    pub fn outputs(&self) -> Vec<(usize, &str)> {
+        self.items.iter().filter_map(|item| item.output.as_deref().ok().map(|output| (item.index, output))).collect()
+    }
+
+    /// Inputs that failed after all retries, with their last error
+    pub fn failures(&self) -> Vec<&BatchItem> {
+        self.items.iter().filter(|item| item.output.is_err()).collect()
+    }
+}
+
+impl<'a> BatchRunner<'a> {
+    /// Runner sending `template` with `{input}` replaced by each input as a user message.
+    /// Defaults to 4 requests at a time, no rate limit beyond the provider's rate limit headers
+    /// (throttling below 10% left) and 2 retries starting after 1 second
+    pub fn new(client: &'a MonoAI, template: &str) -> Self {
+        Self {
+            client,
+            template: template.to_string(),
+            concurrency: 4,
+            requests_per_minute: None,
+            throttle_headroom: 0.1,
+            max_retries: 2,
+            retry_delay: Duration::from_secs(1),
+        }
+    }
+
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Spread request starts, retries included, to stay under the provider's rate limit
+    pub fn with_requests_per_minute(mut self, requests_per_minute: u32) -> Self {
+        self.requests_per_minute = Some(requests_per_minute).filter(|rpm| *rpm > 0);
+        self
+    }
+
+    /// Share of a rate limit window left below which requests slow down, going by the rate limit
+    /// headers of OpenAI and Anthropic responses. 0 only waits out windows that are used up
+    pub fn with_throttle_headroom(mut self, headroom: f64) -> Self {
+        self.throttle_headroom = headroom.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_retries(mut self, max_retries: u32, retry_delay: Duration) -> Self {
+        self.max_retries = max_retries;
+        self.retry_delay = retry_delay;
+        self
+    }
+
+    /// Process the inputs, yielding each outcome as it completes. The total in the progress is
+    /// known when the iterator reports its exact length
+    pub fn stream<I>(&'a self, inputs: I) -> impl Stream<Item = BatchItem> + Send + 'a
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+        I::IntoIter: Send + 'a,
+    {
+        let inputs = inputs.into_iter();
+        let total = match inputs.size_hint() {
+            (lower, Some(upper)) if lower == upper => Some(upper as u64),
+            _ => None,
+        };
+        let clock = JobClock::start();
+        let limiter = Arc::new(Mutex::new(None::<Instant>));
+        let mut completed = 0;
+
+        futures_util::stream::iter(inputs.enumerate())
+            .map(move |(index, input)| {
+                let (input, limiter) = (input.into(), limiter.clone());
+                async move { self.process(index, input, &limiter).await }
+            })
+            .buffer_unordered(self.concurrency)
+            .map(move |(index, input, output, attempts)| {
+                completed += 1;
+                let progress = clock.progress("Processing batch", Some(completed), total);
+                BatchItem { index, input, output, attempts, progress }
+            })
+    }
+
+    /// Process all inputs and collect the outcomes
+    pub async fn run<I>(&'a self, inputs: I) -> BatchReport
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+        I::IntoIter: Send + 'a,
+    {
+        let mut items: Vec<BatchItem> = self.stream(inputs).collect().await;
+        items.sort_by_key(|item| item.index);
+        BatchReport { items }
+    }
+
+    async fn process(&self, index: usize, input: String, limiter: &Mutex<Option<Instant>>) -> (usize, String, Result<String, String>, u32) {
+        let messages = vec![Message::user(self.template.replace(BATCH_INPUT, &input))];
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            self.wait_for_slot(limiter).await;
+            let error = match self.client.send_chat_request_no_stream(&messages).await {
+                Ok(response) => return (index, input, Ok(response.text), attempts),
+                Err(e) => e.to_string(),
+            };
+            if attempts > self.max_retries {
+                return (index, input, Err(error), attempts);
+            }
+            self.client.clock().sleep(self.retry_delay * 2u32.saturating_pow(attempts - 1)).await;
+        }
+    }
+
+    // Reserve the next start time allowed by the rate budget and wait for it, later while the
+    // provider's reported rate limits are running low
+    async fn wait_for_slot(&self, limiter: &Mutex<Option<Instant>>) {
+        let interval = self.requests_per_minute.map(|requests_per_minute| Duration::from_secs(60) / requests_per_minute);
+        let clock = self.client.clock();
+        let throttle = self.client.last_quota().throttle_delay_at(self.throttle_headroom, clock.system_time());
+        if interval.is_none() && throttle.is_none() {
+            return;
+        }
+        let start = {
+            let mut next = limiter.lock().await;
+            let earliest = clock.now() + throttle.unwrap_or_default();
+            let start = next.map_or(earliest, |next| next.max(earliest));
+            *next = Some(start + interval.unwrap_or_default());
+            start
+        };
+        clock.sleep_until(start).await;
+    }
+}
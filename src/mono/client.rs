@@ -1,23 +1,43 @@
 use std::error::Error;
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::pin::Pin;
-use futures_util::{Stream, StreamExt};
-use base64::{Engine as _, engine::general_purpose};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use futures_util::{Stream, StreamExt, future::BoxFuture};
 
-use crate::core::{Message, ToolCall, ChatStreamItem, PullProgress, ModelInfo, Tool, MonoModel};
-use crate::providers::ollama::{OllamaClient, Model};
+use crate::core::media;
+use crate::core::{Message, Quota, ToolCall, ChatStreamItem, ChatStream, PullProgress, ModelInfo, ModelCapabilities, ModelFilter, Tool, ToolRegistry, MonoModel, ToolEvent, ToolProgress, ProgressReporter, FallbackStrategy, ToolOutput, ResponseFormat, AudioInput, TranscriptionOptions, Transcription, Transcriber, TranscriptionFuture, ImageGenerationOptions, ImageGeneration, Guardrail, GuardrailStage, GuardrailVerdict, ModerationInput, ModerationResult, GenerationOptions, ImageDetail, DebugEvent, RequestTags, UsageRecord, UsageCallback, Sink, SinkRecord, now_ms, Clock, SystemClock, Job, JobClock, JobEvent, Classification, Rubric, Score, LanguageDetection, Translation, Redactor, Redactions, Validator, Persona, Conversation, ChatResponse, Checkpoint, split_choices, DeterminismReport, AIRequestError, TruncationStrategy, truncate_history, estimate_tokens, EditFormat, EditValidator, apply_edit};
+use crate::providers::ollama::{OllamaClient, OllamaTransport, Model, RunningModel, ollama_format};
 use crate::providers::anthropic::AnthropicClient;
 use crate::providers::openai::OpenAIClient;
+use crate::providers::openai::client::openai_response_format;
 use crate::providers::openrouter::OpenRouterClient;
+#[cfg(feature = "test-util")]
+use crate::providers::mock::{MockProvider, MockScript};
+use super::shutdown::{Lifecycle, ShutdownReport};
+use crate::eval::{ReplayOptions, ReplayReport, ReplayTurn, reply_text, similarity};
 
 pub enum Provider {
     Ollama(OllamaClient),
     Anthropic(AnthropicClient),
     OpenAI(OpenAIClient),
     OpenRouter(OpenRouterClient),
+    #[cfg(feature = "test-util")]
+    Mock(MockProvider),
 }
 
 pub struct MonoAI {
     provider: Provider,
+    guardrail: Option<Arc<dyn Guardrail>>,
+    usage_callback: Option<UsageCallback>,
+    sink: Option<Arc<dyn Sink>>, // Receives every request, response, tool call and usage record
+    utility_model: Option<String>, // Cheaper model for titles and summaries
+    redactor: Option<Arc<Redactor>>,
+    persona: Option<Arc<Persona>>,
+    context_truncation: Option<TruncationStrategy>, // Retry chats exceeding the context with a shorter history
+    clock: Arc<dyn Clock>, // Times retry backoff and rate limiting
+    lifecycle: Arc<Lifecycle>, // Requests in flight, shared with copies for other models
 }
 
 impl MonoAI {
@@ -25,13 +45,47 @@ impl MonoAI {
     pub fn ollama(endpoint: String, model: String) -> Self {
         Self {
             provider: Provider::Ollama(OllamaClient::new(endpoint, model)),
+            guardrail: None,
+            usage_callback: None,
+            sink: None,
+            utility_model: None,
+            redactor: None,
+            persona: None,
+            context_truncation: None,
+            clock: Arc::new(SystemClock),
+            lifecycle: Arc::default(),
         }
     }
 
+    /// Create Ollama client reaching the server through a Unix socket or a custom HTTP client
+    pub fn ollama_with_transport(endpoint: String, model: String, transport: OllamaTransport) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            provider: Provider::Ollama(OllamaClient::with_transport(endpoint, model, transport)?),
+            guardrail: None,
+            usage_callback: None,
+            sink: None,
+            utility_model: None,
+            redactor: None,
+            persona: None,
+            context_truncation: None,
+            clock: Arc::new(SystemClock),
+            lifecycle: Arc::default(),
+        })
+    }
+
     /// Create Anthropic client with API key and model name
     pub fn anthropic(api_key: String, model: String) -> Self {
         Self {
             provider: Provider::Anthropic(AnthropicClient::new(api_key, model)),
+            guardrail: None,
+            usage_callback: None,
+            sink: None,
+            utility_model: None,
+            redactor: None,
+            persona: None,
+            context_truncation: None,
+            clock: Arc::new(SystemClock),
+            lifecycle: Arc::default(),
         }
     }
 
@@ -39,6 +93,15 @@ impl MonoAI {
     pub fn openai(api_key: String, model: String) -> Self {
         Self {
             provider: Provider::OpenAI(OpenAIClient::new(api_key, model)),
+            guardrail: None,
+            usage_callback: None,
+            sink: None,
+            utility_model: None,
+            redactor: None,
+            persona: None,
+            context_truncation: None,
+            clock: Arc::new(SystemClock),
+            lifecycle: Arc::default(),
         }
     }
 
@@ -46,6 +109,45 @@ impl MonoAI {
     pub fn openrouter(api_key: String, model: String) -> Self {
         Self {
             provider: Provider::OpenRouter(OpenRouterClient::new(api_key, model)),
+            guardrail: None,
+            usage_callback: None,
+            sink: None,
+            utility_model: None,
+            redactor: None,
+            persona: None,
+            context_truncation: None,
+            clock: Arc::new(SystemClock),
+            lifecycle: Arc::default(),
+        }
+    }
+
+    /// Create a client answering from a script, for tests that must not touch the network
+    #[cfg(feature = "test-util")]
+    pub fn mock(script: MockScript) -> Self {
+        Self {
+            provider: Provider::Mock(MockProvider::new(script)),
+            guardrail: None,
+            usage_callback: None,
+            sink: None,
+            utility_model: None,
+            redactor: None,
+            persona: None,
+            context_truncation: None,
+            clock: Arc::new(SystemClock),
+            lifecycle: Arc::default(),
+        }
+    }
+
+    /// Send requests to another API root: an Ollama endpoint, an OpenAI-compatible server,
+    /// a proxy or a `ReplayServer`
+    pub fn set_base_url(&mut self, base_url: &str) {
+        match &mut self.provider {
+            Provider::Ollama(client) => client.endpoint = base_url.trim_end_matches('/').to_string(),
+            Provider::Anthropic(client) => client.set_base_url(base_url),
+            Provider::OpenAI(client) => client.set_base_url(base_url),
+            Provider::OpenRouter(client) => client.set_base_url(base_url),
+            #[cfg(feature = "test-util")]
+            Provider::Mock(_) => {}
         }
     }
 
@@ -56,6 +158,32 @@ impl MonoAI {
             Provider::Anthropic(client) => client.add_tool(tool).await,
             Provider::OpenAI(client) => client.add_tool(tool).await,
             Provider::OpenRouter(client) => client.add_tool(tool).await,
+            #[cfg(feature = "test-util")]
+            Provider::Mock(client) => client.add_tool(tool).await,
+        }
+    }
+
+    /// Access the tool registry for enabling, disabling, namespacing and listing tools
+    pub fn tool_registry(&self) -> &ToolRegistry {
+        match &self.provider {
+            Provider::Ollama(client) => client.tool_registry(),
+            Provider::Anthropic(client) => client.tool_registry(),
+            Provider::OpenAI(client) => client.tool_registry(),
+            Provider::OpenRouter(client) => client.tool_registry(),
+            #[cfg(feature = "test-util")]
+            Provider::Mock(client) => client.tool_registry(),
+        }
+    }
+
+    /// Replace the tool registry, pass a clone of another client's registry to share tools between clients
+    pub fn set_tool_registry(&mut self, registry: ToolRegistry) {
+        match &mut self.provider {
+            Provider::Ollama(client) => client.set_tool_registry(registry),
+            Provider::Anthropic(client) => client.set_tool_registry(registry),
+            Provider::OpenAI(client) => client.set_tool_registry(registry),
+            Provider::OpenRouter(client) => client.set_tool_registry(registry),
+            #[cfg(feature = "test-util")]
+            Provider::Mock(client) => client.set_tool_registry(registry),
         }
     }
 
@@ -66,6 +194,8 @@ impl MonoAI {
             Provider::Anthropic(client) => client.is_fallback_mode().await,
             Provider::OpenAI(client) => client.is_fallback_mode().await,
             Provider::OpenRouter(_) => false,
+            #[cfg(feature = "test-util")]
+            Provider::Mock(_) => false,
         }
     }
 
@@ -75,7 +205,48 @@ impl MonoAI {
             Provider::Ollama(client) => client.set_debug_mode(debug),
             Provider::Anthropic(client) => client.set_debug_mode(debug),
             Provider::OpenAI(client) => client.set_debug_mode(debug),
-            Provider::OpenRouter(_) => {},
+            Provider::OpenRouter(client) => client.set_debug_mode(debug),
+            #[cfg(feature = "test-util")]
+            Provider::Mock(_) => {},
+        }
+    }
+
+    /// Receive the requests, raw stream chunks and fallback tool call output of the provider,
+    /// with API keys redacted
+    pub fn set_debug_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(&DebugEvent) + Send + Sync + 'static,
+    {
+        match &mut self.provider {
+            Provider::Ollama(client) => client.set_debug_callback(callback),
+            Provider::Anthropic(client) => client.set_debug_callback(callback),
+            Provider::OpenAI(client) => client.set_debug_callback(callback),
+            Provider::OpenRouter(client) => client.set_debug_callback(callback),
+            #[cfg(feature = "test-util")]
+            Provider::Mock(_) => {},
+        }
+    }
+
+    /// Resolution OpenAI and OpenRouter vision models look at images with. Anthropic and Ollama
+    /// have no such setting
+    pub fn set_image_detail(&mut self, detail: Option<ImageDetail>) {
+        match &mut self.provider {
+            Provider::OpenAI(client) => client.set_image_detail(detail),
+            Provider::OpenRouter(client) => client.set_image_detail(detail),
+            Provider::Ollama(_) | Provider::Anthropic(_) => {}
+            #[cfg(feature = "test-util")]
+            Provider::Mock(_) => {}
+        }
+    }
+
+    /// Pull the model when chat, generate or embed requests find it isn't installed. Only
+    /// Ollama installs models locally
+    pub fn set_auto_pull(&mut self, enabled: bool) {
+        match &mut self.provider {
+            Provider::Ollama(client) => client.set_auto_pull(enabled),
+            Provider::Anthropic(_) | Provider::OpenAI(_) | Provider::OpenRouter(_) => {}
+            #[cfg(feature = "test-util")]
+            Provider::Mock(_) => {}
         }
     }
 
@@ -85,7 +256,34 @@ impl MonoAI {
             Provider::Ollama(client) => client.debug_mode(),
             Provider::Anthropic(client) => client.debug_mode(),
             Provider::OpenAI(client) => client.debug_mode(),
-            Provider::OpenRouter(_) => false,
+            Provider::OpenRouter(client) => client.debug_mode(),
+            #[cfg(feature = "test-util")]
+            Provider::Mock(_) => false,
+        }
+    }
+
+    /// Choose how fallback tool calls are formatted (XML tags, fenced JSON or constrained JSON output).
+    /// Only Ollama and OpenRouter use fallback tool calling
+    pub fn set_fallback_strategy(&mut self, strategy: FallbackStrategy) {
+        match &mut self.provider {
+            Provider::Ollama(client) => client.set_fallback_strategy(strategy),
+            Provider::Anthropic(_) => {},
+            Provider::OpenAI(_) => {},
+            Provider::OpenRouter(client) => client.set_fallback_strategy(strategy),
+            #[cfg(feature = "test-util")]
+            Provider::Mock(_) => {},
+        }
+    }
+
+    /// Get the fallback tool calling strategy
+    pub fn fallback_strategy(&self) -> FallbackStrategy {
+        match &self.provider {
+            Provider::Ollama(client) => client.fallback_strategy(),
+            Provider::Anthropic(_) => FallbackStrategy::default(),
+            Provider::OpenAI(_) => FallbackStrategy::default(),
+            Provider::OpenRouter(client) => client.fallback_strategy(),
+            #[cfg(feature = "test-util")]
+            Provider::Mock(_) => FallbackStrategy::default(),
         }
     }
 
@@ -96,7 +294,104 @@ impl MonoAI {
             Provider::Anthropic(client) => client.supports_tool_calls().await,
             Provider::OpenAI(client) => client.supports_tool_calls().await,
             Provider::OpenRouter(client) => client.supports_tool_calls().await,
+            #[cfg(feature = "test-util")]
+            Provider::Mock(client) => client.supports_tool_calls().await,
+        }
+    }
+
+    /// Check the latest user message before sending and the reply after receiving, on every provider.
+    /// A blocked reply ends the stream with an error, flags are reported on the final item
+    pub fn set_guardrail(&mut self, guardrail: impl Guardrail + 'static) {
+        self.guardrail = Some(Arc::new(guardrail));
+    }
+
+    /// Called with the usage of every chat request once its stream reports it, tagged with the
+    /// request's `GenerationOptions.tags`
+    pub fn set_usage_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(&UsageRecord) + Send + Sync + 'static,
+    {
+        self.usage_callback = Some(Arc::new(callback));
+    }
+
+    /// Record every chat request and response, tool call and usage record, e.g. as JSONL with
+    /// `JsonlSink`. Messages are recorded after redaction
+    pub fn set_sink(&mut self, sink: impl Sink + 'static) {
+        self.sink = Some(Arc::new(sink));
+    }
+
+    pub fn clear_sink(&mut self) {
+        self.sink = None;
+    }
+
+    /// Time source for the backoff and rate limit waits of `BatchRunner`, e.g. a `MockClock`
+    /// so tests of retries and throttling don't sleep
+    pub fn set_clock(&mut self, clock: impl Clock + 'static) {
+        self.clock = Arc::new(clock);
+    }
+
+    pub fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
+    /// Stop taking requests, wait up to `timeout` for the running ones, streams included, then
+    /// end the rest with an error and flush the sink. New requests fail from the first call on,
+    /// also on copies of the client made for personas and utility models
+    pub async fn shutdown(&self, timeout: Duration) -> ShutdownReport {
+        let report = self.lifecycle.shutdown(timeout, self.clock.as_ref()).await;
+        if let Some(sink) = &self.sink {
+            sink.flush();
         }
+        report
+    }
+
+    /// Whether `shutdown` was called, e.g. to fail health checks while draining
+    pub fn is_shutting_down(&self) -> bool {
+        self.lifecycle.is_closing()
+    }
+
+    /// Model `title`, `summarize`, `detect_language` and `translate` use instead of the
+    /// client's, typically a small and cheap one of the same provider. None uses the client's model
+    pub fn set_utility_model(&mut self, model: Option<String>) {
+        self.utility_model = model;
+    }
+
+    pub fn clear_guardrail(&mut self) {
+        self.guardrail = None;
+    }
+
+    /// Mask personal data in every chat request before it reaches the provider, and the
+    /// guardrail. Replies get the original values back unless the redactor has restoring off
+    pub fn set_redactor(&mut self, redactor: Redactor) {
+        self.redactor = Some(Arc::new(redactor));
+    }
+
+    pub fn clear_redactor(&mut self) {
+        self.redactor = None;
+    }
+
+    /// Send every chat request as `persona`, see `Persona`. Replaces the current one
+    pub fn set_persona(&mut self, persona: Persona) {
+        self.persona = Some(Arc::new(persona));
+    }
+
+    pub fn clear_persona(&mut self) {
+        self.persona = None;
+    }
+
+    pub fn persona(&self) -> Option<&Persona> {
+        self.persona.as_deref()
+    }
+
+    /// When a chat request exceeds the model's context, drop turns from the history with
+    /// `strategy` until it fits the limit the provider reported and retry once. Without it the
+    /// request fails with `AIRequestError::ContextLengthExceeded`
+    pub fn set_context_truncation(&mut self, strategy: TruncationStrategy) {
+        self.context_truncation = Some(strategy);
+    }
+
+    pub fn clear_context_truncation(&mut self) {
+        self.context_truncation = None;
     }
 
     /// Send chat request with real-time streaming response
@@ -104,27 +399,680 @@ impl MonoAI {
         &self,
         messages: &[Message],
     ) -> Result<Pin<Box<dyn Stream<Item = Result<ChatStreamItem, String>> + Send>>, Box<dyn Error>> {
-        match &self.provider {
-            Provider::Ollama(client) => client.send_chat_request(messages).await,
-            Provider::Anthropic(client) => client.send_chat_request(messages).await,
-            Provider::OpenAI(client) => client.send_chat_request(messages).await,
-            Provider::OpenRouter(client) => client.send_chat_request(messages).await,
+        if self.persona.is_some() {
+            return self.send_chat_request_with_options(messages, &GenerationOptions::default()).await;
+        }
+        self.send_chat_stream(messages, None).await
+    }
+
+    /// Send chat request with sampling settings. Fields the provider doesn't support are ignored,
+    /// `unsupported_options` lists them
+    pub async fn send_chat_request_with_options(
+        &self,
+        messages: &[Message],
+        options: &GenerationOptions,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<ChatStreamItem, String>> + Send>>, Box<dyn Error>> {
+        self.send_as_persona(messages, options, self.persona.as_deref()).await
+    }
+
+    /// Request `options.n` completions and stream each one on its own, in index order, for
+    /// sampling several replies to one prompt. See `split_choices`. Providers only return tool
+    /// calls for the first completion, so `n > 1` is refused while tools are registered
+    pub async fn send_chat_request_choices(
+        &self,
+        messages: &[Message],
+        options: &GenerationOptions,
+    ) -> Result<Vec<Pin<Box<dyn Stream<Item = Result<ChatStreamItem, String>> + Send>>>, Box<dyn Error>> {
+        let n = options.n.unwrap_or(1) as usize;
+        if n > 1 && self.unsupported_options(options).contains(&"n") {
+            return Err(format!("Multiple completions are not supported for {} provider", self.provider_name()).into());
+        }
+        let stream = self.send_chat_request_with_options(messages, options).await?;
+        Ok(split_choices(stream, n))
+    }
+
+    /// Send the active branch of a conversation as its persona, or the client's when it has none.
+    /// The conversation's model and options go before the persona's, `options` before both
+    pub async fn send_conversation(
+        &self,
+        conversation: &Conversation,
+        options: &GenerationOptions,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<ChatStreamItem, String>> + Send>>, Box<dyn Error>> {
+        let options = options.or_defaults(conversation.options());
+        let persona = conversation.persona().or(self.persona.as_deref());
+        let Some(model) = conversation.model() else {
+            return self.send_as_persona(&conversation.messages(), &options, persona).await;
+        };
+
+        // The pinned model replaces the persona's, the copy for it keeps the persona's tools
+        let persona = persona.map(|persona| Persona { model: Some(model.to_string()), ..persona.clone() });
+        let client = Some(model).filter(|model| *model != self.model()).and_then(|model| self.for_model(model));
+        client.as_ref().unwrap_or(self).send_as_persona(&conversation.messages(), &options, persona.as_ref()).await
+    }
+
+    /// Re-run the user turns of a recorded conversation on this client's model and compare the
+    /// replies with the recorded ones, e.g. before migrating to another model or provider.
+    /// Tools are not run, tool calls in the replies are compared as `-> name(arguments)`
+    pub async fn replay(&self, transcript: &Conversation, options: &ReplayOptions) -> ReplayReport {
+        let messages = transcript.messages();
+        let mut history = Vec::new();
+        let mut turns = Vec::new();
+        for (index, message) in messages.iter().enumerate() {
+            match message.role.as_str() {
+                "user" => {}
+                // With its own history the model only sees the replies it gave
+                "assistant" | "tool" if options.own_history => continue,
+                _ => {
+                    history.push(message.clone());
+                    continue;
+                }
+            }
+            history.push(message.clone());
+
+            let recorded = messages[index + 1..]
+                .iter()
+                .take_while(|next| next.role != "user")
+                .filter(|next| next.role == "assistant")
+                .map(reply_text)
+                .filter(|text| !text.is_empty())
+                .collect::<Vec<_>>()
+                .join("\n");
+            let replayed = match self.send_chat_request_no_stream_with_options(&history, &options.options).await {
+                Ok(response) => {
+                    let mut reply = Message::assistant(&response.text);
+                    reply.tool_calls = response.tool_calls;
+                    Ok(reply_text(&reply))
+                }
+                Err(e) => Err(e.to_string()),
+            };
+            if options.own_history {
+                history.push(Message::assistant(replayed.as_deref().unwrap_or_default()));
+            }
+
+            turns.push(ReplayTurn {
+                index,
+                prompt: message.content.clone(),
+                similarity: replayed.as_deref().map_or(0.0, |replayed| similarity(&recorded, replayed)),
+                recorded,
+                replayed,
+            });
+        }
+
+        ReplayReport {
+            provider: self.provider_name().to_string(),
+            model: self.model().to_string(),
+            turns,
+            width: options.width.unwrap_or(50),
+        }
+    }
+
+    // The persona's system prompt and options, sent to a copy of the client for its model and
+    // tools when it has its own
+    async fn send_as_persona(
+        &self,
+        messages: &[Message],
+        options: &GenerationOptions,
+        persona: Option<&Persona>,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<ChatStreamItem, String>> + Send>>, Box<dyn Error>> {
+        let Some(persona) = persona else {
+            return self.send_options_request(messages, options).await;
+        };
+        let messages = persona.apply(messages);
+        let options = options.or_defaults(&persona.options);
+        match self.persona_client(persona) {
+            Some(client) => client.send_options_request(&messages, &options).await,
+            None => self.send_options_request(&messages, &options).await,
+        }
+    }
+
+    // Copy for the persona's model with only its tools, None when the client already fits
+    // or is the mock
+    fn persona_client(&self, persona: &Persona) -> Option<MonoAI> {
+        let model = persona.model.as_deref().filter(|model| *model != self.model());
+        if model.is_none() && persona.tools.is_none() {
+            return None;
+        }
+        let mut client = self.for_model(model.unwrap_or(self.model()))?;
+        client.set_tool_registry(match &persona.tools {
+            Some(patterns) => self.tool_registry().subset(patterns),
+            None => self.tool_registry().clone(),
+        });
+        Some(client)
+    }
+
+    async fn send_options_request(
+        &self,
+        messages: &[Message],
+        options: &GenerationOptions,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<ChatStreamItem, String>> + Send>>, Box<dyn Error>> {
+        // Tool calls of the extra completions would be lost
+        if options.n.unwrap_or(1) > 1 && !self.tool_registry().enabled_tools().is_empty() {
+            return Err("Multiple completions can't be requested while tools are registered".into());
         }
+        self.send_chat_stream(messages, Some(options)).await
+    }
+
+    // Stream a chat request through the shared pipeline, with the provider's plain request when
+    // there are no options
+    async fn send_chat_stream(
+        &self,
+        messages: &[Message],
+        options: Option<&GenerationOptions>,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<ChatStreamItem, String>> + Send>>, Box<dyn Error>> {
+        self.lifecycle.track_stream(async {
+            let sent = self.send_checked(messages, options, MonoAI::provider_stream).await?;
+
+            let stream = self.track_usage(sent.reply, options.and_then(|options| options.tags.clone()));
+            let stream = self.record_stream(stream, sent.record, sent.started);
+            let stream = match &self.guardrail {
+                Some(guardrail) => guard_stream(stream, guardrail.clone(), sent.flags),
+                None => stream,
+            };
+            Ok(restore_stream(stream, sent.redactions))
+        }).await
     }
 
-    /// Send chat request without streaming, returns complete response and tool calls
-    pub async fn send_chat_request_no_stream(
+    /// Send chat request with sampling settings without streaming, returns the first completion
+    pub async fn send_chat_request_no_stream_with_options(
         &self,
         messages: &[Message],
-    ) -> Result<(String, Option<Vec<ToolCall>>), Box<dyn Error>> {
+        options: &GenerationOptions,
+    ) -> Result<ChatResponse, Box<dyn Error>> {
+        let started = Instant::now();
+        let stream = self.send_chat_request_with_options(messages, options).await?;
+        ChatResponse::from_stream(stream, started).await
+    }
+
+    /// The exact body `send_chat_request` would post to the provider, without sending it.
+    /// Remote images are downloaded and large images resized as they would be for the request
+    pub async fn build_request(&self, messages: &[Message]) -> Result<serde_json::Value, Box<dyn Error>> {
+        match &self.provider {
+            Provider::Ollama(client) => client.build_request(messages, None).await,
+            Provider::Anthropic(client) => client.build_request(messages).await,
+            Provider::OpenAI(client) => client.build_request(messages, None),
+            Provider::OpenRouter(client) => client.build_request(messages).await,
+            #[cfg(feature = "test-util")]
+            Provider::Mock(client) => client.build_request(messages),
+        }
+    }
+
+    /// The body `send_chat_request_with_options` would post, without sending it
+    pub async fn build_request_with_options(&self, messages: &[Message], options: &GenerationOptions) -> Result<serde_json::Value, Box<dyn Error>> {
         match &self.provider {
-            Provider::Ollama(client) => client.send_chat_request_no_stream(messages).await,
-            Provider::Anthropic(client) => client.send_chat_request_no_stream(messages).await,
-            Provider::OpenAI(client) => client.send_chat_request_no_stream(messages).await,
-            Provider::OpenRouter(client) => client.send_chat_request_no_stream(messages).await,
+            Provider::Ollama(client) => client.build_request(messages, Some(options.into())).await,
+            Provider::Anthropic(client) => client.build_request_with_options(messages, options).await,
+            Provider::OpenAI(client) => client.build_request(messages, Some(options.into())),
+            Provider::OpenRouter(client) => client.build_request_with_options(messages, options).await,
+            #[cfg(feature = "test-util")]
+            Provider::Mock(client) => client.build_request(messages),
         }
     }
 
+    /// Fields of `options` the current provider ignores
+    pub fn unsupported_options(&self, options: &GenerationOptions) -> Vec<&'static str> {
+        match &self.provider {
+            Provider::Ollama(_) => options.unsupported(OllamaClient::SUPPORTED_OPTIONS),
+            Provider::Anthropic(_) => options.unsupported(AnthropicClient::SUPPORTED_OPTIONS),
+            Provider::OpenAI(_) => options.unsupported(OpenAIClient::SUPPORTED_OPTIONS),
+            Provider::OpenRouter(_) => options.unsupported(OpenRouterClient::SUPPORTED_OPTIONS),
+            #[cfg(feature = "test-util")]
+            Provider::Mock(_) => options.unsupported(MockProvider::SUPPORTED_OPTIONS),
+        }
+    }
+
+    /// Which sampling knobs of `options` the provider honors and how far it reproduces replies,
+    /// e.g. to check a `GenerationOptions::deterministic` test run
+    pub fn determinism(&self, options: &GenerationOptions) -> DeterminismReport {
+        match &self.provider {
+            Provider::Ollama(_) => options.determinism(OllamaClient::SUPPORTED_OPTIONS, OllamaClient::REPRODUCIBILITY),
+            Provider::Anthropic(_) => options.determinism(AnthropicClient::SUPPORTED_OPTIONS, AnthropicClient::REPRODUCIBILITY),
+            Provider::OpenAI(_) => options.determinism(OpenAIClient::SUPPORTED_OPTIONS, OpenAIClient::REPRODUCIBILITY),
+            Provider::OpenRouter(_) => options.determinism(OpenRouterClient::SUPPORTED_OPTIONS, OpenRouterClient::REPRODUCIBILITY),
+            #[cfg(feature = "test-util")]
+            Provider::Mock(_) => options.determinism(MockProvider::SUPPORTED_OPTIONS, MockProvider::REPRODUCIBILITY),
+        }
+    }
+
+    /// Send chat request without streaming, returns the complete response with its usage.
+    /// Guardrail blocks become errors, flags are not reported here
+    pub async fn send_chat_request_no_stream(&self, messages: &[Message]) -> Result<ChatResponse, Box<dyn Error>> {
+        if self.persona.is_some() {
+            return self.send_chat_request_no_stream_with_options(messages, &GenerationOptions::default()).await;
+        }
+        self.lifecycle.track(async {
+            let sent = self.send_checked(messages, None, MonoAI::provider_response).await?;
+
+            let mut response = sent.reply;
+            self.record_response(sent.record, Ok(&response), sent.started);
+            if let Some(usage) = &response.usage {
+                self.report_usage(&UsageRecord {
+                    provider: self.provider_name().to_string(),
+                    model: self.model().to_string(),
+                    usage: usage.clone(),
+                    tags: None,
+                });
+            }
+            self.check_output(&response.text).await?;
+            if let Some(redactions) = sent.redactions {
+                for tool_call in response.tool_calls.iter_mut().flatten() {
+                    redactions.restore_json(&mut tool_call.function.arguments);
+                }
+                response.text = redactions.restore(&response.text);
+            }
+            Ok(response)
+        }).await
+    }
+
+    /// Send chat request without streaming and check the reply with `validator`. A failing reply
+    /// is sent back to the model with the validation error to repair, up to `max_repairs` times
+    /// before the error is returned
+    pub async fn send_chat_request_validated(
+        &self,
+        messages: &[Message],
+        validator: &dyn Validator,
+        max_repairs: u32,
+    ) -> Result<String, Box<dyn Error>> {
+        let mut messages = messages.to_vec();
+        let mut repairs = 0;
+        loop {
+            let response = self.send_chat_request_no_stream(&messages).await?.text;
+            let error = match validator.validate(&response) {
+                Ok(()) => return Ok(response),
+                Err(error) => error,
+            };
+            if repairs == max_repairs {
+                return Err(format!("Response failed validation after {} repairs: {}", repairs, error).into());
+            }
+            repairs += 1;
+            messages.push(Message::assistant(&response));
+            messages.push(Message::user(format!(
+                "Your reply is invalid:\n{}\n\nReply again with the problem fixed, without commenting on it.",
+                error
+            )));
+        }
+    }
+
+    /// Ask the model to change `source` as `instruction` says, with its edits written in `format`
+    /// rather than the whole file. Edits that don't apply to the source are sent back to the
+    /// model with the reason, up to `max_repairs` times. Returns the edited source
+    pub async fn request_edit(
+        &self,
+        source: &str,
+        instruction: &str,
+        format: EditFormat,
+        max_repairs: u32,
+    ) -> Result<String, Box<dyn Error>> {
+        let messages = [
+            Message::system(format.instructions()),
+            Message::user(format!("{}\n\nSource:\n```\n{}\n```", instruction, source.trim_end_matches('\n'))),
+        ];
+        let reply = self.send_chat_request_validated(&messages, &EditValidator::new(source, format), max_repairs).await?;
+        Ok(apply_edit(source, &reply, format)?)
+    }
+
+    // Redact, check the input and record a chat request, then send it with `send`, once more
+    // with a shorter history when it overflows the context and truncation is on
+    async fn send_checked<T>(
+        &self,
+        messages: &[Message],
+        options: Option<&GenerationOptions>,
+        send: impl for<'h> Fn(&'h Self, &'h [Message], Option<&'h GenerationOptions>) -> BoxFuture<'h, Result<T, Box<dyn Error>>>,
+    ) -> Result<SentChat<T>, Box<dyn Error>> {
+        let (messages, redactions) = self.redact(messages);
+        let messages = messages.as_ref();
+        let flags = self.check_input(messages).await?;
+        let started = Instant::now();
+        let record = self.record_request(messages, options);
+        let mut truncated = None; // Retried once with a shorter history
+        loop {
+            let history = truncated.as_deref().unwrap_or(messages);
+            match send(self, history, options).await {
+                Ok(reply) => return Ok(SentChat { reply, flags, record, started, redactions }),
+                Err(error) => match self.fit_context(history, &*error) {
+                    Some(shorter) if truncated.is_none() => truncated = Some(shorter),
+                    _ => {
+                        self.record_response(record, Err(&error.to_string()), started);
+                        return Err(error);
+                    }
+                },
+            }
+        }
+    }
+
+    // Streamed chat request to the provider, its plain request when there are no options
+    fn provider_stream<'a>(
+        &'a self,
+        history: &'a [Message],
+        options: Option<&'a GenerationOptions>,
+    ) -> BoxFuture<'a, Result<ChatStream, Box<dyn Error>>> {
+        Box::pin(async move {
+            match (&self.provider, options) {
+                (Provider::Ollama(client), None) => client.send_chat_request(history).await,
+                (Provider::Ollama(client), Some(options)) => client.send_chat_request_stream_with_options(history, Some(options.into())).await,
+                (Provider::Anthropic(client), None) => client.send_chat_request(history).await,
+                (Provider::Anthropic(client), Some(options)) => client.send_chat_request_with_options(history, options).await,
+                (Provider::OpenAI(client), None) => client.send_chat_request(history).await,
+                (Provider::OpenAI(client), Some(options)) => client.send_chat_request_with_options(history, Some(options.into())).await,
+                (Provider::OpenRouter(client), None) => client.send_chat_request(history).await,
+                (Provider::OpenRouter(client), Some(options)) => client.send_chat_request_with_options(history, options).await,
+                #[cfg(feature = "test-util")]
+                (Provider::Mock(client), _) => client.send_chat_request(history).await,
+            }
+        })
+    }
+
+    // Chat request to the provider without streaming, which takes no options
+    fn provider_response<'a>(
+        &'a self,
+        history: &'a [Message],
+        _options: Option<&'a GenerationOptions>,
+    ) -> BoxFuture<'a, Result<ChatResponse, Box<dyn Error>>> {
+        Box::pin(async move {
+            match &self.provider {
+                Provider::Ollama(client) => client.send_chat_request_no_stream(history).await,
+                Provider::Anthropic(client) => client.send_chat_request_no_stream(history).await,
+                Provider::OpenAI(client) => client.send_chat_request_no_stream(history).await,
+                Provider::OpenRouter(client) => client.send_chat_request_no_stream(history).await,
+                #[cfg(feature = "test-util")]
+                Provider::Mock(client) => client.send_chat_request_no_stream(history).await,
+            }
+        })
+    }
+
+    // Masked copy of the conversation for the provider, and the placeholders to restore in
+    // the reply when the redactor restores them
+    fn redact<'a>(&self, messages: &'a [Message]) -> (Cow<'a, [Message]>, Option<Redactions>) {
+        let Some(redactor) = &self.redactor else {
+            return (Cow::Borrowed(messages), None);
+        };
+        let (messages, redactions) = redactor.redact_messages(messages);
+        let redactions = (redactor.restores() && !redactions.is_empty()).then_some(redactions);
+        (Cow::Owned(messages), redactions)
+    }
+
+    // The history shortened to the limit in a context error, None when truncation is off or it
+    // can't get shorter
+    fn fit_context(&self, messages: &[Message], error: &(dyn Error + 'static)) -> Option<Vec<Message>> {
+        let strategy = self.context_truncation?;
+        let (limit, requested) = AIRequestError::as_context_length_exceeded(error)?;
+        // Estimates are rough, scale them by how far the provider's count is over its limit
+        let estimate = estimate_tokens(messages);
+        let budget = match (limit, requested) {
+            (Some(limit), Some(requested)) if requested > 0 => (estimate as u64 * limit as u64 / requested as u64) as u32,
+            (Some(limit), _) => limit.min(estimate),
+            _ => estimate / 2,
+        };
+        let truncated = truncate_history(messages, budget * 9 / 10, strategy);
+        (truncated.len() < messages.len()).then_some(truncated)
+    }
+
+    // Report the usage of the stream to the usage callback and the sink
+    fn track_usage(
+        &self,
+        stream: Pin<Box<dyn Stream<Item = Result<ChatStreamItem, String>> + Send>>,
+        tags: Option<RequestTags>,
+    ) -> Pin<Box<dyn Stream<Item = Result<ChatStreamItem, String>> + Send>> {
+        if self.usage_callback.is_none() && self.sink.is_none() {
+            return stream;
+        }
+        let (callback, sink) = (self.usage_callback.clone(), self.sink.clone());
+        let provider = self.provider_name().to_string();
+        let model = self.model().to_string();
+        Box::pin(stream.inspect(move |item| {
+            if let Ok(ChatStreamItem { usage: Some(usage), .. }) = item {
+                let record = UsageRecord {
+                    provider: provider.clone(),
+                    model: model.clone(),
+                    usage: usage.clone(),
+                    tags: tags.clone(),
+                };
+                if let Some(callback) = &callback {
+                    callback(&record);
+                }
+                if let Some(sink) = &sink {
+                    sink.record(&SinkRecord::from(&record));
+                }
+            }
+        }))
+    }
+
+    fn report_usage(&self, record: &UsageRecord) {
+        if let Some(callback) = &self.usage_callback {
+            callback(record);
+        }
+        if let Some(sink) = &self.sink {
+            sink.record(&SinkRecord::from(record));
+        }
+    }
+
+    // Report a request to the sink, returning the ID its response is recorded under
+    fn record_request(&self, messages: &[Message], options: Option<&GenerationOptions>) -> Option<String> {
+        let sink = self.sink.as_ref()?;
+        let id = options
+            .and_then(|options| options.tags.as_ref())
+            .map_or_else(|| uuid::Uuid::new_v4().to_string(), |tags| tags.correlation_id.clone());
+        sink.record(&SinkRecord::Request {
+            id: id.clone(),
+            timestamp: now_ms(),
+            provider: self.provider_name().to_string(),
+            model: self.model().to_string(),
+            messages: messages.to_vec(),
+            options: options.cloned(),
+        });
+        Some(id)
+    }
+
+    fn record_response(&self, id: Option<String>, response: Result<&ChatResponse, &str>, started: Instant) {
+        let (Some(sink), Some(id)) = (&self.sink, id) else {
+            return;
+        };
+        sink.record(&match response {
+            Ok(response) => SinkRecord::Response {
+                id,
+                timestamp: now_ms(),
+                provider: self.provider_name().to_string(),
+                model: self.model().to_string(),
+                text: response.text.clone(),
+                tool_calls: response.tool_calls.clone(),
+                finish_reason: response.finish_reason.clone(),
+                usage: response.usage.clone(),
+                latency_ms: started.elapsed().as_millis() as u64,
+                error: None,
+            },
+            Err(error) => SinkRecord::Response {
+                id,
+                timestamp: now_ms(),
+                provider: self.provider_name().to_string(),
+                model: self.model().to_string(),
+                text: String::new(),
+                tool_calls: None,
+                finish_reason: None,
+                usage: None,
+                latency_ms: started.elapsed().as_millis() as u64,
+                error: Some(error.to_string()),
+            },
+        });
+    }
+
+    // Collect the first completion of the stream and record it once it is done or fails
+    fn record_stream(
+        &self,
+        stream: Pin<Box<dyn Stream<Item = Result<ChatStreamItem, String>> + Send>>,
+        id: Option<String>,
+        started: Instant,
+    ) -> Pin<Box<dyn Stream<Item = Result<ChatStreamItem, String>> + Send>> {
+        let (Some(sink), Some(id)) = (self.sink.clone(), id) else {
+            return stream;
+        };
+        let provider = self.provider_name().to_string();
+        let model = self.model().to_string();
+        let mut response = ChatResponse::default();
+        let mut recorded = false;
+        Box::pin(stream.inspect(move |item| {
+            if recorded {
+                return;
+            }
+            let error = match item {
+                Ok(item) if item.choice_index.is_some() => return,
+                Ok(item) => {
+                    response.text.push_str(&item.content);
+                    if let Some(tool_calls) = &item.tool_calls {
+                        response.tool_calls.get_or_insert_with(Vec::new).extend(tool_calls.iter().cloned());
+                    }
+                    response.usage = item.usage.clone().or(response.usage.take());
+                    response.finish_reason = item.finish_reason.clone().or(response.finish_reason.take());
+                    if !item.done {
+                        return;
+                    }
+                    None
+                }
+                Err(error) => Some(error.clone()),
+            };
+            recorded = true;
+            sink.record(&SinkRecord::Response {
+                id: id.clone(),
+                timestamp: now_ms(),
+                provider: provider.clone(),
+                model: model.clone(),
+                text: std::mem::take(&mut response.text),
+                tool_calls: response.tool_calls.take(),
+                finish_reason: response.finish_reason.take(),
+                usage: response.usage.take(),
+                latency_ms: started.elapsed().as_millis() as u64,
+                error,
+            });
+        }))
+    }
+
+    // Guardrail verdicts on the latest user message and the tool results the model hasn't seen yet,
+    // flag reasons are returned for reporting
+    async fn check_input(&self, messages: &[Message]) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut flags = match messages.iter().rev().find(|m| m.role == "user") {
+            Some(message) => self.check_prompt(&message.content).await?,
+            None => Vec::new(),
+        };
+        let unseen = messages.iter().rposition(|m| m.role == "assistant").map_or(0, |index| index + 1);
+        for message in messages[unseen..].iter().filter(|m| m.role == "tool") {
+            flags.extend(self.check_request_text(GuardrailStage::ToolResult, &message.content).await?);
+        }
+        Ok(flags)
+    }
+
+    async fn check_prompt(&self, prompt: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        self.check_request_text(GuardrailStage::Input, prompt).await
+    }
+
+    async fn check_request_text(&self, stage: GuardrailStage, text: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let Some(guardrail) = &self.guardrail else {
+            return Ok(Vec::new());
+        };
+        match guardrail.check(stage, text).await {
+            GuardrailVerdict::Allow => Ok(Vec::new()),
+            GuardrailVerdict::Flag(reason) => Ok(vec![reason]),
+            GuardrailVerdict::Block(reason) => Err(format!("Request blocked by guardrail: {}", reason).into()),
+        }
+    }
+
+    async fn check_output(&self, response: &str) -> Result<(), Box<dyn Error>> {
+        let Some(guardrail) = &self.guardrail else {
+            return Ok(());
+        };
+        match guardrail.check(GuardrailStage::Output, response).await {
+            GuardrailVerdict::Block(reason) => Err(format!("Response blocked by guardrail: {}", reason).into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Classify text or an image with the OpenAI moderation endpoint (OpenAI only)
+    pub async fn moderate(&self, input: impl Into<ModerationInput>) -> Result<ModerationResult, Box<dyn Error>> {
+        self.lifecycle.track(async {
+            match &self.provider {
+                Provider::OpenAI(client) => client.moderate(input).await,
+                _ => Err("moderate is only supported for OpenAI provider".into()),
+            }
+        }).await
+    }
+
+    /// Send chat request expecting a JSON reply, returns the parsed value checked against the schema.
+    /// Ollama and OpenAI enforce the format server-side, other providers are instructed through the system prompt
+    pub async fn send_chat_request_structured(
+        &self,
+        messages: &[Message],
+        format: &ResponseFormat,
+    ) -> Result<serde_json::Value, Box<dyn Error>> {
+        let options = match &self.provider {
+            Provider::Ollama(_) => GenerationOptions::default().with_extension("format", ollama_format(format)),
+            Provider::OpenAI(_) => GenerationOptions::default().with_extension("response_format", openai_response_format(format)),
+            _ => GenerationOptions::default(),
+        };
+        let response = self.send_chat_request_no_stream_with_options(&format.apply_instructions(messages), &options).await?;
+        Ok(format.parse(&response.text)?)
+    }
+
+    /// Simple completion expecting a JSON reply, see `send_chat_request_structured`
+    pub async fn generate_structured(&self, prompt: &str, format: &ResponseFormat) -> Result<serde_json::Value, Box<dyn Error>> {
+        self.send_chat_request_structured(&[Message::user(prompt)], format).await
+    }
+
+    /// Input tokens the conversation would use, for budgeting before sending it. Exact for
+    /// Anthropic (count_tokens endpoint), estimated from the text length for the others
+    pub async fn count_tokens(&self, messages: &[Message]) -> Result<u32, Box<dyn Error>> {
+        self.lifecycle.track(async {
+            match &self.provider {
+                Provider::Anthropic(client) => client.count_tokens(messages).await,
+                // OpenRouter has no counting endpoint, a completion to learn the count would be billed
+                Provider::Ollama(_) | Provider::OpenAI(_) | Provider::OpenRouter(_) => Ok(estimate_tokens(messages)),
+                #[cfg(feature = "test-util")]
+                Provider::Mock(_) => Ok(estimate_tokens(messages)),
+            }
+        }).await
+    }
+
+    /// Generate images from a prompt (OpenAI and OpenRouter), cost is reported in `usage` when known
+    pub async fn generate_image(&self, prompt: &str, options: &ImageGenerationOptions) -> Result<ImageGeneration, Box<dyn Error>> {
+        self.lifecycle.track(async {
+            match &self.provider {
+                Provider::OpenAI(client) => client.generate_image(prompt, options).await,
+                Provider::OpenRouter(client) => client.generate_image(prompt, options).await,
+                _ => Err("generate_image is only supported for OpenAI and OpenRouter providers".into()),
+            }
+        }).await
+    }
+
+    /// Speech to text (OpenAI only), see `TranscriptionOptions` for the model and language
+    pub async fn transcribe(&self, audio: &AudioInput, options: &TranscriptionOptions) -> Result<Transcription, Box<dyn Error>> {
+        self.lifecycle.track(async {
+            match &self.provider {
+                Provider::OpenAI(client) => client.transcribe(audio, options).await,
+                _ => Err("transcribe is only supported for OpenAI provider".into()),
+            }
+        }).await
+    }
+
+    /// Speech in any language to English text (OpenAI only)
+    pub async fn translate_audio(&self, audio: &AudioInput, options: &TranscriptionOptions) -> Result<Transcription, Box<dyn Error>> {
+        self.lifecycle.track(async {
+            match &self.provider {
+                Provider::OpenAI(client) => client.translate_audio(audio, options).await,
+                _ => Err("translate_audio is only supported for OpenAI provider".into()),
+            }
+        }).await
+    }
+
+    /// Transcript deltas as the audio is recognized (OpenAI gpt-4o-transcribe models only)
+    pub async fn transcribe_stream(
+        &self,
+        audio: &AudioInput,
+        options: &TranscriptionOptions,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<String, String>> + Send>>, Box<dyn Error>> {
+        self.lifecycle.track_stream(async {
+            match &self.provider {
+                Provider::OpenAI(client) => client.transcribe_stream(audio, options).await,
+                _ => Err("transcribe_stream is only supported for OpenAI provider".into()),
+            }
+        }).await
+    }
+
     /// Send chat request with images from file paths, returns real-time streaming response
     pub async fn send_chat_request_with_images(
         &self,
@@ -173,6 +1121,19 @@ impl MonoAI {
                 }
                 self.send_chat_request(&messages_with_images).await
             }
+            #[cfg(feature = "test-util")]
+            Provider::Mock(_) => {
+                let mut messages_with_images = messages.to_vec();
+                if let Some(last_message) = messages_with_images.last_mut() {
+                    let mut encoded_images = Vec::new();
+                    for image_path in image_paths {
+                        let encoded = self.encode_image_file(&image_path).await?;
+                        encoded_images.push(encoded);
+                    }
+                    last_message.images = Some(encoded_images);
+                }
+                self.send_chat_request(&messages_with_images).await
+            }
         }
     }
 
@@ -181,7 +1142,7 @@ impl MonoAI {
         &self,
         messages: &[Message],
         image_paths: Vec<String>,
-    ) -> Result<(String, Option<Vec<ToolCall>>), Box<dyn Error>> {
+    ) -> Result<ChatResponse, Box<dyn Error>> {
         match &self.provider {
             Provider::Ollama(client) => client.send_chat_request_with_images_no_stream(messages, image_paths).await,
             Provider::Anthropic(_) => {
@@ -223,6 +1184,19 @@ impl MonoAI {
                 }
                 self.send_chat_request_no_stream(&messages_with_images).await
             }
+            #[cfg(feature = "test-util")]
+            Provider::Mock(_) => {
+                let mut messages_with_images = messages.to_vec();
+                if let Some(last_message) = messages_with_images.last_mut() {
+                    let mut encoded_images = Vec::new();
+                    for image_path in image_paths {
+                        let encoded = self.encode_image_file(&image_path).await?;
+                        encoded_images.push(encoded);
+                    }
+                    last_message.images = Some(encoded_images);
+                }
+                self.send_chat_request_no_stream(&messages_with_images).await
+            }
         }
     }
 
@@ -273,15 +1247,28 @@ impl MonoAI {
                 }
                 self.send_chat_request(&messages_with_images).await
             }
-        }
-    }
-
-    /// Send chat request with image data from memory, returns complete response and tool calls (single image: vec![data], multiple: vec![data1, data2])
-    pub async fn send_chat_request_with_image_data_no_stream(
+            #[cfg(feature = "test-util")]
+            Provider::Mock(_) => {
+                let mut messages_with_images = messages.to_vec();
+                if let Some(last_message) = messages_with_images.last_mut() {
+                    let mut encoded_images = Vec::new();
+                    for image_data in images_data {
+                        let encoded = self.encode_image_data(image_data).await?;
+                        encoded_images.push(encoded);
+                    }
+                    last_message.images = Some(encoded_images);
+                }
+                self.send_chat_request(&messages_with_images).await
+            }
+        }
+    }
+
+    /// Send chat request with image data from memory, returns complete response and tool calls (single image: vec![data], multiple: vec![data1, data2])
+    pub async fn send_chat_request_with_image_data_no_stream(
         &self,
         messages: &[Message],
         images_data: Vec<Vec<u8>>,
-    ) -> Result<(String, Option<Vec<ToolCall>>), Box<dyn Error>> {
+    ) -> Result<ChatResponse, Box<dyn Error>> {
         match &self.provider {
             Provider::Ollama(client) => client.send_chat_request_with_images_data_no_stream(messages, images_data).await,
             Provider::Anthropic(_) => {
@@ -323,108 +1310,269 @@ impl MonoAI {
                 }
                 self.send_chat_request_no_stream(&messages_with_images).await
             }
+            #[cfg(feature = "test-util")]
+            Provider::Mock(_) => {
+                let mut messages_with_images = messages.to_vec();
+                if let Some(last_message) = messages_with_images.last_mut() {
+                    let mut encoded_images = Vec::new();
+                    for image_data in images_data {
+                        let encoded = self.encode_image_data(image_data).await?;
+                        encoded_images.push(encoded);
+                    }
+                    last_message.images = Some(encoded_images);
+                }
+                self.send_chat_request_no_stream(&messages_with_images).await
+            }
+        }
+    }
+
+    /// Send chat request with remote images attached to the last message. OpenAI and OpenRouter
+    /// fetch the URLs themselves, for Ollama and Anthropic they're downloaded up to `MAX_IMAGE_DOWNLOAD_BYTES`
+    pub async fn send_chat_request_with_image_urls(
+        &self,
+        messages: &[Message],
+        image_urls: Vec<String>,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<ChatStreamItem, String>> + Send>>, Box<dyn Error>> {
+        let mut messages_with_images = messages.to_vec();
+        if let Some(last_message) = messages_with_images.last_mut() {
+            last_message.image_urls = Some(image_urls);
+        }
+        self.send_chat_request(&messages_with_images).await
+    }
+
+    /// Send chat request with remote images, returns complete response and tool calls
+    pub async fn send_chat_request_with_image_urls_no_stream(
+        &self,
+        messages: &[Message],
+        image_urls: Vec<String>,
+    ) -> Result<ChatResponse, Box<dyn Error>> {
+        let mut messages_with_images = messages.to_vec();
+        if let Some(last_message) = messages_with_images.last_mut() {
+            last_message.image_urls = Some(image_urls);
         }
+        self.send_chat_request_no_stream(&messages_with_images).await
     }
 
-    /// Generate single completion from prompt without conversation context
+    /// Generate single completion from prompt without conversation context. Ollama uses its
+    /// generate endpoint, other providers a chat with the prompt as the user message
     pub async fn generate(&self, prompt: &str) -> Result<String, Box<dyn Error>> {
+        self.generate_with_options(prompt, &GenerationOptions::default()).await
+    }
+
+    /// Single completion with sampling settings, see `generate`
+    pub async fn generate_with_options(&self, prompt: &str, options: &GenerationOptions) -> Result<String, Box<dyn Error>> {
+        match self.native_generate() {
+            Some(client) => {
+                self.lifecycle.track(async {
+                    self.check_prompt(prompt).await?;
+                    let response = client.generate_with_options(prompt, Some(options.into())).await?;
+                    self.check_output(&response).await?;
+                    Ok(response)
+                }).await
+            }
+            None => {
+                let messages = [Message::user(prompt)];
+                Ok(self.send_chat_request_no_stream_with_options(&messages, options).await?.text)
+            }
+        }
+    }
+
+    // Ollama client for its generate endpoint, None when the prompt has to go through chat
+    // for the persona or redactor to apply
+    fn native_generate(&self) -> Option<&OllamaClient> {
         match &self.provider {
-            Provider::Ollama(client) => client.generate(prompt).await,
-            Provider::Anthropic(client) => {
-                // Convert prompt to messages format for Anthropic
-                let messages = vec![Message {
-                    role: "user".to_string(),
-                    content: prompt.to_string(),
-                    images: None,
-                    tool_calls: None,
-                }];
-                let (response, _) = client.send_chat_request_no_stream(&messages).await?;
-                Ok(response)
+            Provider::Ollama(client) if self.persona.is_none() && self.redactor.is_none() => Some(client),
+            _ => None,
+        }
+    }
+
+    /// Fill-in-the-middle completion between `prefix` and `suffix` for code models (Ollama only)
+    pub async fn generate_fim(&self, prefix: &str, suffix: &str) -> Result<String, Box<dyn Error>> {
+        self.lifecycle.track(async {
+            match &self.provider {
+                Provider::Ollama(client) => client.generate_fim(prefix, suffix).await,
+                _ => Err("generate_fim is only supported for Ollama provider".into()),
             }
-            Provider::OpenAI(client) => {
-                // Convert prompt to messages format for OpenAI
-                let messages = vec![Message {
-                    role: "user".to_string(),
-                    content: prompt.to_string(),
-                    images: None,
-                    tool_calls: None,
-                }];
-                let (response, _) = client.send_chat_request_no_stream(&messages).await?;
-                Ok(response)
-            }
-            Provider::OpenRouter(client) => {
-                // Convert prompt to messages format for OpenRouter
-                let messages = vec![Message {
-                    role: "user".to_string(),
-                    content: prompt.to_string(),
-                    images: None,
-                    tool_calls: None,
-                }];
-                let (response, _) = client.send_chat_request_no_stream(&messages).await?;
-                Ok(response)
-            }
-        }
-    }
-
-    /// Generate streaming completion from prompt without conversation context
+        }).await
+    }
+
+    /// Generate streaming completion from prompt without conversation context, see `generate`
     pub async fn generate_stream(
         &self,
         prompt: &str,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<String, String>> + Send>>, Box<dyn Error>> {
-        match &self.provider {
-            Provider::Ollama(client) => client.generate_stream(prompt).await,
-            Provider::Anthropic(client) => {
-                // Convert prompt to messages format for Anthropic and convert stream
-                let messages = vec![Message {
-                    role: "user".to_string(),
-                    content: prompt.to_string(),
-                    images: None,
-                    tool_calls: None,
-                }];
-                let stream = client.send_chat_request(&messages).await?;
-                let mapped_stream = stream.map(|item| {
-                    match item {
-                        Ok(chat_item) => Ok(chat_item.content),
-                        Err(e) => Err(e),
-                    }
-                });
-                Ok(Box::pin(mapped_stream))
+        self.generate_stream_with_options(prompt, &GenerationOptions::default()).await
+    }
+
+    /// Streaming completion with sampling settings, only the first completion when `n` is set
+    pub async fn generate_stream_with_options(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<String, String>> + Send>>, Box<dyn Error>> {
+        if let Some(client) = self.native_generate() {
+            return self.lifecycle.track_stream(async {
+                self.check_prompt(prompt).await?;
+                client.generate_stream_with_options(prompt, Some(options.into())).await
+            }).await;
+        }
+
+        let messages = [Message::user(prompt)];
+        let stream = self.send_chat_request_with_options(&messages, options).await?;
+        Ok(Box::pin(stream.filter_map(|item| async move {
+            match item {
+                Ok(item) if item.choice_index.is_some() || item.content.is_empty() => None,
+                Ok(item) => Some(Ok(item.content)),
+                Err(e) => Some(Err(e)),
             }
-            Provider::OpenAI(client) => {
-                // Convert prompt to messages format for OpenAI and convert stream
-                let messages = vec![Message {
-                    role: "user".to_string(),
-                    content: prompt.to_string(),
-                    images: None,
-                    tool_calls: None,
-                }];
-                let stream = client.send_chat_request(&messages).await?;
-                let mapped_stream = stream.map(|item| {
-                    match item {
-                        Ok(chat_item) => Ok(chat_item.content),
-                        Err(e) => Err(e),
-                    }
-                });
-                Ok(Box::pin(mapped_stream))
-            }
-            Provider::OpenRouter(client) => {
-                // Convert prompt to messages format for OpenRouter and convert stream
-                let messages = vec![Message {
-                    role: "user".to_string(),
-                    content: prompt.to_string(),
-                    images: None,
-                    tool_calls: None,
-                }];
-                let stream = client.send_chat_request(&messages).await?;
-                let mapped_stream = stream.map(|item| {
-                    match item {
-                        Ok(chat_item) => Ok(chat_item.content),
-                        Err(e) => Err(e),
-                    }
-                });
-                Ok(Box::pin(mapped_stream))
+        })))
+    }
+
+    /// Embed texts with the client's model, one vector per input text (Ollama and OpenAI)
+    pub async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, Box<dyn Error>> {
+        self.lifecycle.track(async {
+            match &self.provider {
+                Provider::Ollama(client) => client.embed(texts).await,
+                Provider::Anthropic(_) => Err("embed is not supported for Anthropic provider".into()),
+                Provider::OpenAI(client) => client.embed(texts).await,
+                Provider::OpenRouter(_) => Err("embed is not supported for OpenRouter provider".into()),
+                #[cfg(feature = "test-util")]
+                Provider::Mock(_) => Err("embed is not supported for mock provider".into()),
             }
+        }).await
+    }
+
+    /// Short title for a conversation, e.g. for the session list of a chat UI
+    pub async fn title(&self, messages: &[Message]) -> Result<String, Box<dyn Error>> {
+        let instructions = "Write a title of at most six words for the conversation below. Reply with the title only, without quotes or punctuation at the end.";
+        let title = self.utility_completion(instructions, messages).await?;
+        Ok(title.trim().trim_matches(|c| c == '"' || c == '\'' || c == '.').trim().to_string())
+    }
+
+    /// Summary of a conversation in at most `max_words` words
+    pub async fn summarize(&self, messages: &[Message], max_words: usize) -> Result<String, Box<dyn Error>> {
+        let instructions = format!(
+            "Summarize the conversation below in at most {} words. Keep the facts, decisions and open questions. Reply with the summary only.",
+            max_words
+        );
+        Ok(self.utility_completion(&instructions, messages).await?.trim().to_string())
+    }
+
+    /// Label a text with one of `labels`, using structured output. The label is checked
+    /// against `labels`, the confidence is the model's own estimate
+    pub async fn classify(&self, text: &str, labels: &[&str]) -> Result<Classification, Box<dyn Error>> {
+        let format = Classification::format(labels)?;
+        let value = self.generate_structured(&Classification::prompt(text, labels), &format).await?;
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// Rate a text on a rubric, using structured output. The score is checked to be within
+    /// the rubric's range
+    pub async fn score(&self, text: &str, rubric: &Rubric) -> Result<Score, Box<dyn Error>> {
+        let value = self.generate_structured(&rubric.prompt(text), &rubric.format()).await?;
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// Language a text is written in, using structured output on the utility model when one
+    /// is set
+    pub async fn detect_language(&self, text: &str) -> Result<LanguageDetection, Box<dyn Error>> {
+        self.detect_language_with_options(text, &GenerationOptions::default()).await
+    }
+
+    /// `detect_language` with sampling settings and tags
+    pub async fn detect_language_with_options(&self, text: &str, options: &GenerationOptions) -> Result<LanguageDetection, Box<dyn Error>> {
+        let value = self.utility_structured(&LanguageDetection::prompt(text), &LanguageDetection::format(), options).await?;
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// Translate a text into `target_language`, a name ("German") or code ("de"), using
+    /// structured output on the utility model when one is set. `translate_audio` is for speech
+    pub async fn translate(&self, text: &str, target_language: &str) -> Result<Translation, Box<dyn Error>> {
+        self.translate_with_options(text, target_language, &GenerationOptions::default()).await
+    }
+
+    /// `translate` with sampling settings and tags
+    pub async fn translate_with_options(&self, text: &str, target_language: &str, options: &GenerationOptions) -> Result<Translation, Box<dyn Error>> {
+        let value = self.utility_structured(&Translation::prompt(text, target_language), &Translation::format(), options).await?;
+        Ok(Translation::from_reply(value, target_language)?)
+    }
+
+    // JSON reply from the utility model when one is set. Sent as a chat request so `options`,
+    // usage reporting and guardrails apply, the format is given in the instructions
+    async fn utility_structured(&self, prompt: &str, format: &ResponseFormat, options: &GenerationOptions) -> Result<serde_json::Value, Box<dyn Error>> {
+        let utility = self.utility_model.as_deref().and_then(|model| self.for_model(model));
+        let client = utility.as_ref().unwrap_or(self);
+        let messages = format.apply_instructions(&[Message::user(prompt)]);
+        let response = client.send_chat_request_no_stream_with_options(&messages, options).await?;
+        Ok(format.parse(&response.text)?)
+    }
+
+    // Run a task over the transcript of a conversation with the utility model
+    async fn utility_completion(&self, instructions: &str, messages: &[Message]) -> Result<String, Box<dyn Error>> {
+        let transcript = messages
+            .iter()
+            .filter(|message| message.role == "user" || message.role == "assistant")
+            .filter(|message| !message.content.trim().is_empty())
+            .map(|message| format!("{}: {}", if message.role == "user" { "User" } else { "Assistant" }, message.content.trim()))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        if transcript.is_empty() {
+            return Err("The conversation has no messages to summarize".into());
         }
+
+        let prompt = vec![Message::system(instructions), Message::user(&transcript)];
+        let utility = self.utility_model.as_deref().and_then(|model| self.for_model(model));
+        Ok(utility.as_ref().unwrap_or(self).send_chat_request_no_stream(&prompt).await?.text)
+    }
+
+    // Client for another model with the same provider settings, None for the mock which
+    // keeps its script
+    pub(crate) fn for_model(&self, model: &str) -> Option<MonoAI> {
+        let provider = match &self.provider {
+            Provider::Ollama(client) => Provider::Ollama(client.for_model(model)),
+            Provider::Anthropic(client) => Provider::Anthropic(client.for_model(model)),
+            Provider::OpenAI(client) => Provider::OpenAI(client.for_model(model)),
+            Provider::OpenRouter(client) => Provider::OpenRouter(client.for_model(model)),
+            #[cfg(feature = "test-util")]
+            Provider::Mock(_) => return None,
+        };
+        Some(MonoAI {
+            provider,
+            guardrail: self.guardrail.clone(),
+            usage_callback: self.usage_callback.clone(),
+            sink: self.sink.clone(),
+            utility_model: None,
+            redactor: self.redactor.clone(),
+            persona: None,
+            context_truncation: self.context_truncation,
+            clock: self.clock.clone(),
+            lifecycle: self.lifecycle.clone(),
+        })
+    }
+
+    /// Embed many texts as a job reporting progress per batch of `batch_size` texts
+    pub fn embed_job<'a>(&'a self, texts: &'a [String], batch_size: usize) -> Job<'a, Vec<Vec<f32>>> {
+        let clock = JobClock::start();
+        let batch_size = batch_size.max(1);
+
+        Job::new(futures_util::stream::unfold(Some(Vec::new()), move |embeddings| async move {
+            let mut embeddings: Vec<Vec<f32>> = embeddings?;
+            if embeddings.len() == texts.len() {
+                return Some((JobEvent::Done(embeddings), None));
+            }
+
+            let batch = &texts[embeddings.len()..(embeddings.len() + batch_size).min(texts.len())];
+            let event = match self.embed(batch).await.map_err(|e| e.to_string()) {
+                Ok(vectors) if vectors.len() == batch.len() => {
+                    embeddings.extend(vectors);
+                    JobEvent::Progress(clock.progress("Embedding", Some(embeddings.len() as u64), Some(texts.len() as u64)))
+                }
+                Ok(vectors) => JobEvent::Failed(format!("Expected {} embeddings, got {}", batch.len(), vectors.len())),
+                Err(e) => JobEvent::Failed(e),
+            };
+            let next = matches!(event, JobEvent::Progress(_)).then_some(embeddings);
+            Some((event, next))
+        }))
     }
 
     /// Get available models from any provider
@@ -438,32 +1586,56 @@ impl MonoAI {
                     provider: "Ollama".to_string(),
                     size: Some(m.size),
                     created: None,
+                    capabilities: None,
                 }).collect())
             }
             Provider::Anthropic(client) => {
                 let models = client.get_available_models().await?;
                 Ok(models.into_iter().map(|m| MonoModel {
                     id: m.id.clone(),
+                    created: m.created_timestamp(),
                     name: m.display_name,
                     provider: "Anthropic".to_string(),
                     size: None,
-                    created: Some(m.created_at.parse().unwrap_or(0)),
+                    capabilities: Some(AnthropicClient::known_capabilities(&m.id)),
                 }).collect())
             }
             Provider::OpenAI(client) => {
                 let models = client.get_available_models().await?;
                 Ok(models.into_iter().map(|m| MonoModel {
                     id: m.id.clone(),
+                    capabilities: OpenAIClient::known_capabilities(&m.id),
                     name: m.id,
                     provider: "OpenAI".to_string(),
                     size: None,
                     created: Some(m.created),
                 }).collect())
             }
-            Provider::OpenRouter(client) => {
-                client.get_available_models().await.map_err(|e| e.into())
+            Provider::OpenRouter(client) => client.get_available_models().await,
+            #[cfg(feature = "test-util")]
+            Provider::Mock(client) => Ok(vec![MonoModel {
+                id: client.model.clone(),
+                name: client.model.clone(),
+                provider: "Mock".to_string(),
+                size: None,
+                created: None,
+                capabilities: Some(client.capabilities()),
+            }]),
+        }
+    }
+
+    /// Available models meeting the filter's requirements. Capabilities come from provider metadata,
+    /// `/api/show` of every local model on Ollama and built-in tables for OpenAI and Anthropic
+    pub async fn get_available_models_filtered(&self, filter: &ModelFilter) -> Result<Vec<MonoModel>, Box<dyn Error>> {
+        let mut models = self.get_available_models().await?;
+        if let Provider::Ollama(client) = &self.provider {
+            let infos = futures_util::future::join_all(models.iter().map(|model| client.show_model_info(&model.id))).await;
+            for (model, info) in models.iter_mut().zip(infos) {
+                model.capabilities = info.ok().map(|info| info.model_capabilities());
             }
         }
+        models.retain(|model| filter.matches(model.capabilities.as_ref()));
+        Ok(models)
     }
 
     /// List locally installed models (legacy method, use get_available_models instead)
@@ -474,6 +1646,119 @@ impl MonoAI {
         }
     }
 
+    /// List models currently loaded in memory with VRAM usage and expiry (Ollama only)
+    pub async fn list_running_models(&self) -> Result<Vec<RunningModel>, Box<dyn Error>> {
+        match &self.provider {
+            Provider::Ollama(client) => client.list_running_models().await,
+            _ => Err("list_running_models is only supported for Ollama provider".into()),
+        }
+    }
+
+    /// Verify the provider is reachable and accepts the credentials, for readiness probes.
+    /// Ollama answers `/api/version`, OpenAI and Anthropic fetch the model, OpenRouter checks the key
+    pub async fn health_check(&self) -> Result<(), Box<dyn Error>> {
+        match &self.provider {
+            Provider::Ollama(client) => client.health_check().await,
+            Provider::Anthropic(client) => client.health_check().await,
+            Provider::OpenAI(client) => client.health_check().await,
+            Provider::OpenRouter(client) => client.health_check().await,
+            #[cfg(feature = "test-util")]
+            Provider::Mock(client) => client.health_check().await,
+        }
+    }
+
+    /// Remaining requests, tokens and reset times for dashboards and throttling. OpenAI and Anthropic
+    /// report them in the headers of each response, this returns the latest (empty before the first
+    /// request). OpenRouter is queried for the key's credits
+    pub async fn get_quota(&self) -> Result<Quota, Box<dyn Error>> {
+        match &self.provider {
+            Provider::Ollama(_) => Err("get_quota is not supported for Ollama provider".into()),
+            Provider::Anthropic(client) => Ok(client.quota()),
+            Provider::OpenAI(client) => Ok(client.quota()),
+            Provider::OpenRouter(client) => client.get_quota().await,
+            #[cfg(feature = "test-util")]
+            Provider::Mock(_) => Err("get_quota is not supported for mock provider".into()),
+        }
+    }
+
+    // Rate limits from the latest response headers, without querying the provider
+    pub(crate) fn last_quota(&self) -> Quota {
+        match &self.provider {
+            Provider::Anthropic(client) => client.quota(),
+            Provider::OpenAI(client) => client.quota(),
+            _ => Quota::default(),
+        }
+    }
+
+    /// Round trip time of `health_check`. The first call includes connecting, later calls reuse the connection
+    pub async fn ping_latency(&self) -> Result<Duration, Box<dyn Error>> {
+        let start = Instant::now();
+        self.health_check().await?;
+        Ok(start.elapsed())
+    }
+
+    /// Get the Ollama server version
+    pub async fn server_version(&self) -> Result<String, Box<dyn Error>> {
+        match &self.provider {
+            Provider::Ollama(client) => client.server_version().await,
+            _ => Err("server_version is only supported for Ollama provider".into()),
+        }
+    }
+
+    /// Load the model into memory ahead of the first request (Ollama only)
+    pub async fn load_model(&self) -> Result<(), Box<dyn Error>> {
+        self.lifecycle.track(async {
+            match &self.provider {
+                Provider::Ollama(client) => client.load_model().await,
+                _ => Err("load_model is only supported for Ollama provider".into()),
+            }
+        }).await
+    }
+
+    /// Unload the model and free its memory (Ollama only)
+    pub async fn unload_model(&self) -> Result<(), Box<dyn Error>> {
+        self.lifecycle.track(async {
+            match &self.provider {
+                Provider::Ollama(client) => client.unload_model().await,
+                _ => Err("unload_model is only supported for Ollama provider".into()),
+            }
+        }).await
+    }
+
+    /// What a model supports (tools, vision, JSON mode, streaming) and its context and output limits,
+    /// from Ollama's `/api/show`, OpenRouter's model listing or the built-in OpenAI and Anthropic tables
+    pub async fn capabilities(&self, model: &str) -> Result<ModelCapabilities, Box<dyn Error>> {
+        match &self.provider {
+            Provider::Ollama(client) => Ok(client.show_model_info(model).await?.model_capabilities()),
+            Provider::Anthropic(_) => Ok(AnthropicClient::known_capabilities(model)),
+            Provider::OpenAI(_) => OpenAIClient::known_capabilities(model)
+                .ok_or_else(|| format!("No capabilities known for OpenAI model {}", model).into()),
+            Provider::OpenRouter(client) => client
+                .models()
+                .await?
+                .iter()
+                .find(|m| m.id == model)
+                .map(|m| m.capabilities())
+                .ok_or_else(|| format!("Model {} is not in OpenRouter's model listing", model).into()),
+            #[cfg(feature = "test-util")]
+            Provider::Mock(client) => Ok(client.capabilities()),
+        }
+    }
+
+    /// Start a realtime voice session with the client's credentials. `model` is a realtime
+    /// model such as "gpt-realtime", the client's own model is for text chat
+    #[cfg(feature = "realtime")]
+    pub async fn voice_session(&self, model: &str, config: crate::voice::VoiceConfig) -> Result<crate::voice::VoiceSession, Box<dyn Error>> {
+        match &self.provider {
+            Provider::OpenAI(client) => client.voice_session(model, config).await,
+            Provider::Ollama(_) => Err("voice_session is not supported for Ollama provider".into()),
+            Provider::Anthropic(_) => Err("voice_session is not supported for Anthropic provider".into()),
+            Provider::OpenRouter(_) => Err("voice_session is not supported for OpenRouter provider".into()),
+            #[cfg(feature = "test-util")]
+            Provider::Mock(_) => Err("voice_session is not supported for mock provider".into()),
+        }
+    }
+
     /// Get detailed model information including template and parameters
     pub async fn show_model_info(&self, model_name: &str) -> Result<ModelInfo, Box<dyn Error>> {
         match &self.provider {
@@ -481,17 +1766,23 @@ impl MonoAI {
             Provider::Anthropic(_) => Err("show_model_info is not supported for Anthropic provider".into()),
             Provider::OpenAI(_) => Err("show_model_info is not supported for OpenAI provider".into()),
             Provider::OpenRouter(_) => Err("show_model_info is not supported for OpenRouter provider".into()),
+            #[cfg(feature = "test-util")]
+            Provider::Mock(_) => Err("show_model_info is not supported for mock provider".into()),
         }
     }
 
     /// Download model from provider registry (provider-specific operation)
     pub async fn pull_model(&self, model_name: &str) -> Result<(), Box<dyn Error>> {
-        match &self.provider {
-            Provider::Ollama(client) => client.pull_model(model_name).await,
-            Provider::Anthropic(_) => Err("pull_model is not supported for Anthropic provider".into()),
-            Provider::OpenAI(_) => Err("pull_model is not supported for OpenAI provider".into()),
-            Provider::OpenRouter(_) => Err("pull_model is not supported for OpenRouter provider".into()),
-        }
+        self.lifecycle.track(async {
+            match &self.provider {
+                Provider::Ollama(client) => client.pull_model(model_name).await,
+                Provider::Anthropic(_) => Err("pull_model is not supported for Anthropic provider".into()),
+                Provider::OpenAI(_) => Err("pull_model is not supported for OpenAI provider".into()),
+                Provider::OpenRouter(_) => Err("pull_model is not supported for OpenRouter provider".into()),
+                #[cfg(feature = "test-util")]
+                Provider::Mock(_) => Err("pull_model is not supported for mock provider".into()),
+            }
+        }).await
     }
 
     /// Download model with streaming progress updates (provider-specific operation)
@@ -499,21 +1790,315 @@ impl MonoAI {
         &self,
         model_name: &str,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<PullProgress, String>> + Send>>, Box<dyn Error>> {
-        match &self.provider {
-            Provider::Ollama(client) => client.pull_model_stream(model_name).await,
-            Provider::Anthropic(_) => Err("pull_model_stream is not supported for Anthropic provider".into()),
-            Provider::OpenAI(_) => Err("pull_model_stream is not supported for OpenAI provider".into()),
-            Provider::OpenRouter(_) => Err("pull_model_stream is not supported for OpenRouter provider".into()),
+        self.lifecycle.track_stream(async {
+            match &self.provider {
+                Provider::Ollama(client) => client.pull_model_stream(model_name).await,
+                Provider::Anthropic(_) => Err("pull_model_stream is not supported for Anthropic provider".into()),
+                Provider::OpenAI(_) => Err("pull_model_stream is not supported for OpenAI provider".into()),
+                Provider::OpenRouter(_) => Err("pull_model_stream is not supported for OpenRouter provider".into()),
+                #[cfg(feature = "test-util")]
+                Provider::Mock(_) => Err("pull_model_stream is not supported for mock provider".into()),
+            }
+        }).await
+    }
+
+    /// Download a model as a job, the progress sums the bytes of all layers
+    pub fn pull_model_job<'a>(&'a self, model_name: &'a str) -> Job<'a, ()> {
+        Job::new(self.pull_events(model_name, JobClock::start()).chain(futures_util::stream::iter([JobEvent::Done(())])))
+    }
+
+    /// Chat as a job that first pulls the model when Ollama doesn't have it, so one event stream
+    /// covers the download and the generation. Finishes with the whole reply
+    pub fn chat_job<'a>(&'a self, messages: &'a [Message]) -> Job<'a, String> {
+        let clock = JobClock::start();
+        let pull = futures_util::stream::once(self.model_missing())
+            .flat_map(move |missing| match missing {
+                true => self.pull_events(self.model(), clock).boxed(),
+                false => futures_util::stream::empty().boxed(),
+            })
+            .filter_map(|event| async move {
+                match event {
+                    JobEvent::Progress(progress) => Some(JobEvent::Progress(progress)),
+                    JobEvent::Failed(e) => Some(JobEvent::Failed(e)),
+                    JobEvent::Done(()) | JobEvent::Cancelled => None,
+                }
+            });
+
+        let chat = futures_util::stream::once(async move { self.send_chat_request(messages).await.map_err(|e| e.to_string()) })
+            .flat_map(move |stream| match stream {
+                Ok(stream) => stream
+                    .scan(String::new(), move |reply, item| {
+                        let event = match item {
+                            Ok(item) if item.choice_index.is_some() => JobEvent::Progress(clock.progress("Generating", None, None)),
+                            Ok(item) => {
+                                reply.push_str(&item.content);
+                                match item.done {
+                                    true => JobEvent::Done(std::mem::take(reply)),
+                                    false => JobEvent::Progress(clock.progress("Generating", None, None)),
+                                }
+                            }
+                            Err(e) => JobEvent::Failed(e),
+                        };
+                        futures_util::future::ready(Some(event))
+                    })
+                    .boxed(),
+                Err(e) => futures_util::stream::iter([JobEvent::Failed(e)]).boxed(),
+            });
+
+        Job::new(pull.chain(chat))
+    }
+
+    // Progress events of a pull, ending with `Failed` on errors and without `Done` otherwise
+    fn pull_events<'a, T: Send + 'a>(&'a self, model_name: &'a str, clock: JobClock) -> Pin<Box<dyn Stream<Item = JobEvent<T>> + Send + 'a>> {
+        let progress = futures_util::stream::once(async move { self.pull_model_stream(model_name).await.map_err(|e| e.to_string()) });
+        Box::pin(progress.flat_map(move |stream| match stream {
+            Ok(stream) => stream
+                .scan(HashMap::new(), move |layers, update| {
+                    let event = match update {
+                        Ok(update) => {
+                            if let (Some(digest), Some(total)) = (&update.digest, update.total) {
+                                layers.insert(digest.clone(), (update.completed.unwrap_or(0), total));
+                            }
+                            let (completed, total) = layers.values().fold((0, 0), |(completed, total), (c, t)| (completed + c, total + t));
+                            let sizes = (!layers.is_empty()).then_some((completed, total));
+                            JobEvent::Progress(clock.progress(&update.status, sizes.map(|s| s.0), sizes.map(|s| s.1)))
+                        }
+                        Err(e) => JobEvent::Failed(e),
+                    };
+                    futures_util::future::ready(Some(event))
+                })
+                .boxed(),
+            Err(e) => futures_util::stream::iter([JobEvent::Failed(e)]).boxed(),
+        }))
+    }
+
+    // Whether Ollama lacks the client's model, false for the other providers
+    async fn model_missing(&self) -> bool {
+        let Provider::Ollama(client) = &self.provider else {
+            return false;
+        };
+        let model = &client.model;
+        match client.list_local_models().await {
+            Ok(models) => !models.iter().any(|m| &m.name == model || m.name == format!("{}:latest", model)),
+            Err(_) => false, // Let the chat request report the problem
         }
     }
 
+    /// Upload model to a registry (provider-specific operation)
+    pub async fn push_model(&self, model_name: &str) -> Result<(), Box<dyn Error>> {
+        self.lifecycle.track(async {
+            match &self.provider {
+                Provider::Ollama(client) => client.push_model(model_name).await,
+                Provider::Anthropic(_) => Err("push_model is not supported for Anthropic provider".into()),
+                Provider::OpenAI(_) => Err("push_model is not supported for OpenAI provider".into()),
+                Provider::OpenRouter(_) => Err("push_model is not supported for OpenRouter provider".into()),
+                #[cfg(feature = "test-util")]
+                Provider::Mock(_) => Err("push_model is not supported for mock provider".into()),
+            }
+        }).await
+    }
+
+    /// Upload model with streaming progress updates (provider-specific operation)
+    pub async fn push_model_stream(
+        &self,
+        model_name: &str,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<PullProgress, String>> + Send>>, Box<dyn Error>> {
+        self.lifecycle.track_stream(async {
+            match &self.provider {
+                Provider::Ollama(client) => client.push_model_stream(model_name).await,
+                Provider::Anthropic(_) => Err("push_model_stream is not supported for Anthropic provider".into()),
+                Provider::OpenAI(_) => Err("push_model_stream is not supported for OpenAI provider".into()),
+                Provider::OpenRouter(_) => Err("push_model_stream is not supported for OpenRouter provider".into()),
+                #[cfg(feature = "test-util")]
+                Provider::Mock(_) => Err("push_model_stream is not supported for mock provider".into()),
+            }
+        }).await
+    }
+
+    /// Create model from Modelfile contents (provider-specific operation)
+    pub async fn create_model(&self, model_name: &str, modelfile: &str) -> Result<(), Box<dyn Error>> {
+        self.lifecycle.track(async {
+            match &self.provider {
+                Provider::Ollama(client) => client.create_model(model_name, modelfile).await,
+                Provider::Anthropic(_) => Err("create_model is not supported for Anthropic provider".into()),
+                Provider::OpenAI(_) => Err("create_model is not supported for OpenAI provider".into()),
+                Provider::OpenRouter(_) => Err("create_model is not supported for OpenRouter provider".into()),
+                #[cfg(feature = "test-util")]
+                Provider::Mock(_) => Err("create_model is not supported for mock provider".into()),
+            }
+        }).await
+    }
+
+    /// Create model from Modelfile contents with streaming progress updates (provider-specific operation)
+    pub async fn create_model_stream(
+        &self,
+        model_name: &str,
+        modelfile: &str,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<PullProgress, String>> + Send>>, Box<dyn Error>> {
+        self.lifecycle.track_stream(async {
+            match &self.provider {
+                Provider::Ollama(client) => client.create_model_stream(model_name, modelfile).await,
+                Provider::Anthropic(_) => Err("create_model_stream is not supported for Anthropic provider".into()),
+                Provider::OpenAI(_) => Err("create_model_stream is not supported for OpenAI provider".into()),
+                Provider::OpenRouter(_) => Err("create_model_stream is not supported for OpenRouter provider".into()),
+                #[cfg(feature = "test-util")]
+                Provider::Mock(_) => Err("create_model_stream is not supported for mock provider".into()),
+            }
+        }).await
+    }
+
+    /// Delete locally installed model (provider-specific operation)
+    pub async fn delete_model(&self, model_name: &str) -> Result<(), Box<dyn Error>> {
+        self.lifecycle.track(async {
+            match &self.provider {
+                Provider::Ollama(client) => client.delete_model(model_name).await,
+                Provider::Anthropic(_) => Err("delete_model is not supported for Anthropic provider".into()),
+                Provider::OpenAI(_) => Err("delete_model is not supported for OpenAI provider".into()),
+                Provider::OpenRouter(_) => Err("delete_model is not supported for OpenRouter provider".into()),
+                #[cfg(feature = "test-util")]
+                Provider::Mock(_) => Err("delete_model is not supported for mock provider".into()),
+            }
+        }).await
+    }
+
+    /// Copy model under a new name (provider-specific operation)
+    pub async fn copy_model(&self, source: &str, destination: &str) -> Result<(), Box<dyn Error>> {
+        self.lifecycle.track(async {
+            match &self.provider {
+                Provider::Ollama(client) => client.copy_model(source, destination).await,
+                Provider::Anthropic(_) => Err("copy_model is not supported for Anthropic provider".into()),
+                Provider::OpenAI(_) => Err("copy_model is not supported for OpenAI provider".into()),
+                Provider::OpenRouter(_) => Err("copy_model is not supported for OpenRouter provider".into()),
+                #[cfg(feature = "test-util")]
+                Provider::Mock(_) => Err("copy_model is not supported for mock provider".into()),
+            }
+        }).await
+    }
+
     /// Execute tool calls and return formatted messages for conversation continuation
     pub async fn handle_tool_calls(&self, tool_calls: Vec<ToolCall>) -> Vec<Message> {
-        match &self.provider {
+        let calls = self.sink.as_ref().map(|_| tool_calls.clone());
+        let results = match &self.provider {
             Provider::Ollama(client) => client.handle_tool_calls(tool_calls).await,
             Provider::Anthropic(client) => client.handle_tool_calls(tool_calls).await,
             Provider::OpenAI(client) => client.handle_tool_calls(tool_calls).await,
             Provider::OpenRouter(client) => client.handle_tool_calls(tool_calls).await,
+            #[cfg(feature = "test-util")]
+            Provider::Mock(client) => client.handle_tool_calls(tool_calls).await,
+        };
+        // Results answer the calls by ID, or in order where the provider has no call IDs
+        for (i, tool_call) in calls.iter().flatten().enumerate() {
+            let result = match &tool_call.id {
+                Some(id) => results.iter().find(|result| result.tool_call_id.as_ref() == Some(id)),
+                None => results.get(i),
+            };
+            if let Some(result) = result {
+                self.record_tool_call(tool_call, &result.content, result.is_error == Some(true));
+            }
+        }
+        results
+    }
+
+    fn record_tool_call(&self, tool_call: &ToolCall, result: &str, is_error: bool) {
+        if let Some(sink) = &self.sink {
+            sink.record(&SinkRecord::ToolCall {
+                id: tool_call.id.clone(),
+                timestamp: now_ms(),
+                name: tool_call.function.name.clone(),
+                arguments: tool_call.function.arguments.clone(),
+                result: result.to_string(),
+                is_error,
+            });
+        }
+    }
+
+    /// Run the tool calls a checkpoint is waiting on one at a time, recording each result as it
+    /// arrives. `after_each` sees the checkpoint after every result, to save it there so a crash
+    /// only repeats the unfinished calls
+    pub async fn run_pending_tools(&self, checkpoint: &mut Checkpoint, mut after_each: impl FnMut(&Checkpoint)) {
+        while let Some(tool_call) = checkpoint.pending_tool_calls.first().cloned() {
+            let result = match self.handle_tool_calls(vec![tool_call.clone()]).await.pop() {
+                Some(result) => result,
+                None => {
+                    let error = ToolOutput::error(format!("Error: unknown tool '{}'", tool_call.function.name));
+                    self.tool_response_message(&tool_call, error).await
+                }
+            };
+            let pending = checkpoint.pending_tool_calls.len();
+            checkpoint.record_tool_result(result);
+            // A result answering no pending call would otherwise run the call forever
+            if checkpoint.pending_tool_calls.len() == pending {
+                checkpoint.pending_tool_calls.remove(0);
+            }
+            after_each(checkpoint);
+        }
+    }
+
+    /// Execute tool calls on blocking threads, streaming `ToolEvent::Progress` updates reported by
+    /// the tools (see `report_progress`) and one `ToolEvent::Response` message per tool call
+    pub async fn handle_tool_calls_with_progress(
+        &self,
+        tool_calls: Vec<ToolCall>,
+    ) -> Pin<Box<dyn Stream<Item = ToolEvent> + Send + '_>> {
+        enum Execution {
+            Progress(ToolProgress),
+            Finished(ToolCall, ToolOutput),
+        }
+
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        let registry = self.tool_registry().clone();
+
+        tokio::spawn(async move {
+            for tool_call in tool_calls {
+                // Every call needs a result, providers reject a history with unanswered ones
+                let Some(tool) = registry.get(&tool_call.function.name) else {
+                    let error = ToolOutput::error(format!("Error: unknown tool '{}'", tool_call.function.name));
+                    if tx.send(Execution::Finished(tool_call, error)).is_err() {
+                        break;
+                    }
+                    continue;
+                };
+
+                let progress_tx = tx.clone();
+                let reporter = ProgressReporter::new(
+                    tool_call.id.clone(),
+                    &tool_call.function.name,
+                    Arc::new(move |progress| {
+                        let _ = progress_tx.send(Execution::Progress(progress));
+                    }),
+                );
+                let arguments = tool_call.function.arguments.clone();
+                let output = tokio::task::spawn_blocking(move || reporter.scope(|| tool.invoke_with_attachments(arguments)))
+                    .await
+                    .unwrap_or_else(|e| format!("Error: tool '{}' failed: {}", tool_call.function.name, e).into());
+
+                if tx.send(Execution::Finished(tool_call, output)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Box::pin(futures_util::stream::unfold(rx, move |mut rx| async move {
+            let event = match rx.recv().await? {
+                Execution::Progress(progress) => ToolEvent::Progress(progress),
+                Execution::Finished(tool_call, output) => {
+                    self.record_tool_call(&tool_call, &output.content, output.is_error);
+                    ToolEvent::Response(self.tool_response_message(&tool_call, output).await)
+                }
+            };
+            Some((event, rx))
+        }))
+    }
+
+    /// Format a tool result as a message for conversation continuation
+    pub async fn tool_response_message(&self, tool_call: &ToolCall, output: ToolOutput) -> Message {
+        match &self.provider {
+            Provider::Ollama(client) => client.tool_response_message(tool_call, output).await,
+            Provider::Anthropic(client) => client.tool_response_message(tool_call, output).await,
+            Provider::OpenAI(client) => client.tool_response_message(tool_call, output).await,
+            Provider::OpenRouter(client) => client.tool_response_message(tool_call, output).await,
+            #[cfg(feature = "test-util")]
+            Provider::Mock(client) => client.tool_response_message(tool_call, output).await,
         }
     }
 
@@ -524,16 +2109,32 @@ impl MonoAI {
             Provider::Anthropic(client) => client.process_fallback_response(content).await,
             Provider::OpenAI(client) => client.process_fallback_response(content).await,
             Provider::OpenRouter(client) => client.process_fallback_response(content).await,
+            #[cfg(feature = "test-util")]
+            Provider::Mock(client) => client.process_fallback_response(content).await,
         }
     }
 
     /// Get current model name for display purposes
+    /// "Ollama", "Anthropic", "OpenAI" or "OpenRouter"
+    pub fn provider_name(&self) -> &'static str {
+        match &self.provider {
+            Provider::Ollama(_) => "Ollama",
+            Provider::Anthropic(_) => "Anthropic",
+            Provider::OpenAI(_) => "OpenAI",
+            Provider::OpenRouter(_) => "OpenRouter",
+            #[cfg(feature = "test-util")]
+            Provider::Mock(_) => "Mock",
+        }
+    }
+
     pub fn model(&self) -> &str {
         match &self.provider {
             Provider::Ollama(client) => &client.model,
             Provider::Anthropic(client) => &client.model,
             Provider::OpenAI(client) => &client.model,
             Provider::OpenRouter(client) => &client.model,
+            #[cfg(feature = "test-util")]
+            Provider::Mock(client) => &client.model,
         }
     }
 
@@ -544,6 +2145,8 @@ impl MonoAI {
             Provider::Anthropic(_) => None,
             Provider::OpenAI(_) => None,
             Provider::OpenRouter(_) => None,
+            #[cfg(feature = "test-util")]
+            Provider::Mock(_) => None,
         }
     }
 
@@ -554,6 +2157,8 @@ impl MonoAI {
             Provider::Anthropic(_) => None,
             Provider::OpenAI(_) => None,
             Provider::OpenRouter(_) => None,
+            #[cfg(feature = "test-util")]
+            Provider::Mock(_) => None,
         }
     }
 
@@ -564,6 +2169,8 @@ impl MonoAI {
             Provider::Anthropic(client) => Some(client),
             Provider::OpenAI(_) => None,
             Provider::OpenRouter(_) => None,
+            #[cfg(feature = "test-util")]
+            Provider::Mock(_) => None,
         }
     }
 
@@ -574,17 +2181,198 @@ impl MonoAI {
             Provider::Anthropic(client) => Some(client),
             Provider::OpenAI(_) => None,
             Provider::OpenRouter(_) => None,
+            #[cfg(feature = "test-util")]
+            Provider::Mock(_) => None,
+        }
+    }
+
+    /// Access underlying OpenAI client for provider-specific operations such as files and fine-tuning
+    pub fn as_openai(&self) -> Option<&OpenAIClient> {
+        match &self.provider {
+            Provider::Ollama(_) => None,
+            Provider::Anthropic(_) => None,
+            Provider::OpenAI(client) => Some(client),
+            Provider::OpenRouter(_) => None,
+            #[cfg(feature = "test-util")]
+            Provider::Mock(_) => None,
+        }
+    }
+
+    /// Access underlying OpenAI client mutably for provider-specific operations
+    pub fn as_openai_mut(&mut self) -> Option<&mut OpenAIClient> {
+        match &mut self.provider {
+            Provider::Ollama(_) => None,
+            Provider::Anthropic(_) => None,
+            Provider::OpenAI(client) => Some(client),
+            Provider::OpenRouter(_) => None,
+            #[cfg(feature = "test-util")]
+            Provider::Mock(_) => None,
+        }
+    }
+
+    /// Access underlying OpenRouter client for provider-specific operations such as generation stats
+    pub fn as_openrouter(&self) -> Option<&OpenRouterClient> {
+        match &self.provider {
+            Provider::Ollama(_) => None,
+            Provider::Anthropic(_) => None,
+            Provider::OpenAI(_) => None,
+            Provider::OpenRouter(client) => Some(client),
+            #[cfg(feature = "test-util")]
+            Provider::Mock(_) => None,
+        }
+    }
+
+    /// Access underlying OpenRouter client mutably for provider-specific operations
+    pub fn as_openrouter_mut(&mut self) -> Option<&mut OpenRouterClient> {
+        match &mut self.provider {
+            Provider::Ollama(_) => None,
+            Provider::Anthropic(_) => None,
+            Provider::OpenAI(_) => None,
+            Provider::OpenRouter(client) => Some(client),
+            #[cfg(feature = "test-util")]
+            Provider::Mock(_) => None,
         }
     }
 
-    /// Encode image file to base64 string for use in Message.images
+    /// Access the underlying mock to extend its script or inspect the requests it received
+    #[cfg(feature = "test-util")]
+    pub fn as_mock(&self) -> Option<&MockProvider> {
+        match &self.provider {
+            Provider::Mock(client) => Some(client),
+            _ => None,
+        }
+    }
+
+    /// Encode image file to base64 string for use in Message.images, fails for formats
+    /// other than PNG, JPEG, GIF and WebP
     pub async fn encode_image_file(&self, path: &str) -> Result<String, Box<dyn std::error::Error>> {
-        let image_bytes = std::fs::read(path)?;
-        Ok(general_purpose::STANDARD.encode(image_bytes))
+        Ok(media::encode_image_file(path)?.data)
     }
 
     /// Encode image bytes to base64 string for use in Message.images
     pub async fn encode_image_data(&self, bytes: Vec<u8>) -> Result<String, Box<dyn std::error::Error>> {
-        Ok(general_purpose::STANDARD.encode(bytes))
+        Ok(media::encode_image_bytes(&bytes)?.data)
     }
-}
\ No newline at end of file
+}
+
+// A chat request `send_checked` got a reply for, with what finishing the reply needs
+struct SentChat<T> {
+    reply: T,
+    flags: Vec<String>, // Raised by the input check, for the output check of streams
+    record: Option<String>,
+    started: Instant,
+    redactions: Option<Redactions>,
+}
+
+// Put redacted values back into a reply as it streams, holding back text that may be the
+// start of a placeholder split across chunks
+fn restore_stream(
+    stream: Pin<Box<dyn Stream<Item = Result<ChatStreamItem, String>> + Send>>,
+    redactions: Option<Redactions>,
+) -> Pin<Box<dyn Stream<Item = Result<ChatStreamItem, String>> + Send>> {
+    let Some(redactions) = redactions else {
+        return stream;
+    };
+    let mut pending: HashMap<Option<usize>, String> = HashMap::new(); // Per completion
+    Box::pin(stream.map(move |item| {
+        let mut item = item?;
+        let buffer = pending.entry(item.choice_index).or_default();
+        buffer.push_str(&item.content);
+        let text = std::mem::take(buffer);
+        let ready = match item.done {
+            true => text.as_str(),
+            false => {
+                let (ready, tail) = Redactions::split_pending(&text);
+                *buffer = tail.to_string();
+                ready
+            }
+        };
+        item.content = redactions.restore(ready);
+        item.thinking = item.thinking.map(|thinking| redactions.restore(&thinking));
+        for tool_call in item.tool_calls.iter_mut().flatten() {
+            redactions.restore_json(&mut tool_call.function.arguments);
+        }
+        Ok(item)
+    }))
+}
+
+// Runs the output guardrail on the complete reply when the stream finishes. Each completion is
+// checked on its own, flags of the input are reported with all
+fn guard_stream(
+    stream: Pin<Box<dyn Stream<Item = Result<ChatStreamItem, String>> + Send>>,
+    guardrail: Arc<dyn Guardrail>,
+    flags: Vec<String>,
+) -> Pin<Box<dyn Stream<Item = Result<ChatStreamItem, String>> + Send>> {
+    Box::pin(futures_util::stream::unfold(
+        (stream, guardrail, HashMap::new(), flags),
+        |(mut stream, guardrail, mut contents, flags): (_, _, HashMap<Option<usize>, String>, _)| async move {
+            let mut item = match stream.next().await? {
+                Ok(item) => item,
+                Err(e) => return Some((Err(e), (stream, guardrail, contents, flags))),
+            };
+            contents.entry(item.choice_index).or_default().push_str(&item.content);
+
+            if item.done {
+                let content = contents.remove(&item.choice_index).unwrap_or_default();
+                let mut item_flags = flags.clone();
+                if !content.trim().is_empty() {
+                    match guardrail.check(GuardrailStage::Output, &content).await {
+                        GuardrailVerdict::Allow => {}
+                        GuardrailVerdict::Flag(reason) => item_flags.push(reason),
+                        GuardrailVerdict::Block(reason) => {
+                            let error = format!("Response blocked by guardrail: {}", reason);
+                            return Some((Err(error), (stream, guardrail, contents, flags)));
+                        }
+                    }
+                }
+                if !item_flags.is_empty() {
+                    item.guardrail_flags = Some(item_flags);
+                }
+            }
+
+            Some((Ok(item), (stream, guardrail, contents, flags)))
+        },
+    ))
+}
+
+impl Transcriber for MonoAI {
+    fn transcribe<'a>(&'a self, audio: &'a AudioInput, options: &'a TranscriptionOptions) -> TranscriptionFuture<'a> {
+        Box::pin(MonoAI::transcribe(self, audio, options))
+    }
+
+    fn translate_audio<'a>(&'a self, audio: &'a AudioInput, options: &'a TranscriptionOptions) -> TranscriptionFuture<'a> {
+        Box::pin(MonoAI::translate_audio(self, audio, options))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn classify_sends_redacted_text() {
+        let mut client = MonoAI::mock(MockScript::new().with_text(r#"{"reasoning": "Asks for a refund", "label": "billing", "confidence": 0.9}"#));
+        client.set_redactor(Redactor::new());
+
+        let classification = client.classify("Refund the order of jane.doe@example.com", &["billing", "shipping"]).await.unwrap();
+
+        assert_eq!(classification.label, "billing");
+        let requests = client.as_mock().unwrap().requests();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].iter().all(|message| !message.content.contains("jane.doe@example.com")), "{:?}", requests[0]);
+        assert!(requests[0].iter().any(|message| message.content.contains("[EMAIL_1]")), "{:?}", requests[0]);
+    }
+
+    #[tokio::test]
+    async fn requests_after_shutdown_are_refused() {
+        let mut client = MonoAI::mock(MockScript::new().with_text(r#"{"reasoning": "Asks for a refund", "label": "billing", "confidence": 0.9}"#));
+        client.set_clock(crate::core::MockClock::new());
+        client.shutdown(Duration::from_secs(1)).await;
+
+        let error = client.count_tokens(&[Message::user("Hello")]).await.unwrap_err();
+        assert_eq!(error.to_string(), "The client is shutting down and takes no new requests");
+        let error = client.classify("Refund my order", &["billing", "shipping"]).await.unwrap_err();
+        assert_eq!(error.to_string(), "The client is shutting down and takes no new requests");
+        assert!(client.as_mock().unwrap().requests().is_empty());
+    }
+}
@@ -0,0 +1,124 @@
+use std::error::Error;
+
+use futures_util::future::join_all;
+
+use crate::core::{Message, Rubric, Score};
+use super::client::MonoAI;
+
+/// Samples several completions of the same conversation from one or more clients and picks
+/// the best, scored by an optional judge. One client with several samples is best-of-N
+pub struct Ensemble<'a> {
+    clients: Vec<&'a MonoAI>,
+    samples: usize, // Per client
+    judge: Option<(&'a MonoAI, Rubric)>,
+}
+
+/// One completion of an `Ensemble`
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub provider: String,
+    pub model: String,
+    pub output: Result<String, String>,
+    pub score: Option<Result<Score, String>>, // None without a judge or when the completion failed
+}
+
+/// All candidates of an `Ensemble` run and the position of the best one
+#[derive(Debug, Clone)]
+pub struct EnsembleResult {
+    pub best: usize,
+    pub candidates: Vec<Candidate>,
+}
+
+impl EnsembleResult {
+    pub fn best(&self) -> &Candidate {
+        &self.candidates[self.best]
+    }
+
+    /// Text of the best candidate
+    pub fn text(&self) -> &str {
+        self.best().output.as_deref().unwrap_or_default()
+    }
+}
+
+impl<'a> Ensemble<'a> {
+    pub fn new(clients: &[&'a MonoAI]) -> Self {
+        Self {
+            clients: clients.to_vec(),
+            samples: 1,
+            judge: None,
+        }
+    }
+
+    /// Completions to request from each client
+    pub fn with_samples(mut self, samples: usize) -> Self {
+        self.samples = samples.max(1);
+        self
+    }
+
+    /// Score every candidate with `judge` on `rubric`, the highest score wins. Without a judge
+    /// the first successful completion wins
+    pub fn with_judge(mut self, judge: &'a MonoAI, rubric: Rubric) -> Self {
+        self.judge = Some((judge, rubric));
+        self
+    }
+
+    /// Request all completions concurrently, then score them. Fails only when no completion
+    /// succeeded
+    pub async fn run(&self, messages: &[Message]) -> Result<EnsembleResult, Box<dyn Error>> {
+        if self.clients.is_empty() {
+            return Err("An ensemble needs at least one client".into());
+        }
+
+        let requests = self.clients.iter().flat_map(|client| std::iter::repeat_n(*client, self.samples)).map(|client| async move {
+            let output = client.send_chat_request_no_stream(messages).await.map(|response| response.text).map_err(|e| e.to_string());
+            Candidate {
+                provider: client.provider_name().to_string(),
+                model: client.model().to_string(),
+                output,
+                score: None,
+            }
+        });
+        let mut candidates = join_all(requests).await;
+
+        if let Some((judge, rubric)) = &self.judge {
+            let request = messages.iter().rev().find(|message| message.role == "user").map(|message| message.content.as_str()).unwrap_or_default();
+            let scores = candidates.iter().map(|candidate| async move {
+                let output = candidate.output.as_ref().ok()?;
+                let text = format!("Request:\n{}\n\nResponse:\n{}", request, output);
+                Some(judge.score(&text, rubric).await.map_err(|e| e.to_string()))
+            });
+            let scores = join_all(scores).await;
+            for (candidate, score) in candidates.iter_mut().zip(scores) {
+                candidate.score = score;
+            }
+        }
+
+        let best = candidates
+            .iter()
+            .enumerate()
+            .filter(|(_, candidate)| candidate.output.is_ok())
+            .map(|(index, candidate)| (index, score_of(candidate)))
+            // Earlier candidates win ties
+            .fold(None, |best: Option<(usize, f64)>, (index, score)| match best {
+                Some((_, best_score)) if best_score >= score => best,
+                _ => Some((index, score)),
+            })
+            .map(|(index, _)| index);
+
+        match best {
+            Some(best) => Ok(EnsembleResult { best, candidates }),
+            None => {
+                let errors: Vec<String> = candidates.into_iter().filter_map(|candidate| candidate.output.err()).collect();
+                Err(format!("Every ensemble completion failed: {}", errors.join("; ")).into())
+            }
+        }
+    }
+}
+
+// Unscored candidates rank below every scored one
+fn score_of(candidate: &Candidate) -> f64 {
+    match &candidate.score {
+        Some(Ok(score)) => score.score,
+        _ => f64::NEG_INFINITY,
+    }
+}
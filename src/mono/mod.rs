@@ -1,5 +1,13 @@
 pub mod client;
+pub mod batch;
+pub mod ensemble;
+pub mod injection;
+pub mod shutdown;
+pub mod router;
 
 pub use client::*;
-
-
+pub use batch::*;
+pub use ensemble::*;
+pub use injection::*;
+pub use shutdown::*;
+pub use router::*;
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use futures_util::Stream;
+
+use crate::core::{ChatResponse, ChatStreamItem, GenerationOptions, LanguageDetection, Message, RequestTags, Translation};
+use super::client::MonoAI;
+
+/// Kind of work a request needs, mapped to a model by `ModelTier`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Tier {
+    Fast, // Cheap and quick: classification, titles, extraction
+    Smart, // The strongest model: reasoning, code, long answers
+    Vision, // Takes images
+    Named(String), // Any other tier the application defines
+}
+
+impl fmt::Display for Tier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Tier::Fast => write!(f, "fast"),
+            Tier::Smart => write!(f, "smart"),
+            Tier::Vision => write!(f, "vision"),
+            Tier::Named(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// Routes requests tagged with a `Tier` to the client configured for it, so application code
+/// names the kind of work and the model ids stay in one place. Clients can be of different
+/// providers. Requests carry the tier as the `tier` tag, for usage and cost per tier
+#[derive(Default)]
+pub struct ModelTier {
+    clients: HashMap<Tier, Arc<MonoAI>>,
+    fallbacks: HashMap<Tier, Tier>,
+}
+
+impl ModelTier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serve `tier` with `client`. One client can serve several tiers when passed as an `Arc`
+    pub fn with_client(mut self, tier: Tier, client: impl Into<Arc<MonoAI>>) -> Self {
+        self.clients.insert(tier, client.into());
+        self
+    }
+
+    /// Serve `tier` with the client of `to` while it has none, e.g. `Vision` with `Smart`
+    pub fn with_fallback(mut self, tier: Tier, to: Tier) -> Self {
+        self.fallbacks.insert(tier, to);
+        self
+    }
+
+    /// Client serving `tier`, following fallbacks
+    pub fn client(&self, tier: &Tier) -> Result<&MonoAI, Box<dyn Error>> {
+        let mut current = tier;
+        // A chain longer than the fallbacks goes in a circle
+        for _ in 0..=self.fallbacks.len() {
+            if let Some(client) = self.clients.get(current) {
+                return Ok(client);
+            }
+            match self.fallbacks.get(current) {
+                Some(next) => current = next,
+                None => break,
+            }
+        }
+        Err(format!("No model is configured for the '{}' tier", tier).into())
+    }
+
+    /// Tiers with a client of their own
+    pub fn tiers(&self) -> Vec<&Tier> {
+        self.clients.keys().collect()
+    }
+
+    pub async fn send_chat_request(
+        &self,
+        tier: &Tier,
+        messages: &[Message],
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<ChatStreamItem, String>> + Send>>, Box<dyn Error>> {
+        self.send_chat_request_with_options(tier, messages, &GenerationOptions::default()).await
+    }
+
+    pub async fn send_chat_request_with_options(
+        &self,
+        tier: &Tier,
+        messages: &[Message],
+        options: &GenerationOptions,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<ChatStreamItem, String>> + Send>>, Box<dyn Error>> {
+        self.client(tier)?.send_chat_request_with_options(messages, &tagged(tier, options)).await
+    }
+
+    pub async fn send_chat_request_no_stream(&self, tier: &Tier, messages: &[Message]) -> Result<ChatResponse, Box<dyn Error>> {
+        self.send_chat_request_no_stream_with_options(tier, messages, &GenerationOptions::default()).await
+    }
+
+    pub async fn send_chat_request_no_stream_with_options(
+        &self,
+        tier: &Tier,
+        messages: &[Message],
+        options: &GenerationOptions,
+    ) -> Result<ChatResponse, Box<dyn Error>> {
+        self.client(tier)?.send_chat_request_no_stream_with_options(messages, &tagged(tier, options)).await
+    }
+
+    pub async fn generate(&self, tier: &Tier, prompt: &str) -> Result<String, Box<dyn Error>> {
+        self.client(tier)?.generate_with_options(prompt, &tagged(tier, &GenerationOptions::default())).await
+    }
+
+    /// `MonoAI::detect_language` on the `Fast` tier
+    pub async fn detect_language(&self, text: &str) -> Result<LanguageDetection, Box<dyn Error>> {
+        let options = tagged(&Tier::Fast, &GenerationOptions::default());
+        self.client(&Tier::Fast)?.detect_language_with_options(text, &options).await
+    }
+
+    /// `MonoAI::translate` on the `Fast` tier
+    pub async fn translate(&self, text: &str, target_language: &str) -> Result<Translation, Box<dyn Error>> {
+        let options = tagged(&Tier::Fast, &GenerationOptions::default());
+        self.client(&Tier::Fast)?.translate_with_options(text, target_language, &options).await
+    }
+}
+
+// The options with the tier added to their tags
+fn tagged(tier: &Tier, options: &GenerationOptions) -> GenerationOptions {
+    let tags = options.tags.clone().unwrap_or_default().with(RequestTags::TIER, &tier.to_string());
+    GenerationOptions { tags: Some(tags), ..options.clone() }
+}
@@ -0,0 +1,140 @@
+use std::error::Error;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use futures_util::{Stream, StreamExt};
+use tokio::sync::watch;
+
+use crate::core::Clock;
+
+/// What `MonoAI::shutdown` found
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShutdownReport {
+    pub drained: usize, // Requests that finished within the timeout
+    pub aborted: usize, // Requests still running at the timeout, ended with an error
+}
+
+/// Requests in flight on a client and its copies, refusing new ones once shutting down
+#[derive(Debug)]
+pub(crate) struct Lifecycle {
+    closing: AtomicBool,
+    in_flight: watch::Sender<usize>,
+    abort: watch::Sender<bool>,
+    aborted: AtomicUsize,
+}
+
+impl Default for Lifecycle {
+    fn default() -> Self {
+        Self {
+            closing: AtomicBool::new(false),
+            in_flight: watch::Sender::new(0),
+            abort: watch::Sender::new(false),
+            aborted: AtomicUsize::new(0),
+        }
+    }
+}
+
+// Counts a request as in flight until dropped
+pub(crate) struct InFlight(Arc<Lifecycle>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.in_flight.send_modify(|count| *count -= 1);
+    }
+}
+
+type BoxStream<T> = Pin<Box<dyn Stream<Item = Result<T, String>> + Send>>;
+
+impl Lifecycle {
+    pub(crate) fn is_closing(&self) -> bool {
+        self.closing.load(Ordering::SeqCst)
+    }
+
+    // Counted before checking so `shutdown` can't miss a request starting as it closes
+    fn begin(self: &Arc<Self>) -> Result<InFlight, Box<dyn Error>> {
+        self.in_flight.send_modify(|count| *count += 1);
+        let guard = InFlight(self.clone());
+        if self.is_closing() {
+            return Err("The client is shutting down and takes no new requests".into());
+        }
+        Ok(guard)
+    }
+
+    // Resolves once `shutdown` gives up waiting
+    async fn aborted(&self) {
+        let mut abort = self.abort.subscribe();
+        let _ = abort.wait_for(|abort| *abort).await;
+    }
+
+    /// Run a request to completion, or fail it when shutdown aborts it
+    pub(crate) async fn track<T>(self: &Arc<Self>, request: impl Future<Output = Result<T, Box<dyn Error>>>) -> Result<T, Box<dyn Error>> {
+        let _guard = self.begin()?;
+        tokio::select! {
+            result = request => result,
+            _ = self.aborted() => {
+                self.aborted.fetch_add(1, Ordering::SeqCst);
+                Err("Request aborted by client shutdown".into())
+            }
+        }
+    }
+
+    /// Like `track` for a streaming request, which stays in flight until the stream ends or is
+    /// dropped. An aborted stream ends with an error item
+    pub(crate) async fn track_stream<T: Send + 'static>(
+        self: &Arc<Self>,
+        request: impl Future<Output = Result<BoxStream<T>, Box<dyn Error>>>,
+    ) -> Result<BoxStream<T>, Box<dyn Error>> {
+        let guard = self.begin()?;
+        let stream = tokio::select! {
+            result = request => result?,
+            _ = self.aborted() => {
+                self.aborted.fetch_add(1, Ordering::SeqCst);
+                return Err("Request aborted by client shutdown".into());
+            }
+        };
+
+        Ok(Box::pin(futures_util::stream::unfold(Some((stream, guard)), |state| async move {
+            let (mut stream, guard) = state?;
+            let lifecycle = guard.0.clone();
+            tokio::select! {
+                item = stream.next() => item.map(|item| (item, Some((stream, guard)))),
+                _ = lifecycle.aborted() => {
+                    lifecycle.aborted.fetch_add(1, Ordering::SeqCst);
+                    Some((Err("Request aborted by client shutdown".to_string()), None))
+                }
+            }
+        })))
+    }
+
+    /// Refuse new requests, wait up to `timeout` on `clock` for the running ones and abort the rest
+    pub(crate) async fn shutdown(&self, timeout: Duration, clock: &dyn Clock) -> ShutdownReport {
+        self.closing.store(true, Ordering::SeqCst);
+        let running = *self.in_flight.borrow();
+        let aborted_before = self.aborted.load(Ordering::SeqCst);
+
+        let mut in_flight = self.in_flight.subscribe();
+        let drained = tokio::select! {
+            _ = in_flight.wait_for(|count| *count == 0) => true,
+            _ = clock.sleep(timeout) => false,
+        };
+        if !drained {
+            self.abort.send_replace(true);
+            // Aborted requests end as soon as their tasks are polled, which may never happen for
+            // streams nobody reads, so only a short grace period
+            tokio::select! {
+                _ = in_flight.wait_for(|count| *count == 0) => {}
+                _ = clock.sleep(Duration::from_millis(100)) => {}
+            }
+        }
+
+        let aborted = self.aborted.load(Ordering::SeqCst) - aborted_before;
+        let still_running = *self.in_flight.borrow();
+        ShutdownReport {
+            drained: running.saturating_sub(aborted + still_running),
+            aborted: aborted + still_running,
+        }
+    }
+}
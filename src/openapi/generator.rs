@@ -0,0 +1,440 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, LazyLock};
+
+use serde_json::{json, Map, Value};
+
+use crate::core::{AIRequestError, Tool, ToolRegistry};
+
+const HTTP_METHODS: [&str; 7] = ["get", "post", "put", "patch", "delete", "head", "options"];
+
+// Runs requests of tools called outside a multi-threaded tokio runtime
+static FALLBACK_RUNTIME: LazyLock<tokio::runtime::Runtime> = LazyLock::new(|| {
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .enable_all()
+        .build()
+        .expect("tokio runtime for OpenAPI tools builds")
+});
+
+#[derive(Clone)]
+struct ParamLocation {
+    name: String, // Argument name, the property holding the request body for `body`
+    location: String, // path, query, header or body
+}
+
+#[derive(Clone)]
+struct Operation {
+    method: String,
+    path: String,
+    params: Vec<ParamLocation>,
+}
+
+/// Generates `Tool` definitions from an OpenAPI 3 / Swagger 2 JSON document.
+/// Each operation becomes a tool whose function performs the HTTP call.
+pub struct OpenApiToolGenerator {
+    spec: Value,
+    base_url: Option<String>,
+    headers: Vec<(String, String)>,
+    variables: HashMap<String, String>,
+    operation_filter: Option<Vec<String>>,
+}
+
+impl OpenApiToolGenerator {
+    /// Parse an OpenAPI/Swagger document from JSON text
+    pub fn from_json(spec: &str) -> Result<Self, AIRequestError> {
+        Ok(Self::from_value(serde_json::from_str(spec)?))
+    }
+
+    pub fn from_value(spec: Value) -> Self {
+        Self {
+            spec,
+            base_url: None,
+            headers: Vec::new(),
+            variables: HashMap::new(),
+            operation_filter: None,
+        }
+    }
+
+    /// Override the server URL taken from the document (`servers` or `host`/`basePath`)
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = Some(base_url.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// Send a header with every call. `{NAME}` placeholders are filled from `with_variable`
+    /// values first, then from environment variables at call time, e.g. `"Bearer {API_TOKEN}"`
+    pub fn with_header(mut self, name: &str, template: &str) -> Self {
+        self.headers.push((name.to_string(), template.to_string()));
+        self
+    }
+
+    /// Provide a value for a `{NAME}` placeholder used in header templates
+    pub fn with_variable(mut self, name: &str, value: &str) -> Self {
+        self.variables.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Only generate tools for the given operation ids / tool names
+    pub fn only_operations(mut self, operations: &[&str]) -> Self {
+        self.operation_filter = Some(operations.iter().map(|o| o.to_string()).collect());
+        self
+    }
+
+    /// Build one tool per operation in the document
+    pub fn generate(&self) -> Result<Vec<Tool>, AIRequestError> {
+        let base_url = self
+            .base_url
+            .clone()
+            .or_else(|| self.spec_base_url())
+            .ok_or_else(|| AIRequestError::Other("OpenAPI document has no server URL, use with_base_url".to_string()))?;
+
+        let paths = self
+            .spec
+            .get("paths")
+            .and_then(|p| p.as_object())
+            .ok_or_else(|| AIRequestError::Other("OpenAPI document has no paths".to_string()))?;
+
+        let headers = Arc::new(self.headers.clone());
+        let variables = Arc::new(self.variables.clone());
+        let mut tools = Vec::new();
+
+        for (path, item) in paths {
+            let shared_params = item.get("parameters").and_then(|p| p.as_array()).cloned().unwrap_or_default();
+
+            for method in HTTP_METHODS {
+                let operation = match item.get(method) {
+                    Some(op) => op,
+                    None => continue,
+                };
+
+                let name = sanitize_name(
+                    operation
+                        .get("operationId")
+                        .and_then(|id| id.as_str())
+                        .map(|id| id.to_string())
+                        .unwrap_or_else(|| format!("{}_{}", method, path))
+                        .as_str(),
+                );
+
+                if let Some(filter) = &self.operation_filter && !filter.contains(&name) {
+                    continue;
+                }
+
+                let description = operation
+                    .get("summary")
+                    .or_else(|| operation.get("description"))
+                    .and_then(|d| d.as_str())
+                    .map(|d| d.to_string())
+                    .unwrap_or_else(|| format!("{} {}", method.to_uppercase(), path));
+
+                let mut op_params = shared_params.clone();
+                if let Some(own) = operation.get("parameters").and_then(|p| p.as_array()) {
+                    op_params.extend(own.iter().cloned());
+                }
+
+                let (parameters, locations) = self.build_parameters(&op_params, operation);
+                let op = Operation {
+                    method: method.to_uppercase(),
+                    path: path.clone(),
+                    params: locations,
+                };
+
+                let base_url = base_url.clone();
+                let headers = headers.clone();
+                let variables = variables.clone();
+                tools.push(Tool {
+                    name,
+                    description,
+                    parameters,
+                    function: Box::new(move |args| execute_operation(&base_url, &op, &headers, &variables, &args)),
+                });
+            }
+        }
+
+        Ok(tools)
+    }
+
+    /// Generate tools and register them on a registry under a namespace
+    pub fn register_into(&self, registry: &ToolRegistry, namespace: &str) -> Result<usize, AIRequestError> {
+        let tools = self.generate()?;
+        let count = tools.len();
+        for tool in tools {
+            registry.register_in(namespace, tool)?;
+        }
+        Ok(count)
+    }
+
+    fn spec_base_url(&self) -> Option<String> {
+        if let Some(url) = self
+            .spec
+            .get("servers")
+            .and_then(|s| s.as_array())
+            .and_then(|s| s.first())
+            .and_then(|s| s.get("url"))
+            .and_then(|u| u.as_str())
+        {
+            return Some(url.trim_end_matches('/').to_string());
+        }
+
+        // Swagger 2: scheme://host/basePath
+        let host = self.spec.get("host").and_then(|h| h.as_str())?;
+        let scheme = self
+            .spec
+            .get("schemes")
+            .and_then(|s| s.as_array())
+            .and_then(|s| s.first())
+            .and_then(|s| s.as_str())
+            .unwrap_or("https");
+        let base_path = self.spec.get("basePath").and_then(|b| b.as_str()).unwrap_or("");
+        Some(format!("{}://{}{}", scheme, host, base_path).trim_end_matches('/').to_string())
+    }
+
+    fn build_parameters(&self, params: &[Value], operation: &Value) -> (Value, Vec<ParamLocation>) {
+        let mut properties = Map::new();
+        let mut required = Vec::new();
+        let mut locations = Vec::new();
+
+        let params: Vec<Value> = params.iter().map(|param| self.resolve(param)).collect();
+        // The request body goes in `body`, unless a parameter already has that name
+        let taken: Vec<&str> = params
+            .iter()
+            .filter(|param| param.get("in").and_then(|l| l.as_str()) != Some("body"))
+            .filter_map(|param| param.get("name").and_then(|n| n.as_str()))
+            .collect();
+        let mut body_property = "body".to_string();
+        while taken.contains(&body_property.as_str()) {
+            body_property.insert_str(0, "request_");
+        }
+
+        for param in &params {
+            let name = match param.get("name").and_then(|n| n.as_str()) {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+            let location = param.get("in").and_then(|l| l.as_str()).unwrap_or("query").to_string();
+            if location == "cookie" || location == "formData" {
+                continue;
+            }
+
+            let mut schema = if location == "body" {
+                param.get("schema").cloned().unwrap_or_else(|| json!({}))
+            } else if let Some(schema) = param.get("schema") {
+                schema.clone()
+            } else {
+                // Swagger 2 keeps the type on the parameter itself
+                let mut schema = Map::new();
+                for key in ["type", "items", "enum", "format", "minimum", "maximum"] {
+                    if let Some(value) = param.get(key) {
+                        schema.insert(key.to_string(), value.clone());
+                    }
+                }
+                Value::Object(schema)
+            };
+            if let (Some(description), Some(obj)) = (param.get("description"), schema.as_object_mut()) {
+                obj.entry("description").or_insert_with(|| description.clone());
+            }
+
+            let property = if location == "body" { body_property.clone() } else { name };
+            if location == "path" || param.get("required").and_then(|r| r.as_bool()).unwrap_or(false) {
+                required.push(property.clone());
+            }
+            properties.insert(property.clone(), schema);
+            locations.push(ParamLocation { name: property, location });
+        }
+
+        // OpenAPI 3 request body
+        if let Some(body) = operation.get("requestBody") {
+            let body = self.resolve(body);
+            if let Some(schema) = body
+                .get("content")
+                .and_then(|c| c.get("application/json"))
+                .and_then(|c| c.get("schema"))
+            {
+                properties.insert(body_property.clone(), schema.clone());
+                if body.get("required").and_then(|r| r.as_bool()).unwrap_or(false) {
+                    required.push(body_property.clone());
+                }
+                locations.push(ParamLocation { name: body_property, location: "body".to_string() });
+            }
+        }
+
+        (
+            json!({
+                "type": "object",
+                "properties": properties,
+                "required": required,
+            }),
+            locations,
+        )
+    }
+
+    // Inline local `$ref`s (#/components/..., #/definitions/...) so the schema is self-contained
+    fn resolve(&self, value: &Value) -> Value {
+        self.resolve_refs(value, &mut Vec::new())
+    }
+
+    // `visiting` holds the refs being inlined above this value, a ref back to one of them is
+    // recursive and left as `{}`
+    fn resolve_refs(&self, value: &Value, visiting: &mut Vec<String>) -> Value {
+        match value {
+            Value::Object(obj) => {
+                if let Some(reference) = obj.get("$ref").and_then(|r| r.as_str()) {
+                    if visiting.iter().any(|seen| seen == reference) {
+                        return json!({});
+                    }
+                    let target = reference
+                        .strip_prefix("#/")
+                        .map(|pointer| format!("/{}", pointer))
+                        .and_then(|pointer| self.spec.pointer(&pointer).cloned())
+                        .unwrap_or_else(|| json!({}));
+                    visiting.push(reference.to_string());
+                    let resolved = self.resolve_refs(&target, visiting);
+                    visiting.pop();
+                    return resolved;
+                }
+                let resolved: Map<String, Value> = obj
+                    .iter()
+                    .map(|(k, v)| (k.clone(), self.resolve_refs(v, visiting)))
+                    .collect();
+                Value::Object(resolved)
+            }
+            Value::Array(items) => Value::Array(items.iter().map(|v| self.resolve_refs(v, visiting)).collect()),
+            other => other.clone(),
+        }
+    }
+}
+
+fn sanitize_name(raw: &str) -> String {
+    let mut name: String = raw
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+        .collect();
+    while name.contains("__") {
+        name = name.replace("__", "_");
+    }
+    let name = name.trim_matches('_').to_string();
+    name.chars().take(64).collect()
+}
+
+fn fill_template(template: &str, variables: &HashMap<String, String>) -> String {
+    let mut result = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        match rest[start..].find('}') {
+            Some(end) => {
+                let key = &rest[start + 1..start + end];
+                let value = variables
+                    .get(key)
+                    .cloned()
+                    .or_else(|| std::env::var(key).ok())
+                    .unwrap_or_default();
+                result.push_str(&value);
+                rest = &rest[start + end + 1..];
+            }
+            None => {
+                result.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+fn value_to_param(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn encode_path_segment(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn execute_operation(
+    base_url: &str,
+    op: &Operation,
+    headers: &[(String, String)],
+    variables: &HashMap<String, String>,
+    args: &Value,
+) -> String {
+    let mut path = op.path.clone();
+    let mut query = Vec::new();
+    let mut extra_headers = Vec::new();
+    let mut body = None;
+
+    for param in &op.params {
+        let value = match args.get(&param.name) {
+            Some(v) if !v.is_null() => v,
+            _ => continue,
+        };
+        match param.location.as_str() {
+            "path" => {
+                let encoded = encode_path_segment(&value_to_param(value));
+                path = path.replace(&format!("{{{}}}", param.name), &encoded);
+            }
+            "query" => query.push((param.name.clone(), value_to_param(value))),
+            "header" => extra_headers.push((param.name.clone(), value_to_param(value))),
+            "body" => body = Some(value.clone()),
+            _ => {}
+        }
+    }
+
+    let url = format!("{}{}", base_url, path);
+    let method = op.method.clone();
+    let mut all_headers: Vec<(String, String)> = headers
+        .iter()
+        .map(|(name, template)| (name.clone(), fill_template(template, variables)))
+        .collect();
+    all_headers.extend(extra_headers);
+
+    let request = async move {
+        let client = reqwest::Client::new();
+        let method = reqwest::Method::from_bytes(method.as_bytes()).map_err(|e| e.to_string())?;
+        let mut request = client.request(method, &url).query(&query);
+        for (name, value) in all_headers {
+            request = request.header(name, value);
+        }
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request.send().await.map_err(|e| e.to_string())?;
+        let status = response.status();
+        let text = response.text().await.map_err(|e| e.to_string())?;
+        if status.is_success() {
+            Ok(text)
+        } else {
+            Ok(format!("HTTP {}: {}", status.as_u16(), text))
+        }
+    };
+
+    match block_on(request) {
+        Ok(text) => text,
+        Err(e) => format!("Error: request failed: {}", e),
+    }
+}
+
+// Tools are synchronous. On a multi-threaded runtime the request runs on the caller's runtime
+// while its worker blocks, otherwise on a shared background runtime, since blocking a
+// current-thread runtime would stop the request it waits for
+fn block_on(request: impl Future<Output = Result<String, String>> + Send + 'static) -> Result<String, String> {
+    if let Ok(handle) = tokio::runtime::Handle::try_current()
+        && handle.runtime_flavor() == tokio::runtime::RuntimeFlavor::MultiThread
+    {
+        return tokio::task::block_in_place(|| handle.block_on(request));
+    }
+    let (sender, receiver) = std::sync::mpsc::channel();
+    FALLBACK_RUNTIME.spawn(async move {
+        let _ = sender.send(request.await);
+    });
+    receiver.recv().unwrap_or_else(|_| Err("request task panicked".to_string()))
+}
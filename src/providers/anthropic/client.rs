@@ -2,22 +2,31 @@ use futures_util::{Stream, StreamExt};
 use reqwest::Client;
 use std::error::Error;
 use std::pin::Pin;
-use std::collections::HashMap;
-use bytes::Bytes;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Instant;
+use uuid::Uuid;
 
-use crate::core::{Message, ToolCall, ChatStreamItem, Tool, TokenUsage};
+use crate::core::{Message, ToolCall, ChatStreamItem, Tool, ToolRegistry, ToolOutput, DocumentSource, GenerationOptions, lookup_pricing, ModelCapabilities, ModelPricing, image_media_type, validate_images, normalize_history, HistoryRules, reject_audio, fit_images, ImageLimits, resolve_image_urls, parse_stream, DebugLog, DebugEvent, SendLogged, stderr_debug_callback, ChatResponse, Reproducibility, chat_api_error, api_error, Quota, request_body};
 use super::types::*;
+use super::ServerTool;
+use super::stream::AnthropicStreamParser;
 
 // Manual Anthropic model pricing function (based on official Anthropic pricing)
-fn get_anthropic_model_pricing(model: &str) -> (f64, f64) {
+pub(super) fn get_anthropic_model_pricing(model: &str) -> (f64, f64) {
     match model {
         // Claude 4 series
         "claude-opus-4-1-20250805" => (15.00e-6, 75.00e-6), // Claude Opus 4.1: $15 / $75
         "claude-opus-4-20250514" => (15.00e-6, 75.00e-6), // Claude Opus 4: $15 / $75
         "claude-sonnet-4-20250514" => (3.00e-6, 15.00e-6), // Claude Sonnet 4: $3 / $15
         
+        "claude-opus-4-5-20251101" | "claude-opus-4-5" => (5.00e-6, 25.00e-6), // Claude Opus 4.5: $5 / $25
+        "claude-opus-4-1" | "claude-opus-4-0" => (15.00e-6, 75.00e-6), // Claude Opus 4.1 / 4 aliases
+        "claude-sonnet-4-5-20250929" | "claude-sonnet-4-5" | "claude-sonnet-4-0" => (3.00e-6, 15.00e-6), // Claude Sonnet 4.5 / 4: $3 / $15
+        "claude-haiku-4-5-20251001" | "claude-haiku-4-5" => (1.00e-6, 5.00e-6), // Claude Haiku 4.5: $1 / $5
+
         // Claude 3.7 series
-        "claude-3-7-sonnet-20250219" => (3.00e-6, 15.00e-6), // Claude Sonnet 3.7: $3 / $15
+        "claude-3-7-sonnet-20250219" | "claude-3-7-sonnet-latest" => (3.00e-6, 15.00e-6), // Claude Sonnet 3.7: $3 / $15
         
         // Claude 3.5 series
         "claude-3-5-sonnet-20241022" | "claude-3-5-sonnet-latest" => (3.00e-6, 15.00e-6), // Claude Sonnet 3.5 (New): $3 / $15
@@ -33,86 +42,336 @@ fn get_anthropic_model_pricing(model: &str) -> (f64, f64) {
     }
 }
 
+// Prices with overrides and fetched prices taking precedence over the built-in table. Cache
+// reads cost a tenth of the input price
+pub(super) fn anthropic_pricing(model: &str) -> Option<ModelPricing> {
+    lookup_pricing(model).or_else(|| match get_anthropic_model_pricing(model) {
+        (0.0, 0.0) => None,
+        (input, output) => Some(ModelPricing { input, output, cached_input: Some(input * 0.1) }),
+    })
+}
+
 pub struct AnthropicClient {
     client: Client,
     api_key: String,
     pub model: String,
-    tools: Vec<Tool>,
+    base_url: String,
+    tools: ToolRegistry,
+    thinking_budget: Option<u32>,
+    server_tools: Vec<ServerTool>,
+    debug: DebugLog,
 }
 
 impl AnthropicClient {
+    /// `GenerationOptions` fields sent to the API
+    pub const SUPPORTED_OPTIONS: &'static [&'static str] = &["temperature", "max_tokens", "user"];
+
+    /// The API takes no seed
+    pub const REPRODUCIBILITY: Reproducibility = Reproducibility::Unseeded;
+
+    /// Context window, output limit and parameters of a Claude model from a built-in table.
+    /// Every Claude 3 and later model accepts images and tools
+    pub fn known_capabilities(model: &str) -> ModelCapabilities {
+        let family = |prefix: &str| model.starts_with(prefix);
+        let (max_output_tokens, thinking) = if family("claude-opus-4") && !family("claude-opus-4-5") {
+            (32_000, true)
+        } else if family("claude-sonnet-4") || family("claude-opus-4-5") || family("claude-haiku-4") || family("claude-3-7") {
+            (64_000, true)
+        } else if family("claude-3-5") {
+            (8_192, false)
+        } else {
+            (4_096, false)
+        };
+
+        let mut supported_parameters = vec!["max_tokens".to_string(), "temperature".to_string(), "tools".to_string(), "stream".to_string()];
+        if thinking {
+            supported_parameters.push("reasoning".to_string());
+        }
+
+        let pricing = anthropic_pricing(model);
+
+        ModelCapabilities {
+            context_length: Some(200_000),
+            max_output_tokens: Some(max_output_tokens),
+            input_modalities: vec!["text".to_string(), "image".to_string(), "file".to_string()],
+            output_modalities: vec!["text".to_string()],
+            supported_parameters,
+            pricing,
+        }
+    }
+
     pub fn new(api_key: String, model: String) -> Self {
         Self {
             client: Client::new(),
             api_key,
             model,
-            tools: Vec::new(),
+            base_url: "https://api.anthropic.com/v1".to_string(),
+            tools: ToolRegistry::new(),
+            thinking_budget: None,
+            server_tools: Vec::new(),
+            debug: DebugLog::new("Anthropic"),
+        }
+    }
+
+    /// A client with the same credentials and endpoint for another model. Tools and model
+    /// settings are not carried over
+    pub fn for_model(&self, model: &str) -> Self {
+        Self {
+            client: self.client.clone(),
+            api_key: self.api_key.clone(),
+            model: model.to_string(),
+            base_url: self.base_url.clone(),
+            tools: ToolRegistry::new(),
+            thinking_budget: None,
+            server_tools: Vec::new(),
+            debug: self.debug.clone(),
         }
     }
 
+    /// API root requests go to, `https://api.anthropic.com/v1` by default. Point it at a proxy
+    /// or a `ReplayServer`
+    pub fn set_base_url(&mut self, base_url: &str) {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Rate limits from the `anthropic-ratelimit-*` headers of the latest response, empty before the first
+    pub fn quota(&self) -> Quota {
+        self.debug.last_quota()
+    }
+
+    /// Enable an Anthropic-defined tool such as web search or computer use
+    pub fn add_server_tool(&mut self, tool: ServerTool) {
+        self.server_tools.retain(|existing| existing.name() != tool.name());
+        self.server_tools.push(tool);
+    }
+
+    pub fn server_tools(&self) -> &[ServerTool] {
+        &self.server_tools
+    }
+
+    /// Enable extended thinking with a token budget (at least 1024), None disables it.
+    /// The reasoning streams into `ChatStreamItem.thinking`
+    pub fn set_thinking_budget(&mut self, budget_tokens: Option<u32>) {
+        self.thinking_budget = budget_tokens;
+    }
+
+    pub fn thinking_budget(&self) -> Option<u32> {
+        self.thinking_budget
+    }
+
     pub async fn add_tool(&mut self, tool: Tool) -> Result<(), Box<dyn Error>> {
-        self.tools.push(tool);
+        self.tools.register(tool)?;
         Ok(())
     }
 
+    pub fn tool_registry(&self) -> &ToolRegistry {
+        &self.tools
+    }
+
+    pub fn set_tool_registry(&mut self, registry: ToolRegistry) {
+        self.tools = registry;
+    }
+
     pub async fn is_fallback_mode(&self) -> bool {
         false // Anthropic has native tool support
     }
 
-    pub fn set_debug_mode(&mut self, _debug: bool) {
-        // Anthropic debug mode not yet implemented or planned
+    /// Log requests with API keys redacted, raw stream chunks and fallback tool call output to stderr
+    pub fn set_debug_mode(&mut self, debug: bool) {
+        self.debug.set_callback(debug.then(stderr_debug_callback));
     }
 
     pub fn debug_mode(&self) -> bool {
-        false
+        self.debug.is_enabled()
+    }
+
+    /// Send debug events to a callback instead of stderr, e.g. to forward them to `tracing`
+    pub fn set_debug_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(&DebugEvent) + Send + Sync + 'static,
+    {
+        self.debug.set_callback(Some(Arc::new(callback)));
     }
 
     pub async fn supports_tool_calls(&self) -> Result<bool, Box<dyn Error>> {
         Ok(true) // Anthropic Claude models support native tool calling
     }
 
-    pub async fn get_available_models(&self) -> Result<Vec<AnthropicModel>, Box<dyn Error>> {
+    /// Check the API is reachable, the key is valid and the model exists by fetching the model
+    pub async fn health_check(&self) -> Result<(), Box<dyn Error>> {
         let response = self
             .client
-            .get("https://api.anthropic.com/v1/models")
+            .get(format!("{}/models/{}", self.base_url, self.model))
             .header("x-api-key", &self.api_key)
             .header("anthropic-version", "2023-06-01")
-            .send()
+            .send_logged(&self.debug)
             .await?;
 
         if !response.status().is_success() {
             let error_text = response.text().await?;
-            return Err(format!("Anthropic API error: {}", error_text).into());
+            return Err(format!("Anthropic health check failed: {}", error_text).into());
+        }
+        Ok(())
+    }
+
+    /// All models available to the API key, following pagination
+    pub async fn get_available_models(&self) -> Result<Vec<AnthropicModel>, Box<dyn Error>> {
+        let mut models = Vec::new();
+        let mut after_id: Option<String> = None;
+
+        loop {
+            let mut query = vec![("limit", "1000".to_string())];
+            if let Some(after_id) = &after_id {
+                query.push(("after_id", after_id.clone()));
+            }
+
+            let response = self
+                .client
+                .get(format!("{}/models", self.base_url))
+                .header("x-api-key", &self.api_key)
+                .header("anthropic-version", "2023-06-01")
+                .query(&query)
+                .send_logged(&self.debug)
+                .await?;
+
+            if !response.status().is_success() {
+                let status = response.status().as_u16();
+                let error_text = response.text().await?;
+                return Err(api_error("Anthropic", status, &error_text).into());
+            }
+
+            let models_response: AnthropicModelsResponse = response.json().await?;
+            models.extend(models_response.data);
+
+            match models_response.last_id {
+                Some(last_id) if models_response.has_more => after_id = Some(last_id),
+                _ => break,
+            }
         }
 
-        let models_response: AnthropicModelsResponse = response.json().await?;
-        Ok(models_response.data)
+        Ok(models)
     }
 
-    fn convert_to_anthropic_message(&self, message: &Message) -> AnthropicMessage {
-        // Check if this is a tool result message
-        if message.role == "user" && message.content.starts_with("TOOL_RESULT:") {
-            // Parse the encoded tool result: "TOOL_RESULT:tool_id:result_content"
-            let parts: Vec<&str> = message.content.splitn(3, ':').collect();
-            if parts.len() == 3 {
-                let tool_use_id = parts[1];
-                let result_content = parts[2];
+    fn convert_messages(&self, messages: &[Message]) -> Vec<AnthropicMessage> {
+        // Ids generated for tool calls that came without one, as (tool name, id), so the
+        // following tool results can reference them
+        let mut generated_ids: VecDeque<(String, String)> = VecDeque::new();
+        let mut anthropic_messages: Vec<AnthropicMessage> = Vec::new();
+
+        for message in messages {
+            // Sent separately, see `system_prompt`
+            if message.role == "system" {
+                continue;
+            }
+            let mut converted = self.convert_to_anthropic_message(message, &mut generated_ids);
+            let cache_control = message.cache.map(CacheControl::from);
+
+            // All results for one assistant turn go back in a single user turn
+            if message.role == "tool" && let Some(previous) = anthropic_messages.last_mut() {
+                let only_results = previous.content.iter().all(|block| matches!(block, ContentBlock::ToolResult { .. }));
+                if previous.role == "user" && only_results {
+                    previous.content.append(&mut converted.content);
+                    // The breakpoint moves to the end of the merged turn
+                    if cache_control.is_some() {
+                        previous.cache_control = cache_control;
+                    }
+                    continue;
+                }
+            }
+            converted.cache_control = cache_control;
+            anthropic_messages.push(converted);
+        }
+        anthropic_messages
+    }
 
-                let content_blocks = vec![ContentBlock::ToolResult {
-                    tool_use_id: tool_use_id.to_string(),
-                    content: result_content.to_string(),
-                }];
+    // Anthropic takes the system prompt as a request field rather than a message. Cached
+    // system messages need the block form to carry their breakpoint
+    fn system_prompt(messages: &[Message]) -> Option<SystemPrompt> {
+        let system: Vec<&Message> = messages.iter().filter(|message| message.role == "system").collect();
+        if system.is_empty() {
+            return None;
+        }
+        if system.iter().all(|message| message.cache.is_none()) {
+            let text: Vec<&str> = system.iter().map(|message| message.content.as_str()).collect();
+            return Some(SystemPrompt::Text(text.join("\n\n")));
+        }
+        Some(SystemPrompt::Blocks(
+            system
+                .iter()
+                .map(|message| SystemBlock {
+                    block_type: "text".to_string(),
+                    text: message.content.clone(),
+                    cache_control: message.cache.map(CacheControl::from),
+                })
+                .collect(),
+        ))
+    }
 
-                return AnthropicMessage {
-                    role: message.role.clone(),
-                    content: content_blocks,
-                };
+    fn convert_to_anthropic_message(&self, message: &Message, generated_ids: &mut VecDeque<(String, String)>) -> AnthropicMessage {
+        // Tool results are sent back as tool_result blocks in a user turn
+        if message.role == "tool" {
+            // Empty text blocks are rejected, a result with no output is sent without content
+            let mut result_blocks = Vec::new();
+            if !message.content.is_empty() {
+                result_blocks.push(ContentBlock::Text {
+                    text: message.content.clone(),
+                });
+            }
+            for image_data in message.images.iter().flatten() {
+                result_blocks.push(ContentBlock::Image {
+                    source: ImageSource {
+                        source_type: "base64".to_string(),
+                        media_type: image_media_type(image_data).to_string(),
+                        data: image_data.clone(),
+                    },
+                });
             }
+
+            // Only a call of the same tool can take a generated id, others stay "unknown"
+            let tool_use_id = message.tool_call_id.clone().or_else(|| {
+                let position = generated_ids.iter().position(|(name, _)| Some(name) == message.name.as_ref())?;
+                generated_ids.remove(position).map(|(_, id)| id)
+            });
+
+            return AnthropicMessage {
+                role: "user".to_string(),
+                content: vec![ContentBlock::ToolResult {
+                    tool_use_id: tool_use_id.unwrap_or_else(|| "unknown".to_string()),
+                    content: result_blocks,
+                    is_error: message.is_error,
+                }],
+                cache_control: None,
+            };
+        }
+
+        // Empty text blocks are rejected, e.g. on assistant turns that only call tools
+        let mut content_blocks = Vec::new();
+        let has_other_blocks = message.tool_calls.is_some() || message.images.is_some() || message.documents.is_some();
+        if !message.content.is_empty() || !has_other_blocks {
+            content_blocks.push(ContentBlock::Text {
+                text: message.content.clone(),
+            });
         }
 
-        let mut content_blocks = vec![ContentBlock::Text {
-            text: message.content.clone(),
-        }];
+        // Thinking blocks lead the assistant turn and must be returned unmodified. Reasoning
+        // without a signature came from another provider and can't be verified, so it is dropped
+        let mut thinking_blocks: Vec<ContentBlock> = message
+            .thinking
+            .iter()
+            .flatten()
+            .filter_map(|block| match (&block.signature, block.redacted) {
+                (_, true) => Some(ContentBlock::RedactedThinking { data: block.thinking.clone() }),
+                (Some(signature), false) => Some(ContentBlock::Thinking {
+                    thinking: block.thinking.clone(),
+                    signature: signature.clone(),
+                }),
+                (None, false) => None,
+            })
+            .collect();
 
         // Add images if present
         if let Some(images) = &message.images {
@@ -120,17 +379,47 @@ impl AnthropicClient {
                 content_blocks.insert(0, ContentBlock::Image {
                     source: ImageSource {
                         source_type: "base64".to_string(),
-                        media_type: "image/jpeg".to_string(), 
+                        media_type: image_media_type(image_data).to_string(),
                         data: image_data.clone(),
                     },
                 });
             }
         }
 
+        // Documents go before the text that asks about them
+        let document_blocks = message.documents.iter().flatten().map(|document| {
+            let source = match &document.source {
+                DocumentSource::Pdf(data) => DocumentBlockSource {
+                    source_type: "base64".to_string(),
+                    media_type: "application/pdf".to_string(),
+                    data: data.clone(),
+                },
+                DocumentSource::Text(text) => DocumentBlockSource {
+                    source_type: "text".to_string(),
+                    media_type: "text/plain".to_string(),
+                    data: text.clone(),
+                },
+            };
+            ContentBlock::Document {
+                source,
+                title: document.title.clone(),
+                citations: if document.citations { Some(CitationsConfig { enabled: true }) } else { None },
+            }
+        });
+        let text_position = content_blocks
+            .iter()
+            .position(|block| matches!(block, ContentBlock::Text { .. }))
+            .unwrap_or(content_blocks.len());
+        content_blocks.splice(text_position..text_position, document_blocks);
+
         // Add tool calls if present
         if let Some(tool_calls) = &message.tool_calls {
             for tool_call in tool_calls {
-                let tool_id = tool_call.id.clone().unwrap_or_else(|| format!("call_{}", "generated_id"));
+                let tool_id = tool_call.id.clone().unwrap_or_else(|| {
+                    let id = format!("toolu_{}", Uuid::new_v4().simple());
+                    generated_ids.push_back((tool_call.function.name.clone(), id.clone()));
+                    id
+                });
                 content_blocks.push(ContentBlock::ToolUse {
                     id: tool_id,
                     name: tool_call.function.name.clone(),
@@ -139,277 +428,200 @@ impl AnthropicClient {
             }
         }
 
+        thinking_blocks.append(&mut content_blocks);
+
         AnthropicMessage {
             role: message.role.clone(),
-            content: content_blocks,
+            content: thinking_blocks,
+            cache_control: None,
         }
     }
 
-    fn convert_tools_to_anthropic(&self) -> Vec<AnthropicTool> {
-        self.tools
+    fn convert_tools_to_anthropic(&self) -> Vec<AnthropicToolDefinition> {
+        // Registered tools named like a server tool execute it (e.g. `bash`) rather than define it
+        let mut definitions: Vec<AnthropicToolDefinition> = self.tools
+            .enabled_tools()
             .iter()
-            .map(|tool| AnthropicTool {
+            .filter(|tool| !self.server_tools.iter().any(|server_tool| server_tool.name() == tool.name))
+            .map(|tool| AnthropicToolDefinition::Custom(AnthropicTool {
                 name: tool.name.clone(),
                 description: tool.description.clone(),
                 input_schema: tool.parameters.clone(),
-            })
-            .collect()
+                cache_control: None,
+            }))
+            .collect();
+        definitions.extend(self.server_tools.iter().map(|tool| AnthropicToolDefinition::Server(tool.to_json())));
+
+        // The breakpoint on the last definition caches all of them
+        if let Some(cache_control) = self.tools.cache_hint().map(CacheControl::from) {
+            match definitions.last_mut() {
+                Some(AnthropicToolDefinition::Custom(tool)) => tool.cache_control = Some(cache_control),
+                Some(AnthropicToolDefinition::Server(tool)) => tool["cache_control"] = serde_json::to_value(cache_control).unwrap_or_default(),
+                None => {}
+            }
+        }
+        definitions
     }
 
-    pub async fn send_chat_request(
-        &self,
-        messages: &[Message],
-    ) -> Result<Pin<Box<dyn Stream<Item = Result<ChatStreamItem, String>> + Send>>, Box<dyn Error>> {
-        let anthropic_messages: Vec<AnthropicMessage> = messages
-            .iter()
-            .map(|msg| self.convert_to_anthropic_message(msg))
-            .collect();
+    // POST with the auth, version and beta headers the configured tools need
+    fn messages_request(&self, url: &str) -> reqwest::RequestBuilder {
+        let mut betas: Vec<&str> = self.server_tools.iter().filter_map(|tool| tool.beta()).collect();
+        betas.dedup();
+
+        let mut request_builder = self
+            .client
+            .post(url)
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", "2023-06-01")
+            .header("content-type", "application/json");
+        if !betas.is_empty() {
+            request_builder = request_builder.header("anthropic-beta", betas.join(","));
+        }
+        request_builder
+    }
+
+    fn thinking_config(&self) -> Option<ThinkingConfig> {
+        self.thinking_budget.map(|budget_tokens| ThinkingConfig {
+            config_type: "enabled".to_string(),
+            budget_tokens,
+        })
+    }
 
+    fn tool_definitions(&self) -> Option<Vec<AnthropicToolDefinition>> {
+        if !self.tools.has_enabled_tools() && self.server_tools.is_empty() {
+            None
+        } else {
+            Some(self.convert_tools_to_anthropic())
+        }
+    }
 
-        let request = AnthropicRequest {
+    /// Exact input token count of the conversation with the client's tools and settings,
+    /// without generating a reply
+    pub async fn count_tokens(&self, messages: &[Message]) -> Result<u32, Box<dyn Error>> {
+        reject_audio(messages, "Anthropic")?;
+        let messages = &*resolve_image_urls(messages).await?;
+        let messages = &*normalize_history(messages, HistoryRules::ANTHROPIC)?;
+        let messages = &*fit_images(messages, ImageLimits::ANTHROPIC)?;
+        let request = CountTokensRequest {
             model: self.model.clone(),
-            max_tokens: 4096,
-            messages: anthropic_messages,
-            system: None,
-            temperature: None,
-            tools: if self.tools.is_empty() {
-                None
-            } else {
-                Some(self.convert_tools_to_anthropic())
-            },
-            stream: Some(true),
+            messages: self.convert_messages(messages),
+            system: Self::system_prompt(messages),
+            tools: self.tool_definitions(),
+            thinking: self.thinking_config(),
         };
 
         let response = self
-            .client
-            .post("https://api.anthropic.com/v1/messages")
-            .header("x-api-key", &self.api_key)
-            .header("anthropic-version", "2023-06-01")
-            .header("content-type", "application/json")
+            .messages_request(&format!("{}/messages/count_tokens", self.base_url))
             .json(&request)
-            .send()
+            .send_logged(&self.debug)
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let error_text = response.text().await?;
+            return Err(api_error("Anthropic", status, &error_text).into());
+        }
+
+        let count: CountTokensResponse = response.json().await?;
+        Ok(count.input_tokens)
+    }
+
+    pub async fn send_chat_request(
+        &self,
+        messages: &[Message],
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<ChatStreamItem, String>> + Send>>, Box<dyn Error>> {
+        self.send_chat_request_with_options(messages, &GenerationOptions::default()).await
+    }
+
+    /// Chat with sampling settings, see `SUPPORTED_OPTIONS` for the fields sent
+    pub async fn send_chat_request_with_options(
+        &self,
+        messages: &[Message],
+        options: &GenerationOptions,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<ChatStreamItem, String>> + Send>>, Box<dyn Error>> {
+        let request = self.chat_request(messages, options).await?;
+        let debug = self.debug.with_tags(options.tags.clone());
+
+        let response = self
+            .messages_request(&format!("{}/messages", self.base_url))
+            .json(&request_body(&request, options.extensions.as_ref())?)
+            .send_logged(&debug)
             .await?;
 
         if !response.status().is_success() {
+            let status = response.status().as_u16();
             let error_text = response.text().await?;
-            return Err(format!("Anthropic API error: {}", error_text).into());
+            return Err(chat_api_error("Anthropic", status, &error_text));
         }
 
-        let stream = response.bytes_stream();
+        let stream = debug.inspect_stream(response.bytes_stream());
         
         // Create a stateful stream processor
-        Ok(Box::pin(AnthropicStreamProcessor::new(stream, self.model.clone())))
+        Ok(Box::pin(parse_stream(stream, AnthropicStreamParser::new(self.model.clone())).map(|item| item.and_then(|item| item))))
+    }
+
+    /// The body `send_chat_request` would post to `/messages`, without sending it
+    pub async fn build_request(&self, messages: &[Message]) -> Result<serde_json::Value, Box<dyn Error>> {
+        self.build_request_with_options(messages, &GenerationOptions::default()).await
+    }
+
+    pub async fn build_request_with_options(&self, messages: &[Message], options: &GenerationOptions) -> Result<serde_json::Value, Box<dyn Error>> {
+        Ok(request_body(&self.chat_request(messages, options).await?, options.extensions.as_ref())?)
+    }
+
+    // Remote images are downloaded here, the rest only converts the messages
+    async fn chat_request(&self, messages: &[Message], options: &GenerationOptions) -> Result<AnthropicRequest, Box<dyn Error>> {
+        reject_audio(messages, "Anthropic")?;
+        let messages = &*resolve_image_urls(messages).await?;
+        validate_images(messages)?;
+        let messages = &*normalize_history(messages, HistoryRules::ANTHROPIC)?;
+        let messages = &*fit_images(messages, ImageLimits::ANTHROPIC)?;
+        let anthropic_messages = self.convert_messages(messages);
+
+        Ok(AnthropicRequest {
+            model: self.model.clone(),
+            // The thinking budget counts towards max_tokens
+            max_tokens: options.max_tokens.unwrap_or(4096) + self.thinking_budget.unwrap_or(0),
+            messages: anthropic_messages,
+            system: Self::system_prompt(messages),
+            temperature: options.temperature.filter(|_| self.thinking_budget.is_none()), // Extended thinking rejects a custom temperature
+            tools: self.tool_definitions(),
+            stream: Some(true),
+            thinking: self.thinking_config(),
+            metadata: options.end_user().map(|user_id| AnthropicMetadata { user_id }),
+        })
     }
 
+    /// Complete reply, streamed underneath: long replies can outlast the non-streaming timeout
     pub async fn send_chat_request_no_stream(
         &self,
         messages: &[Message],
-    ) -> Result<(String, Option<Vec<ToolCall>>), Box<dyn Error>> {
-        let mut full_response = String::new();
-        let mut tool_calls: Option<Vec<ToolCall>> = None;
-        let mut stream = self.send_chat_request(messages).await?;
-
-        while let Some(item) = stream.next().await {
-            let item = item.map_err(|e| format!("Stream error: {}", e))?;
-            if !item.content.is_empty() {
-                full_response.push_str(&item.content);
-            }
-            if let Some(tc) = item.tool_calls {
-                tool_calls = Some(tc);
-            }
-            if item.done {
-                return Ok((full_response, tool_calls));
-            }
-        }
-        Ok((full_response, tool_calls))
+    ) -> Result<ChatResponse, Box<dyn Error>> {
+        let started = Instant::now();
+        let stream = self.send_chat_request(messages).await?;
+        ChatResponse::from_stream(stream, started).await
     }
 
     pub async fn handle_tool_calls(&self, tool_calls: Vec<ToolCall>) -> Vec<Message> {
         let mut tool_responses = Vec::new();
         for tool_call in tool_calls {
-            if let Some(tool) = self
-                .tools
-                .iter()
-                .find(|t| t.name == tool_call.function.name)
-            {
-                let result = (tool.function)(tool_call.function.arguments.clone());
-                
-                // Use the tool call ID if available, otherwise use "unknown"
-                let tool_id = tool_call.id.unwrap_or_else(|| "unknown".to_string());
-                
-                // Create a message that can be identified as a tool result
-                // Use the encoded format: TOOL_RESULT:tool_id:result_content
-                tool_responses.push(Message {
-                    role: "user".to_string(),
-                    content: format!("TOOL_RESULT:{}:{}", tool_id, result),
-                    images: None,
-                    tool_calls: None,
-                });
-            }
+            // Claude expects a result for every tool_use block
+            let output = match self.tools.get(&tool_call.function.name) {
+                Some(tool) => tool.invoke_with_attachments(tool_call.function.arguments.clone()),
+                None => ToolOutput::error(format!("Error: unknown tool '{}'", tool_call.function.name)),
+            };
+            tool_responses.push(self.tool_response_message(&tool_call, output).await);
         }
         tool_responses
     }
 
+    /// Format a tool result as a message for conversation continuation
+    pub async fn tool_response_message(&self, tool_call: &ToolCall, output: ToolOutput) -> Message {
+        Message::tool_result(tool_call, output)
+    }
+
     pub async fn process_fallback_response(&self, content: &str) -> (String, Option<Vec<ToolCall>>) {
         // Anthropic doesn't need fallback processing
         (content.to_string(), None)
     }
 }
-
-// Custom stream processor to handle stateful tool call accumulation
-struct AnthropicStreamProcessor {
-    inner: Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>,
-    // Track tool calls being accumulated: tool_id -> (name, accumulated_json)
-    accumulating_tools: HashMap<String, (String, String)>,
-    pending_results: std::collections::VecDeque<Result<ChatStreamItem, String>>,
-    usage: Option<TokenUsage>,
-    model: String,
-}
-
-impl AnthropicStreamProcessor {
-    fn new(stream: impl Stream<Item = Result<Bytes, reqwest::Error>> + Send + 'static, model: String) -> Self {
-        Self {
-            inner: Box::pin(stream),
-            accumulating_tools: HashMap::new(),
-            pending_results: std::collections::VecDeque::new(),
-            usage: None,
-            model,
-        }
-    }
-    
-    // Calculate cost based on token usage
-    fn calculate_cost(&self, prompt_tokens: u32, completion_tokens: u32) -> f64 {
-        let (input_price, output_price) = get_anthropic_model_pricing(&self.model);
-        (prompt_tokens as f64 * input_price) + (completion_tokens as f64 * output_price)
-    }
-}
-
-impl Stream for AnthropicStreamProcessor {
-    type Item = Result<ChatStreamItem, String>;
-
-    fn poll_next(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Option<Self::Item>> {
-        loop {
-            // Return any pending results first
-            if let Some(result) = self.pending_results.pop_front() {
-                return std::task::Poll::Ready(Some(result));
-            }
-
-            // Poll the inner stream
-            match self.inner.as_mut().poll_next(cx) {
-                std::task::Poll::Ready(Some(chunk_result)) => {
-                    match chunk_result {
-                        Ok(chunk) => {
-                            let lines = chunk.split(|&b| b == b'\n');
-
-                            for line in lines {
-                                if line.is_empty() {
-                                    continue;
-                                }
-
-                                // Skip "data: " prefix from SSE
-                                let line_str = String::from_utf8_lossy(line);
-                                if line_str.starts_with("data: ") {
-                                    let json_str = &line_str[6..];
-                                    if json_str.trim() == "[DONE]" {
-                                        self.pending_results.push_back(Ok(ChatStreamItem {
-                                            content: String::new(),
-                                            tool_calls: None,
-                                            done: true,
-                                            usage: None,
-                                        }));
-                                        continue;
-                                    }
-
-                                    if let Ok(event) = serde_json::from_str::<StreamingEvent>(json_str) {
-                                        match event {
-                                            StreamingEvent::ContentBlockDelta { delta, .. } => {
-                                                match delta {
-                                                    Delta::TextDelta { text } => {
-                                                        self.pending_results.push_back(Ok(ChatStreamItem {
-                                                            content: text,
-                                                            tool_calls: None,
-                                                            done: false,
-                                                            usage: None,
-                                                        }));
-                                                    }
-                                                    Delta::InputJsonDelta { partial_json } => {
-                                                        // Find the most recently added tool (last in iteration order)
-                                                        if let Some((_, accumulated_json)) = self.accumulating_tools.values_mut().last() {
-                                                            accumulated_json.push_str(&partial_json);
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                            StreamingEvent::ContentBlockStart { content_block, .. } => {
-                                                if let ContentBlock::ToolUse { id, name, input: _ } = content_block {
-                                                    // Start accumulating a new tool call
-                                                    self.accumulating_tools.insert(id, (name, String::new()));
-                                                }
-                                            }
-                                            StreamingEvent::ContentBlockStop { .. } => {
-                                                // Finish all accumulated tool calls
-                                                let mut completed_tools = Vec::new();
-                                                for (tool_id, (tool_name, accumulated_json)) in self.accumulating_tools.drain() {
-                                                    if let Ok(arguments) = serde_json::from_str::<serde_json::Value>(&accumulated_json) {
-                                                        // Create tool call with the ID properly stored
-                                                        let tool_call = ToolCall {
-                                                            id: Some(tool_id),
-                                                            function: crate::core::Function {
-                                                                name: tool_name,
-                                                                arguments,
-                                                            },
-                                                        };
-                                                        completed_tools.push(tool_call);
-                                                    }
-                                                }
-                                                
-                                                if !completed_tools.is_empty() {
-                                                    self.pending_results.push_back(Ok(ChatStreamItem {
-                                                        content: String::new(),
-                                                        tool_calls: Some(completed_tools),
-                                                        done: false,
-                                                        usage: None,
-                                                    }));
-                                                }
-                                            }
-                                            StreamingEvent::MessageDelta { delta } => {
-                                                if let Some(usage) = delta.usage {
-                                                    let cost_usd = Some(self.calculate_cost(usage.input_tokens, usage.output_tokens));
-                                                    self.usage = Some(TokenUsage {
-                                                        prompt_tokens: Some(usage.input_tokens),
-                                                        completion_tokens: Some(usage.output_tokens),
-                                                        total_tokens: Some(usage.input_tokens + usage.output_tokens),
-                                                        cost_usd,
-                                                    });
-                                                }
-                                            }
-                                            StreamingEvent::MessageStop => {
-                                                let usage = self.usage.clone();
-                                                self.pending_results.push_back(Ok(ChatStreamItem {
-                                                    content: String::new(),
-                                                    tool_calls: None,
-                                                    done: true,
-                                                    usage,
-                                                }));
-                                            }
-                                            StreamingEvent::Ping => {
-                                                // Ignore ping events
-                                            }
-                                            _ => {
-                                                // Handle other event types as needed
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                            // Continue the loop to check for pending results
-                        }
-                        Err(e) => return std::task::Poll::Ready(Some(Err(e.to_string())))
-                    }
-                }
-                std::task::Poll::Ready(None) => return std::task::Poll::Ready(None),
-                std::task::Poll::Pending => return std::task::Poll::Pending,
-            }
-        }
-    }
-}
\ No newline at end of file
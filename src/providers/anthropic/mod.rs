@@ -1,5 +1,9 @@
 pub mod client;
 pub mod types;
+pub mod server_tools;
+pub mod stream;
 
 pub use client::AnthropicClient;
-pub use types::*;
\ No newline at end of file
+pub use types::*;
+pub use server_tools::ServerTool;
+pub use stream::AnthropicStreamParser;
\ No newline at end of file
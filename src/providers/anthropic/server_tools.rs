@@ -0,0 +1,84 @@
+use serde_json::{json, Value};
+
+/// Anthropic-defined tools. Web search runs on Anthropic's servers, its activity is reported
+/// through `ChatStreamItem.server_tool_event`. Computer use, text editor and bash calls arrive
+/// as regular tool calls and are executed by registering a `Tool` with the same name
+#[derive(Debug, Clone)]
+pub enum ServerTool {
+    WebSearch {
+        max_uses: Option<u32>,
+        allowed_domains: Option<Vec<String>>,
+        blocked_domains: Option<Vec<String>>,
+    },
+    /// Tool name `computer`
+    ComputerUse {
+        display_width_px: u32,
+        display_height_px: u32,
+        display_number: Option<u32>,
+    },
+    /// Tool name `str_replace_editor`
+    TextEditor,
+    /// Tool name `bash`
+    Bash,
+}
+
+impl ServerTool {
+    pub fn web_search() -> Self {
+        ServerTool::WebSearch {
+            max_uses: None,
+            allowed_domains: None,
+            blocked_domains: None,
+        }
+    }
+
+    /// Name the model uses when calling the tool
+    pub fn name(&self) -> &'static str {
+        match self {
+            ServerTool::WebSearch { .. } => "web_search",
+            ServerTool::ComputerUse { .. } => "computer",
+            ServerTool::TextEditor => "str_replace_editor",
+            ServerTool::Bash => "bash",
+        }
+    }
+
+    /// Beta header value the tool requires, if any
+    pub fn beta(&self) -> Option<&'static str> {
+        match self {
+            ServerTool::WebSearch { .. } => None,
+            _ => Some("computer-use-2025-01-24"),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut definition = match self {
+            ServerTool::WebSearch { .. } => json!({ "type": "web_search_20250305" }),
+            ServerTool::ComputerUse { .. } => json!({ "type": "computer_20250124" }),
+            ServerTool::TextEditor => json!({ "type": "text_editor_20250124" }),
+            ServerTool::Bash => json!({ "type": "bash_20250124" }),
+        };
+        definition["name"] = json!(self.name());
+
+        match self {
+            ServerTool::WebSearch { max_uses, allowed_domains, blocked_domains } => {
+                if let Some(max_uses) = max_uses {
+                    definition["max_uses"] = json!(max_uses);
+                }
+                if let Some(allowed_domains) = allowed_domains {
+                    definition["allowed_domains"] = json!(allowed_domains);
+                }
+                if let Some(blocked_domains) = blocked_domains {
+                    definition["blocked_domains"] = json!(blocked_domains);
+                }
+            }
+            ServerTool::ComputerUse { display_width_px, display_height_px, display_number } => {
+                definition["display_width_px"] = json!(display_width_px);
+                definition["display_height_px"] = json!(display_height_px);
+                if let Some(display_number) = display_number {
+                    definition["display_number"] = json!(display_number);
+                }
+            }
+            ServerTool::TextEditor | ServerTool::Bash => {}
+        }
+        definition
+    }
+}
@@ -0,0 +1,308 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::core::{ChatStreamItem, ToolCall, TokenUsage, ThinkingBlock, ServerToolEvent, SseParser, StreamParser};
+use super::client::anthropic_pricing;
+use super::types::*;
+
+/// Parser for the Anthropic messages event stream. Accumulates tool call arguments, thinking
+/// blocks and usage across events, reporting them when their block or the message ends
+pub struct AnthropicStreamParser {
+    sse: SseParser,
+    // Track tool calls being accumulated: tool_id -> (name, accumulated_json)
+    accumulating_tools: HashMap<String, (String, String)>,
+    pending_results: VecDeque<Result<ChatStreamItem, String>>,
+    usage: Option<TokenUsage>,
+    input_usage: Usage, // Input side counts from message_start, output tokens come later
+    model: String,
+    thinking_blocks: Vec<ThinkingBlock>,
+    // Server tool call being streamed: (id, name, accumulated_json)
+    server_tool_call: Option<(String, String, String)>,
+    stop_reason: Option<String>,
+}
+
+impl AnthropicStreamParser {
+    /// `model` picks the prices for the cost in the reported usage
+    pub fn new(model: String) -> Self {
+        Self {
+            sse: SseParser::new(),
+            accumulating_tools: HashMap::new(),
+            pending_results: VecDeque::new(),
+            usage: None,
+            input_usage: Usage::default(),
+            model,
+            thinking_blocks: Vec::new(),
+            server_tool_call: None,
+            stop_reason: None,
+        }
+    }
+
+    // Cost with cache reads at their discount and cache writes at their premium: 1.25 times the
+    // input price for five minute entries, twice for one hour entries
+    fn calculate_cost(&self, output_tokens: u32) -> f64 {
+        let Some(pricing) = anthropic_pricing(&self.model) else { return 0.0 };
+        let input = &self.input_usage;
+        let (write_5m, write_1h) = match &input.cache_creation {
+            Some(creation) => (creation.ephemeral_5m_input_tokens, creation.ephemeral_1h_input_tokens),
+            None => (input.cache_creation_input_tokens, 0),
+        };
+        let writes = write_5m as f64 * pricing.input * 1.25 + write_1h as f64 * pricing.input * 2.0;
+        let prompt_tokens = input.input_tokens + input.cache_read_input_tokens;
+        pricing.cost_with_cache(prompt_tokens, input.cache_read_input_tokens, output_tokens) + writes
+    }
+
+    // Merge counts from message_start / message_delta, keeping the input counts seen earlier
+    fn update_usage(&mut self, usage: Usage) {
+        if usage.input_tokens > 0 || usage.cache_read_input_tokens > 0 || usage.cache_creation_input_tokens > 0 {
+            self.input_usage = Usage { output_tokens: 0, ..usage };
+        }
+        let input = &self.input_usage;
+        let prompt_tokens = input.input_tokens + input.cache_read_input_tokens + input.cache_creation_input_tokens;
+        let cost_usd = Some(self.calculate_cost(usage.output_tokens));
+        self.usage = Some(TokenUsage {
+            prompt_tokens: Some(prompt_tokens),
+            completion_tokens: Some(usage.output_tokens),
+            total_tokens: Some(prompt_tokens + usage.output_tokens),
+            cost_usd,
+            cached_prompt_tokens: Some(self.input_usage.cache_read_input_tokens),
+            reasoning_tokens: None, // Thinking tokens are not counted separately
+            audio_tokens: None,
+        });
+    }
+
+    fn handle_data(&mut self, data: &str) {
+        if data.trim() == "[DONE]" {
+            self.pending_results.push_back(Ok(ChatStreamItem {
+                done: true,
+                ..Default::default()
+            }));
+            return;
+        }
+
+        if let Ok(event) = serde_json::from_str::<StreamingEvent>(data) {
+            self.handle_event(event);
+        }
+    }
+
+    fn handle_event(&mut self, event: StreamingEvent) {
+        match event {
+            StreamingEvent::ContentBlockDelta { delta, .. } => {
+                match delta {
+                    Delta::TextDelta { text } => {
+                        self.pending_results.push_back(Ok(ChatStreamItem {
+                            content: text,
+                            ..Default::default()
+                        }));
+                    }
+                    Delta::ThinkingDelta { thinking } => {
+                        if let Some(block) = self.thinking_blocks.last_mut() {
+                            block.thinking.push_str(&thinking);
+                        }
+                        self.pending_results.push_back(Ok(ChatStreamItem {
+                            thinking: Some(thinking),
+                            ..Default::default()
+                        }));
+                    }
+                    Delta::CitationsDelta { citation } => {
+                        self.pending_results.push_back(Ok(ChatStreamItem {
+                            citations: Some(vec![citation.into()]),
+                            ..Default::default()
+                        }));
+                    }
+                    Delta::SignatureDelta { signature } => {
+                        if let Some(block) = self.thinking_blocks.last_mut() {
+                            block.signature = Some(signature);
+                        }
+                    }
+                    Delta::InputJsonDelta { partial_json } => {
+                        if let Some((_, _, accumulated_json)) = self.server_tool_call.as_mut() {
+                            accumulated_json.push_str(&partial_json);
+                        // Find the most recently added tool (last in iteration order)
+                        } else if let Some((_, accumulated_json)) = self.accumulating_tools.values_mut().last() {
+                            accumulated_json.push_str(&partial_json);
+                        }
+                    }
+                }
+            }
+            StreamingEvent::ContentBlockStart { content_block, .. } => {
+                match content_block {
+                    ContentBlock::ToolUse { id, name, input: _ } => {
+                        // Start accumulating a new tool call
+                        self.accumulating_tools.insert(id, (name, String::new()));
+                    }
+                    ContentBlock::Thinking { thinking, .. } => {
+                        self.thinking_blocks.push(ThinkingBlock {
+                            thinking,
+                            signature: None,
+                            redacted: false,
+                        });
+                    }
+                    ContentBlock::ServerToolUse { id, name, .. } => {
+                        self.server_tool_call = Some((id, name, String::new()));
+                    }
+                    ContentBlock::WebSearchToolResult { tool_use_id, content } => {
+                        self.pending_results.push_back(Ok(ChatStreamItem {
+                            server_tool_event: Some(ServerToolEvent::Result { tool_use_id, content }),
+                            ..Default::default()
+                        }));
+                    }
+                    ContentBlock::RedactedThinking { data } => {
+                        self.thinking_blocks.push(ThinkingBlock {
+                            thinking: data,
+                            signature: None,
+                            redacted: true,
+                        });
+                    }
+                    _ => {}
+                }
+            }
+            StreamingEvent::ContentBlockStop { .. } => {
+                if let Some((id, name, accumulated_json)) = self.server_tool_call.take() {
+                    let input = serde_json::from_str(&accumulated_json).unwrap_or(serde_json::Value::Null);
+                    self.pending_results.push_back(Ok(ChatStreamItem {
+                        server_tool_event: Some(ServerToolEvent::Call { id, name, input }),
+                        ..Default::default()
+                    }));
+                    return;
+                }
+
+                // Finish all accumulated tool calls
+                let mut completed_tools = Vec::new();
+                for (tool_id, (tool_name, accumulated_json)) in self.accumulating_tools.drain() {
+                    if let Ok(arguments) = serde_json::from_str::<serde_json::Value>(&accumulated_json) {
+                        // Create tool call with the ID properly stored
+                        let tool_call = ToolCall {
+                            id: Some(tool_id),
+                            function: crate::core::Function {
+                                name: tool_name,
+                                arguments,
+                            },
+                        };
+                        completed_tools.push(tool_call);
+                    }
+                }
+
+                if !completed_tools.is_empty() {
+                    self.pending_results.push_back(Ok(ChatStreamItem {
+                        tool_calls: Some(completed_tools),
+                        ..Default::default()
+                    }));
+                }
+            }
+            StreamingEvent::MessageStart { message } => {
+                // Input tokens are only reported here, output tokens follow in message_delta
+                self.update_usage(message.usage);
+            }
+            StreamingEvent::MessageDelta { delta, usage } => {
+                if delta.stop_reason.is_some() {
+                    self.stop_reason = delta.stop_reason;
+                }
+                if let Some(usage) = usage {
+                    self.update_usage(usage);
+                }
+            }
+            StreamingEvent::MessageStop => {
+                let usage = self.usage.clone();
+                let thinking_blocks = std::mem::take(&mut self.thinking_blocks);
+                self.pending_results.push_back(Ok(ChatStreamItem {
+                    done: true,
+                    usage,
+                    thinking_blocks: if thinking_blocks.is_empty() { None } else { Some(thinking_blocks) },
+                    finish_reason: self.stop_reason.take(),
+                    ..Default::default()
+                }));
+            }
+            StreamingEvent::Ping => {
+                // Ignore ping events
+            }
+            _ => {
+                // Handle other event types as needed
+            }
+        }
+    }
+}
+
+impl StreamParser for AnthropicStreamParser {
+    type Item = Result<ChatStreamItem, String>;
+
+    fn push(&mut self, chunk: &[u8]) -> Vec<Self::Item> {
+        for event in self.sse.push(chunk) {
+            self.handle_data(&event.data);
+        }
+        self.pending_results.drain(..).collect()
+    }
+
+    fn finish(&mut self) -> Vec<Self::Item> {
+        for event in self.sse.finish() {
+            self.handle_data(&event.data);
+        }
+        self.pending_results.drain(..).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::stream::tests::{parse_chunks, parse_every_split};
+
+    const BODY: &str = concat!(
+        "event: message_start\n",
+        "data: {\"type\":\"message_start\",\"message\":{\"id\":\"msg_01\",\"type\":\"message\",\"role\":\"assistant\",\"content\":[],\"model\":\"claude-sonnet-4-20250514\",\"stop_reason\":null,\"stop_sequence\":null,\"usage\":{\"input_tokens\":25,\"output_tokens\":1}}}\n\n",
+        "event: content_block_start\n",
+        "data: {\"type\":\"content_block_start\",\"index\":0,\"content_block\":{\"type\":\"thinking\",\"thinking\":\"\"}}\n\n",
+        "event: content_block_delta\n",
+        "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"thinking_delta\",\"thinking\":\"Wetter für München…\"}}\n\n",
+        "event: content_block_delta\n",
+        "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"signature_delta\",\"signature\":\"EqQBCgIYAhIM\"}}\n\n",
+        "event: content_block_stop\n",
+        "data: {\"type\":\"content_block_stop\",\"index\":0}\n\n",
+        "event: ping\n",
+        "data: {\"type\": \"ping\"}\n\n",
+        "event: content_block_start\n",
+        "data: {\"type\":\"content_block_start\",\"index\":1,\"content_block\":{\"type\":\"text\",\"text\":\"\"}}\n\n",
+        "event: content_block_delta\n",
+        "data: {\"type\":\"content_block_delta\",\"index\":1,\"delta\":{\"type\":\"text_delta\",\"text\":\"Ich sehe nach ☀️\"}}\n\n",
+        "event: content_block_stop\n",
+        "data: {\"type\":\"content_block_stop\",\"index\":1}\n\n",
+        "event: content_block_start\n",
+        "data: {\"type\":\"content_block_start\",\"index\":2,\"content_block\":{\"type\":\"tool_use\",\"id\":\"toolu_01\",\"name\":\"get_weather\",\"input\":{}}}\n\n",
+        "event: content_block_delta\n",
+        "data: {\"type\":\"content_block_delta\",\"index\":2,\"delta\":{\"type\":\"input_json_delta\",\"partial_json\":\"{\\\"city\\\": \\\"Mün\"}}\n\n",
+        "event: content_block_delta\n",
+        "data: {\"type\":\"content_block_delta\",\"index\":2,\"delta\":{\"type\":\"input_json_delta\",\"partial_json\":\"chen\\\"}\"}}\n\n",
+        "event: content_block_stop\n",
+        "data: {\"type\":\"content_block_stop\",\"index\":2}\n\n",
+        "event: message_delta\n",
+        "data: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"tool_use\",\"stop_sequence\":null},\"usage\":{\"output_tokens\":89}}\n\n",
+        "event: message_stop\n",
+        "data: {\"type\":\"message_stop\"}\n\n",
+    );
+
+    // Each event gives its own items, so they match exactly whatever the chunking
+    fn debug_items(items: Vec<Result<ChatStreamItem, String>>) -> Vec<String> {
+        items.iter().map(|item| format!("{:?}", item)).collect()
+    }
+
+    #[test]
+    fn items_survive_every_split() {
+        parse_every_split(BODY.as_bytes(), || AnthropicStreamParser::new("claude-sonnet-4-20250514".to_string()), debug_items);
+
+        let items: Vec<ChatStreamItem> = parse_chunks(AnthropicStreamParser::new("claude-sonnet-4-20250514".to_string()), &[BODY.as_bytes()])
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        let content: String = items.iter().map(|item| item.content.as_str()).collect();
+        assert_eq!(content, "Ich sehe nach ☀️");
+
+        let tool_calls = items.iter().find_map(|item| item.tool_calls.as_ref()).unwrap();
+        assert_eq!(tool_calls[0].id.as_deref(), Some("toolu_01"));
+        assert_eq!(tool_calls[0].function.arguments, serde_json::json!({ "city": "München" }));
+
+        let last = items.last().unwrap();
+        assert!(last.done);
+        assert_eq!(last.finish_reason.as_deref(), Some("tool_use"));
+        assert_eq!(last.usage.as_ref().unwrap().total_tokens, Some(114));
+        let thinking = &last.thinking_blocks.as_ref().unwrap()[0];
+        assert_eq!(thinking.thinking, "Wetter für München…");
+        assert_eq!(thinking.signature.as_deref(), Some("EqQBCgIYAhIM"));
+    }
+}
@@ -1,9 +1,64 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
+use serde::ser::SerializeStruct;
+use crate::core::{CacheHint, Citation, CitationLocation};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct AnthropicMessage {
     pub role: String,
     pub content: Vec<ContentBlock>,
+    #[serde(skip)]
+    pub cache_control: Option<CacheControl>, // Sent on the last content block
+}
+
+impl Serialize for AnthropicMessage {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut content = serde_json::to_value(&self.content).map_err(serde::ser::Error::custom)?;
+        if let (Some(cache_control), Some(last)) = (&self.cache_control, content.as_array_mut().and_then(|blocks| blocks.last_mut())) {
+            last["cache_control"] = serde_json::to_value(cache_control).map_err(serde::ser::Error::custom)?;
+        }
+        let mut message = serializer.serialize_struct("AnthropicMessage", 2)?;
+        message.serialize_field("role", &self.role)?;
+        message.serialize_field("content", &content)?;
+        message.end()
+    }
+}
+
+/// Prompt caching breakpoint, the request is cached up to the block carrying it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CacheControl {
+    #[serde(rename = "type")]
+    pub control_type: String, // "ephemeral"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<String>, // "1h" for extended caching, 5 minutes otherwise
+}
+
+impl From<CacheHint> for CacheControl {
+    fn from(hint: CacheHint) -> Self {
+        Self {
+            control_type: "ephemeral".to_string(),
+            ttl: match hint {
+                CacheHint::Ephemeral => None,
+                CacheHint::Extended => Some("1h".to_string()),
+            },
+        }
+    }
+}
+
+/// System prompt as plain text, or as blocks when parts of it are cached
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum SystemPrompt {
+    Text(String),
+    Blocks(Vec<SystemBlock>),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SystemBlock {
+    #[serde(rename = "type")]
+    pub block_type: String, // "text"
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControl>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -22,8 +77,38 @@ pub enum ContentBlock {
     #[serde(rename = "tool_result")]
     ToolResult {
         tool_use_id: String,
-        content: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        content: Vec<ContentBlock>, // Text and image blocks
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        is_error: Option<bool>,
     },
+    #[serde(rename = "server_tool_use")]
+    ServerToolUse {
+        id: String,
+        name: String,
+        input: serde_json::Value,
+    },
+    #[serde(rename = "web_search_tool_result")]
+    WebSearchToolResult {
+        tool_use_id: String,
+        content: serde_json::Value, // Search results, or an error object
+    },
+    #[serde(rename = "document")]
+    Document {
+        source: DocumentBlockSource,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        title: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        citations: Option<CitationsConfig>,
+    },
+    #[serde(rename = "thinking")]
+    Thinking {
+        thinking: String,
+        #[serde(default)]
+        signature: String, // Absent from content_block_start, it follows in a signature_delta
+    },
+    #[serde(rename = "redacted_thinking")]
+    RedactedThinking { data: String },
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -34,19 +119,73 @@ pub struct ImageSource {
     pub data: String,        
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DocumentBlockSource {
+    #[serde(rename = "type")]
+    pub source_type: String, // "base64" for PDFs, "text" for plain text
+    pub media_type: String,
+    pub data: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CitationsConfig {
+    pub enabled: bool,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AnthropicRequest {
     pub model: String,
     pub max_tokens: u32,
     pub messages: Vec<AnthropicMessage>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub system: Option<String>,
+    pub system: Option<SystemPrompt>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub temperature: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub tools: Option<Vec<AnthropicTool>>,
+    pub tools: Option<Vec<AnthropicToolDefinition>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stream: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<ThinkingConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<AnthropicMetadata>,
+}
+
+/// End-user identifier for Anthropic's abuse detection
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AnthropicMetadata {
+    pub user_id: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct CountTokensRequest {
+    pub model: String,
+    pub messages: Vec<AnthropicMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<SystemPrompt>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<AnthropicToolDefinition>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<ThinkingConfig>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CountTokensResponse {
+    pub input_tokens: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ThinkingConfig {
+    #[serde(rename = "type")]
+    pub config_type: String, // "enabled"
+    pub budget_tokens: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum AnthropicToolDefinition {
+    Custom(AnthropicTool),
+    Server(serde_json::Value), // See `ServerTool::to_json`
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -54,6 +193,8 @@ pub struct AnthropicTool {
     pub name: String,
     pub description: String,
     pub input_schema: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControl>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -69,10 +210,26 @@ pub struct AnthropicResponse {
     pub usage: Usage,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Default)]
 pub struct Usage {
-    pub input_tokens: u32,
+    #[serde(default)]
+    pub input_tokens: u32, // Absent from message_delta events, excludes cache reads and writes
+    #[serde(default)]
     pub output_tokens: u32,
+    #[serde(default)]
+    pub cache_read_input_tokens: u32,
+    #[serde(default)]
+    pub cache_creation_input_tokens: u32,
+    #[serde(default)]
+    pub cache_creation: Option<CacheCreation>, // Cache writes split by lifetime
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct CacheCreation {
+    #[serde(default)]
+    pub ephemeral_5m_input_tokens: u32,
+    #[serde(default)]
+    pub ephemeral_1h_input_tokens: u32,
 }
 
 // Streaming event types
@@ -91,7 +248,11 @@ pub enum StreamingEvent {
     #[serde(rename = "content_block_stop")]
     ContentBlockStop { index: u32 },
     #[serde(rename = "message_delta")]
-    MessageDelta { delta: MessageDelta },
+    MessageDelta {
+        delta: MessageDelta,
+        #[serde(default)]
+        usage: Option<Usage>, // Cumulative output tokens
+    },
     #[serde(rename = "message_stop")]
     MessageStop,
     #[serde(rename = "ping")]
@@ -120,13 +281,84 @@ pub enum Delta {
     TextDelta { text: String },
     #[serde(rename = "input_json_delta")]
     InputJsonDelta { partial_json: String },
+    #[serde(rename = "thinking_delta")]
+    ThinkingDelta { thinking: String },
+    #[serde(rename = "signature_delta")]
+    SignatureDelta { signature: String },
+    #[serde(rename = "citations_delta")]
+    CitationsDelta { citation: AnthropicCitation },
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type")]
+pub enum AnthropicCitation {
+    #[serde(rename = "char_location")]
+    CharLocation {
+        cited_text: String,
+        document_index: usize,
+        document_title: Option<String>,
+        start_char_index: usize,
+        end_char_index: usize,
+    },
+    #[serde(rename = "page_location")]
+    PageLocation {
+        cited_text: String,
+        document_index: usize,
+        document_title: Option<String>,
+        start_page_number: usize,
+        end_page_number: usize,
+    },
+    #[serde(rename = "content_block_location")]
+    ContentBlockLocation {
+        cited_text: String,
+        document_index: usize,
+        document_title: Option<String>,
+        start_block_index: usize,
+        end_block_index: usize,
+    },
+    #[serde(rename = "web_search_result_location")]
+    WebSearchResultLocation {
+        cited_text: String,
+        url: String,
+        title: Option<String>,
+    },
+}
+
+impl From<AnthropicCitation> for Citation {
+    fn from(citation: AnthropicCitation) -> Self {
+        match citation {
+            AnthropicCitation::CharLocation { cited_text, document_index, document_title, start_char_index, end_char_index } => Citation {
+                cited_text,
+                document_index: Some(document_index),
+                document_title,
+                location: CitationLocation::Chars { start: start_char_index, end: end_char_index },
+            },
+            AnthropicCitation::PageLocation { cited_text, document_index, document_title, start_page_number, end_page_number } => Citation {
+                cited_text,
+                document_index: Some(document_index),
+                document_title,
+                location: CitationLocation::Pages { start: start_page_number, end: end_page_number },
+            },
+            AnthropicCitation::ContentBlockLocation { cited_text, document_index, document_title, start_block_index, end_block_index } => Citation {
+                cited_text,
+                document_index: Some(document_index),
+                document_title,
+                location: CitationLocation::Blocks { start: start_block_index, end: end_block_index },
+            },
+            AnthropicCitation::WebSearchResultLocation { cited_text, url, title } => Citation {
+                cited_text,
+                document_index: None,
+                document_title: None,
+                location: CitationLocation::Url { url, title },
+            },
+        }
+    }
 }
 
 #[derive(Deserialize, Debug)]
 pub struct MessageDelta {
     pub stop_reason: Option<String>,
     pub stop_sequence: Option<String>,
-    pub usage: Option<Usage>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -151,4 +383,27 @@ pub struct AnthropicModel {
     #[serde(rename = "type")]
     pub model_type: String,
     pub created_at: String,
+}
+
+impl AnthropicModel {
+    /// `created_at` (RFC 3339, e.g. `2025-02-19T00:00:00Z`) as a Unix timestamp
+    pub fn created_timestamp(&self) -> Option<u64> {
+        let date = self.created_at.get(..10)?;
+        let mut parts = date.split('-').map(|p| p.parse::<i64>().ok());
+        let (year, month, day) = (parts.next()??, parts.next()??, parts.next()??);
+
+        let time = self.created_at.get(11..19).unwrap_or("00:00:00");
+        let mut parts = time.split(':').map(|p| p.parse::<i64>().ok());
+        let (hour, minute, second) = (parts.next()??, parts.next()??, parts.next()??);
+
+        // Days since 1970-01-01 in the proleptic Gregorian calendar
+        let y = if month <= 2 { year - 1 } else { year };
+        let era = y.div_euclid(400);
+        let yoe = y - era * 400;
+        let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        let days = era * 146097 + doe - 719468;
+
+        u64::try_from(days * 86400 + hour * 3600 + minute * 60 + second).ok()
+    }
 }
\ No newline at end of file
@@ -0,0 +1,183 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use futures_util::{Stream, StreamExt};
+
+use crate::core::{Message, ToolCall, ChatStreamItem, ModelCapabilities, Tool, ToolRegistry, ToolOutput, TokenUsage, ChatResponse, Reproducibility};
+use super::types::{MockResponse, MockScript};
+
+/// Provider answering from a script instead of the network, for testing chat logic without API keys.
+/// Every received conversation is recorded for assertions
+pub struct MockProvider {
+    pub model: String,
+    responses: Mutex<VecDeque<MockResponse>>,
+    requests: Mutex<Vec<Vec<Message>>>,
+    delay: Option<Duration>,
+    tools: ToolRegistry,
+}
+
+impl MockProvider {
+    /// The mock ignores every `GenerationOptions` field
+    pub const SUPPORTED_OPTIONS: &'static [&'static str] = &[];
+
+    /// Scripted replies always repeat
+    pub const REPRODUCIBILITY: Reproducibility = Reproducibility::Always;
+
+    pub fn new(script: MockScript) -> Self {
+        Self {
+            model: "mock".to_string(),
+            responses: Mutex::new(script.responses.into()),
+            requests: Mutex::new(Vec::new()),
+            delay: script.delay,
+            tools: ToolRegistry::new(),
+        }
+    }
+
+    /// Append a reply to the end of the script
+    pub fn push_response(&self, response: MockResponse) {
+        self.responses.lock().unwrap().push_back(response);
+    }
+
+    /// Replies left in the script
+    pub fn remaining(&self) -> usize {
+        self.responses.lock().unwrap().len()
+    }
+
+    /// Conversations sent to the mock, oldest first
+    pub fn requests(&self) -> Vec<Vec<Message>> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    pub async fn add_tool(&mut self, tool: Tool) -> Result<(), Box<dyn Error>> {
+        self.tools.register(tool)?;
+        Ok(())
+    }
+
+    pub fn tool_registry(&self) -> &ToolRegistry {
+        &self.tools
+    }
+
+    pub fn set_tool_registry(&mut self, registry: ToolRegistry) {
+        self.tools = registry;
+    }
+
+    pub async fn supports_tool_calls(&self) -> Result<bool, Box<dyn Error>> {
+        Ok(true)
+    }
+
+    pub async fn health_check(&self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    pub fn capabilities(&self) -> ModelCapabilities {
+        ModelCapabilities {
+            context_length: None,
+            max_output_tokens: None,
+            input_modalities: vec!["text".to_string(), "image".to_string()],
+            output_modalities: vec!["text".to_string()],
+            supported_parameters: vec!["tools".to_string(), "response_format".to_string(), "stream".to_string()],
+            pricing: None,
+        }
+    }
+
+    /// The conversation the mock would record, with the model and enabled tools
+    pub fn build_request(&self, messages: &[Message]) -> Result<serde_json::Value, Box<dyn Error>> {
+        let tools: Vec<serde_json::Value> = self
+            .tools
+            .enabled_tools()
+            .iter()
+            .map(|tool| serde_json::json!({"name": tool.name, "description": tool.description, "parameters": tool.parameters}))
+            .collect();
+        Ok(serde_json::json!({
+            "model": self.model,
+            "messages": messages,
+            "tools": tools,
+        }))
+    }
+
+    // Records the conversation and takes the next reply
+    fn next_response(&self, messages: &[Message]) -> Result<MockResponse, Box<dyn Error>> {
+        self.requests.lock().unwrap().push(messages.to_vec());
+        self.responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .ok_or_else(|| "Mock script has no replies left".into())
+    }
+
+    pub async fn send_chat_request(
+        &self,
+        messages: &[Message],
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<ChatStreamItem, String>> + Send>>, Box<dyn Error>> {
+        let (chunks, tool_calls, error) = match self.next_response(messages)? {
+            MockResponse::Text(text) => (text.split_inclusive(' ').map(str::to_string).collect(), None, None),
+            MockResponse::Chunks(chunks) => (chunks, None, None),
+            MockResponse::ToolCalls { content, tool_calls } => {
+                let chunks = if content.is_empty() { Vec::new() } else { vec![content] };
+                (chunks, Some(tool_calls), None)
+            }
+            MockResponse::Error(error) => return Err(error.into()),
+            MockResponse::StreamError { chunks, error } => (chunks, None, Some(error)),
+        };
+
+        let prompt_tokens = messages.iter().map(|message| (message.content.len() as u32).div_ceil(4)).sum();
+        let completion_tokens = chunks.len() as u32;
+
+        let mut items: Vec<Result<ChatStreamItem, String>> = chunks.into_iter().map(|chunk| Ok(stream_item(chunk))).collect();
+        match error {
+            Some(error) => items.push(Err(error)),
+            None => items.push(Ok(ChatStreamItem {
+                tool_calls,
+                done: true,
+                usage: Some(TokenUsage::with_tokens(prompt_tokens, completion_tokens)),
+                finish_reason: Some("stop".to_string()),
+                ..stream_item(String::new())
+            })),
+        }
+
+        let delay = self.delay;
+        Ok(Box::pin(futures_util::stream::iter(items).then(move |item| async move {
+            if let Some(delay) = delay {
+                tokio::time::sleep(delay).await;
+            }
+            item
+        })))
+    }
+
+    pub async fn send_chat_request_no_stream(&self, messages: &[Message]) -> Result<ChatResponse, Box<dyn Error>> {
+        let started = Instant::now();
+        let stream = self.send_chat_request(messages).await?;
+        ChatResponse::from_stream(stream, started).await
+    }
+
+    pub async fn handle_tool_calls(&self, tool_calls: Vec<ToolCall>) -> Vec<Message> {
+        let mut tool_responses = Vec::new();
+        for tool_call in tool_calls {
+            // Every call needs a result, even one naming an unknown or disabled tool
+            let output = match self.tools.get(&tool_call.function.name) {
+                Some(tool) => tool.invoke_with_attachments(tool_call.function.arguments.clone()),
+                None => ToolOutput::error(format!("Error: unknown tool '{}'", tool_call.function.name)),
+            };
+            tool_responses.push(self.tool_response_message(&tool_call, output).await);
+        }
+        tool_responses
+    }
+
+    /// Format a tool result as a message for conversation continuation
+    pub async fn tool_response_message(&self, tool_call: &ToolCall, output: ToolOutput) -> Message {
+        Message::tool_result(tool_call, output)
+    }
+
+    pub async fn process_fallback_response(&self, content: &str) -> (String, Option<Vec<ToolCall>>) {
+        (content.to_string(), None)
+    }
+}
+
+fn stream_item(content: String) -> ChatStreamItem {
+    ChatStreamItem {
+        content,
+        ..Default::default()
+    }
+}
@@ -0,0 +1,82 @@
+use std::time::Duration;
+
+use crate::core::{Function, ToolCall};
+
+/// One scripted reply, each chat request consumes the next
+#[derive(Debug, Clone)]
+pub enum MockResponse {
+    Text(String), // Streamed a word at a time
+    Chunks(Vec<String>), // Streamed as given
+    ToolCalls { content: String, tool_calls: Vec<ToolCall> },
+    Error(String), // The request fails before streaming
+    StreamError { chunks: Vec<String>, error: String }, // Streams the chunks, then fails
+}
+
+/// Replies a `MockProvider` gives, in order
+#[derive(Debug, Clone, Default)]
+pub struct MockScript {
+    pub responses: Vec<MockResponse>,
+    pub delay: Option<Duration>, // Pause before each streamed item
+}
+
+impl MockScript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_text(mut self, text: &str) -> Self {
+        self.responses.push(MockResponse::Text(text.to_string()));
+        self
+    }
+
+    pub fn with_chunks(mut self, chunks: &[&str]) -> Self {
+        self.responses.push(MockResponse::Chunks(chunks.iter().map(|chunk| chunk.to_string()).collect()));
+        self
+    }
+
+    /// A reply calling one tool, its id is `mock_call_<n>` counting calls across the script from 1
+    pub fn with_tool_call(mut self, name: &str, arguments: serde_json::Value) -> Self {
+        let call_number = self.tool_call_count() + 1;
+        self.responses.push(MockResponse::ToolCalls {
+            content: String::new(),
+            tool_calls: vec![ToolCall {
+                id: Some(format!("mock_call_{}", call_number)),
+                function: Function { name: name.to_string(), arguments },
+            }],
+        });
+        self
+    }
+
+    pub fn with_tool_calls(mut self, content: &str, tool_calls: Vec<ToolCall>) -> Self {
+        self.responses.push(MockResponse::ToolCalls { content: content.to_string(), tool_calls });
+        self
+    }
+
+    pub fn with_error(mut self, error: &str) -> Self {
+        self.responses.push(MockResponse::Error(error.to_string()));
+        self
+    }
+
+    pub fn with_stream_error(mut self, chunks: &[&str], error: &str) -> Self {
+        self.responses.push(MockResponse::StreamError {
+            chunks: chunks.iter().map(|chunk| chunk.to_string()).collect(),
+            error: error.to_string(),
+        });
+        self
+    }
+
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    fn tool_call_count(&self) -> usize {
+        self.responses
+            .iter()
+            .map(|response| match response {
+                MockResponse::ToolCalls { tool_calls, .. } => tool_calls.len(),
+                _ => 0,
+            })
+            .sum()
+    }
+}
@@ -2,8 +2,12 @@ pub mod ollama;
 pub mod anthropic;
 pub mod openai;
 pub mod openrouter;
+#[cfg(feature = "test-util")]
+pub mod mock;
 
 pub use ollama::{OllamaClient, Model, ListModelsResponse, OllamaOptions};
 pub use anthropic::{AnthropicClient};
 pub use openai::{OpenAIClient};
-pub use openrouter::{OpenRouterClient};
\ No newline at end of file
+pub use openrouter::{OpenRouterClient};
+#[cfg(feature = "test-util")]
+pub use mock::{MockProvider, MockScript, MockResponse};
\ No newline at end of file
@@ -1,65 +1,322 @@
-use base64::{Engine as _, engine::general_purpose};
 use futures_util::{Stream, StreamExt};
 use reqwest::Client;
 use serde_json::json;
 use std::error::Error;
 use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::core::{Message, ToolCall, ChatStreamItem, PullProgress, ModelInfo, Tool, ToolRegistry, FallbackToolHandler, FallbackStrategy, ToolOutput, ResponseFormat, validate_images, normalize_history, HistoryRules, reject_audio, resolve_image_urls, encode_image_file, encode_image_bytes, NdjsonParser, parse_stream, DebugLog, DebugEvent, SendLogged, stderr_debug_callback, ChatTemplate, ChatResponse, Reproducibility, chat_api_error, api_error, merge_extensions, ProviderKind};
+use super::stream::OllamaChatStreamParser;
+use super::{OllamaOptions, KeepAlive, EmbedOptions, EmbedResponse, Model, ListModelsResponse, RunningModel, ListRunningModelsResponse, VersionResponse, OllamaTransport};
+
+
+// "0.5.7-rc1" -> [0, 5, 7], missing parts count as 0 when compared
+fn parse_version(version: &str) -> Vec<u64> {
+    let mut parts: Vec<u64> = version
+        .trim_start_matches('v')
+        .split(['.', '-', '+'])
+        .take(3)
+        .map(|part| part.parse().unwrap_or(0))
+        .collect();
+    parts.resize(3, 0);
+    parts
+}
 
-use crate::core::{Message, ToolCall, ChatStreamItem, PullProgress, ModelInfo, Tool, FallbackToolHandler, TokenUsage};
-use super::{OllamaOptions, ChatResponse, Model, ListModelsResponse};
-use super::utilities::StreamingXmlFilter;
+// Split a Modelfile into the structured fields `/api/create` expects
+fn modelfile_to_create_request(modelfile: &str) -> serde_json::Value {
+    let mut request = json!({});
+    let mut parameters = serde_json::Map::new();
+    let mut lines = modelfile.lines();
 
+    while let Some(line) = lines.next() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (instruction, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let mut value = rest.trim().to_string();
+
+        // Triple quoted values may span several lines
+        if let Some(stripped) = value.strip_prefix("\"\"\"") {
+            let mut text = stripped.to_string();
+            while !text.ends_with("\"\"\"") {
+                match lines.next() {
+                    Some(next) => {
+                        text.push('\n');
+                        text.push_str(next);
+                    }
+                    None => break,
+                }
+            }
+            value = text.trim_end_matches("\"\"\"").to_string();
+        } else if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+            value = value[1..value.len() - 1].to_string();
+        }
 
-impl Tool {
-    fn to_json(&self) -> serde_json::Value {
-        json!({
-            "type": "function",
-            "function": {
-                "name": self.name,
-                "description": self.description,
-                "parameters": self.parameters,
+        match instruction.to_uppercase().as_str() {
+            "FROM" => request["from"] = json!(value),
+            "SYSTEM" => request["system"] = json!(value),
+            "TEMPLATE" => request["template"] = json!(value),
+            "LICENSE" => request["license"] = json!(value),
+            "PARAMETER" => {
+                if let Some((key, raw)) = value.split_once(char::is_whitespace) {
+                    let raw = raw.trim();
+                    let parsed = serde_json::from_str::<serde_json::Value>(raw).unwrap_or_else(|_| json!(raw));
+                    // Repeated parameters such as `stop` collect into a list
+                    match parameters.get_mut(key) {
+                        Some(serde_json::Value::Array(existing)) => existing.push(parsed),
+                        Some(existing) => *existing = json!([existing.clone(), parsed]),
+                        None if key == "stop" => {
+                            parameters.insert(key.to_string(), json!([parsed]));
+                        }
+                        None => {
+                            parameters.insert(key.to_string(), parsed);
+                        }
+                    }
+                }
             }
-        })
+            _ => {}
+        }
+    }
+
+    if !parameters.is_empty() {
+        request["parameters"] = serde_json::Value::Object(parameters);
+    }
+    request
+}
+
+// `format` accepts either "json" or a full JSON schema
+pub(crate) fn ollama_format(format: &ResponseFormat) -> serde_json::Value {
+    match format {
+        ResponseFormat::Json => json!("json"),
+        ResponseFormat::JsonSchema { schema, .. } => schema.clone(),
     }
 }
 
+// Ollama identifies tool results by `tool_name` rather than a call id
+fn to_ollama_messages(messages: &[Message]) -> Vec<serde_json::Value> {
+    messages
+        .iter()
+        .map(|message| {
+            let mut value = json!(message.with_inlined_documents());
+            if let Some(obj) = value.as_object_mut() {
+                obj.remove("tool_call_id");
+                obj.remove("is_error");
+                if let Some(name) = obj.remove("name") {
+                    obj.insert("tool_name".to_string(), name);
+                }
+                // Ollama takes previous reasoning as a single string
+                if obj.remove("thinking").is_some() {
+                    let thinking: String = message.thinking.iter().flatten()
+                        .filter(|block| !block.redacted)
+                        .map(|block| block.thinking.as_str())
+                        .collect();
+                    obj.insert("thinking".to_string(), json!(thinking));
+                }
+            }
+            value
+        })
+        .collect()
+}
+
+type PullProgressCallback = Arc<dyn Fn(&PullProgress) + Send + Sync>;
+
 pub struct OllamaClient {
     client: Client,
     pub endpoint: String,
     pub model: String,
-    tools: Vec<Tool>,
+    tools: ToolRegistry,
     debug_mode: bool,
+    fallback_strategy: FallbackStrategy,
+    keep_alive: Option<KeepAlive>,
+    think: Option<bool>,
+    auto_pull: bool,
+    pull_progress_callback: Option<PullProgressCallback>,
+    debug: DebugLog,
 }
 
 impl OllamaClient {
+    /// `GenerationOptions` fields mapped onto `OllamaOptions`
+    pub const SUPPORTED_OPTIONS: &'static [&'static str] = &["temperature", "max_tokens", "seed", "presence_penalty", "frequency_penalty"];
+
+    /// Seeded replies repeat on the same model and hardware
+    pub const REPRODUCIBILITY: Reproducibility = Reproducibility::Exact;
+
     pub fn new(endpoint: String, model: String) -> Self {
+        Self::with_client(endpoint, model, Client::new())
+    }
+
+    /// Connect through a Unix socket or a custom HTTP client instead of plain TCP
+    pub fn with_transport(endpoint: String, model: String, transport: OllamaTransport) -> Result<Self, Box<dyn Error>> {
+        Ok(Self::with_client(endpoint, model, transport.build_client()?))
+    }
+
+    fn with_client(endpoint: String, model: String, client: Client) -> Self {
         Self {
-            client: Client::new(),
+            client,
             endpoint,
             model,
-            tools: Vec::new(),
+            tools: ToolRegistry::new(),
             debug_mode: false,
+            fallback_strategy: FallbackStrategy::default(),
+            keep_alive: None,
+            think: None,
+            auto_pull: false,
+            pull_progress_callback: None,
+            debug: DebugLog::new("Ollama"),
         }
     }
 
+    /// A client for another model on the same server and transport. Tools are not carried over
+    pub fn for_model(&self, model: &str) -> Self {
+        let mut client = Self::with_client(self.endpoint.clone(), model.to_string(), self.client.clone());
+        client.keep_alive = self.keep_alive;
+        client.auto_pull = self.auto_pull;
+        client.pull_progress_callback = self.pull_progress_callback.clone();
+        client.debug = self.debug.clone();
+        client
+    }
+
+    /// Log requests with API keys redacted, raw stream chunks and fallback tool call output to stderr.
+    /// Fallback tool call markup is also left in the streamed content
     pub fn set_debug_mode(&mut self, debug: bool) {
         self.debug_mode = debug;
+        self.debug.set_callback(debug.then(stderr_debug_callback));
     }
 
     pub fn debug_mode(&self) -> bool {
         self.debug_mode
     }
 
+    /// Send debug events to a callback instead of stderr, e.g. to forward them to `tracing`
+    pub fn set_debug_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(&DebugEvent) + Send + Sync + 'static,
+    {
+        self.debug.set_callback(Some(Arc::new(callback)));
+    }
+
+    pub fn set_fallback_strategy(&mut self, strategy: FallbackStrategy) {
+        self.fallback_strategy = strategy;
+    }
+
+    pub fn fallback_strategy(&self) -> FallbackStrategy {
+        self.fallback_strategy
+    }
+
+    /// Keep alive sent with chat, generate and embed requests, None uses the server default (5 minutes)
+    pub fn set_keep_alive(&mut self, keep_alive: Option<KeepAlive>) {
+        self.keep_alive = keep_alive;
+    }
+
+    pub fn keep_alive(&self) -> Option<KeepAlive> {
+        self.keep_alive
+    }
+
+    /// `think` sent with chat and generate requests. `Some(true)` returns the reasoning of thinking
+    /// models in `ChatStreamItem.thinking` instead of inline `<think>` tags, `Some(false)` disables
+    /// it on models that think by default, None uses the model default
+    pub fn set_think(&mut self, think: Option<bool>) {
+        self.think = think;
+    }
+
+    pub fn think(&self) -> Option<bool> {
+        self.think
+    }
+
+    /// Pull the model when a request finds it isn't installed, then retry the request
+    pub fn set_auto_pull(&mut self, enabled: bool) {
+        self.auto_pull = enabled;
+    }
+
+    pub fn auto_pull(&self) -> bool {
+        self.auto_pull
+    }
+
+    /// Called with each progress update of an automatic pull
+    pub fn set_pull_progress_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(&PullProgress) + Send + Sync + 'static,
+    {
+        self.pull_progress_callback = Some(Arc::new(callback));
+    }
+
+    // Send a request that needs the model installed, pulling it first on a "model not found" error in auto-pull mode
+    async fn post_model_request(&self, path: &str, request_body: &serde_json::Value, debug: &DebugLog) -> Result<reqwest::Response, Box<dyn Error>> {
+        let url = format!("{}{}", self.endpoint, path);
+        let response = self.client.post(&url).json(request_body).send_logged(debug).await?;
+
+        if !self.auto_pull || response.status() != reqwest::StatusCode::NOT_FOUND {
+            return Ok(response);
+        }
+        let status = response.status().as_u16();
+        let error_text = response.text().await?;
+        if !error_text.contains("not found") {
+            return Err(api_error("Ollama", status, &error_text).into());
+        }
+
+        // Embeddings can name another model than the client's
+        let model = request_body["model"].as_str().unwrap_or(&self.model);
+        let mut progress = self.pull_model_stream(model).await?;
+        while let Some(update) = progress.next().await {
+            let update = update.map_err(|e| format!("Auto-pull of {} failed: {}", model, e))?;
+            if let Some(callback) = &self.pull_progress_callback {
+                callback(&update);
+            }
+        }
+
+        Ok(self.client.post(&url).json(request_body).send_logged(debug).await?)
+    }
+
+    /// Load the model into memory ahead of the first request, kept loaded per the client's
+    /// keep_alive or indefinitely when none is set
+    pub async fn load_model(&self) -> Result<(), Box<dyn Error>> {
+        self.set_model_residency(self.keep_alive.unwrap_or(KeepAlive::Forever)).await
+    }
+
+    /// Unload the model and free its memory now
+    pub async fn unload_model(&self) -> Result<(), Box<dyn Error>> {
+        self.set_model_residency(KeepAlive::UnloadImmediately).await
+    }
+
+    // An empty generate request only loads or unloads the model, never auto-pulled since
+    // unloading a model that isn't installed shouldn't download it
+    async fn set_model_residency(&self, keep_alive: KeepAlive) -> Result<(), Box<dyn Error>> {
+        let request_body = json!({ "model": self.model, "prompt": "", "keep_alive": keep_alive, "stream": false });
+        let response = self
+            .client
+            .post(format!("{}/api/generate", self.endpoint))
+            .json(&request_body)
+            .send_logged(&self.debug)
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let error_text = response.text().await?;
+            return Err(api_error("Ollama", status, &error_text).into());
+        }
+        Ok(())
+    }
+
     pub async fn add_tool(&mut self, tool: Tool) -> Result<(), Box<dyn Error>> {
-        self.tools.push(tool);
+        self.tools.register(tool)?;
         
         // Tool support is now determined dynamically when needed
         
         Ok(())
     }
 
+    pub fn tool_registry(&self) -> &ToolRegistry {
+        &self.tools
+    }
+
+    pub fn set_tool_registry(&mut self, registry: ToolRegistry) {
+        self.tools = registry;
+    }
+
     pub async fn is_fallback_mode(&self) -> bool {
-        if self.tools.is_empty() {
+        if !self.tools.has_enabled_tools() {
             false // No tools, no fallback needed
         } else {
             // Dynamically check if model supports native tools
@@ -82,24 +339,104 @@ impl OllamaClient {
     pub async fn list_local_models(&self) -> Result<Vec<Model>, Box<dyn Error>> {
         let response = self
             .client
-            .get(&format!("{}/api/tags", self.endpoint))
-            .send()
+            .get(format!("{}/api/tags", self.endpoint))
+            .send_logged(&self.debug)
             .await?
             .json::<ListModelsResponse>()
             .await?;
         Ok(response.models)
     }
 
+    /// Models currently loaded in memory, with VRAM usage and unload time
+    pub async fn list_running_models(&self) -> Result<Vec<RunningModel>, Box<dyn Error>> {
+        let response = self
+            .client
+            .get(format!("{}/api/ps", self.endpoint))
+            .send_logged(&self.debug)
+            .await?
+            .json::<ListRunningModelsResponse>()
+            .await?;
+        Ok(response.models)
+    }
+
+    pub async fn server_version(&self) -> Result<String, Box<dyn Error>> {
+        let response = self
+            .client
+            .get(format!("{}/api/version", self.endpoint))
+            .send_logged(&self.debug)
+            .await?
+            .json::<VersionResponse>()
+            .await?;
+        Ok(response.version)
+    }
+
+    /// Check the server answers `/api/version`
+    pub async fn health_check(&self) -> Result<(), Box<dyn Error>> {
+        let response = self
+            .client
+            .get(format!("{}/api/version", self.endpoint))
+            .send_logged(&self.debug)
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(format!("Ollama health check failed: {}", response.status()).into());
+        }
+        Ok(())
+    }
+
+    /// Check the server is at least `minimum` (e.g. "0.5.0") before relying on newer features
+    pub async fn server_version_at_least(&self, minimum: &str) -> Result<bool, Box<dyn Error>> {
+        let version = self.server_version().await?;
+        Ok(parse_version(&version) >= parse_version(minimum))
+    }
+
     pub async fn get_available_models(&self) -> Result<Vec<Model>, Box<dyn Error>> {
         self.list_local_models().await
     }
 
+    pub async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, Box<dyn Error>> {
+        self.embed_with_options(texts, EmbedOptions::default()).await
+    }
+
+    /// Embed texts with `/api/embed`, sending them in batches. Returns one vector per input text
+    pub async fn embed_with_options(&self, texts: &[String], options: EmbedOptions) -> Result<Vec<Vec<f32>>, Box<dyn Error>> {
+        let model = options.model.clone().unwrap_or_else(|| self.model.clone());
+        let batch_size = options.batch_size.unwrap_or(64).max(1);
+        let mut embeddings = Vec::with_capacity(texts.len());
+
+        for batch in texts.chunks(batch_size) {
+            let mut request_body = json!({
+                "model": model,
+                "input": batch,
+            });
+            if let Some(keep_alive) = options.keep_alive.or(self.keep_alive) {
+                request_body["keep_alive"] = json!(keep_alive);
+            }
+            if let Some(truncate) = options.truncate {
+                request_body["truncate"] = json!(truncate);
+            }
+
+            let response = self.post_model_request("/api/embed", &request_body, &self.debug).await?;
+
+            if !response.status().is_success() {
+                let status = response.status().as_u16();
+                let error_text = response.text().await?;
+                return Err(api_error("Ollama", status, &error_text).into());
+            }
+
+            let embed_response: EmbedResponse = response.json().await?;
+            embeddings.extend(embed_response.embeddings);
+        }
+
+        Ok(embeddings)
+    }
+
     pub async fn show_model_info(&self, model_name: &str) -> Result<ModelInfo, Box<dyn Error>> {
         let response = self
             .client
-            .post(&format!("{}/api/show", self.endpoint))
+            .post(format!("{}/api/show", self.endpoint))
             .json(&json!({ "name": model_name }))
-            .send()
+            .send_logged(&self.debug)
             .await?
             .json::<ModelInfo>()
             .await?;
@@ -107,12 +444,15 @@ impl OllamaClient {
     }
 
     pub async fn pull_model(&self, model_name: &str) -> Result<(), Box<dyn Error>> {
-        println!("Pulling model: {}", model_name);
-        let mut stream = self.pull_model_stream(model_name).await?;
+        self.pull_model_with_progress(model_name, |_| {}).await
+    }
 
+    /// Pull a model, passing each status update to `on_progress`
+    pub async fn pull_model_with_progress(&self, model_name: &str, mut on_progress: impl FnMut(&PullProgress)) -> Result<(), Box<dyn Error>> {
+        let mut stream = self.pull_model_stream(model_name).await?;
         while let Some(progress) = stream.next().await {
             let progress = progress.map_err(|e| format!("Stream error: {}", e))?;
-            println!("{}", progress.status);
+            on_progress(&progress);
         }
         Ok(())
     }
@@ -122,67 +462,130 @@ impl OllamaClient {
         model_name: &str,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<PullProgress, String>> + Send>>, Box<dyn Error>>
     {
-        let stream = self
+        self.progress_stream("/api/pull", json!({ "name": model_name, "stream": true })).await
+    }
+
+    pub async fn push_model(&self, model_name: &str) -> Result<(), Box<dyn Error>> {
+        let mut stream = self.push_model_stream(model_name).await?;
+        while let Some(progress) = stream.next().await {
+            progress.map_err(|e| format!("Push error: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// Upload a model to a registry, the name must include a namespace (e.g. `user/model:tag`)
+    pub async fn push_model_stream(
+        &self,
+        model_name: &str,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<PullProgress, String>> + Send>>, Box<dyn Error>>
+    {
+        self.progress_stream("/api/push", json!({ "model": model_name, "stream": true })).await
+    }
+
+    pub async fn create_model(&self, model_name: &str, modelfile: &str) -> Result<(), Box<dyn Error>> {
+        let mut stream = self.create_model_stream(model_name, modelfile).await?;
+        while let Some(progress) = stream.next().await {
+            progress.map_err(|e| format!("Create error: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// Create a model from Modelfile contents (FROM, SYSTEM, TEMPLATE, PARAMETER, ...)
+    pub async fn create_model_stream(
+        &self,
+        model_name: &str,
+        modelfile: &str,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<PullProgress, String>> + Send>>, Box<dyn Error>>
+    {
+        // Newer servers take the Modelfile as structured fields, older ones as the raw text
+        let mut request_body = modelfile_to_create_request(modelfile);
+        request_body["model"] = json!(model_name);
+        request_body["modelfile"] = json!(modelfile);
+        request_body["stream"] = json!(true);
+        self.progress_stream("/api/create", request_body).await
+    }
+
+    pub async fn delete_model(&self, model_name: &str) -> Result<(), Box<dyn Error>> {
+        let response = self
             .client
-            .post(&format!("{}/api/pull", self.endpoint))
-            .json(&json!({ "name": model_name, "stream": true }))
-            .send()
-            .await?
-            .bytes_stream();
+            .delete(format!("{}/api/delete", self.endpoint))
+            .json(&json!({ "model": model_name }))
+            .send_logged(&self.debug)
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let error_text = response.text().await?;
+            return Err(api_error("Ollama", status, &error_text).into());
+        }
+        Ok(())
+    }
 
-        let stream = stream.map(
-            |item| -> Result<Vec<Result<PullProgress, String>>, Box<dyn Error>> {
-                let chunk = item?;
-                let lines = chunk.split(|&b| b == b'\n');
-                let mut results = Vec::new();
+    pub async fn copy_model(&self, source: &str, destination: &str) -> Result<(), Box<dyn Error>> {
+        let response = self
+            .client
+            .post(format!("{}/api/copy", self.endpoint))
+            .json(&json!({ "source": source, "destination": destination }))
+            .send_logged(&self.debug)
+            .await?;
 
-                for line in lines {
-                    if line.is_empty() {
-                        continue;
-                    }
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let error_text = response.text().await?;
+            return Err(api_error("Ollama", status, &error_text).into());
+        }
+        Ok(())
+    }
 
-                    let line_str = String::from_utf8_lossy(line);
-                    match serde_json::from_str::<serde_json::Value>(&line_str) {
-                        Ok(json) => {
-                            results.push(Ok(PullProgress {
-                                status: json
-                                    .get("status")
-                                    .and_then(|s| s.as_str())
-                                    .unwrap_or("")
-                                    .to_string(),
-                                digest: json
-                                    .get("digest")
-                                    .and_then(|s| s.as_str())
-                                    .map(|s| s.to_string()),
-                                total: json.get("total").and_then(|n| n.as_u64()),
-                                completed: json.get("completed").and_then(|n| n.as_u64()),
-                            }));
-                        }
-                        Err(_) => {
-                            results.push(Ok(PullProgress {
-                                status: line_str.to_string(),
-                                digest: None,
-                                total: None,
-                                completed: None,
-                            }));
-                        }
-                    }
-                }
+    // Stream newline-delimited status updates from pull, push and create
+    async fn progress_stream(
+        &self,
+        path: &str,
+        request_body: serde_json::Value,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<PullProgress, String>> + Send>>, Box<dyn Error>>
+    {
+        let response = self
+            .client
+            .post(format!("{}{}", self.endpoint, path))
+            .json(&request_body)
+            .send_logged(&self.debug)
+            .await?;
 
-                Ok(results)
-            },
-        );
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let error_text = response.text().await?;
+            return Err(api_error("Ollama", status, &error_text).into());
+        }
 
-        let flattened_stream = stream
-            .map(
-                |result: Result<Vec<Result<PullProgress, String>>, Box<dyn Error>>| match result {
-                    Ok(items) => futures_util::stream::iter(items),
-                    Err(e) => futures_util::stream::iter(vec![Err(e.to_string())]),
-                },
-            )
-            .flatten();
+        let stream = parse_stream(response.bytes_stream(), NdjsonParser::new()).map(|line| {
+            let line = line?;
+            match serde_json::from_str::<serde_json::Value>(&line) {
+                Ok(json) if json.get("error").is_some() => {
+                    Err(json["error"].as_str().unwrap_or("unknown error").to_string())
+                }
+                Ok(json) => Ok(PullProgress {
+                    status: json
+                        .get("status")
+                        .and_then(|s| s.as_str())
+                        .unwrap_or("")
+                        .to_string(),
+                    digest: json
+                        .get("digest")
+                        .and_then(|s| s.as_str())
+                        .map(|s| s.to_string()),
+                    total: json.get("total").and_then(|n| n.as_u64()),
+                    completed: json.get("completed").and_then(|n| n.as_u64()),
+                }),
+                Err(_) => Ok(PullProgress {
+                    status: line,
+                    digest: None,
+                    total: None,
+                    completed: None,
+                }),
+            }
+        });
 
-        Ok(Box::pin(flattened_stream))
+        Ok(Box::pin(stream))
     }
 
     pub async fn send_chat_request_with_images(
@@ -197,7 +600,7 @@ impl OllamaClient {
         &self,
         messages: &[Message],
         image_paths: Vec<String>,
-    ) -> Result<(String, Option<Vec<ToolCall>>), Box<dyn Error>> {
+    ) -> Result<ChatResponse, Box<dyn Error>> {
         self.send_chat_request_with_images_no_stream_and_options(messages, image_paths, None).await
     }
 
@@ -209,8 +612,7 @@ impl OllamaClient {
     ) -> Result<Pin<Box<dyn Stream<Item = Result<ChatStreamItem, String>> + Send>>, Box<dyn Error>> {
         let mut encoded_images = Vec::new();
         for image_path in image_paths {
-            let image_bytes = std::fs::read(image_path)?;
-            encoded_images.push(general_purpose::STANDARD.encode(image_bytes));
+            encoded_images.push(encode_image_file(&image_path)?.data);
         }
 
         let mut messages_with_images = messages.to_vec();
@@ -226,11 +628,10 @@ impl OllamaClient {
         messages: &[Message],
         image_paths: Vec<String>,
         options: Option<OllamaOptions>,
-    ) -> Result<(String, Option<Vec<ToolCall>>), Box<dyn Error>> {
+    ) -> Result<ChatResponse, Box<dyn Error>> {
         let mut encoded_images = Vec::new();
         for image_path in image_paths {
-            let image_bytes = std::fs::read(image_path)?;
-            encoded_images.push(general_purpose::STANDARD.encode(image_bytes));
+            encoded_images.push(encode_image_file(&image_path)?.data);
         }
 
         let mut messages_with_images = messages.to_vec();
@@ -253,7 +654,7 @@ impl OllamaClient {
         &self,
         messages: &[Message],
         images_data: Vec<Vec<u8>>,
-    ) -> Result<(String, Option<Vec<ToolCall>>), Box<dyn Error>> {
+    ) -> Result<ChatResponse, Box<dyn Error>> {
         self.send_chat_request_with_images_data_no_stream_and_options(messages, images_data, None).await
     }
 
@@ -265,7 +666,7 @@ impl OllamaClient {
     ) -> Result<Pin<Box<dyn Stream<Item = Result<ChatStreamItem, String>> + Send>>, Box<dyn Error>> {
         let mut encoded_images = Vec::new();
         for image_bytes in images_data {
-            encoded_images.push(general_purpose::STANDARD.encode(image_bytes));
+            encoded_images.push(encode_image_bytes(&image_bytes)?.data);
         }
 
         let mut messages_with_images = messages.to_vec();
@@ -281,10 +682,10 @@ impl OllamaClient {
         messages: &[Message],
         images_data: Vec<Vec<u8>>,
         options: Option<OllamaOptions>,
-    ) -> Result<(String, Option<Vec<ToolCall>>), Box<dyn Error>> {
+    ) -> Result<ChatResponse, Box<dyn Error>> {
         let mut encoded_images = Vec::new();
         for image_bytes in images_data {
-            encoded_images.push(general_purpose::STANDARD.encode(image_bytes));
+            encoded_images.push(encode_image_bytes(&image_bytes)?.data);
         }
 
         let mut messages_with_images = messages.to_vec();
@@ -305,7 +706,7 @@ impl OllamaClient {
     pub async fn send_chat_request_no_stream(
         &self,
         messages: &[Message],
-    ) -> Result<(String, Option<Vec<ToolCall>>), Box<dyn Error>> {
+    ) -> Result<ChatResponse, Box<dyn Error>> {
         self.send_chat_request_no_stream_with_options(messages, None).await
     }
 
@@ -313,180 +714,145 @@ impl OllamaClient {
         &self,
         messages: &[Message],
         options: Option<OllamaOptions>,
-    ) -> Result<(String, Option<Vec<ToolCall>>), Box<dyn Error>> {
+    ) -> Result<ChatResponse, Box<dyn Error>> {
+        // Streaming costs nothing extra locally, and the stream parser filters tool call markup
+        let started = Instant::now();
+        let stream = self.send_chat_request_stream_with_options(messages, options).await?;
+        ChatResponse::from_stream(stream, started).await
+    }
+
+    pub async fn send_chat_request_stream(
+        &self,
+        messages: &[Message],
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<ChatStreamItem, String>> + Send>>, Box<dyn Error>>
+    {
+        self.send_chat_request_stream_with_options(messages, None).await
+    }
+
+    pub async fn send_chat_request_stream_with_options(
+        &self,
+        messages: &[Message],
+        options: Option<OllamaOptions>,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<ChatStreamItem, String>> + Send>>, Box<dyn Error>>
+    {
+        self.chat_stream(messages, options, None).await
+    }
+
+    /// Chat reply constrained to JSON, or to a schema on Ollama 0.5+, parsed and validated
+    pub async fn send_chat_request_structured(
+        &self,
+        messages: &[Message],
+        format: &ResponseFormat,
+    ) -> Result<serde_json::Value, Box<dyn Error>> {
+        let messages = format.apply_instructions(messages);
+        let mut stream = self.chat_stream(&messages, None, Some(format)).await?;
         let mut full_response = String::new();
-        let mut tool_calls: Option<Vec<ToolCall>> = None;
-        let mut stream = self.send_chat_request_stream_with_options(messages, options).await?;
 
         while let Some(item) = stream.next().await {
             let item = item.map_err(|e| format!("Stream error: {}", e))?;
-            if !item.content.is_empty() {
-                full_response.push_str(&item.content);
-            }
-            if let Some(tc) = item.tool_calls {
-                tool_calls = Some(tc);
-            }
+            full_response.push_str(&item.content);
             if item.done {
-                return Ok((full_response, tool_calls));
+                break;
             }
         }
-        Ok((full_response, tool_calls))
+        Ok(format.parse(&full_response)?)
     }
 
-    pub async fn send_chat_request_stream(
+    async fn chat_stream(
         &self,
         messages: &[Message],
+        options: Option<OllamaOptions>,
+        format: Option<&ResponseFormat>,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<ChatStreamItem, String>> + Send>>, Box<dyn Error>>
     {
-        self.send_chat_request_stream_with_options(messages, None).await
+        let debug = self.debug.with_tags(options.as_ref().and_then(|options| options.tags.clone()));
+        let (request_body, is_fallback) = self.chat_request(messages, options, format).await?;
+
+        let response = self.post_model_request("/api/chat", &request_body, &debug).await?;
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let error_text = response.text().await?;
+            return Err(chat_api_error("Ollama", status, &error_text));
+        }
+        let stream = debug.inspect_stream(response.bytes_stream());
+
+        let fallback_strategy = if is_fallback { Some(self.fallback_strategy) } else { None };
+        let parser = OllamaChatStreamParser::new(fallback_strategy, self.debug_mode).with_debug_log(debug);
+        Ok(Box::pin(parse_stream(stream, parser).map(|item| item.and_then(|item| item))))
     }
 
-    pub async fn send_chat_request_stream_with_options(
+    /// The body `send_chat_request_stream_with_options` would post to `/api/chat`, without sending it
+    pub async fn build_request(&self, messages: &[Message], options: Option<OllamaOptions>) -> Result<serde_json::Value, Box<dyn Error>> {
+        Ok(self.chat_request(messages, options, None).await?.0)
+    }
+
+    // The request body, and whether tools are described in the prompt instead of sent natively
+    async fn chat_request(
         &self,
         messages: &[Message],
         options: Option<OllamaOptions>,
-    ) -> Result<Pin<Box<dyn Stream<Item = Result<ChatStreamItem, String>> + Send>>, Box<dyn Error>>
-    {
+        format: Option<&ResponseFormat>,
+    ) -> Result<(serde_json::Value, bool), Box<dyn Error>> {
+        reject_audio(messages, "Ollama")?;
+        let messages = &*resolve_image_urls(messages).await?;
+        validate_images(messages)?;
+        let messages = &*normalize_history(messages, HistoryRules::OLLAMA)?;
         let mut messages_to_send = messages.to_vec();
         
         // In fallback mode, inject tool context into the system message
         let is_fallback = self.is_fallback_mode().await;
-        if is_fallback && !self.tools.is_empty() {
-            let tool_context = FallbackToolHandler::generate_tool_context(&self.tools);
+        let tools = self.tools.enabled_tools();
+        if is_fallback && !tools.is_empty() {
+            let tool_context = FallbackToolHandler::generate_tool_context_with_strategy(&tools, self.fallback_strategy);
             
             // Find existing system message or create one
             if let Some(system_msg) = messages_to_send.iter_mut().find(|msg| msg.role == "system") {
                 system_msg.content.push_str(&tool_context);
             } else {
                 // Insert system message at the beginning
-                messages_to_send.insert(0, Message {
-                    role: "system".to_string(),
-                    content: format!("You are a helpful assistant.{}", tool_context),
-                    images: None,
-                    tool_calls: None,
-                });
+                messages_to_send.insert(0, Message::system(format!("You are a helpful assistant.{}", tool_context)));
             }
         }
 
         let mut request_body = json!({
             "model": self.model,
-            "messages": messages_to_send,
+            "messages": to_ollama_messages(&messages_to_send),
             "stream": true,
         });
 
         // Only add tools if not in fallback mode
-        if !is_fallback && !self.tools.is_empty() {
+        if !is_fallback && !tools.is_empty() {
             let tools_json: Vec<serde_json::Value> =
-                self.tools.iter().map(|t| t.to_json()).collect();
+                tools.iter().map(|t| t.to_provider_schema(ProviderKind::Ollama)).collect();
             request_body["tools"] = serde_json::Value::Array(tools_json);
         }
 
+        // Constrain the whole reply to JSON so small models can't break the tool call format
+        if is_fallback && !tools.is_empty() && self.fallback_strategy == FallbackStrategy::ConstrainedJson {
+            request_body["format"] = json!("json");
+        }
+
+        // An explicitly requested format takes precedence
+        if let Some(format) = format {
+            request_body["format"] = ollama_format(format);
+        }
+
+        let extensions = options.as_ref().and_then(|opts| opts.extensions.clone());
         if let Some(opts) = options {
             request_body["options"] = serde_json::to_value(opts)?;
         }
 
-        let stream = self
-            .client
-            .post(&format!("{}/api/chat", self.endpoint))
-            .json(&request_body)
-            .send()
-            .await?
-            .bytes_stream();
+        if let Some(keep_alive) = self.keep_alive {
+            request_body["keep_alive"] = json!(keep_alive);
+        }
 
-        let fallback_mode = self.is_fallback_mode().await;
-        let debug_mode = self.debug_mode;
-        
-        // Create a stateful stream that handles tool calling internally
-        let stream = futures_util::stream::unfold(
-            (stream, StreamingXmlFilter::new(), String::new(), false),
-            move |(mut stream, mut xml_filter, mut accumulated_raw, mut stream_done)| async move {
-                match stream.next().await {
-                    Some(chunk_result) => {
-                        match chunk_result {
-                            Ok(chunk) => {
-                                let lines = chunk.split(|&b| b == b'\n');
-                                let mut results = Vec::new();
-
-                                for line in lines {
-                                    if line.is_empty() {
-                                        continue;
-                                    }
-                                    match serde_json::from_slice::<ChatResponse>(&line) {
-                                        Ok(chat_response) => {
-                                            let mut tool_calls = chat_response.message.tool_calls.clone();
-                                            let raw_content = chat_response.message.content.clone();
-                                            
-                                            // Accumulate raw content for fallback tool detection
-                                            accumulated_raw.push_str(&raw_content);
-                                            
-                                            // Apply XML filtering when debug is disabled
-                                            let content = if !debug_mode {
-                                                xml_filter.process_chunk(&raw_content)
-                                            } else {
-                                                raw_content.clone()
-                                            };
-                                            
-                                            // On stream completion, check for fallback tool calls
-                                            if chat_response.done && fallback_mode && tool_calls.is_none() {
-                                                if let Some(fallback_tools) = FallbackToolHandler::parse_fallback_tool_calls(&accumulated_raw) {
-                                                    tool_calls = Some(fallback_tools);
-                                                }
-                                                stream_done = true;
-                                            }
-                                            
-                                            // Extract token usage if available (usually only on done=true)
-                                            let usage = if chat_response.done {
-                                                if let (Some(prompt_tokens), Some(completion_tokens)) = 
-                                                    (chat_response.prompt_eval_count, chat_response.eval_count) {
-                                                    // Ollama is free (local), so cost is always $0
-                                                    let cost_usd = Some(0.0);
-                                                    Some(TokenUsage {
-                                                        prompt_tokens: Some(prompt_tokens),
-                                                        completion_tokens: Some(completion_tokens),
-                                                        total_tokens: Some(prompt_tokens + completion_tokens),
-                                                        cost_usd,
-                                                    })
-                                                } else {
-                                                    None
-                                                }
-                                            } else {
-                                                None
-                                            };
-                                            
-                                            results.push(Ok(ChatStreamItem {
-                                                content,
-                                                tool_calls,
-                                                done: chat_response.done,
-                                                usage,
-                                            }));
-                                        }
-                                        Err(e) => {
-                                            eprintln!("\nError parsing response: {}", e);
-                                            eprintln!("Problematic line: {:?}", String::from_utf8_lossy(&line));
-                                        }
-                                    }
-                                }
-                                
-                                Some((Ok(results), (stream, xml_filter, accumulated_raw, stream_done)))
-                            }
-                            Err(e) => Some((Err(Box::new(e) as Box<dyn Error>), (stream, xml_filter, accumulated_raw, stream_done)))
-                        }
-                    }
-                    None => None
-                }
-            }
-        );
+        if let Some(think) = self.think {
+            request_body["think"] = json!(think);
+        }
 
-        let flattened_stream = stream
-            .map(
-                |result| match result {
-                    Ok(items) => futures_util::stream::iter(items),
-                    Err(e) => futures_util::stream::iter(vec![Err(e.to_string())]),
-                },
-            )
-            .flatten();
+        merge_extensions(&mut request_body, extensions.as_ref());
 
-        Ok(Box::pin(flattened_stream))
+        Ok((request_body, is_fallback))
     }
 
     pub async fn generate(
@@ -500,6 +866,26 @@ impl OllamaClient {
         &self,
         prompt: &str,
         options: Option<OllamaOptions>,
+    ) -> Result<String, Box<dyn Error>> {
+        self.generate_request(prompt, options, None).await
+    }
+
+    /// Completion constrained to JSON, or to a schema on Ollama 0.5+, parsed and validated
+    pub async fn generate_structured(
+        &self,
+        prompt: &str,
+        format: &ResponseFormat,
+    ) -> Result<serde_json::Value, Box<dyn Error>> {
+        let prompt = format!("{}{}", prompt, format.instructions());
+        let response = self.generate_request(&prompt, None, Some(format)).await?;
+        Ok(format.parse(&response)?)
+    }
+
+    async fn generate_request(
+        &self,
+        prompt: &str,
+        options: Option<OllamaOptions>,
+        format: Option<&ResponseFormat>,
     ) -> Result<String, Box<dyn Error>> {
         let mut request_body = json!({
             "model": self.model,
@@ -507,17 +893,153 @@ impl OllamaClient {
             "stream": false,
         });
 
+        if let Some(format) = format {
+            request_body["format"] = ollama_format(format);
+        }
+
+        let extensions = options.as_ref().and_then(|opts| opts.extensions.clone());
         if let Some(opts) = options {
             request_body["options"] = serde_json::to_value(opts)?;
         }
 
+        if let Some(keep_alive) = self.keep_alive {
+            request_body["keep_alive"] = json!(keep_alive);
+        }
+
+        if let Some(think) = self.think {
+            request_body["think"] = json!(think);
+        }
+
+        merge_extensions(&mut request_body, extensions.as_ref());
+
+        let response = self
+            .post_model_request("/api/generate", &request_body, &self.debug)
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let error_text = response.text().await?;
+            return Err(api_error("Ollama", status, &error_text).into());
+        }
+
+        let response_json: serde_json::Value = response.json().await?;
+        Ok(response_json["response"]
+            .as_str()
+            .unwrap_or("")
+            .to_string())
+    }
+
+    /// Prompt format of the model, recognized from the template Ollama reports for it
+    pub async fn chat_template(&self) -> Result<ChatTemplate, Box<dyn Error>> {
+        let info = self.show_model_info(&self.model).await?;
+        ChatTemplate::detect(&info.template).ok_or_else(|| format!("Unrecognized chat template for model {}", self.model).into())
+    }
+
+    /// Completion of a prompt used as is, without the model's template. Render conversations
+    /// with `ChatTemplate::render`, its stop sequences are passed along
+    pub async fn generate_raw(&self, prompt: &str, template: Option<&ChatTemplate>) -> Result<String, Box<dyn Error>> {
+        let mut request_body = json!({
+            "model": self.model,
+            "prompt": prompt,
+            "raw": true,
+            "stream": false,
+        });
+
+        if let Some(template) = template {
+            request_body["options"] = json!({"stop": template.stop_sequences()});
+        }
+
+        if let Some(keep_alive) = self.keep_alive {
+            request_body["keep_alive"] = json!(keep_alive);
+        }
+
+        let response = self
+            .post_model_request("/api/generate", &request_body, &self.debug)
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let error_text = response.text().await?;
+            return Err(api_error("Ollama", status, &error_text).into());
+        }
+
+        let response_json: serde_json::Value = response.json().await?;
+        Ok(response_json["response"]
+            .as_str()
+            .unwrap_or("")
+            .to_string())
+    }
+
+    /// Raw completion through the `/completion` endpoint of a llama.cpp server (`llama-server`)
+    /// used as the endpoint, with the conversation rendered by `template`
+    pub async fn completion(&self, messages: &[Message], template: &ChatTemplate) -> Result<String, Box<dyn Error>> {
+        let request_body = json!({
+            "prompt": template.render(messages, true),
+            "stop": template.stop_sequences(),
+            "stream": false,
+        });
+
         let response = self
             .client
-            .post(&format!("{}/api/generate", self.endpoint))
+            .post(format!("{}/completion", self.endpoint))
             .json(&request_body)
-            .send()
+            .send_logged(&self.debug)
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let error_text = response.text().await?;
+            return Err(api_error("llama.cpp", status, &error_text).into());
+        }
+
+        let response_json: serde_json::Value = response.json().await?;
+        Ok(response_json["content"]
+            .as_str()
+            .unwrap_or("")
+            .to_string())
+    }
+
+    /// Fill-in-the-middle completion of the code between `prefix` and `suffix`, for models
+    /// whose template supports a suffix (codegemma, qwen2.5-coder, starcoder2, ...)
+    pub async fn generate_fim(&self, prefix: &str, suffix: &str) -> Result<String, Box<dyn Error>> {
+        self.generate_fim_with_options(prefix, suffix, None).await
+    }
+
+    pub async fn generate_fim_with_options(
+        &self,
+        prefix: &str,
+        suffix: &str,
+        options: Option<OllamaOptions>,
+    ) -> Result<String, Box<dyn Error>> {
+        let mut request_body = json!({
+            "model": self.model,
+            "prompt": prefix,
+            "suffix": suffix,
+            "stream": false,
+        });
+
+        let extensions = options.as_ref().and_then(|opts| opts.extensions.clone());
+        if let Some(opts) = options {
+            request_body["options"] = serde_json::to_value(opts)?;
+        }
+
+        if let Some(keep_alive) = self.keep_alive {
+            request_body["keep_alive"] = json!(keep_alive);
+        }
+
+        merge_extensions(&mut request_body, extensions.as_ref());
+
+        let response = self
+            .post_model_request("/api/generate", &request_body, &self.debug)
             .await?;
 
+        // Models without FIM support are rejected with "does not support insert"
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let error_text = response.text().await?;
+            return Err(api_error("Ollama", status, &error_text).into());
+        }
+
         let response_json: serde_json::Value = response.json().await?;
         Ok(response_json["response"]
             .as_str()
@@ -525,6 +1047,35 @@ impl OllamaClient {
             .to_string())
     }
 
+    /// Fill-in-the-middle through the `/infill` endpoint of a llama.cpp server (`llama-server`)
+    /// used as the endpoint, the loaded model is used and `model` is ignored
+    pub async fn infill(&self, prefix: &str, suffix: &str) -> Result<String, Box<dyn Error>> {
+        let request_body = json!({
+            "input_prefix": prefix,
+            "input_suffix": suffix,
+            "stream": false,
+        });
+
+        let response = self
+            .client
+            .post(format!("{}/infill", self.endpoint))
+            .json(&request_body)
+            .send_logged(&self.debug)
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let error_text = response.text().await?;
+            return Err(api_error("llama.cpp", status, &error_text).into());
+        }
+
+        let response_json: serde_json::Value = response.json().await?;
+        Ok(response_json["content"]
+            .as_str()
+            .unwrap_or("")
+            .to_string())
+    }
+
     pub async fn generate_stream(
         &self,
         prompt: &str,
@@ -543,92 +1094,74 @@ impl OllamaClient {
             "stream": true,
         });
 
+        let extensions = options.as_ref().and_then(|opts| opts.extensions.clone());
         if let Some(opts) = options {
             request_body["options"] = serde_json::to_value(opts)?;
         }
 
-        let stream = self
-            .client
-            .post(&format!("{}/api/generate", self.endpoint))
-            .json(&request_body)
-            .send()
-            .await?
-            .bytes_stream();
+        if let Some(keep_alive) = self.keep_alive {
+            request_body["keep_alive"] = json!(keep_alive);
+        }
 
-        let stream = stream.map(
-            |item| -> Result<Vec<Result<String, String>>, Box<dyn Error>> {
-                let chunk = item?;
-                let lines = chunk.split(|&b| b == b'\n');
-                let mut results = Vec::new();
+        if let Some(think) = self.think {
+            request_body["think"] = json!(think);
+        }
 
-                for line in lines {
-                    if line.is_empty() {
-                        continue;
-                    }
+        merge_extensions(&mut request_body, extensions.as_ref());
 
-                    match serde_json::from_slice::<serde_json::Value>(&line) {
-                        Ok(json) => {
-                            if let Some(response) = json["response"].as_str() {
-                                results.push(Ok(response.to_string()));
-                            }
-                        }
-                        Err(e) => {
-                            results.push(Err(format!("Parse error: {}", e)));
-                        }
-                    }
-                }
+        let response = self
+            .post_model_request("/api/generate", &request_body, &self.debug)
+            .await?;
 
-                Ok(results)
-            },
-        );
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let error_text = response.text().await?;
+            return Err(api_error("Ollama", status, &error_text).into());
+        }
 
-        let flattened_stream = stream
-            .map(
-                |result: Result<Vec<Result<String, String>>, Box<dyn Error>>| match result {
-                    Ok(items) => futures_util::stream::iter(items),
-                    Err(e) => futures_util::stream::iter(vec![Err(e.to_string())]),
-                },
-            )
-            .flatten();
+        let stream = parse_stream(response.bytes_stream(), NdjsonParser::new()).map(|line| {
+            let json = serde_json::from_str::<serde_json::Value>(&line?).map_err(|e| format!("Parse error: {}", e))?;
+            Ok(json["response"].as_str().map(str::to_string))
+        });
 
-        Ok(Box::pin(flattened_stream))
+        Ok(Box::pin(stream.filter_map(|item: Result<Option<String>, String>| async move { item.transpose() })))
     }
 
     pub async fn handle_tool_calls(&self, tool_calls: Vec<ToolCall>) -> Vec<Message> {
         let mut tool_responses = Vec::new();
         for tool_call in tool_calls {
-            if let Some(tool) = self
-                .tools
-                .iter()
-                .find(|t| t.name == tool_call.function.name)
-            {
-                let result = (tool.function)(tool_call.function.arguments.clone());
-                
-                // In fallback mode, format tool response as user message with tool context
-                let is_fallback = self.is_fallback_mode().await;
-                let (role, content) = if is_fallback {
-                    ("user".to_string(), format!("Tool response from {}: {}", tool_call.function.name, result))
-                } else {
-                    ("tool".to_string(), result)
-                };
-                
-                tool_responses.push(Message {
-                    role,
-                    content,
-                    images: None,
-                    tool_calls: None,
-                });
-            }
+            // Every call needs a result, even one naming an unknown or disabled tool
+            let output = match self.tools.get(&tool_call.function.name) {
+                Some(tool) => tool.invoke_with_attachments(tool_call.function.arguments.clone()),
+                None => ToolOutput::error(format!("Error: unknown tool '{}'", tool_call.function.name)),
+            };
+            tool_responses.push(self.tool_response_message(&tool_call, output).await);
         }
         tool_responses
     }
 
+    /// Format a tool result as a message for conversation continuation
+    pub async fn tool_response_message(&self, tool_call: &ToolCall, output: ToolOutput) -> Message {
+        // In fallback mode, format tool response as user message with tool context
+        if self.is_fallback_mode().await {
+            return Message {
+                role: "user".to_string(),
+                content: format!("Tool response from {}: {}", tool_call.function.name, output.content),
+                images: if output.images.is_empty() { None } else { Some(output.images) },
+                ..Default::default()
+            };
+        }
+
+        Message::tool_result(tool_call, output)
+    }
+
     pub async fn process_fallback_response(&self, content: &str) -> (String, Option<Vec<ToolCall>>) {
         let is_fallback = self.is_fallback_mode().await;
         if !is_fallback {
             return (content.to_string(), None);
         }
 
-        FallbackToolHandler::process_fallback_response(content)
+        self.debug.fallback_output(content);
+        FallbackToolHandler::process_fallback_response_with_strategy(content, self.fallback_strategy)
     }
 }
\ No newline at end of file
@@ -1,8 +1,11 @@
 pub mod client;
 pub mod options;
+pub mod stream;
 pub mod types;
-pub mod utilities;
+pub mod transport;
 
 pub use client::*;
 pub use options::*;
-pub use types::*;
\ No newline at end of file
+pub use stream::*;
+pub use types::*;
+pub use transport::*;
\ No newline at end of file
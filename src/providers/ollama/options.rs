@@ -1,4 +1,8 @@
-use serde::Serialize;
+use serde::{Serialize, Serializer};
+use serde_json::{Map, Value};
+use std::time::Duration;
+
+use crate::core::{GenerationOptions, RequestTags};
 
 #[derive(Serialize, Debug, Default)]
 pub struct OllamaOptions {
@@ -11,6 +15,10 @@ pub struct OllamaOptions {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub repeat_penalty: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub seed: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub num_predict: Option<i32>,
@@ -38,4 +46,52 @@ pub struct OllamaOptions {
     pub use_mlock: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub num_thread: Option<i32>,
-}
\ No newline at end of file
+    #[serde(skip)]
+    pub tags: Option<RequestTags>, // Reported in debug logs, not sent to Ollama
+    #[serde(skip)]
+    pub extensions: Option<Map<String, Value>>, // Merged into the request body, see `GenerationOptions::extensions`
+}
+
+impl From<&GenerationOptions> for OllamaOptions {
+    fn from(options: &GenerationOptions) -> Self {
+        Self {
+            temperature: options.temperature,
+            num_predict: options.max_tokens.map(|max_tokens| max_tokens as i32),
+            seed: options.seed.map(|seed| seed as i32),
+            presence_penalty: options.presence_penalty,
+            frequency_penalty: options.frequency_penalty,
+            tags: options.tags.clone(),
+            extensions: options.extensions.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct EmbedOptions {
+    pub model: Option<String>,      // Defaults to the client's model
+    pub keep_alive: Option<KeepAlive>, // Defaults to the client's keep_alive
+    pub truncate: Option<bool>,     // Truncate inputs that exceed the context length instead of erroring
+    pub batch_size: Option<usize>,  // Texts per request, defaults to 64
+}
+
+/// How long Ollama keeps a model in memory after a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeepAlive {
+    Forever,
+    UnloadImmediately,
+    Duration(Duration),
+}
+
+impl Serialize for KeepAlive {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            KeepAlive::Forever => serializer.serialize_i64(-1),
+            KeepAlive::UnloadImmediately => serializer.serialize_i64(0),
+            KeepAlive::Duration(duration) if duration.subsec_nanos() == 0 => serializer.serialize_u64(duration.as_secs()),
+            // A number is read as whole seconds, so fractions go as a duration string, rounded up
+            // to the millisecond as "0" would unload the model
+            KeepAlive::Duration(duration) => serializer.serialize_str(&format!("{}ms", duration.as_nanos().div_ceil(1_000_000))),
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use crate::core::{ChatStreamItem, FallbackStrategy, FallbackStreamFilter, FallbackToolHandler, GenerationTiming, NdjsonParser, DebugLog, StreamParser, ThinkingBlock, TokenUsage};
+use super::ChatResponse;
+
+/// Parser for the newline-delimited JSON of Ollama's `/api/chat` stream. With a fallback
+/// strategy, tool calls written into the reply are parsed when the stream finishes
+pub struct OllamaChatStreamParser {
+    lines: NdjsonParser,
+    tool_call_filter: FallbackStreamFilter,
+    fallback_strategy: Option<FallbackStrategy>,
+    debug_mode: bool,
+    accumulated_raw: String,
+    accumulated_thinking: String,
+    debug: DebugLog,
+}
+
+impl OllamaChatStreamParser {
+    /// `fallback_strategy` is the tool call format the model was told to use, None when it calls
+    /// tools natively. In debug mode the fallback markup is left in the content
+    pub fn new(fallback_strategy: Option<FallbackStrategy>, debug_mode: bool) -> Self {
+        Self {
+            lines: NdjsonParser::new(),
+            // Outside fallback mode keep hiding stray XML tool call markup as before
+            tool_call_filter: FallbackStreamFilter::new(fallback_strategy.unwrap_or(FallbackStrategy::Xml)),
+            fallback_strategy,
+            debug_mode,
+            accumulated_raw: String::new(),
+            accumulated_thinking: String::new(),
+            debug: DebugLog::new("Ollama"),
+        }
+    }
+
+    pub(crate) fn with_debug_log(mut self, debug: DebugLog) -> Self {
+        self.debug = debug;
+        self
+    }
+
+    fn handle_line(&mut self, line: &str) -> Option<Result<ChatStreamItem, String>> {
+        let chat_response = match serde_json::from_str::<ChatResponse>(line) {
+            Ok(chat_response) => chat_response,
+            Err(e) => {
+                self.debug.parse_error(line, e);
+                return None;
+            }
+        };
+
+        let mut tool_calls = chat_response.message.tool_calls.clone();
+        let raw_content = chat_response.message.content.clone();
+
+        // Accumulate raw content for fallback tool detection
+        self.accumulated_raw.push_str(&raw_content);
+
+        // Hide fallback tool call markup when debug is disabled
+        let mut content = if !self.debug_mode {
+            self.tool_call_filter.process_chunk(&raw_content)
+        } else {
+            raw_content.clone()
+        };
+        if chat_response.done && !self.debug_mode {
+            content.push_str(&self.tool_call_filter.finish(&self.accumulated_raw));
+        }
+
+        // On stream completion, check for fallback tool calls
+        if let Some(strategy) = self.fallback_strategy && chat_response.done && tool_calls.is_none() {
+            self.debug.fallback_output(&self.accumulated_raw);
+            tool_calls = FallbackToolHandler::parse_fallback_tool_calls_with_strategy(&self.accumulated_raw, strategy);
+        }
+
+        // Extract token usage if available (usually only on done=true)
+        let usage = if chat_response.done {
+            // prompt_eval_count is omitted when the whole prompt was cached
+            chat_response.eval_count.map(|completion_tokens| {
+                let prompt_tokens = chat_response.prompt_eval_count.unwrap_or(0);
+                TokenUsage {
+                    prompt_tokens: Some(prompt_tokens),
+                    completion_tokens: Some(completion_tokens),
+                    total_tokens: Some(prompt_tokens + completion_tokens),
+                    cost_usd: Some(0.0), // Ollama is free (local), so cost is always $0
+                    cached_prompt_tokens: None,
+                    reasoning_tokens: None,
+                    audio_tokens: None,
+                }
+            })
+        } else {
+            None
+        };
+
+        let timing = if chat_response.done {
+            Some(GenerationTiming::from_nanos(
+                chat_response.total_duration,
+                chat_response.load_duration,
+                chat_response.prompt_eval_duration,
+                chat_response.eval_duration,
+                chat_response.prompt_eval_count,
+                chat_response.eval_count,
+            ))
+        } else {
+            None
+        };
+
+        let thinking = chat_response.message.thinking.filter(|t| !t.is_empty());
+        if let Some(thinking) = &thinking {
+            self.accumulated_thinking.push_str(thinking);
+        }
+        let thinking_blocks = if chat_response.done && !self.accumulated_thinking.is_empty() {
+            Some(vec![ThinkingBlock {
+                thinking: self.accumulated_thinking.clone(),
+                signature: None,
+                redacted: false,
+            }])
+        } else {
+            None
+        };
+
+        Some(Ok(ChatStreamItem {
+            content,
+            tool_calls,
+            done: chat_response.done,
+            usage,
+            timing,
+            thinking,
+            thinking_blocks,
+            finish_reason: chat_response.done_reason,
+            ..Default::default()
+        }))
+    }
+}
+
+impl StreamParser for OllamaChatStreamParser {
+    type Item = Result<ChatStreamItem, String>;
+
+    fn push(&mut self, chunk: &[u8]) -> Vec<Self::Item> {
+        self.lines.push(chunk).iter().filter_map(|line| self.handle_line(line)).collect()
+    }
+
+    fn finish(&mut self) -> Vec<Self::Item> {
+        self.lines.finish().iter().filter_map(|line| self.handle_line(line)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::stream::tests::{parse_chunks, parse_every_split};
+
+    const BODY: &str = concat!(
+        "{\"model\":\"qwen3\",\"created_at\":\"2025-06-01T10:00:00Z\",\"message\":{\"role\":\"assistant\",\"content\":\"\",\"thinking\":\"Überlege…\"},\"done\":false}\n",
+        "{\"model\":\"qwen3\",\"created_at\":\"2025-06-01T10:00:00Z\",\"message\":{\"role\":\"assistant\",\"content\":\"Ciao, \"},\"done\":false}\n",
+        "{\"model\":\"qwen3\",\"created_at\":\"2025-06-01T10:00:00Z\",\"message\":{\"role\":\"assistant\",\"content\":\"come stai? 🇮🇹\"},\"done\":false}\n",
+        "{\"model\":\"qwen3\",\"created_at\":\"2025-06-01T10:00:01Z\",\"message\":{\"role\":\"assistant\",\"content\":\"\"},\"done_reason\":\"stop\",\"done\":true,\"total_duration\":900000000,\"load_duration\":10000000,\"prompt_eval_count\":12,\"prompt_eval_duration\":50000000,\"eval_count\":8,\"eval_duration\":800000000}\n",
+    );
+
+    // Fallback tool call markup written across several lines of the reply
+    const FALLBACK_BODY: &str = concat!(
+        "{\"model\":\"llama3\",\"created_at\":\"2025-06-01T10:00:00Z\",\"message\":{\"role\":\"assistant\",\"content\":\"Sí. <tool_\"},\"done\":false}\n",
+        "{\"model\":\"llama3\",\"created_at\":\"2025-06-01T10:00:00Z\",\"message\":{\"role\":\"assistant\",\"content\":\"call>{\\\"function\\\": {\\\"name\\\": \\\"get_weather\\\", \"},\"done\":false}\n",
+        "{\"model\":\"llama3\",\"created_at\":\"2025-06-01T10:00:00Z\",\"message\":{\"role\":\"assistant\",\"content\":\"\\\"arguments\\\": {\\\"city\\\": \\\"Málaga\\\"}}}</tool_call>\"},\"done\":false}\n",
+        "{\"model\":\"llama3\",\"created_at\":\"2025-06-01T10:00:01Z\",\"message\":{\"role\":\"assistant\",\"content\":\"\"},\"done_reason\":\"stop\",\"done\":true,\"prompt_eval_count\":40,\"eval_count\":20}",
+    );
+
+    // Each line gives one item, so they match exactly whatever the chunking
+    fn debug_items(items: Vec<Result<ChatStreamItem, String>>) -> Vec<String> {
+        items.iter().map(|item| format!("{:?}", item)).collect()
+    }
+
+    fn parse(body: &str, fallback_strategy: Option<FallbackStrategy>) -> Vec<ChatStreamItem> {
+        parse_every_split(body.as_bytes(), || OllamaChatStreamParser::new(fallback_strategy, false), debug_items);
+        parse_chunks(OllamaChatStreamParser::new(fallback_strategy, false), &[body.as_bytes()])
+            .into_iter()
+            .map(Result::unwrap)
+            .collect()
+    }
+
+    #[test]
+    fn items_survive_every_split() {
+        let items = parse(BODY, None);
+        assert_eq!(items.len(), 4);
+        let content: String = items.iter().map(|item| item.content.as_str()).collect();
+        assert_eq!(content, "Ciao, come stai? 🇮🇹");
+
+        let last = items.last().unwrap();
+        assert!(last.done);
+        assert_eq!(last.finish_reason.as_deref(), Some("stop"));
+        assert_eq!(last.usage.as_ref().unwrap().total_tokens, Some(20));
+        assert_eq!(last.thinking_blocks.as_ref().unwrap()[0].thinking, "Überlege…");
+    }
+
+    #[test]
+    fn fallback_tool_calls_survive_every_split() {
+        let items = parse(FALLBACK_BODY, Some(FallbackStrategy::Xml));
+        let content: String = items.iter().map(|item| item.content.as_str()).collect();
+        assert_eq!(content.trim(), "Sí.");
+
+        let tool_calls = items.last().unwrap().tool_calls.as_ref().unwrap();
+        assert_eq!(tool_calls[0].function.name, "get_weather");
+        assert_eq!(tool_calls[0].function.arguments, serde_json::json!({ "city": "Málaga" }));
+    }
+}
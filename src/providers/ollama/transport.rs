@@ -0,0 +1,29 @@
+use reqwest::Client;
+use std::error::Error;
+#[cfg(unix)]
+use std::path::PathBuf;
+
+/// How the client reaches the Ollama server
+#[derive(Debug, Clone, Default)]
+pub enum OllamaTransport {
+    /// HTTP(S) to the endpoint URL, also covers SSH tunnels forwarding a local port
+    #[default]
+    Tcp,
+    /// Unix domain socket for servers without an exposed TCP port. The endpoint URL is still
+    /// used for the Host header and path prefix, e.g. `http://localhost`
+    #[cfg(unix)]
+    UnixSocket(PathBuf),
+    /// Preconfigured reqwest client, for proxies (including SOCKS over SSH), custom TLS or timeouts
+    Client(Client),
+}
+
+impl OllamaTransport {
+    pub(crate) fn build_client(self) -> Result<Client, Box<dyn Error>> {
+        match self {
+            OllamaTransport::Tcp => Ok(Client::new()),
+            #[cfg(unix)]
+            OllamaTransport::UnixSocket(path) => Ok(Client::builder().unix_socket(path).build()?),
+            OllamaTransport::Client(client) => Ok(client),
+        }
+    }
+}
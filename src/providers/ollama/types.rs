@@ -1,11 +1,26 @@
 use serde::Deserialize;
-use crate::core::Message;
+use crate::core::ToolCall;
+
+#[derive(Deserialize, Debug)]
+pub struct ChatResponseMessage {
+    pub role: String,
+    #[serde(default)]
+    pub content: String,
+    #[serde(default)]
+    pub thinking: Option<String>, // Present when the request enabled `think`
+    #[serde(default)]
+    pub tool_calls: Option<Vec<ToolCall>>,
+}
 
 #[derive(Deserialize, Debug)]
 pub struct ChatResponse {
-    pub message: Message,
+    pub message: ChatResponseMessage,
     pub done: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_duration: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub load_duration: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub prompt_eval_count: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub prompt_eval_duration: Option<u64>,
@@ -13,6 +28,8 @@ pub struct ChatResponse {
     pub eval_count: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub eval_duration: Option<u64>,
+    #[serde(default)]
+    pub done_reason: Option<String>, // "stop", "length" or "load", on the last response
 }
 
 #[derive(Deserialize, Debug)]
@@ -26,4 +43,29 @@ pub struct Model {
 #[derive(Deserialize, Debug)]
 pub struct ListModelsResponse {
     pub models: Vec<Model>,
-}
\ No newline at end of file
+}
+#[derive(Deserialize, Debug)]
+pub struct EmbedResponse {
+    pub embeddings: Vec<Vec<f32>>,
+    pub prompt_eval_count: Option<u32>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RunningModel {
+    pub name: String,
+    pub model: String,
+    pub size: u64,      // Total memory used in bytes
+    pub size_vram: u64, // Part of `size` held in GPU memory
+    pub digest: String,
+    pub expires_at: String, // RFC 3339 time the model will be unloaded
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ListRunningModelsResponse {
+    pub models: Vec<RunningModel>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct VersionResponse {
+    pub version: String,
+}
@@ -1,15 +1,61 @@
 use futures_util::{Stream, StreamExt};
-use reqwest::Client;
+use reqwest::{Client, Method};
 use std::error::Error;
 use std::pin::Pin;
-use std::collections::HashMap;
-use bytes::Bytes;
+use std::sync::Arc;
+use std::time::Instant;
 
-use crate::core::{Message, ToolCall, ChatStreamItem, Tool, TokenUsage};
+use crate::core::{Message, ToolCall, ChatStreamItem, Tool, ToolRegistry, TokenUsage, ToolOutput, ResponseFormat, AudioInput, TranscriptionOptions, Transcription, Transcriber, TranscriptionFuture, ImageGenerationOptions, ImageGeneration, GeneratedImage, ModerationInput, ModerationResult, Guardrail, GuardrailStage, GuardrailVerdict, GuardrailFuture, lookup_pricing, ModelCapabilities, ModelPricing, ImageDetail, image_url_content, remote_image_content, input_audio_content, validate_images, normalize_history, HistoryRules, fit_images, ImageLimits, SseParser, parse_stream, DebugLog, DebugEvent, SendLogged, stderr_debug_callback, ChatResponse, Reproducibility, chat_api_error, api_error, Quota, request_body};
 use super::types::*;
+use super::stream::OpenAIStreamParser;
+use super::{OpenAIOptions, OpenAIModelCapabilities, TokenLimitParam, ReasoningEffort};
+
+// Prices with overrides and fetched prices taking precedence over the built-in table
+fn openai_pricing(model: &str) -> Option<ModelPricing> {
+    lookup_pricing(model).or_else(|| match get_openai_model_pricing(model) {
+        (0.0, 0.0) => None,
+        (input, output) => Some(ModelPricing { input, output, cached_input: openai_cached_input_ratio(model).map(|ratio| input * ratio) }),
+    })
+}
+
+// Share of the input price charged for cached prompt tokens, None for models without caching
+fn openai_cached_input_ratio(model: &str) -> Option<f64> {
+    let family = |prefix: &str| model.starts_with(prefix);
+    if family("gpt-5") {
+        Some(0.1)
+    } else if family("gpt-4.1") || family("o3") && !family("o3-mini") || family("o4-mini") || family("codex-mini") {
+        Some(0.25)
+    } else if family("gpt-4o") || family("o1") || family("o3-mini") {
+        Some(0.5)
+    } else {
+        None
+    }
+}
+
+// Token counts and cost in USD of a request, cached prompt tokens at their discounted rate
+pub(super) fn openai_usage(model: &str, usage: &OpenAIUsage) -> TokenUsage {
+    let prompt_details = usage.prompt_tokens_details.as_ref();
+    let completion_details = usage.completion_tokens_details.as_ref();
+    let cached_prompt_tokens = prompt_details.and_then(|details| details.cached_tokens);
+    let audio_tokens = [prompt_details.and_then(|details| details.audio_tokens), completion_details.and_then(|details| details.audio_tokens)]
+        .into_iter()
+        .flatten()
+        .reduce(|a, b| a + b);
+    TokenUsage {
+        prompt_tokens: Some(usage.prompt_tokens),
+        completion_tokens: Some(usage.completion_tokens),
+        total_tokens: Some(usage.total_tokens),
+        cost_usd: Some(openai_pricing(model).map_or(0.0, |pricing| {
+            pricing.cost_with_cache(usage.prompt_tokens, cached_prompt_tokens.unwrap_or(0), usage.completion_tokens)
+        })),
+        cached_prompt_tokens,
+        reasoning_tokens: completion_details.and_then(|details| details.reasoning_tokens),
+        audio_tokens,
+    }
+}
 
 // Manual OpenAI model pricing function (based on official OpenAI pricing)
-fn get_openai_model_pricing(model: &str) -> (f64, f64) {
+pub(super) fn get_openai_model_pricing(model: &str) -> (f64, f64) {
     match model {
         // GPT-5 series - Current models
         "gpt-5" | "gpt-5-chat-latest" | "gpt-5-2025-08-07" => (1.25e-6, 10.00e-6), // $1.25/1M input, $10.00/1M output
@@ -67,88 +113,374 @@ fn get_openai_model_pricing(model: &str) -> (f64, f64) {
     }
 }
 
+// Per-image prices of the DALL-E models, which don't report token usage
+fn get_openai_image_pricing(model: &str, size: &str, quality: &str) -> Option<f64> {
+    match (model, size, quality) {
+        ("dall-e-3", "1024x1024", "hd") => Some(0.08),
+        ("dall-e-3", "1024x1024", _) => Some(0.04),
+        ("dall-e-3", "1024x1792" | "1792x1024", "hd") => Some(0.12),
+        ("dall-e-3", "1024x1792" | "1792x1024", _) => Some(0.08),
+        ("dall-e-2", "256x256", _) => Some(0.016),
+        ("dall-e-2", "512x512", _) => Some(0.018),
+        ("dall-e-2", "1024x1024", _) => Some(0.02),
+        _ => None,
+    }
+}
+
 pub struct OpenAIClient {
     client: Client,
     api_key: String,
     pub model: String,
-    tools: Vec<Tool>,
+    base_url: String,
+    tools: ToolRegistry,
+    reasoning_effort: Option<ReasoningEffort>,
+    organization: Option<String>,
+    project: Option<String>,
+    extra_headers: Vec<(String, String)>,
+    image_detail: Option<ImageDetail>,
+    strict_tools: bool,
+    debug: DebugLog,
+}
+
+// JSON mode for `Json`, structured outputs for a schema
+pub(crate) fn openai_response_format(format: &ResponseFormat) -> serde_json::Value {
+    match format {
+        ResponseFormat::Json => serde_json::json!({"type": "json_object"}),
+        ResponseFormat::JsonSchema { name, schema } => serde_json::json!({
+            "type": "json_schema",
+            "json_schema": {"name": name, "schema": schema},
+        }),
+    }
 }
 
 impl OpenAIClient {
+    /// `GenerationOptions` fields sent to the API
+    pub const SUPPORTED_OPTIONS: &'static [&'static str] =
+        &["temperature", "max_tokens", "seed", "n", "presence_penalty", "frequency_penalty", "logit_bias", "user"];
+
+    /// Seeded replies may change with `system_fingerprint`
+    pub const REPRODUCIBILITY: Reproducibility = Reproducibility::BestEffort;
+
+    /// Context window, modalities and parameters of a chat model from a built-in table,
+    /// None for models that aren't chat models (embeddings, audio, images, moderation)
+    pub fn known_capabilities(model: &str) -> Option<ModelCapabilities> {
+        let base = model.strip_prefix("ft:").unwrap_or(model);
+        let family = |prefix: &str| base == prefix || base.starts_with(&format!("{}-", prefix));
+        let not_chat = ["embedding", "tts", "whisper", "dall-e", "moderation", "gpt-image", "transcribe", "realtime", "davinci", "babbage", "instruct"];
+        if not_chat.iter().any(|name| base.contains(name)) {
+            return None;
+        }
+
+        // (context length, max output tokens, accepts images, supports tools)
+        let (context_length, max_output_tokens, vision, tools) = if family("gpt-5-chat") || family("chatgpt-4o") {
+            (128_000, 16_384, true, false)
+        } else if family("gpt-5") {
+            (400_000, 128_000, true, true)
+        } else if family("gpt-4.1") {
+            (1_047_576, 32_768, true, true)
+        } else if family("gpt-4o") {
+            (128_000, 16_384, !base.contains("audio") && !base.contains("search"), !base.contains("search"))
+        } else if family("o1-mini") || family("o1-preview") {
+            (128_000, 65_536, false, false)
+        } else if family("o3-mini") {
+            (200_000, 100_000, false, true)
+        } else if family("o1") || family("o3") || family("o4-mini") || family("codex-mini") {
+            (200_000, 100_000, true, true)
+        } else if family("gpt-4-turbo") || (family("gpt-4") && base.ends_with("-preview")) {
+            (128_000, 4_096, family("gpt-4-turbo"), true)
+        } else if family("gpt-4") {
+            (8_192, 8_192, false, true)
+        } else if family("gpt-3.5-turbo") {
+            (16_385, 4_096, false, true)
+        } else {
+            return None;
+        };
+
+        let request = OpenAIModelCapabilities::for_model(model);
+        let mut supported_parameters = vec!["max_tokens".to_string(), "response_format".to_string(), "seed".to_string(), "stream".to_string()];
+        if tools {
+            supported_parameters.push("tools".to_string());
+        }
+        if request.reasoning_effort {
+            supported_parameters.push("reasoning".to_string());
+        }
+        if request.temperature {
+            supported_parameters.push("temperature".to_string());
+        }
+
+        let pricing = openai_pricing(model);
+
+        let mut input_modalities = vec!["text".to_string()];
+        if vision {
+            input_modalities.push("image".to_string());
+        }
+        if base.contains("audio") {
+            input_modalities.push("audio".to_string());
+        }
+
+        Some(ModelCapabilities {
+            context_length: Some(context_length),
+            max_output_tokens: Some(max_output_tokens),
+            input_modalities,
+            output_modalities: vec!["text".to_string()],
+            supported_parameters,
+            pricing,
+        })
+    }
+
     pub fn new(api_key: String, model: String) -> Self {
         Self {
             client: Client::new(),
             api_key,
             model,
-            tools: Vec::new(),
+            base_url: "https://api.openai.com/v1".to_string(),
+            tools: ToolRegistry::new(),
+            reasoning_effort: None,
+            organization: None,
+            project: None,
+            extra_headers: Vec::new(),
+            image_detail: None,
+            strict_tools: false,
+            debug: DebugLog::new("OpenAI"),
+        }
+    }
+
+    /// A client with the same credentials, endpoint and headers for another model. Tools and
+    /// model settings are not carried over
+    pub fn for_model(&self, model: &str) -> Self {
+        Self {
+            client: self.client.clone(),
+            api_key: self.api_key.clone(),
+            model: model.to_string(),
+            base_url: self.base_url.clone(),
+            tools: ToolRegistry::new(),
+            reasoning_effort: None,
+            organization: self.organization.clone(),
+            project: self.project.clone(),
+            extra_headers: self.extra_headers.clone(),
+            image_detail: self.image_detail,
+            strict_tools: false,
+            debug: self.debug.clone(),
         }
     }
 
+    /// API root requests go to, `https://api.openai.com/v1` by default. Point it at an
+    /// OpenAI-compatible server, a proxy or a `ReplayServer`
+    pub fn set_base_url(&mut self, base_url: &str) {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Rate limits from the `x-ratelimit-*` headers of the latest response, empty before the first
+    pub fn quota(&self) -> Quota {
+        self.debug.last_quota()
+    }
+
+    /// Realtime voice session on this client's endpoint and key, see `VoiceSession::openai`
+    #[cfg(feature = "realtime")]
+    pub async fn voice_session(&self, model: &str, config: crate::voice::VoiceConfig) -> Result<crate::voice::VoiceSession, Box<dyn Error>> {
+        crate::voice::VoiceSession::openai_at(&self.base_url, &self.api_key, model, config).await
+    }
+
+    /// Sent as `OpenAI-Organization`, bills requests to that organization
+    pub fn set_organization(&mut self, organization: Option<String>) {
+        self.organization = organization;
+    }
+
+    pub fn organization(&self) -> Option<&str> {
+        self.organization.as_deref()
+    }
+
+    /// Sent as `OpenAI-Project`, attributes usage to that project
+    pub fn set_project(&mut self, project: Option<String>) {
+        self.project = project;
+    }
+
+    pub fn project(&self) -> Option<&str> {
+        self.project.as_deref()
+    }
+
+    /// Header added to every request, e.g. for API gateways keying usage off custom headers
+    pub fn add_header(&mut self, name: &str, value: &str) {
+        self.extra_headers.push((name.to_string(), value.to_string()));
+    }
+
+    pub fn clear_headers(&mut self) {
+        self.extra_headers.clear();
+    }
+
+    /// Resolution vision models look at images with, the API picks when None
+    pub fn set_image_detail(&mut self, detail: Option<ImageDetail>) {
+        self.image_detail = detail;
+    }
+
+    pub fn image_detail(&self) -> Option<ImageDetail> {
+        self.image_detail
+    }
+
+    /// Default reasoning effort for models that accept it, see `OpenAIModelCapabilities`
+    pub fn set_reasoning_effort(&mut self, effort: Option<ReasoningEffort>) {
+        self.reasoning_effort = effort;
+    }
+
+    pub fn reasoning_effort(&self) -> Option<ReasoningEffort> {
+        self.reasoning_effort
+    }
+
+    /// Request parameters the client's model accepts
+    pub fn model_capabilities(&self) -> OpenAIModelCapabilities {
+        OpenAIModelCapabilities::for_model(&self.model)
+    }
+
     pub async fn add_tool(&mut self, tool: Tool) -> Result<(), Box<dyn Error>> {
-        self.tools.push(tool);
+        self.tools.register(tool)?;
         Ok(())
     }
 
+    pub fn tool_registry(&self) -> &ToolRegistry {
+        &self.tools
+    }
+
+    pub fn set_tool_registry(&mut self, registry: ToolRegistry) {
+        self.tools = registry;
+    }
+
+    /// Send tools with `strict: true`, so the arguments always match the schema. Schemas are
+    /// rewritten for it (see `Tool::strict_parameters`), optional arguments arrive as null and are
+    /// dropped before the tool runs. Tools strict mode can't express are sent without it
+    pub fn set_strict_tools(&mut self, strict: bool) {
+        self.strict_tools = strict;
+    }
+
+    pub fn strict_tools(&self) -> bool {
+        self.strict_tools
+    }
+
     pub async fn is_fallback_mode(&self) -> bool {
         false // OpenAI has native tool support
     }
 
-    pub fn set_debug_mode(&mut self, _debug: bool) {
-        // OpenAI debug mode not yet implemented
+    /// Log requests with API keys redacted, raw stream chunks and fallback tool call output to stderr
+    pub fn set_debug_mode(&mut self, debug: bool) {
+        self.debug.set_callback(debug.then(stderr_debug_callback));
     }
 
     pub fn debug_mode(&self) -> bool {
-        false
+        self.debug.is_enabled()
+    }
+
+    /// Send debug events to a callback instead of stderr, e.g. to forward them to `tracing`
+    pub fn set_debug_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(&DebugEvent) + Send + Sync + 'static,
+    {
+        self.debug.set_callback(Some(Arc::new(callback)));
     }
 
     pub async fn supports_tool_calls(&self) -> Result<bool, Box<dyn Error>> {
         Ok(true) // OpenAI models support native tool calling
     }
 
+    /// Check the API is reachable, the key is valid and the model exists by fetching the model
+    pub async fn health_check(&self) -> Result<(), Box<dyn Error>> {
+        let response = self
+            .request(Method::GET, format!("{}/models/{}", self.base_url, self.model))
+            .send_logged(&self.debug)
+            .await?;
+
+        if !response.status().is_success() {
+            let error_text = response.text().await?;
+            return Err(format!("OpenAI health check failed: {}", error_text).into());
+        }
+        Ok(())
+    }
+
     pub async fn get_available_models(&self) -> Result<Vec<OpenAIModel>, Box<dyn Error>> {
         let response = self
-            .client
-            .get("https://api.openai.com/v1/models")
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .send()
+            .request(Method::GET, format!("{}/models", self.base_url))
+            .send_logged(&self.debug)
             .await?;
 
         if !response.status().is_success() {
+            let status = response.status().as_u16();
             let error_text = response.text().await?;
-            return Err(format!("OpenAI API error: {}", error_text).into());
+            return Err(api_error("OpenAI", status, &error_text).into());
         }
 
         let models_response: OpenAIModelsResponse = response.json().await?;
         Ok(models_response.data)
     }
 
+    /// Embed texts with `/v1/embeddings` using the client's model, e.g. text-embedding-3-small
+    pub async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, Box<dyn Error>> {
+        let mut embeddings = Vec::with_capacity(texts.len());
+
+        // The API accepts at most 2048 inputs per request
+        for batch in texts.chunks(2048) {
+            let response = self
+                .request(Method::POST, format!("{}/embeddings", self.base_url))
+                .json(&serde_json::json!({
+                    "model": self.model,
+                    "input": batch,
+                }))
+                .send_logged(&self.debug)
+                .await?;
+
+            if !response.status().is_success() {
+                let status = response.status().as_u16();
+                let error_text = response.text().await?;
+                return Err(api_error("OpenAI", status, &error_text).into());
+            }
+
+            let mut embedding_response: OpenAIEmbeddingResponse = response.json().await?;
+            embedding_response.data.sort_by_key(|e| e.index);
+            embeddings.extend(embedding_response.data.into_iter().map(|e| e.embedding));
+        }
+
+        Ok(embeddings)
+    }
+
+    fn convert_messages(&self, messages: &[Message]) -> Vec<OpenAIMessage> {
+        let mut openai_messages = Vec::new();
+        let mut pending_tool_images = Vec::new();
+
+        for message in messages {
+            // Tool messages only carry text. Images returned by tools follow as a user message once
+            // the run of tool messages answering the assistant's tool calls is complete
+            if message.role != "tool" && !pending_tool_images.is_empty() {
+                openai_messages.push(tool_images_message(std::mem::take(&mut pending_tool_images), self.image_detail));
+            }
+            if message.role == "tool" && let Some(images) = &message.images {
+                pending_tool_images.extend(images.iter().cloned());
+            }
+            openai_messages.push(self.convert_to_openai_message(message));
+        }
+        if !pending_tool_images.is_empty() {
+            openai_messages.push(tool_images_message(pending_tool_images, self.image_detail));
+        }
+
+        openai_messages
+    }
+
     fn convert_to_openai_message(&self, message: &Message) -> OpenAIMessage {
-        // Check if this is a tool result message
-        if message.role == "tool" {
-            // For OpenAI, tool results need tool_call_id and content
-            // We'll extract the tool_call_id from our encoded format if present
-            let (tool_call_id, content) = if message.content.starts_with("TOOL_RESULT:") {
-                let parts: Vec<&str> = message.content.splitn(3, ':').collect();
-                if parts.len() == 3 {
-                    (Some(parts[1].to_string()), parts[2].to_string())
-                } else {
-                    (None, message.content.clone())
-                }
-            } else {
-                (None, message.content.clone())
-            };
+        let message = &message.with_inlined_documents();
 
+        // Tool results reference the call they answer by id
+        if message.role == "tool" {
             return OpenAIMessage {
                 role: Some(message.role.clone()),
-                content: Some(serde_json::Value::String(content)),
+                content: Some(serde_json::Value::String(message.content.clone())),
                 tool_calls: None,
-                tool_call_id,
+                tool_call_id: message.tool_call_id.clone(),
             };
         }
 
         // Convert tool calls if present
-        let tool_calls = if let Some(tc) = &message.tool_calls {
-            Some(tc.iter().map(|call| {
+        let tool_calls = message.tool_calls.as_ref().map(|tc| {
+            tc.iter().map(|call| {
                 OpenAIToolCall {
                     id: Some(call.id.clone().unwrap_or_else(|| format!("call_{}", "generated_id"))),
                     call_type: Some("function".to_string()),
@@ -157,39 +489,37 @@ impl OpenAIClient {
                         arguments: Some(serde_json::to_string(&call.function.arguments).unwrap_or_default()),
                     },
                 }
-            }).collect())
-        } else {
-            None
-        };
+            }).collect()
+        });
 
-        // Handle vision messages with images for OpenAI's structured content format
-        let content = if let Some(ref images) = message.images {
-            if !images.is_empty() {
-                // Create structured content array for OpenAI vision API
-                let mut content_items = vec![];
-                
-                // Add text content
-                if !message.content.is_empty() {
-                    content_items.push(serde_json::json!({
-                        "type": "text",
-                        "text": message.content
-                    }));
-                }
-                
-                // Add image content in OpenAI's base64 format
-                for image in images {
-                    content_items.push(serde_json::json!({
-                        "type": "image_url", 
-                        "image_url": {
-                            "url": format!("data:image/jpeg;base64,{}", image)
-                        }
-                    }));
-                }
-                
-                Some(serde_json::Value::Array(content_items))
-            } else {
-                Some(serde_json::Value::String(message.content.clone()))
+        // Handle vision and audio messages with OpenAI's structured content format
+        let images = message.images.iter().flatten();
+        let image_urls = message.image_urls.iter().flatten();
+        let audio = message.audio.iter().flatten();
+        let content = if images.clone().next().is_some() || image_urls.clone().next().is_some() || audio.clone().next().is_some() {
+            // Create structured content array for OpenAI vision API
+            let mut content_items = vec![];
+            
+            // Add text content
+            if !message.content.is_empty() {
+                content_items.push(serde_json::json!({
+                    "type": "text",
+                    "text": message.content
+                }));
+            }
+            
+            // Add image content in OpenAI's base64 format, remote images are passed by URL
+            for image in images {
+                content_items.push(image_url_content(image, self.image_detail));
             }
+            for url in image_urls {
+                content_items.push(remote_image_content(url, self.image_detail));
+            }
+            for audio in audio {
+                content_items.push(input_audio_content(audio));
+            }
+            
+            Some(serde_json::Value::Array(content_items))
         } else {
             Some(serde_json::Value::String(message.content.clone()))
         };
@@ -204,8 +534,22 @@ impl OpenAIClient {
 
     fn convert_tools_to_openai(&self) -> Vec<OpenAITool> {
         self.tools
+            .enabled_tools()
             .iter()
             .map(|tool| {
+                // Strict mode where the schema allows it, the tools it can't express are sent as before
+                if let Some(parameters) = self.strict_tools.then(|| tool.strict_parameters()).flatten() {
+                    return OpenAITool {
+                        tool_type: "function".to_string(),
+                        function: OpenAIToolFunction {
+                            name: tool.name.clone(),
+                            description: tool.description.clone(),
+                            parameters,
+                            strict: Some(true),
+                        },
+                    };
+                }
+
                 // Ensure the parameters have additionalProperties: false for OpenAI compatibility
                 let mut parameters = tool.parameters.clone();
                 if let Some(obj) = parameters.as_object_mut() {
@@ -218,6 +562,7 @@ impl OpenAIClient {
                         name: tool.name.clone(),
                         description: tool.description.clone(),
                         parameters,
+                        strict: None,
                     },
                 }
             })
@@ -228,384 +573,618 @@ impl OpenAIClient {
         &self,
         messages: &[Message],
     ) -> Result<Pin<Box<dyn Stream<Item = Result<ChatStreamItem, String>> + Send>>, Box<dyn Error>> {
-        let openai_messages: Vec<OpenAIMessage> = messages
-            .iter()
-            .map(|msg| self.convert_to_openai_message(msg))
-            .collect();
+        self.chat_stream(messages, None, None).await
+    }
+
+    pub async fn send_chat_request_with_options(
+        &self,
+        messages: &[Message],
+        options: Option<OpenAIOptions>,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<ChatStreamItem, String>> + Send>>, Box<dyn Error>> {
+        self.chat_stream(messages, None, options).await
+    }
+
+    /// Chat reply in JSON mode, or constrained to a schema with structured outputs, parsed and validated
+    pub async fn send_chat_request_structured(
+        &self,
+        messages: &[Message],
+        format: &ResponseFormat,
+    ) -> Result<serde_json::Value, Box<dyn Error>> {
+        let response_format = openai_response_format(format);
+
+        // JSON mode requires the word "JSON" to appear in the conversation
+        let messages = format.apply_instructions(messages);
+        let mut stream = self.chat_stream(&messages, Some(response_format), None).await?;
+        let mut full_response = String::new();
+
+        while let Some(item) = stream.next().await {
+            let item = item.map_err(|e| format!("Stream error: {}", e))?;
+            full_response.push_str(&item.content);
+            if item.done {
+                break;
+            }
+        }
+        Ok(format.parse(&full_response)?)
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[Message],
+        response_format: Option<serde_json::Value>,
+        options: Option<OpenAIOptions>,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<ChatStreamItem, String>> + Send>>, Box<dyn Error>> {
+        let debug = self.debug.with_tags(options.as_ref().and_then(|options| options.tags.clone()));
+        let extensions = options.as_ref().and_then(|options| options.extensions.clone());
+        let request = self.chat_request(messages, response_format, options)?;
+
+        let response = self
+            .request(Method::POST, format!("{}/chat/completions", self.base_url))
+            .header("content-type", "application/json")
+            .json(&request_body(&request, extensions.as_ref())?)
+            .send_logged(&debug)
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let error_text = response.text().await?;
+            return Err(chat_api_error("OpenAI", status, &error_text));
+        }
 
-        let request = OpenAIRequest {
+        let stream = debug.inspect_stream(response.bytes_stream());
+        
+        // Create a stateful stream parser with model for pricing
+        Ok(Box::pin(parse_stream(stream, OpenAIStreamParser::new(self.model.clone())).map(|item| {
+            item.map_err(|e| format!("Stream error: {}", e)).and_then(|item| item)
+        })))
+    }
+
+    /// The body `send_chat_request_with_options` would post to `/chat/completions`, without sending it
+    pub fn build_request(&self, messages: &[Message], options: Option<OpenAIOptions>) -> Result<serde_json::Value, Box<dyn Error>> {
+        let extensions = options.as_ref().and_then(|options| options.extensions.clone());
+        Ok(request_body(&self.chat_request(messages, None, options)?, extensions.as_ref())?)
+    }
+
+    fn chat_request(
+        &self,
+        messages: &[Message],
+        response_format: Option<serde_json::Value>,
+        options: Option<OpenAIOptions>,
+    ) -> Result<OpenAIRequest, Box<dyn Error>> {
+        validate_images(messages)?;
+        let messages = &*normalize_history(messages, HistoryRules::OPENAI)?;
+        let messages = &*fit_images(messages, ImageLimits::OPENAI)?;
+        let openai_messages = self.convert_messages(messages);
+        let options = options.unwrap_or_default();
+        let capabilities = self.model_capabilities();
+
+        let max_tokens = options.max_tokens.unwrap_or(4096);
+        let token_limit_param = options.token_limit_param.unwrap_or(capabilities.token_limit_param);
+        let reasoning_effort = options.reasoning_effort.or(self.reasoning_effort).filter(|_| capabilities.reasoning_effort);
+
+        Ok(OpenAIRequest {
             model: self.model.clone(),
             messages: openai_messages,
-            temperature: None,
-            // Use max_completion_tokens for o1 and gpt-5 models, max_tokens for others
-            max_tokens: if self.model.contains("o1") || self.model.contains("gpt-5") { None } else { Some(4096) },
-            max_completion_tokens: if self.model.contains("o1") || self.model.contains("gpt-5") { Some(4096) } else { None },
-            tools: if self.tools.is_empty() {
+            temperature: options.temperature.filter(|_| capabilities.temperature),
+            max_tokens: (token_limit_param == TokenLimitParam::MaxTokens).then_some(max_tokens),
+            max_completion_tokens: (token_limit_param == TokenLimitParam::MaxCompletionTokens).then_some(max_tokens),
+            reasoning_effort,
+            seed: options.seed,
+            n: options.n,
+            presence_penalty: options.presence_penalty,
+            frequency_penalty: options.frequency_penalty,
+            logit_bias: options.logit_bias,
+            user: options.user.or_else(|| options.tags.as_ref().and_then(|tags| tags.user_id()).map(str::to_string)),
+            tools: if !self.tools.has_enabled_tools() {
                 None
             } else {
                 Some(self.convert_tools_to_openai())
             },
             stream: Some(true),
             stream_options: Some(OpenAIStreamOptions { include_usage: true }),
-        };
+            response_format,
+        })
+    }
+
+    pub async fn send_chat_request_no_stream(
+        &self,
+        messages: &[Message],
+    ) -> Result<ChatResponse, Box<dyn Error>> {
+        self.send_chat_request_no_stream_with_options(messages, None).await
+    }
+
+    /// Complete reply from a single JSON response instead of an event stream
+    pub async fn send_chat_request_no_stream_with_options(
+        &self,
+        messages: &[Message],
+        options: Option<OpenAIOptions>,
+    ) -> Result<ChatResponse, Box<dyn Error>> {
+        let started = Instant::now();
+        let debug = self.debug.with_tags(options.as_ref().and_then(|options| options.tags.clone()));
+        let extensions = options.as_ref().and_then(|options| options.extensions.clone());
+        let mut request = self.chat_request(messages, None, options)?;
+        // Usage is always part of the response body
+        request.stream = None;
+        request.stream_options = None;
 
         let response = self
-            .client
-            .post("https://api.openai.com/v1/chat/completions")
-            .header("Authorization", format!("Bearer {}", self.api_key))
+            .request(Method::POST, format!("{}/chat/completions", self.base_url))
             .header("content-type", "application/json")
-            .json(&request)
-            .send()
+            .json(&request_body(&request, extensions.as_ref())?)
+            .send_logged(&debug)
             .await?;
 
         if !response.status().is_success() {
+            let status = response.status().as_u16();
             let error_text = response.text().await?;
-            return Err(format!("OpenAI API error: {}", error_text).into());
+            return Err(chat_api_error("OpenAI", status, &error_text));
         }
 
-        let stream = response.bytes_stream();
-        
-        // Create a stateful stream processor with model for pricing
-        Ok(Box::pin(OpenAIStreamProcessor::new(Box::pin(stream), self.model.clone())))
-    }
-
-    pub async fn send_chat_request_no_stream(
-        &self,
-        messages: &[Message],
-    ) -> Result<(String, Option<Vec<ToolCall>>), Box<dyn Error>> {
-        let mut full_response = String::new();
-        let mut tool_calls: Option<Vec<ToolCall>> = None;
-        let mut stream = self.send_chat_request(messages).await?;
+        let mut response: OpenAIResponse = response.json().await?;
+        // Tool calls are only read from the first completion, the extra ones requested with `n` give their text
+        response.choices.sort_by_key(|choice| choice.index);
+        let mut choices = response.choices.into_iter();
+        let choice = choices.next().filter(|choice| choice.index == 0).ok_or("No choices in OpenAI response")?;
+        let extra_choices = choices
+            .map(|choice| {
+                let content = choice.message.and_then(|message| message.content);
+                content.as_ref().and_then(|content| content.as_str()).unwrap_or_default().to_string()
+            })
+            .collect();
+        let message = choice.message.ok_or("No message in OpenAI response")?;
+        let tool_calls = message.tool_calls.map(|tool_calls| {
+            tool_calls
+                .into_iter()
+                .map(|tool_call| ToolCall {
+                    id: tool_call.id,
+                    function: crate::core::Function {
+                        name: tool_call.function.name.unwrap_or_default(),
+                        arguments: tool_call.function.arguments.and_then(|args| serde_json::from_str(&args).ok()).unwrap_or(serde_json::Value::Null),
+                    },
+                })
+                .collect()
+        });
 
-        while let Some(item) = stream.next().await {
-            let item = item.map_err(|e| format!("Stream error: {}", e))?;
-            if !item.content.is_empty() {
-                full_response.push_str(&item.content);
-            }
-            if let Some(tc) = item.tool_calls {
-                tool_calls = Some(tc);
-            }
-            if item.done {
-                return Ok((full_response, tool_calls));
-            }
-        }
-        Ok((full_response, tool_calls))
+        Ok(ChatResponse {
+            text: message.content.as_ref().and_then(|content| content.as_str()).unwrap_or_default().to_string(),
+            tool_calls,
+            usage: response.usage.map(|usage| openai_usage(&self.model, &usage)),
+            finish_reason: choice.finish_reason,
+            latency: started.elapsed(),
+            choices: extra_choices,
+        })
     }
 
     pub async fn handle_tool_calls(&self, tool_calls: Vec<ToolCall>) -> Vec<Message> {
         let mut tool_responses = Vec::new();
         for tool_call in tool_calls {
-            if let Some(tool) = self
-                .tools
-                .iter()
-                .find(|t| t.name == tool_call.function.name)
-            {
-                let result = (tool.function)(tool_call.function.arguments.clone());
-                
-                // Use the tool call ID if available, otherwise use "unknown"
-                let tool_id = tool_call.id.unwrap_or_else(|| "unknown".to_string());
-                
-                // Create a message that can be identified as a tool result
-                // Use the encoded format: TOOL_RESULT:tool_id:result_content
-                tool_responses.push(Message {
-                    role: "tool".to_string(),
-                    content: format!("TOOL_RESULT:{}:{}", tool_id, result),
-                    images: None,
-                    tool_calls: None,
-                });
-            }
+            // Every call needs a result, even one naming an unknown or disabled tool
+            let output = match self.tools.get(&tool_call.function.name) {
+                Some(tool) => tool.invoke_with_attachments(tool_call.function.arguments.clone()),
+                None => ToolOutput::error(format!("Error: unknown tool '{}'", tool_call.function.name)),
+            };
+            tool_responses.push(self.tool_response_message(&tool_call, output).await);
         }
         tool_responses
     }
 
+    /// Format a tool result as a message for conversation continuation
+    pub async fn tool_response_message(&self, tool_call: &ToolCall, output: ToolOutput) -> Message {
+        Message::tool_result(tool_call, output)
+    }
+
     pub async fn process_fallback_response(&self, content: &str) -> (String, Option<Vec<ToolCall>>) {
         // OpenAI doesn't need fallback processing since it has native tool support
         (content.to_string(), None)
     }
-}
 
-// Custom stream processor for OpenAI streaming responses
-struct OpenAIStreamProcessor {
-    stream: Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>,
-    accumulated_content: String,
-    accumulated_tool_calls: HashMap<usize, ToolCall>,
-    // Track tool arguments being accumulated: tool_index -> accumulated_json_string
-    accumulating_tool_args: HashMap<usize, String>,
-    // Buffer for incomplete SSE events that span chunk boundaries
-    buffer: String,
-    done: bool,
-    usage: Option<TokenUsage>,
-    model: String,
-}
+    /// Speech to text with `/v1/audio/transcriptions`, whisper-1 unless `options.model` is set
+    pub async fn transcribe(&self, audio: &AudioInput, options: &TranscriptionOptions) -> Result<Transcription, Box<dyn Error>> {
+        self.audio_request("transcriptions", audio, options).await
+    }
 
-impl OpenAIStreamProcessor {
-    fn new(stream: Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>, model: String) -> Self {
-        Self {
-            stream,
-            accumulated_content: String::new(),
-            accumulated_tool_calls: HashMap::new(),
-            accumulating_tool_args: HashMap::new(),
-            buffer: String::new(),
-            done: false,
-            usage: None,
-            model,
+    /// Speech in any language to English text with `/v1/audio/translations` (whisper-1 only)
+    pub async fn translate_audio(&self, audio: &AudioInput, options: &TranscriptionOptions) -> Result<Transcription, Box<dyn Error>> {
+        self.audio_request("translations", audio, options).await
+    }
+
+    /// Transcript deltas as they are recognized, needs gpt-4o-transcribe or gpt-4o-mini-transcribe
+    pub async fn transcribe_stream(
+        &self,
+        audio: &AudioInput,
+        options: &TranscriptionOptions,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<String, String>> + Send>>, Box<dyn Error>> {
+        let model = options.model.clone().unwrap_or_else(|| "gpt-4o-transcribe".to_string());
+        let (content_type, body) = audio_form(audio, options, &model, &[("stream", "true")]);
+
+        let response = self
+            .request(Method::POST, format!("{}/audio/transcriptions", self.base_url))
+            .header("content-type", content_type)
+            .body(body)
+            .send_logged(&self.debug)
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let error_text = response.text().await?;
+            return Err(api_error("OpenAI", status, &error_text).into());
         }
+
+        let stream = parse_stream(response.bytes_stream(), SseParser::new()).filter_map(|event| async move {
+            match event {
+                Ok(event) => {
+                    let event = serde_json::from_str::<serde_json::Value>(&event.data).ok()?;
+                    (event["type"] == "transcript.text.delta").then(|| Ok(event["delta"].as_str().unwrap_or("").to_string()))
+                }
+                Err(e) => Some(Err(e)),
+            }
+        });
+
+        Ok(Box::pin(stream))
     }
 
-    // Calculate cost based on token usage
-    fn calculate_cost(&self, prompt_tokens: u32, completion_tokens: u32) -> f64 {
-        let (input_price, output_price) = get_openai_model_pricing(&self.model);
-        eprintln!("Debug: StreamProcessor model '{}' pricing: input=${:.9}, output=${:.9}", self.model, input_price, output_price);
-        let cost = (prompt_tokens as f64 * input_price) + (completion_tokens as f64 * output_price);
-        eprintln!("Debug: StreamProcessor cost calculation: {} * {:.9} + {} * {:.9} = {:.9}", prompt_tokens, input_price, completion_tokens, output_price, cost);
-        cost
+    /// Generate images with `/v1/images/generations`, gpt-image-1 unless `options.model` is set.
+    /// gpt-image-1 returns base64 data, the dall-e models return URLs
+    pub async fn generate_image(&self, prompt: &str, options: &ImageGenerationOptions) -> Result<ImageGeneration, Box<dyn Error>> {
+        let model = options.model.clone().unwrap_or_else(|| "gpt-image-1".to_string());
+        let mut body = serde_json::json!({
+            "model": model,
+            "prompt": prompt,
+        });
+        if let Some(size) = &options.size {
+            body["size"] = serde_json::json!(size);
+        }
+        if let Some(quality) = &options.quality {
+            body["quality"] = serde_json::json!(quality);
+        }
+        if let Some(format) = &options.format {
+            body["output_format"] = serde_json::json!(format);
+        }
+        if let Some(n) = options.n {
+            body["n"] = serde_json::json!(n);
+        }
+
+        let response = self
+            .request(Method::POST, format!("{}/images/generations", self.base_url))
+            .json(&body)
+            .send_logged(&self.debug)
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let error_text = response.text().await?;
+            return Err(api_error("OpenAI", status, &error_text).into());
+        }
+
+        let image_response: OpenAIImageResponse = response.json().await?;
+        let images: Vec<GeneratedImage> = image_response
+            .data
+            .into_iter()
+            .map(|image| GeneratedImage {
+                b64_data: image.b64_json,
+                url: image.url,
+                revised_prompt: image.revised_prompt,
+            })
+            .collect();
+
+        let usage = match image_response.usage {
+            // gpt-image-1 bills text input at $5/1M and image output at $40/1M tokens
+            Some(usage) => {
+                let mut token_usage = TokenUsage::with_tokens(usage.input_tokens, usage.output_tokens);
+                token_usage.cost_usd = Some(usage.input_tokens as f64 * 5.00e-6 + usage.output_tokens as f64 * 40.00e-6);
+                Some(token_usage)
+            }
+            None => {
+                let size = options.size.as_deref().unwrap_or("1024x1024");
+                let quality = options.quality.as_deref().unwrap_or("standard");
+                get_openai_image_pricing(&model, size, quality).map(|price| TokenUsage {
+                    cost_usd: Some(price * images.len() as f64),
+                    ..TokenUsage::new()
+                })
+            }
+        };
+
+        Ok(ImageGeneration { images, usage })
     }
-}
 
-impl Stream for OpenAIStreamProcessor {
-    type Item = Result<ChatStreamItem, String>;
+    /// Classify text or an image with `/v1/moderations`. Uses omni-moderation-latest unless
+    /// the client's model is a moderation model
+    pub async fn moderate(&self, input: impl Into<ModerationInput>) -> Result<ModerationResult, Box<dyn Error>> {
+        let model = if self.model.contains("moderation") { self.model.as_str() } else { "omni-moderation-latest" };
+        let input = match input.into() {
+            ModerationInput::Text(text) => serde_json::json!([{ "type": "text", "text": text }]),
+            ModerationInput::ImageUrl(url) => serde_json::json!([{ "type": "image_url", "image_url": { "url": url } }]),
+        };
 
-    fn poll_next(
-        mut self: Pin<&mut Self>,
-        cx: &mut std::task::Context<'_>,
-    ) -> std::task::Poll<Option<Self::Item>> {
-        if self.done {
-            return std::task::Poll::Ready(None);
+        let response = self
+            .request(Method::POST, format!("{}/moderations", self.base_url))
+            .json(&serde_json::json!({
+                "model": model,
+                "input": input,
+            }))
+            .send_logged(&self.debug)
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let error_text = response.text().await?;
+            return Err(api_error("OpenAI", status, &error_text).into());
         }
 
+        let moderation_response: OpenAIModerationResponse = response.json().await?;
+        let result = moderation_response.results.into_iter().next().ok_or("No moderation result in response")?;
+        let mut categories: Vec<String> = result
+            .categories
+            .into_iter()
+            .filter(|(_, flagged)| *flagged)
+            .map(|(category, _)| category)
+            .collect();
+        categories.sort();
+
+        Ok(ModerationResult {
+            flagged: result.flagged,
+            categories,
+            category_scores: result.category_scores,
+        })
+    }
+
+    /// Upload a file with `/v1/files`, e.g. JSONL training data with purpose "fine-tune"
+    pub async fn upload_file(&self, path: &str, purpose: &str) -> Result<OpenAIFile, Box<dyn Error>> {
+        let data = std::fs::read(path)?;
+        let filename = std::path::Path::new(path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| "upload.jsonl".to_string());
+        self.upload_file_bytes(data, &filename, purpose).await
+    }
+
+    pub async fn upload_file_bytes(&self, data: Vec<u8>, filename: &str, purpose: &str) -> Result<OpenAIFile, Box<dyn Error>> {
+        let (content_type, body) = multipart_form(&[("purpose", purpose.to_string())], filename, &data);
+
+        let response = self
+            .request(Method::POST, format!("{}/files", self.base_url))
+            .header("content-type", content_type)
+            .body(body)
+            .send_logged(&self.debug)
+            .await?;
+
+        Self::parse_response(response).await
+    }
+
+    pub async fn list_files(&self) -> Result<Vec<OpenAIFile>, Box<dyn Error>> {
+        let files: OpenAIListResponse<OpenAIFile> = self.get(&format!("{}/files", self.base_url)).await?;
+        Ok(files.data)
+    }
+
+    pub async fn delete_file(&self, file_id: &str) -> Result<(), Box<dyn Error>> {
+        let response = self
+            .request(Method::DELETE, format!("{}/files/{}", self.base_url, file_id))
+            .send_logged(&self.debug)
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let error_text = response.text().await?;
+            return Err(api_error("OpenAI", status, &error_text).into());
+        }
+        Ok(())
+    }
+
+    /// Raw content of an uploaded or generated file, e.g. the results file of a fine-tuning job
+    pub async fn file_content(&self, file_id: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        let response = self
+            .request(Method::GET, format!("{}/files/{}/content", self.base_url, file_id))
+            .send_logged(&self.debug)
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let error_text = response.text().await?;
+            return Err(api_error("OpenAI", status, &error_text).into());
+        }
+        Ok(response.bytes().await?.to_vec())
+    }
+
+    /// Start fine-tuning `model` on an uploaded training file
+    pub async fn create_fine_tuning_job(
+        &self,
+        training_file: &str,
+        model: &str,
+        options: &FineTuningOptions,
+    ) -> Result<FineTuningJob, Box<dyn Error>> {
+        let mut body = serde_json::to_value(options)?;
+        body["training_file"] = serde_json::json!(training_file);
+        body["model"] = serde_json::json!(model);
+
+        let response = self
+            .request(Method::POST, format!("{}/fine_tuning/jobs", self.base_url))
+            .json(&body)
+            .send_logged(&self.debug)
+            .await?;
+
+        Self::parse_response(response).await
+    }
+
+    pub async fn get_fine_tuning_job(&self, job_id: &str) -> Result<FineTuningJob, Box<dyn Error>> {
+        self.get(&format!("{}/fine_tuning/jobs/{}", self.base_url, job_id)).await
+    }
+
+    /// Most recent jobs first
+    pub async fn list_fine_tuning_jobs(&self) -> Result<Vec<FineTuningJob>, Box<dyn Error>> {
+        let jobs: OpenAIListResponse<FineTuningJob> = self.get(&format!("{}/fine_tuning/jobs", self.base_url)).await?;
+        Ok(jobs.data)
+    }
+
+    pub async fn cancel_fine_tuning_job(&self, job_id: &str) -> Result<FineTuningJob, Box<dyn Error>> {
+        let response = self
+            .request(Method::POST, format!("{}/fine_tuning/jobs/{}/cancel", self.base_url, job_id))
+            .send_logged(&self.debug)
+            .await?;
+
+        Self::parse_response(response).await
+    }
+
+    /// Progress messages of a job, most recent first
+    pub async fn fine_tuning_job_events(&self, job_id: &str) -> Result<Vec<FineTuningEvent>, Box<dyn Error>> {
+        let events: OpenAIListResponse<FineTuningEvent> =
+            self.get(&format!("{}/fine_tuning/jobs/{}/events", self.base_url, job_id)).await?;
+        Ok(events.data)
+    }
+
+    /// Poll a job until it succeeds, fails or is cancelled, calling `on_update` with each status
+    pub async fn wait_for_fine_tuning_job<F>(
+        &self,
+        job_id: &str,
+        poll_interval: std::time::Duration,
+        mut on_update: F,
+    ) -> Result<FineTuningJob, Box<dyn Error>>
+    where
+        F: FnMut(&FineTuningJob),
+    {
         loop {
-            match self.stream.as_mut().poll_next(cx) {
-                std::task::Poll::Ready(Some(chunk_result)) => {
-                    match chunk_result {
-                        Ok(chunk) => {
-                            let chunk_str = String::from_utf8_lossy(&chunk);
-                            
-                            // Add new chunk to buffer
-                            self.buffer.push_str(&chunk_str);
-                            
-                            // Collect all content from complete SSE events in buffer
-                            let mut accumulated_content = String::new();
-                            let mut has_any_tool_calls = false;
-                            
-                            // Process complete SSE events from buffer
-                            while let Some(event_end) = self.buffer.find("\n\n") {
-                                let event = self.buffer[..event_end].to_string();
-                                self.buffer = self.buffer[event_end + 2..].to_string(); // Remove processed event + \n\n
-                                
-                                // Parse each line in the event
-                                for line in event.lines() {
-                                    if line.starts_with("data: ") {
-                                        let json_str = &line[6..]; // Remove "data: " prefix
-                                    
-                                    if json_str == "[DONE]" {
-                                        self.done = true;
-                                        let final_tool_calls = if !self.accumulated_tool_calls.is_empty() {
-                                            let mut tool_calls = Vec::new();
-                                            for (i, mut tool_call) in self.accumulated_tool_calls.clone() {
-                                                // Parse the accumulated argument string
-                                                if let Some(args_str) = self.accumulating_tool_args.get(&i) {
-                                                    if !args_str.is_empty() {
-                                                        if let Ok(args) = serde_json::from_str::<serde_json::Value>(args_str) {
-                                                            tool_call.function.arguments = args;
-                                                        }
-                                                    }
-                                                }
-                                                tool_calls.push(tool_call);
-                                            }
-                                            Some(tool_calls)
-                                        } else {
-                                            None
-                                        };
-                                        
-                                        return std::task::Poll::Ready(Some(Ok(ChatStreamItem {
-                                            content: String::new(),
-                                            tool_calls: final_tool_calls,
-                                            done: true,
-                                            usage: self.usage.clone(),
-                                        })));
-                                    }
-                                    
-                                    match serde_json::from_str::<OpenAIStreamChunk>(json_str) {
-                                        Ok(chunk) => {
-                                            // Extract usage information if available
-                                            if let Some(usage) = &chunk.usage {
-                                                let cost_usd = Some(self.calculate_cost(usage.prompt_tokens, usage.completion_tokens));
-                                                self.usage = Some(TokenUsage {
-                                                    prompt_tokens: Some(usage.prompt_tokens),
-                                                    completion_tokens: Some(usage.completion_tokens),
-                                                    total_tokens: Some(usage.total_tokens),
-                                                    cost_usd,
-                                                });
-                                            }
-                                            
-                                            if let Some(choice) = chunk.choices.first() {
-                                                if let Some(delta) = &choice.delta {
-                                                    // Handle content delta
-                                                    if let Some(delta_content) = &delta.content {
-                                                        if let Some(text) = delta_content.as_str() {
-                                                            accumulated_content.push_str(text);
-                                                            self.accumulated_content.push_str(text);
-                                                        }
-                                                    }
-                                                    
-                                                    // Handle tool call deltas
-                                                    if let Some(tool_calls) = &delta.tool_calls {
-                                                        has_any_tool_calls = true;
-                                                        for (i, tool_call) in tool_calls.iter().enumerate() {
-                                                            // Ensure tool call entry exists
-                                                            if !self.accumulated_tool_calls.contains_key(&i) {
-                                                                self.accumulated_tool_calls.insert(i, ToolCall {
-                                                                    id: tool_call.id.clone(),
-                                                                    function: crate::core::Function {
-                                                                        name: tool_call.function.name.clone().unwrap_or_default(),
-                                                                        arguments: serde_json::Value::Null,
-                                                                    },
-                                                                });
-                                                            }
-                                                            
-                                                            // Accumulate function arguments as string chunks
-                                                            if let Some(ref args_str) = tool_call.function.arguments {
-                                                                if !args_str.is_empty() {
-                                                                    let accumulated_args = self.accumulating_tool_args.entry(i).or_insert_with(String::new);
-                                                                    accumulated_args.push_str(args_str);
-                                                                }
-                                                            }
-                                                            
-                                                            // Update name if provided
-                                                            if let Some(ref name) = tool_call.function.name {
-                                                                if !name.is_empty() {
-                                                                    if let Some(entry) = self.accumulated_tool_calls.get_mut(&i) {
-                                                                        entry.function.name = name.clone();
-                                                                    }
-                                                                }
-                                                            }
-                                                            
-                                                            // Update ID if provided
-                                                            if let Some(ref id) = tool_call.id {
-                                                                if !id.is_empty() {
-                                                                    if let Some(entry) = self.accumulated_tool_calls.get_mut(&i) {
-                                                                        entry.id = Some(id.clone());
-                                                                    }
-                                                                }
-                                                            }
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                        }
-                                        Err(e) => {
-                                            return std::task::Poll::Ready(Some(Err(format!("JSON parse error: {}", e))));
-                                        }
-                                    }
-                                    } // End of line processing
-                                } // End of event.lines() loop
-                            } // End of while let Some(event_end) loop
-                            
-                            // Return accumulated content from all processed events
-                            if !accumulated_content.is_empty() || has_any_tool_calls {
-                                return std::task::Poll::Ready(Some(Ok(ChatStreamItem {
-                                    content: accumulated_content,
-                                    tool_calls: None, // Don't return partial tool calls
-                                    done: false,
-                                    usage: None,
-                                })));
-                            }
-                        }
-                        Err(e) => {
-                            return std::task::Poll::Ready(Some(Err(format!("Stream error: {}", e))));
-                        }
-                    }
-                }
-                std::task::Poll::Ready(None) => {                    
-                    // Process any remaining data in the buffer before ending
-                    if !self.buffer.is_empty() {
-                        let buffer_clone = self.buffer.clone();
-                        for line in buffer_clone.lines() {
-                            if line.starts_with("data: ") {
-                                let json_str = &line[6..];
-                                
-                                if json_str == "[DONE]" {
-                                    // Stream done signal found in buffer
-                                } else if !json_str.is_empty() {
-                                    // Process this final chunk
-                                    match serde_json::from_str::<OpenAIStreamChunk>(json_str) {
-                                        Ok(chunk) => {
-                                            if let Some(choice) = chunk.choices.first() {
-                                                if let Some(delta) = &choice.delta {
-                                                    if let Some(tool_calls) = &delta.tool_calls {
-                                                        for (i, tool_call) in tool_calls.iter().enumerate() {
-                                                            if let Some(ref args_str) = tool_call.function.arguments {
-                                                                if !args_str.is_empty() {
-                                                                    let accumulated_args = self.accumulating_tool_args.entry(i).or_insert_with(String::new);
-                                                                    accumulated_args.push_str(args_str);
-                                                                }
-                                                            }
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                        }
-                                        Err(_) => {
-                                            // Failed to parse final buffer JSON, ignore
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    // Process any remaining data in buffer before ending
-                    let buffer_content = self.buffer.clone();
-                    if !buffer_content.is_empty() {
-                        for line in buffer_content.lines() {
-                            if line.starts_with("data: ") {
-                                let json_str = &line[6..];
-                                if json_str != "[DONE]" && !json_str.is_empty() {
-                                    if let Ok(chunk) = serde_json::from_str::<OpenAIStreamChunk>(json_str) {
-                                        if let Some(usage) = &chunk.usage {
-                                            let cost_usd = Some(self.calculate_cost(usage.prompt_tokens, usage.completion_tokens));
-                                            self.usage = Some(TokenUsage {
-                                                prompt_tokens: Some(usage.prompt_tokens),
-                                                completion_tokens: Some(usage.completion_tokens),
-                                                total_tokens: Some(usage.total_tokens),
-                                                cost_usd,
-                                            });
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    
-                    self.done = true;
-                    let final_tool_calls = if !self.accumulated_tool_calls.is_empty() {
-                        let mut tool_calls = Vec::new();
-                        for (i, mut tool_call) in self.accumulated_tool_calls.clone() {
-                            // Parse the accumulated argument string when stream ends
-                            if let Some(args_str) = self.accumulating_tool_args.get(&i) {
-                                if !args_str.is_empty() {
-                                    if let Ok(args) = serde_json::from_str::<serde_json::Value>(args_str) {
-                                        tool_call.function.arguments = args;
-                                    }
-                                }
-                            }
-                            tool_calls.push(tool_call);
-                        }
-                        Some(tool_calls)
-                    } else {
-                        None
-                    };
-                    
-                    return std::task::Poll::Ready(Some(Ok(ChatStreamItem {
-                        content: String::new(),
-                        tool_calls: final_tool_calls,
-                        done: true,
-                        usage: self.usage.clone(),
-                    })));
-                }
-                std::task::Poll::Pending => {
-                    return std::task::Poll::Pending;
-                }
+            let job = self.get_fine_tuning_job(job_id).await?;
+            on_update(&job);
+            if job.is_finished() {
+                return Ok(job);
             }
+            tokio::time::sleep(poll_interval).await;
+        }
+    }
+
+    // Request with the auth, organization, project and extra headers
+    fn request(&self, method: Method, url: impl reqwest::IntoUrl) -> reqwest::RequestBuilder {
+        let mut request_builder = self
+            .client
+            .request(method, url)
+            .header("Authorization", format!("Bearer {}", self.api_key));
+        if let Some(organization) = &self.organization {
+            request_builder = request_builder.header("OpenAI-Organization", organization);
+        }
+        if let Some(project) = &self.project {
+            request_builder = request_builder.header("OpenAI-Project", project);
+        }
+        for (name, value) in &self.extra_headers {
+            request_builder = request_builder.header(name, value);
+        }
+        request_builder
+    }
+
+    async fn get<T: serde::de::DeserializeOwned>(&self, url: &str) -> Result<T, Box<dyn Error>> {
+        let response = self
+            .request(Method::GET, url)
+            .send_logged(&self.debug)
+            .await?;
+
+        Self::parse_response(response).await
+    }
+
+    async fn parse_response<T: serde::de::DeserializeOwned>(response: reqwest::Response) -> Result<T, Box<dyn Error>> {
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let error_text = response.text().await?;
+            return Err(api_error("OpenAI", status, &error_text).into());
+        }
+        Ok(response.json().await?)
+    }
+
+    async fn audio_request(&self, endpoint: &str, audio: &AudioInput, options: &TranscriptionOptions) -> Result<Transcription, Box<dyn Error>> {
+        let model = options.model.clone().unwrap_or_else(|| "whisper-1".to_string());
+        // verbose_json adds the detected language and duration but only whisper-1 supports it
+        let response_format = if model == "whisper-1" { "verbose_json" } else { "json" };
+        let (content_type, body) = audio_form(audio, options, &model, &[("response_format", response_format)]);
+
+        let response = self
+            .request(Method::POST, format!("{}/audio/{}", self.base_url, endpoint))
+            .header("content-type", content_type)
+            .body(body)
+            .send_logged(&self.debug)
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let error_text = response.text().await?;
+            return Err(api_error("OpenAI", status, &error_text).into());
         }
+
+        let json: serde_json::Value = response.json().await?;
+        Ok(Transcription {
+            text: json["text"].as_str().unwrap_or("").to_string(),
+            language: json["language"].as_str().map(|language| language.to_string()),
+            duration: json["duration"].as_f64(),
+        })
+    }
+}
+
+impl Transcriber for OpenAIClient {
+    fn transcribe<'a>(&'a self, audio: &'a AudioInput, options: &'a TranscriptionOptions) -> TranscriptionFuture<'a> {
+        Box::pin(OpenAIClient::transcribe(self, audio, options))
+    }
+
+    fn translate_audio<'a>(&'a self, audio: &'a AudioInput, options: &'a TranscriptionOptions) -> TranscriptionFuture<'a> {
+        Box::pin(OpenAIClient::translate_audio(self, audio, options))
     }
-}
\ No newline at end of file
+}
+
+// Blocks content the moderation endpoint flags
+impl Guardrail for OpenAIClient {
+    fn check<'a>(&'a self, _stage: GuardrailStage, text: &'a str) -> GuardrailFuture<'a> {
+        Box::pin(async move {
+            match self.moderate(text).await {
+                Ok(result) if result.flagged => GuardrailVerdict::Block(format!("flagged for {}", result.categories.join(", "))),
+                Ok(_) => GuardrailVerdict::Allow,
+                Err(e) => GuardrailVerdict::Flag(format!("moderation unavailable: {}", e)),
+            }
+        })
+    }
+}
+
+// multipart/form-data body with the audio file and the options as text fields
+fn audio_form(audio: &AudioInput, options: &TranscriptionOptions, model: &str, extra_fields: &[(&str, &str)]) -> (String, Vec<u8>) {
+    let mut fields: Vec<(&str, String)> = vec![("model", model.to_string())];
+    if let Some(language) = &options.language {
+        fields.push(("language", language.clone()));
+    }
+    if let Some(prompt) = &options.prompt {
+        fields.push(("prompt", prompt.clone()));
+    }
+    if let Some(temperature) = options.temperature {
+        fields.push(("temperature", temperature.to_string()));
+    }
+    fields.extend(extra_fields.iter().map(|(name, value)| (*name, value.to_string())));
+
+    multipart_form(&fields, &audio.filename, &audio.data)
+}
+
+// multipart/form-data body with text fields followed by a `file` part, returns the content type and body
+fn multipart_form(fields: &[(&str, String)], filename: &str, data: &[u8]) -> (String, Vec<u8>) {
+    let boundary = format!("mono-ai-{}", uuid::Uuid::new_v4().simple());
+
+    let mut body = Vec::new();
+    for (name, value) in fields {
+        body.extend_from_slice(format!("--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n", boundary, name, value).as_bytes());
+    }
+    let filename = filename.replace('"', "");
+    body.extend_from_slice(
+        format!("--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\nContent-Type: application/octet-stream\r\n\r\n", boundary, filename).as_bytes(),
+    );
+    body.extend_from_slice(data);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+
+    (format!("multipart/form-data; boundary={}", boundary), body)
+}
+
+fn tool_images_message(images: Vec<String>, detail: Option<ImageDetail>) -> OpenAIMessage {
+    let mut content_items = vec![serde_json::json!({
+        "type": "text",
+        "text": "Images returned by the tool calls above:"
+    })];
+    for image in images {
+        content_items.push(image_url_content(&image, detail));
+    }
+
+    OpenAIMessage {
+        role: Some("user".to_string()),
+        content: Some(serde_json::Value::Array(content_items)),
+        tool_calls: None,
+        tool_call_id: None,
+    }
+}
@@ -1,5 +1,9 @@
 pub mod client;
+pub mod options;
+pub mod stream;
 pub mod types;
 
 pub use client::OpenAIClient;
-pub use types::*;
\ No newline at end of file
+pub use options::*;
+pub use stream::OpenAIStreamParser;
+pub use types::*;
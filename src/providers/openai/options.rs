@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+use crate::core::{GenerationOptions, RequestTags};
+
+/// Per-request settings, unset fields fall back to the client's settings and the model's defaults
+#[derive(Debug, Default, Clone)]
+pub struct OpenAIOptions {
+    pub max_tokens: Option<u32>, // Output limit, defaults to 4096
+    pub token_limit_param: Option<TokenLimitParam>, // Overrides the model table, e.g. for new or fine-tuned models
+    pub reasoning_effort: Option<ReasoningEffort>,
+    pub temperature: Option<f32>, // Ignored by reasoning models
+    pub seed: Option<i64>,
+    pub n: Option<u32>,
+    pub presence_penalty: Option<f32>,
+    pub frequency_penalty: Option<f32>,
+    pub logit_bias: Option<HashMap<u32, f32>>,
+    pub user: Option<String>,
+    pub tags: Option<RequestTags>, // Reported in debug logs, the user ID tag is sent as `user`
+    pub extensions: Option<Map<String, Value>>, // Merged into the request body, see `GenerationOptions::extensions`
+}
+
+impl From<&GenerationOptions> for OpenAIOptions {
+    fn from(options: &GenerationOptions) -> Self {
+        Self {
+            max_tokens: options.max_tokens,
+            temperature: options.temperature,
+            seed: options.seed,
+            n: options.n,
+            presence_penalty: options.presence_penalty,
+            frequency_penalty: options.frequency_penalty,
+            logit_bias: options.logit_bias.clone(),
+            user: options.end_user(),
+            tags: options.tags.clone(),
+            extensions: options.extensions.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Request field carrying the output limit. Reasoning models reject `max_tokens`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenLimitParam {
+    MaxTokens,
+    MaxCompletionTokens,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningEffort {
+    Minimal, // gpt-5 family only
+    Low,
+    Medium,
+    High,
+}
+
+/// Request parameters a model accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpenAIModelCapabilities {
+    pub token_limit_param: TokenLimitParam,
+    pub reasoning_effort: bool,
+    pub temperature: bool,
+}
+
+impl OpenAIModelCapabilities {
+    /// Capabilities by model family, fine-tuned models (`ft:<base>:...`) follow their base model
+    pub fn for_model(model: &str) -> Self {
+        let base = model.strip_prefix("ft:").unwrap_or(model);
+        let family = |prefix: &str| base == prefix || base.starts_with(&format!("{}-", prefix));
+
+        let reasoning = |reasoning_effort: bool| Self {
+            token_limit_param: TokenLimitParam::MaxCompletionTokens,
+            reasoning_effort,
+            temperature: false,
+        };
+
+        if family("o1-mini") || family("o1-preview") {
+            reasoning(false)
+        } else if family("o1") || family("o3") || family("o4-mini") || family("codex-mini") {
+            reasoning(true)
+        } else if family("gpt-5-chat") {
+            Self {
+                token_limit_param: TokenLimitParam::MaxCompletionTokens,
+                reasoning_effort: false,
+                temperature: true,
+            }
+        } else if family("gpt-5") {
+            reasoning(true)
+        } else {
+            Self {
+                token_limit_param: TokenLimitParam::MaxTokens,
+                reasoning_effort: false,
+                temperature: true,
+            }
+        }
+    }
+}
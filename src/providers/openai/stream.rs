@@ -0,0 +1,263 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::core::{ChatStreamItem, ToolCall, TokenUsage, SseParser, StreamParser};
+use super::client::openai_usage;
+use super::types::*;
+
+/// Parser for the OpenAI chat completions event stream. Content from all events in a chunk
+/// is reported as one item, tool calls and usage come with the final item. Extra completions
+/// requested with `n` end with their own done item, tool calls are only read from the first
+pub struct OpenAIStreamParser {
+    sse: SseParser,
+    accumulated_tool_calls: HashMap<usize, ToolCall>,
+    // Track tool arguments being accumulated: tool_index -> accumulated_json_string
+    accumulating_tool_args: HashMap<usize, String>,
+    done: bool,
+    usage: Option<TokenUsage>,
+    model: String,
+    // Content of the chunk being parsed, for the first completion and the extra ones requested with `n`
+    chunk_content: String,
+    choice_content: BTreeMap<usize, String>,
+    choice_finish_reasons: BTreeMap<usize, String>, // Extra completions that finished in this chunk
+    has_any_tool_calls: bool,
+    finish_reason: Option<String>,
+}
+
+impl OpenAIStreamParser {
+    /// `model` picks the prices for the cost in the reported usage
+    pub fn new(model: String) -> Self {
+        Self {
+            sse: SseParser::new(),
+            accumulated_tool_calls: HashMap::new(),
+            accumulating_tool_args: HashMap::new(),
+            done: false,
+            usage: None,
+            model,
+            chunk_content: String::new(),
+            choice_content: BTreeMap::new(),
+            choice_finish_reasons: BTreeMap::new(),
+            has_any_tool_calls: false,
+            finish_reason: None,
+        }
+    }
+
+    fn handle_data(&mut self, data: &str, items: &mut Vec<Result<ChatStreamItem, String>>) {
+        if data == "[DONE]" {
+            self.finish_stream(items);
+            return;
+        }
+
+        let chunk = match serde_json::from_str::<OpenAIStreamChunk>(data) {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                items.push(Err(format!("JSON parse error: {}", e)));
+                return;
+            }
+        };
+
+        // Extract usage information if available
+        if let Some(usage) = &chunk.usage {
+            self.usage = Some(openai_usage(&self.model, usage));
+        }
+
+        for choice in &chunk.choices {
+            if choice.index > 0 {
+                if let Some(text) = choice.delta.as_ref().and_then(|delta| delta.content.as_ref()).and_then(|content| content.as_str()) {
+                    self.choice_content.entry(choice.index as usize).or_default().push_str(text);
+                }
+                if let Some(reason) = &choice.finish_reason {
+                    self.choice_finish_reasons.insert(choice.index as usize, reason.clone());
+                }
+                continue;
+            }
+            if let Some(reason) = &choice.finish_reason {
+                self.finish_reason = Some(reason.clone());
+            }
+            let Some(delta) = &choice.delta else { continue };
+
+            if let Some(text) = delta.content.as_ref().and_then(|content| content.as_str()) {
+                self.chunk_content.push_str(text);
+            }
+
+            if let Some(tool_calls) = &delta.tool_calls {
+                self.has_any_tool_calls = true;
+                for (i, tool_call) in tool_calls.iter().enumerate() {
+                    // Ensure tool call entry exists
+                    let entry = self.accumulated_tool_calls.entry(i).or_insert_with(|| ToolCall {
+                        id: tool_call.id.clone(),
+                        function: crate::core::Function {
+                            name: tool_call.function.name.clone().unwrap_or_default(),
+                            arguments: serde_json::Value::Null,
+                        },
+                    });
+
+                    // Update name and ID if provided
+                    if let Some(name) = tool_call.function.name.as_ref().filter(|name| !name.is_empty()) {
+                        entry.function.name = name.clone();
+                    }
+                    if let Some(id) = tool_call.id.as_ref().filter(|id| !id.is_empty()) {
+                        entry.id = Some(id.clone());
+                    }
+
+                    // Accumulate function arguments as string chunks
+                    if let Some(args_str) = tool_call.function.arguments.as_ref().filter(|args| !args.is_empty()) {
+                        self.accumulating_tool_args.entry(i).or_default().push_str(args_str);
+                    }
+                }
+            }
+        }
+    }
+
+    // Content of the chunk first, then the extra completions
+    fn flush_chunk(&mut self, items: &mut Vec<Result<ChatStreamItem, String>>) {
+        if !self.chunk_content.is_empty() || self.has_any_tool_calls {
+            items.push(Ok(ChatStreamItem {
+                content: std::mem::take(&mut self.chunk_content),
+                tool_calls: None, // Don't return partial tool calls
+                ..Default::default()
+            }));
+        }
+        self.has_any_tool_calls = false;
+        self.flush_choices(items);
+    }
+
+    // Content of the extra completions, then the done items of those that finished
+    fn flush_choices(&mut self, items: &mut Vec<Result<ChatStreamItem, String>>) {
+        for (index, content) in std::mem::take(&mut self.choice_content) {
+            items.push(Ok(choice_item(index, content)));
+        }
+        for (index, reason) in std::mem::take(&mut self.choice_finish_reasons) {
+            items.push(Ok(ChatStreamItem { done: true, finish_reason: Some(reason), ..choice_item(index, String::new()) }));
+        }
+    }
+
+    // The final item carries the complete tool calls and the usage
+    fn finish_stream(&mut self, items: &mut Vec<Result<ChatStreamItem, String>>) {
+        self.done = true;
+        self.flush_choices(items);
+
+        let final_tool_calls = if !self.accumulated_tool_calls.is_empty() {
+            let mut tool_calls = Vec::new();
+            for (i, mut tool_call) in self.accumulated_tool_calls.drain() {
+                // Parse the accumulated argument string
+                if let Some(args_str) = self.accumulating_tool_args.get(&i) && let Ok(args) = serde_json::from_str::<serde_json::Value>(args_str) {
+                    tool_call.function.arguments = args;
+                }
+                tool_calls.push(tool_call);
+            }
+            Some(tool_calls)
+        } else {
+            None
+        };
+
+        items.push(Ok(ChatStreamItem {
+            content: std::mem::take(&mut self.chunk_content),
+            tool_calls: final_tool_calls,
+            done: true,
+            usage: self.usage.clone(),
+            finish_reason: self.finish_reason.take(),
+            ..Default::default()
+        }));
+    }
+}
+
+impl StreamParser for OpenAIStreamParser {
+    type Item = Result<ChatStreamItem, String>;
+
+    fn push(&mut self, chunk: &[u8]) -> Vec<Self::Item> {
+        let mut items = Vec::new();
+        for event in self.sse.push(chunk) {
+            if self.done {
+                break;
+            }
+            self.handle_data(&event.data, &mut items);
+        }
+        if !self.done {
+            self.flush_chunk(&mut items);
+        }
+        items
+    }
+
+    /// Streams ending without `[DONE]` still report the tool calls and usage seen
+    fn finish(&mut self) -> Vec<Self::Item> {
+        let mut items = Vec::new();
+        if self.done {
+            return items;
+        }
+        for event in self.sse.finish() {
+            if self.done {
+                break;
+            }
+            self.handle_data(&event.data, &mut items);
+        }
+        if !self.done {
+            self.flush_chunk(&mut items);
+            self.finish_stream(&mut items);
+        }
+        items
+    }
+}
+
+fn choice_item(index: usize, content: String) -> ChatStreamItem {
+    ChatStreamItem {
+        content,
+        choice_index: Some(index),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::stream::tests::{parse_chunks, parse_every_split};
+
+    const BODY: &str = concat!(
+        "data: {\"id\":\"chatcmpl-1\",\"object\":\"chat.completion.chunk\",\"created\":1718000000,\"model\":\"gpt-4o-mini\",\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"\"},\"finish_reason\":null}]}\n\n",
+        "data: {\"id\":\"chatcmpl-1\",\"object\":\"chat.completion.chunk\",\"created\":1718000000,\"model\":\"gpt-4o-mini\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"Grüße aus \"},\"finish_reason\":null}]}\n\n",
+        "data: {\"id\":\"chatcmpl-1\",\"object\":\"chat.completion.chunk\",\"created\":1718000000,\"model\":\"gpt-4o-mini\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"東京 🗼\"},\"finish_reason\":null}]}\n\n",
+        "data: {\"id\":\"chatcmpl-1\",\"object\":\"chat.completion.chunk\",\"created\":1718000000,\"model\":\"gpt-4o-mini\",\"choices\":[{\"index\":0,\"delta\":{\"tool_calls\":[{\"index\":0,\"id\":\"call_abc\",\"type\":\"function\",\"function\":{\"name\":\"get_weather\",\"arguments\":\"\"}}]},\"finish_reason\":null}]}\n\n",
+        "data: {\"id\":\"chatcmpl-1\",\"object\":\"chat.completion.chunk\",\"created\":1718000000,\"model\":\"gpt-4o-mini\",\"choices\":[{\"index\":0,\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"{\\\"city\\\":\"}}]},\"finish_reason\":null}]}\n\n",
+        "data: {\"id\":\"chatcmpl-1\",\"object\":\"chat.completion.chunk\",\"created\":1718000000,\"model\":\"gpt-4o-mini\",\"choices\":[{\"index\":0,\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"\\\"Tōkyō\\\"}\"}}]},\"finish_reason\":null}]}\n\n",
+        "data: {\"id\":\"chatcmpl-1\",\"object\":\"chat.completion.chunk\",\"created\":1718000000,\"model\":\"gpt-4o-mini\",\"choices\":[{\"index\":0,\"delta\":{},\"finish_reason\":\"tool_calls\"}]}\n\n",
+        "data: {\"id\":\"chatcmpl-1\",\"object\":\"chat.completion.chunk\",\"created\":1718000000,\"model\":\"gpt-4o-mini\",\"choices\":[],\"usage\":{\"prompt_tokens\":42,\"completion_tokens\":17,\"total_tokens\":59}}\n\n",
+        "data: [DONE]\n\n",
+    );
+
+    // Content is reported per chunk, so only its concatenation and the done items are compared
+    fn normalize(items: Vec<Result<ChatStreamItem, String>>) -> (String, Vec<String>) {
+        let mut content = String::new();
+        let mut done = Vec::new();
+        for item in items {
+            let item = item.unwrap();
+            content.push_str(&item.content);
+            if item.done {
+                done.push(format!("{:?}", ChatStreamItem { content: String::new(), ..item }));
+            }
+        }
+        (content, done)
+    }
+
+    #[test]
+    fn items_survive_every_split() {
+        let (content, done) = parse_every_split(BODY.as_bytes(), || OpenAIStreamParser::new("gpt-4o-mini".to_string()), normalize);
+        assert_eq!(content, "Grüße aus 東京 🗼");
+        assert_eq!(done.len(), 1);
+
+        let items = parse_chunks(OpenAIStreamParser::new("gpt-4o-mini".to_string()), &[BODY.as_bytes()]);
+        let last = items.last().unwrap().as_ref().unwrap();
+        let tool_calls = last.tool_calls.as_ref().unwrap();
+        assert_eq!(tool_calls[0].id.as_deref(), Some("call_abc"));
+        assert_eq!(tool_calls[0].function.name, "get_weather");
+        assert_eq!(tool_calls[0].function.arguments, serde_json::json!({ "city": "Tōkyō" }));
+        assert_eq!(last.usage.as_ref().unwrap().total_tokens, Some(59));
+        assert_eq!(last.finish_reason.as_deref(), Some("tool_calls"));
+    }
+
+    #[test]
+    fn stream_without_done_marker_still_finishes() {
+        let body = BODY.trim_end_matches("data: [DONE]\n\n").trim_end();
+        let (content, done) = parse_every_split(body.as_bytes(), || OpenAIStreamParser::new("gpt-4o-mini".to_string()), normalize);
+        assert_eq!(content, "Grüße aus 東京 🗼");
+        assert_eq!(done.len(), 1);
+    }
+}
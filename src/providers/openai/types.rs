@@ -56,6 +56,22 @@ pub struct OpenAIRequest {
     pub stream: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stream_options: Option<OpenAIStreamOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reasoning_effort: Option<super::ReasoningEffort>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logit_bias: Option<std::collections::HashMap<u32, f32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -75,6 +91,8 @@ pub struct OpenAIToolFunction {
     pub name: String,
     pub description: String,
     pub parameters: serde_json::Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strict: Option<bool>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -104,6 +122,26 @@ pub struct OpenAIUsage {
     pub prompt_tokens: u32,
     pub completion_tokens: u32,
     pub total_tokens: u32,
+    #[serde(default)]
+    pub prompt_tokens_details: Option<OpenAIPromptTokensDetails>,
+    #[serde(default)]
+    pub completion_tokens_details: Option<OpenAICompletionTokensDetails>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct OpenAIPromptTokensDetails {
+    #[serde(default)]
+    pub cached_tokens: Option<u32>, // Prefix served from the automatic prompt cache
+    #[serde(default)]
+    pub audio_tokens: Option<u32>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct OpenAICompletionTokensDetails {
+    #[serde(default)]
+    pub reasoning_tokens: Option<u32>,
+    #[serde(default)]
+    pub audio_tokens: Option<u32>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -129,4 +167,121 @@ pub struct OpenAIModel {
     pub object: String,
     pub created: u64,
     pub owned_by: String,
-}
\ No newline at end of file
+}
+#[derive(Deserialize, Debug)]
+pub struct OpenAIEmbeddingResponse {
+    pub data: Vec<OpenAIEmbedding>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct OpenAIEmbedding {
+    pub index: usize,
+    pub embedding: Vec<f32>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct OpenAIImageResponse {
+    pub data: Vec<OpenAIImageData>,
+    #[serde(default)]
+    pub usage: Option<OpenAIImageUsage>, // gpt-image-1 only
+}
+
+#[derive(Deserialize, Debug)]
+pub struct OpenAIImageData {
+    #[serde(default)]
+    pub b64_json: Option<String>,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub revised_prompt: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct OpenAIImageUsage {
+    pub input_tokens: u32,
+    pub output_tokens: u32,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct OpenAIModerationResponse {
+    pub results: Vec<OpenAIModeration>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct OpenAIModeration {
+    pub flagged: bool,
+    pub categories: std::collections::HashMap<String, bool>,
+    pub category_scores: std::collections::HashMap<String, f64>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct OpenAIFile {
+    pub id: String,
+    pub bytes: u64,
+    pub created_at: u64,
+    pub filename: String,
+    pub purpose: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct OpenAIListResponse<T> {
+    pub data: Vec<T>,
+    #[serde(default)]
+    pub has_more: bool,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct FineTuningOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub validation_file: Option<String>, // File ID
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suffix: Option<String>, // Up to 64 characters added to the fine-tuned model name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hyperparameters: Option<FineTuningHyperparameters>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FineTuningHyperparameters {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n_epochs: Option<serde_json::Value>, // Number or "auto"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub batch_size: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub learning_rate_multiplier: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct FineTuningJob {
+    pub id: String,
+    pub model: String,
+    pub status: String, // validating_files, queued, running, succeeded, failed or cancelled
+    pub created_at: u64,
+    #[serde(default)]
+    pub finished_at: Option<u64>,
+    #[serde(default)]
+    pub fine_tuned_model: Option<String>, // Set once the job succeeds
+    pub training_file: String,
+    #[serde(default)]
+    pub validation_file: Option<String>,
+    #[serde(default)]
+    pub trained_tokens: Option<u64>,
+    #[serde(default)]
+    pub error: Option<serde_json::Value>,
+}
+
+impl FineTuningJob {
+    /// Whether the job stopped, successfully or not
+    pub fn is_finished(&self) -> bool {
+        matches!(self.status.as_str(), "succeeded" | "failed" | "cancelled")
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct FineTuningEvent {
+    pub id: String,
+    pub created_at: u64,
+    pub level: String,
+    pub message: String,
+}
@@ -1,11 +1,12 @@
-use crate::core::{Message, ChatStreamItem, ToolCall, Tool, MonoModel, TokenUsage, FallbackToolHandler};
+use crate::core::{Message, ChatStreamItem, ToolCall, Tool, ToolRegistry, MonoModel, TokenUsage, FallbackToolHandler, FallbackStrategy, ToolOutput, ImageGenerationOptions, ImageGeneration, GeneratedImage, GenerationOptions, lookup_pricing, ImageDetail, image_url_content, remote_image_content, input_audio_content, validate_images, normalize_history, HistoryRules, fit_images, ImageLimits, encode_image_file, encode_image_bytes, parse_stream, DebugLog, DebugEvent, SendLogged, stderr_debug_callback, ChatResponse, CacheHint, Reproducibility, chat_api_error, api_error, Quota, request_body};
 use super::types::*;
-use reqwest::Client;
+use super::stream::{OpenRouterStreamParser, StreamEvent, FallbackCalls};
+use reqwest::{Client, Method};
 use serde_json::json;
-use std::collections::HashMap;
 use futures_util::{StreamExt, Stream};
 use std::pin::Pin;
-use base64::{Engine as _};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime};
 
 #[derive(Debug, Clone)]
 pub struct ChatMessage {
@@ -14,14 +15,6 @@ pub struct ChatMessage {
     pub tool_calls: Option<Vec<ToolCall>>,
 }
 
-#[derive(Debug, Clone)]
-pub enum StreamEvent {
-    Content(String),
-    ToolCall { id: String, name: String, arguments: String },
-    Done,
-    Usage(TokenUsage),
-}
-
 #[derive(Debug, Clone)]
 pub struct StreamOptions {
     pub include_usage: bool,
@@ -32,160 +25,59 @@ pub struct OpenRouterClient {
     api_key: String,
     pub model: String,
     base_url: String,
-    tools: Vec<Tool>,
-}
-
-struct OpenRouterStreamProcessor {
-    buffer: String,
-    accumulating_tool_args: HashMap<usize, String>,
-    tool_call_info: HashMap<usize, (String, String)>,
-    usage: Option<TokenUsage>,
+    tools: ToolRegistry,
+    fallback_strategy: FallbackStrategy,
+    image_detail: Option<ImageDetail>,
+    app_url: Option<String>,
+    app_title: Option<String>,
+    models_cache: RwLock<Option<(Instant, Arc<Vec<OpenRouterModel>>)>>,
+    reasoning: Option<OpenRouterReasoning>,
+    debug: DebugLog,
 }
 
-impl OpenRouterStreamProcessor {
-    fn new() -> Self {
-        Self {
-            buffer: String::new(),
-            accumulating_tool_args: HashMap::new(),
-            tool_call_info: HashMap::new(),
-            usage: None,
-        }
-    }
-
-    fn process_chunk(&mut self, chunk: &str) -> Vec<StreamEvent> {
-        self.buffer.push_str(chunk);
-        let mut events = Vec::new();
-
-        while let Some(event_end) = self.buffer.find("\n\n") {
-            let event_data = self.buffer[..event_end].trim().to_string();
-            self.buffer = self.buffer[event_end + 2..].to_string();
-
-            if event_data.starts_with(':') {
-                continue;
-            }
-
-            if let Some(data) = event_data.strip_prefix("data: ") {
-                if data == "[DONE]" {
-                    events.push(StreamEvent::Done);
-                    break;
-                }
+// How long the `/models` listing is reused before fetching it again
+const MODELS_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
 
-                match serde_json::from_str::<OpenRouterResponse>(data) {
-                    Ok(response) => {
-                        // Extract usage information if available
-                        if let Some(usage) = &response.usage {
-                            let token_usage = TokenUsage {
-                                prompt_tokens: Some(usage.prompt_tokens),
-                                completion_tokens: Some(usage.completion_tokens),
-                                total_tokens: Some(usage.total_tokens),
-                                cost_usd: None, // Will be calculated later in the stream
-                            };
-                            self.usage = Some(token_usage.clone());
-                            events.push(StreamEvent::Usage(token_usage));
-                        }
-                        
-                        if let Some(choice) = response.choices.first() {
-                            if let Some(delta) = &choice.delta {
-                                
-                                // Check content
-                                if let Some(content_str) = delta.content.as_str() {
-                                    if !content_str.is_empty() {
-                                        events.push(StreamEvent::Content(content_str.to_string()));
-                                    }
-                                }
-
-                                // Check tool calls
-                                match &delta.tool_calls {
-                                    Some(tool_calls) => {
-                                        for (index, tool_call) in tool_calls.iter().enumerate() {
-                                            
-                                            // Store ID and name when we first see them
-                                            if let Some(id) = &tool_call.id {
-                                                if let Some(function) = &tool_call.function {
-                                                    if let Some(name) = &function.name {
-                                                        self.tool_call_info.insert(index, (id.clone(), name.clone()));
-                                                    }
-                                                }
-                                            }
-                                            
-                                            if let Some(function) = &tool_call.function {
-                                                if let Some(args) = &function.arguments {
-                                                    let accumulated = self
-                                                        .accumulating_tool_args
-                                                        .entry(index)
-                                                        .or_insert_with(String::new);
-                                                    accumulated.push_str(args);
-
-                                                    // Try to parse as JSON
-                                                    match serde_json::from_str::<serde_json::Value>(accumulated) {
-                                                        Ok(_parsed) => {
-                                                            // Use stored ID and name if available
-                                                            if let Some((stored_id, stored_name)) = self.tool_call_info.get(&index) {
-                                                                events.push(StreamEvent::ToolCall {
-                                                                    id: stored_id.clone(),
-                                                                    name: stored_name.clone(),
-                                                                    arguments: accumulated.clone(),
-                                                                });
-                                                                self.tool_call_info.remove(&index);
-                                                            } else if let Some(id) = &tool_call.id {
-                                                                events.push(StreamEvent::ToolCall {
-                                                                    id: id.clone(),
-                                                                    name: function.name.clone().unwrap_or_default(),
-                                                                    arguments: accumulated.clone(),
-                                                                });
-                                                            }
-                                                            self.accumulating_tool_args.remove(&index);
-                                                        },
-                                                        Err(_) => {
-                                                            // JSON parsing failed, continue accumulating
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    },
-                                    None => {
-                                        // No tool calls in this chunk
-                                    }
-                                }
-                            }
-
-                            if let Some(finish_reason) = &choice.finish_reason {
-                                if !finish_reason.is_empty() {
-                                    events.push(StreamEvent::Done);
-                                }
-                            }
-                        }
-                    },
-                    Err(_) => {
-                        // Failed to parse JSON chunk, skip it
-                    }
-                }
-            }
-        }
+impl OpenRouterClient {
+    /// `GenerationOptions` fields sent to the API, OpenRouter returns a single completion
+    pub const SUPPORTED_OPTIONS: &'static [&'static str] =
+        &["temperature", "max_tokens", "seed", "presence_penalty", "frequency_penalty", "logit_bias", "user"];
 
-        events
-    }
-}
+    /// The seed reaches models whose upstream provider supports it
+    pub const REPRODUCIBILITY: Reproducibility = Reproducibility::BestEffort;
 
-impl OpenRouterClient {
     pub fn new(api_key: String, model: String) -> Self {
         Self {
             client: Client::new(),
             api_key,
             model,
             base_url: "https://openrouter.ai/api/v1".to_string(),
-            tools: Vec::new(),
+            tools: ToolRegistry::new(),
+            fallback_strategy: FallbackStrategy::default(),
+            image_detail: None,
+            app_url: None,
+            app_title: None,
+            models_cache: RwLock::new(None),
+            reasoning: None,
+            debug: DebugLog::new("OpenRouter"),
         }
     }
 
     pub async fn add_tool(&mut self, tool: Tool) -> Result<(), Box<dyn std::error::Error>> {
-        self.tools.push(tool);
+        self.tools.register(tool)?;
         Ok(())
     }
 
+    pub fn tool_registry(&self) -> &ToolRegistry {
+        &self.tools
+    }
+
+    pub fn set_tool_registry(&mut self, registry: ToolRegistry) {
+        self.tools = registry;
+    }
+
     pub async fn is_fallback_mode(&self) -> bool {
-        if self.tools.is_empty() {
+        if !self.tools.has_enabled_tools() {
             false // No tools, no fallback needed
         } else {
             // Dynamically check if model supports native tools
@@ -193,33 +85,102 @@ impl OpenRouterClient {
         }
     }
 
-    pub fn set_debug_mode(&mut self, _debug: bool) {
-        // OpenRouter debug mode not implemented
+    /// Log requests with API keys redacted, raw stream chunks and fallback tool call output to stderr
+    pub fn set_debug_mode(&mut self, debug: bool) {
+        self.debug.set_callback(debug.then(stderr_debug_callback));
     }
 
     pub fn debug_mode(&self) -> bool {
-        false
+        self.debug.is_enabled()
+    }
+
+    /// Send debug events to a callback instead of stderr, e.g. to forward them to `tracing`
+    pub fn set_debug_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(&DebugEvent) + Send + Sync + 'static,
+    {
+        self.debug.set_callback(Some(Arc::new(callback)));
+    }
+
+    /// A client with the same credentials, endpoint and app attribution for another model.
+    /// Tools and model settings are not carried over
+    pub fn for_model(&self, model: &str) -> Self {
+        let mut client = Self::new(self.api_key.clone(), model.to_string());
+        client.client = self.client.clone();
+        client.base_url = self.base_url.clone();
+        client.app_url = self.app_url.clone();
+        client.app_title = self.app_title.clone();
+        client.image_detail = self.image_detail;
+        client.debug = self.debug.clone();
+        client
+    }
+
+    /// API root requests go to, `https://openrouter.ai/api/v1` by default. Point it at a proxy
+    /// or a `ReplayServer`
+    pub fn set_base_url(&mut self, base_url: &str) {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Resolution vision models look at images with, the model picks when None
+    pub fn set_image_detail(&mut self, detail: Option<ImageDetail>) {
+        self.image_detail = detail;
+    }
+
+    pub fn image_detail(&self) -> Option<ImageDetail> {
+        self.image_detail
+    }
+
+    /// Reasoning for models that support it, streamed through `ChatStreamItem.thinking` unless excluded
+    pub fn set_reasoning(&mut self, reasoning: Option<OpenRouterReasoning>) {
+        self.reasoning = reasoning;
+    }
+
+    pub fn reasoning(&self) -> Option<&OpenRouterReasoning> {
+        self.reasoning.as_ref()
+    }
+
+    fn reasoning_budget(&self) -> u32 {
+        self.reasoning.as_ref().and_then(|reasoning| reasoning.max_tokens).unwrap_or(0)
+    }
+
+    /// Sent as `HTTP-Referer`, identifies the app on OpenRouter's rankings
+    pub fn set_app_url(&mut self, url: Option<String>) {
+        self.app_url = url;
+    }
+
+    pub fn app_url(&self) -> Option<&str> {
+        self.app_url.as_deref()
+    }
+
+    /// Sent as `X-Title`, the app name shown on OpenRouter's rankings
+    pub fn set_app_title(&mut self, title: Option<String>) {
+        self.app_title = title;
+    }
+
+    pub fn app_title(&self) -> Option<&str> {
+        self.app_title.as_deref()
+    }
+
+    pub fn set_fallback_strategy(&mut self, strategy: FallbackStrategy) {
+        self.fallback_strategy = strategy;
+    }
+
+    pub fn fallback_strategy(&self) -> FallbackStrategy {
+        self.fallback_strategy
     }
 
     pub async fn supports_tool_calls(&self) -> Result<bool, Box<dyn std::error::Error>> {
-        // Get all models to find our specific model and check supported parameters
-        let response = self
-            .client
-            .get(&format!("{}/models", self.base_url))
-            .header("Authorization", &format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .send()
-            .await?;
-            
-        if !response.status().is_success() {
-            // If we can't get models list, assume no tool support
+        // If we can't get models list, assume no tool support
+        let Ok(models) = self.models().await else {
             return Ok(false);
-        }
-        
-        let models_response: OpenRouterModelsResponse = response.json().await?;
+        };
         
         // Find our specific model in the list
-        if let Some(model_info) = models_response.data.iter().find(|m| m.id == self.model) {
+        if let Some(model_info) = models.iter().find(|m| m.id == self.model) {
             // Check if 'tools' is in the supported_parameters
             if let Some(supported_params) = &model_info.supported_parameters {
                 Ok(supported_params.contains(&"tools".to_string()))
@@ -233,110 +194,239 @@ impl OpenRouterClient {
         }
     }
 
-    pub async fn get_usage_for_messages(
-        &self,
-        messages: &[Message],
-        tools: Option<&[Tool]>,
-        images: &[String],
-    ) -> Result<Option<TokenUsage>, Box<dyn std::error::Error>> {
-        let openrouter_messages = self.convert_messages(messages, images);
-        let openrouter_tools = tools.map(|t| self.convert_tools(t));
+    /// Token counts, cost and latency of a completion by its id, the id of the response chunks
+    pub async fn get_generation(&self, id: &str) -> Result<OpenRouterGeneration, Box<dyn std::error::Error>> {
+        Ok(fetch_generation(&self.client, &self.api_key, &self.base_url, id).await?)
+    }
 
-        let request = OpenRouterRequest {
-            model: self.model.clone(),
-            messages: openrouter_messages,
-            tools: openrouter_tools,
-            tool_choice: None,
-            stream: Some(false), // Non-streaming to get usage
-            max_tokens: Some(1), // Minimal tokens since we just want usage
-            temperature: Some(0.7),
-            stream_options: None, // Not needed for non-streaming
+    /// Credits purchased and used on the account, in USD
+    pub async fn get_credits(&self) -> Result<OpenRouterCredits, Box<dyn std::error::Error>> {
+        let response = self
+            .request(Method::GET, format!("{}/credits", self.base_url))
+            .send_logged(&self.debug)
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(api_error("OpenRouter", status, &error_text).into());
+        }
+
+        let credits: OpenRouterCreditsResponse = response.json().await?;
+        Ok(credits.data)
+    }
+
+    /// Credit limit and usage of the API key
+    pub async fn get_key(&self) -> Result<OpenRouterKey, Box<dyn std::error::Error>> {
+        let response = self
+            .request(Method::GET, format!("{}/key", self.base_url))
+            .send_logged(&self.debug)
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(api_error("OpenRouter", status, &error_text).into());
+        }
+
+        let key: OpenRouterKeyResponse = response.json().await?;
+        Ok(key.data)
+    }
+
+    /// Credits used and left on the key, or on the account for keys without a credit limit
+    pub async fn get_quota(&self) -> Result<Quota, Box<dyn std::error::Error>> {
+        let key = self.get_key().await?;
+        let credits_remaining = match key.limit_remaining {
+            Some(remaining) => Some(remaining),
+            None => self.get_credits().await.ok().map(|credits| credits.remaining()),
         };
+        Ok(Quota {
+            credits_remaining,
+            credits_used: Some(key.usage),
+            observed_at: Some(SystemTime::now()),
+            ..self.debug.last_quota()
+        })
+    }
 
+    /// Check the API is reachable and the key is valid with `/key`
+    pub async fn health_check(&self) -> Result<(), Box<dyn std::error::Error>> {
         let response = self
-            .client
-            .post(&format!("{}/chat/completions", self.base_url))
-            .header("Authorization", &format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .json(&request)
-            .send()
+            .request(Method::GET, format!("{}/key", self.base_url))
+            .send_logged(&self.debug)
             .await?;
 
         if !response.status().is_success() {
-            // Don't fail the main request if usage call fails
-            return Ok(None);
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(format!("OpenRouter health check failed: {}", error_text).into());
         }
+        Ok(())
+    }
 
-        let openrouter_response: OpenRouterResponse = response.json().await?;
-        
-        if let Some(usage) = openrouter_response.usage {
-            Ok(Some(TokenUsage {
-                prompt_tokens: Some(usage.prompt_tokens),
-                completion_tokens: Some(usage.completion_tokens),
-                total_tokens: Some(usage.total_tokens),
-                cost_usd: None,
-            }))
-        } else {
-            Ok(None)
+    // Authorized request with the app attribution headers
+    fn request(&self, method: Method, url: impl reqwest::IntoUrl) -> reqwest::RequestBuilder {
+        let mut request_builder = self
+            .client
+            .request(method, url)
+            .header("Authorization", format!("Bearer {}", self.api_key));
+        if let Some(app_url) = &self.app_url {
+            request_builder = request_builder.header("HTTP-Referer", app_url);
+        }
+        if let Some(app_title) = &self.app_title {
+            request_builder = request_builder.header("X-Title", app_title);
         }
+        request_builder
     }
 
-    pub async fn get_available_models(&self) -> Result<Vec<MonoModel>, Box<dyn std::error::Error>> {
+    /// Generate images with a model that outputs images, e.g. google/gemini-2.5-flash-image-preview.
+    /// `options.model` overrides the client's model, size and quality are left to the prompt
+    pub async fn generate_image(
+        &self,
+        prompt: &str,
+        options: &ImageGenerationOptions,
+    ) -> Result<ImageGeneration, Box<dyn std::error::Error>> {
+        let request = json!({
+            "model": options.model.clone().unwrap_or_else(|| self.model.clone()),
+            "messages": [{ "role": "user", "content": prompt }],
+            "modalities": ["image", "text"],
+            "usage": { "include": true }, // Adds the billed cost to the usage
+        });
+
         let response = self
-            .client
-            .get(&format!("{}/models", self.base_url))
-            .header("Authorization", &format!("Bearer {}", self.api_key))
+            .request(Method::POST, format!("{}/chat/completions", self.base_url))
             .header("Content-Type", "application/json")
-            .send()
+            .json(&request)
+            .send_logged(&self.debug)
             .await?;
 
         if !response.status().is_success() {
+            let status = response.status().as_u16();
             let error_text = response.text().await.unwrap_or_default();
-            return Err(format!("OpenRouter API error: {}", error_text).into());
+            return Err(api_error("OpenRouter", status, &error_text).into());
         }
 
-        let openrouter_response: OpenRouterModelsResponse = response.json().await?;
-        
-        let models = openrouter_response.data
-            .into_iter()
+        let json: serde_json::Value = response.json().await?;
+        let mut images = Vec::new();
+        for choice in json["choices"].as_array().into_iter().flatten() {
+            for image in choice["message"]["images"].as_array().into_iter().flatten() {
+                let Some(url) = image["image_url"]["url"].as_str() else { continue };
+                // Images come back as data URLs
+                let b64_data = url.split_once(";base64,").map(|(_, data)| data.to_string());
+                images.push(GeneratedImage {
+                    url: if b64_data.is_none() { Some(url.to_string()) } else { None },
+                    b64_data,
+                    revised_prompt: None,
+                });
+            }
+        }
+
+        if images.is_empty() {
+            return Err("No images in response, the model may not support image output".into());
+        }
+
+        let usage = &json["usage"];
+        let usage = usage.is_object().then(|| TokenUsage {
+            prompt_tokens: usage["prompt_tokens"].as_u64().map(|tokens| tokens as u32),
+            completion_tokens: usage["completion_tokens"].as_u64().map(|tokens| tokens as u32),
+            total_tokens: usage["total_tokens"].as_u64().map(|tokens| tokens as u32),
+            cost_usd: usage["cost"].as_f64(),
+            cached_prompt_tokens: usage["prompt_tokens_details"]["cached_tokens"].as_u64().map(|tokens| tokens as u32),
+            reasoning_tokens: usage["completion_tokens_details"]["reasoning_tokens"].as_u64().map(|tokens| tokens as u32),
+            audio_tokens: None,
+        });
+
+        Ok(ImageGeneration { images, usage })
+    }
+
+    pub async fn get_available_models(&self) -> Result<Vec<MonoModel>, Box<dyn std::error::Error>> {
+        let models = self
+            .models()
+            .await?
+            .iter()
             .map(|model| MonoModel {
-                id: model.id,
-                name: model.name,
+                id: model.id.clone(),
+                name: model.name.clone(),
                 provider: "OpenRouter".to_string(),
                 size: None,
-                created: None,
+                created: model.created,
+                capabilities: Some(model.capabilities()),
             })
             .collect();
 
         Ok(models)
     }
 
+    /// The `/models` listing with full metadata, fetched at most once an hour
+    pub async fn models(&self) -> Result<Arc<Vec<OpenRouterModel>>, Box<dyn std::error::Error>> {
+        if let Some((fetched_at, models)) = self.models_cache.read().unwrap_or_else(|e| e.into_inner()).as_ref()
+            && fetched_at.elapsed() < MODELS_CACHE_TTL
+        {
+            return Ok(models.clone());
+        }
+
+        let response = self
+            .request(Method::GET, format!("{}/models", self.base_url))
+            .header("Content-Type", "application/json")
+            .send_logged(&self.debug)
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(api_error("OpenRouter", status, &error_text).into());
+        }
+
+        let openrouter_response: OpenRouterModelsResponse = response.json().await?;
+        let models = Arc::new(openrouter_response.data);
+        *self.models_cache.write().unwrap_or_else(|e| e.into_inner()) = Some((Instant::now(), models.clone()));
+        Ok(models)
+    }
+
+    /// Fetch the model listing again on next use, e.g. to pick up newly released models
+    pub fn clear_models_cache(&self) {
+        *self.models_cache.write().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
     fn convert_messages(&self, messages: &[Message], images: &[String]) -> Vec<OpenRouterMessage> {
         let mut openrouter_messages = Vec::new();
         let mut last_tool_call_info: Option<(String, String)> = None;
 
+        let mut pending_tool_images: Vec<String> = Vec::new();
+
         for message in messages {
+            let message = &message.with_inlined_documents();
+            // Tool messages only carry text. Images returned by tools follow as a user message once
+            // the run of tool messages answering the assistant's tool calls is complete
+            if message.role != "tool" && !pending_tool_images.is_empty() {
+                openrouter_messages.push(tool_images_message(std::mem::take(&mut pending_tool_images), self.image_detail));
+            }
+            if message.role == "tool" && let Some(tool_images) = &message.images {
+                pending_tool_images.extend(tool_images.iter().cloned());
+            }
+
             // Track tool call IDs and names from assistant messages
-            if message.role == "assistant" && message.tool_calls.is_some() {
-                if let Some(tool_calls) = &message.tool_calls {
-                    if let Some(first_call) = tool_calls.first() {
-                        if let Some(id) = &first_call.id {
-                            let name = first_call.function.name.clone();
-                            last_tool_call_info = Some((id.clone(), name.clone()));
-                        }
-                    }
-                }
+            if message.role == "assistant"
+                && let Some(first_call) = message.tool_calls.as_ref().and_then(|calls| calls.first())
+                && let Some(id) = &first_call.id
+            {
+                let name = first_call.function.name.clone();
+                last_tool_call_info = Some((id.clone(), name.clone()));
             }
 
-            // Handle tool result messages using OpenRouter's standard format
+            // Handle tool result messages using OpenRouter's standard format, falling back to the
+            // last assistant tool call for results that don't carry their call id
             if message.role == "tool" {
-                if let Some((tool_use_id, tool_name)) = &last_tool_call_info {                    
+                let call_info = match (&message.tool_call_id, &message.name) {
+                    (Some(id), name) => Some((id.clone(), name.clone().unwrap_or_default())),
+                    (None, _) => last_tool_call_info.clone(),
+                };
+                if let Some((tool_use_id, tool_name)) = call_info {
                     let msg = OpenRouterMessage {
                         role: "tool".to_string(),
                         content: serde_json::Value::String(message.content.clone()),
-                        name: Some(tool_name.clone()),
+                        name: Some(tool_name),
                         tool_calls: None,
-                        tool_call_id: Some(tool_use_id.clone()),
+                        tool_call_id: Some(tool_use_id),
+                        reasoning: None,
                     };
                     openrouter_messages.push(msg);
                     continue;
@@ -354,16 +444,26 @@ impl OpenRouterClient {
 
             if message.role == "user" && !images.is_empty() {
                 for image in images {
-                    content_items.push(json!({
-                        "type": "image_url",
-                        "image_url": {
-                            "url": format!("data:image/jpeg;base64,{}", image)
-                        }
-                    }));
+                    content_items.push(image_url_content(image, self.image_detail));
                 }
             }
+            for url in message.image_urls.iter().flatten() {
+                content_items.push(remote_image_content(url, self.image_detail));
+            }
+            for audio in message.audio.iter().flatten() {
+                content_items.push(input_audio_content(audio));
+            }
 
-            let content = if content_items.len() == 1 && content_items[0]["type"] == "text" {
+            // Cache breakpoints go on a content part, as with Anthropic's API, so the message
+            // needs the parts form
+            if let (Some(hint), Some(last)) = (message.cache, content_items.last_mut()) {
+                last["cache_control"] = match hint {
+                    CacheHint::Ephemeral => json!({"type": "ephemeral"}),
+                    CacheHint::Extended => json!({"type": "ephemeral", "ttl": "1h"}),
+                };
+            }
+
+            let content = if content_items.len() == 1 && content_items[0]["type"] == "text" && message.cache.is_none() {
                 // Use simple string for basic text messages
                 serde_json::Value::String(message.content.clone())
             } else if content_items.is_empty() {
@@ -374,18 +474,16 @@ impl OpenRouterClient {
                 json!(content_items)
             };
 
-            let tool_calls = if let Some(calls) = &message.tool_calls {
-                Some(calls.iter().map(|call| OpenRouterToolCall {
+            let tool_calls = message.tool_calls.as_ref().map(|calls| {
+                calls.iter().map(|call| OpenRouterToolCall {
                     id: call.id.clone(),
                     call_type: Some("function".to_string()),
                     function: Some(OpenRouterFunctionCall {
                         name: Some(call.function.name.clone()),
                         arguments: Some(serde_json::to_string(&call.function.arguments).unwrap_or_default()),
                     }),
-                }).collect())
-            } else {
-                None
-            };
+                }).collect()
+            });
 
             openrouter_messages.push(OpenRouterMessage {
                 role: message.role.clone(),
@@ -393,13 +491,17 @@ impl OpenRouterClient {
                 name: None,
                 tool_calls,
                 tool_call_id: None,
+                reasoning: None,
             });
         }
+        if !pending_tool_images.is_empty() {
+            openrouter_messages.push(tool_images_message(pending_tool_images, self.image_detail));
+        }
 
         openrouter_messages
     }
 
-    fn convert_tools(&self, tools: &[Tool]) -> Vec<OpenRouterTool> {
+    fn convert_tools(&self, tools: &[Arc<Tool>]) -> Vec<OpenRouterTool> {
         tools
             .iter()
             .map(|tool| OpenRouterTool {
@@ -416,7 +518,7 @@ impl OpenRouterClient {
     pub async fn chat_completion(
         &self,
         messages: Vec<Message>,
-        tools: Option<Vec<Tool>>,
+        tools: Option<Vec<Arc<Tool>>>,
         images: Vec<String>,
     ) -> Result<String, Box<dyn std::error::Error>> {
         let openrouter_messages = self.convert_messages(&messages, &images);
@@ -428,33 +530,39 @@ impl OpenRouterClient {
             tools: openrouter_tools,
             tool_choice: None,
             stream: Some(false),
-            max_tokens: Some(4096),
+            max_tokens: Some(4096 + self.reasoning_budget()),
             temperature: Some(0.7),
             stream_options: None, // Not needed for non-streaming
+            seed: None,
+            presence_penalty: None,
+            frequency_penalty: None,
+            logit_bias: None,
+            user: None,
+            usage: None,
+            reasoning: self.reasoning.clone(),
+            response_format: None,
         };
 
         let response = self
-            .client
-            .post(&format!("{}/chat/completions", self.base_url))
-            .header("Authorization", &format!("Bearer {}", self.api_key))
+            .request(Method::POST, format!("{}/chat/completions", self.base_url))
             .header("Content-Type", "application/json")
             .json(&request)
-            .send()
+            .send_logged(&self.debug)
             .await?;
 
         if !response.status().is_success() {
+            let status = response.status().as_u16();
             let error_text = response.text().await.unwrap_or_default();
-            return Err(format!("OpenRouter API error: {}", error_text).into());
+            return Err(chat_api_error("OpenRouter", status, &error_text));
         }
 
         let openrouter_response: OpenRouterResponse = response.json().await?;
 
-        if let Some(choice) = openrouter_response.choices.first() {
-            if let Some(message) = &choice.message {
-                if let Some(content) = message.content.as_str() {
-                    return Ok(content.to_string());
-                }
-            }
+        if let Some(choice) = openrouter_response.choices.first()
+            && let Some(message) = &choice.message
+            && let Some(content) = message.content.as_str()
+        {
+            return Ok(content.to_string());
         }
 
         Err("No content in response".into())
@@ -463,153 +571,139 @@ impl OpenRouterClient {
     pub async fn chat_completion_stream(
         &self,
         messages: Vec<Message>,
-        tools: Option<Vec<Tool>>,
+        tools: Option<Vec<Arc<Tool>>>,
         _options: StreamOptions,
         images: Vec<String>,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamEvent, String>> + Send>>, Box<dyn std::error::Error>> {
-        let openrouter_messages = self.convert_messages(&messages, &images);
-        let openrouter_tools = tools.as_ref().map(|t| self.convert_tools(t));
+        self.completion_stream(messages, tools, images, &GenerationOptions::default()).await
+    }
 
-        let request = OpenRouterRequest {
-            model: self.model.clone(),
-            messages: openrouter_messages,
-            tools: openrouter_tools,
-            tool_choice: None,
-            stream: Some(true),
-            max_tokens: Some(4096),
-            temperature: Some(0.7),
-            stream_options: Some(super::types::OpenRouterStreamOptions { include_usage: true }),
-        };
+    async fn completion_stream(
+        &self,
+        messages: Vec<Message>,
+        tools: Option<Vec<Arc<Tool>>>,
+        images: Vec<String>,
+        options: &GenerationOptions,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamEvent, String>> + Send>>, Box<dyn std::error::Error>> {
+        let request = self.completion_request(&messages, tools, &images, options);
+        let debug = self.debug.with_tags(options.tags.clone());
 
         let response = self
-            .client
-            .post(&format!("{}/chat/completions", self.base_url))
-            .header("Authorization", &format!("Bearer {}", self.api_key))
+            .request(Method::POST, format!("{}/chat/completions", self.base_url))
             .header("Content-Type", "application/json")
-            .json(&request)
-            .send()
+            .json(&request_body(&request, options.extensions.as_ref())?)
+            .send_logged(&debug)
             .await?;
 
         if !response.status().is_success() {
+            let status = response.status().as_u16();
             let error_text = response.text().await.unwrap_or_default();
-            return Err(format!("OpenRouter API error: {}", error_text).into());
+            return Err(chat_api_error("OpenRouter", status, &error_text));
         }
 
-        let stream = response.bytes_stream();
-        let mut processor = OpenRouterStreamProcessor::new();
+        Ok(parse_stream(debug.inspect_stream(response.bytes_stream()), OpenRouterStreamParser::new()))
+    }
 
-        let event_stream = stream.map(move |chunk| {
-            match chunk {
-                Ok(bytes) => {
-                    let chunk_str = String::from_utf8_lossy(&bytes);
-                    let events = processor.process_chunk(&chunk_str);
-                    events
-                }
-                Err(e) => {
-                    vec![StreamEvent::Content(format!("Network error: {}", e))]
-                }
-            }
-        })
-        .map(|events| futures_util::stream::iter(events.into_iter().map(Ok)))
-        .flatten();
+    fn completion_request(&self, messages: &[Message], tools: Option<Vec<Arc<Tool>>>, images: &[String], options: &GenerationOptions) -> OpenRouterRequest {
+        // Like Ollama's `format: json`, keeps the whole reply one JSON object
+        let constrained = self.active_fallback(tools.as_ref()) == Some(FallbackStrategy::ConstrainedJson);
+        let openrouter_messages = self.convert_messages(messages, images);
+        let openrouter_tools = tools.as_ref().map(|t| self.convert_tools(t));
 
-        Ok(Box::pin(event_stream))
+        OpenRouterRequest {
+            model: self.model.clone(),
+            messages: openrouter_messages,
+            tools: openrouter_tools,
+            tool_choice: None,
+            stream: Some(true),
+            // The reasoning budget counts towards max_tokens
+            max_tokens: Some(options.max_tokens.unwrap_or(4096) + self.reasoning_budget()),
+            temperature: Some(options.temperature.unwrap_or(0.7)),
+            stream_options: Some(super::types::OpenRouterStreamOptions { include_usage: true }),
+            seed: options.seed,
+            presence_penalty: options.presence_penalty,
+            frequency_penalty: options.frequency_penalty,
+            logit_bias: options.logit_bias.clone(),
+            user: options.end_user(),
+            usage: Some(OpenRouterUsageOptions { include: true }),
+            reasoning: self.reasoning.clone(),
+            response_format: constrained.then(|| serde_json::json!({"type": "json_object"})),
+        }
+    }
+
+    // Tool call format the model was told to use, None when it calls the `native_tools` natively
+    // or there are no tools
+    fn active_fallback(&self, native_tools: Option<&Vec<Arc<Tool>>>) -> Option<FallbackStrategy> {
+        (native_tools.is_none() && self.tools.has_enabled_tools()).then_some(self.fallback_strategy)
     }
 
     pub async fn send_chat_request(
         &self,
         messages: &[Message],
     ) -> Result<Pin<Box<dyn futures_util::Stream<Item = Result<ChatStreamItem, String>> + Send>>, Box<dyn std::error::Error>> {
-        let mut messages_to_send = messages.to_vec();
-        
-        // In fallback mode, inject tool context into the system message
-        let is_fallback = self.is_fallback_mode().await;
-        let tools = if !self.tools.is_empty() && !is_fallback {
-            Some(self.tools.iter().map(|tool| Tool {
-                name: tool.name.clone(),
-                description: tool.description.clone(),
-                parameters: tool.parameters.clone(),
-                function: Box::new(|_| "Not implemented".to_string()),
-            }).collect())
-        } else {
-            None
-        };
-        
-        if is_fallback && !self.tools.is_empty() {
-            let tool_context = FallbackToolHandler::generate_tool_context(&self.tools);
-            
-            // Find existing system message or create one
-            if let Some(system_msg) = messages_to_send.iter_mut().find(|msg| msg.role == "system") {
-                system_msg.content.push_str(&tool_context);
-            } else {
-                // Insert system message at the beginning
-                messages_to_send.insert(0, Message {
-                    role: "system".to_string(),
-                    content: format!("You are a helpful assistant.{}", tool_context),
-                    images: None,
-                    tool_calls: None,
-                });
-            }
-        }
-
-        let images: Vec<String> = messages
-            .iter()
-            .filter_map(|m| m.images.as_ref())
-            .flatten()
-            .cloned()
-            .collect();
+        self.send_chat_request_with_options(messages, &GenerationOptions::default()).await
+    }
 
-        let stream_options = StreamOptions { include_usage: true };
-        let event_stream = self.chat_completion_stream(messages_to_send, tools, stream_options, images).await?;
+    /// Chat with sampling settings, see `SUPPORTED_OPTIONS` for the fields sent
+    pub async fn send_chat_request_with_options(
+        &self,
+        messages: &[Message],
+        options: &GenerationOptions,
+    ) -> Result<Pin<Box<dyn futures_util::Stream<Item = Result<ChatStreamItem, String>> + Send>>, Box<dyn std::error::Error>> {
+        let (messages_to_send, tools, images) = self.prepare_messages(messages).await?;
+        let mut fallback = self.active_fallback(tools.as_ref()).map(FallbackCalls::new);
+        let debug = self.debug.with_tags(options.tags.clone());
+        let event_stream = self.completion_stream(messages_to_send, tools, images, options).await?;
 
-        // Store client info for usage request
+        // Client info for completing the usage once the stream is done
         let api_key = self.api_key.clone();
         let model = self.model.clone();
         let base_url = self.base_url.clone();
         let client = self.client.clone();
-        let messages_for_usage = messages.to_vec();
         
         let mapped_stream = event_stream.then(move |event| {
             let api_key = api_key.clone();
             let model = model.clone();
             let base_url = base_url.clone();
             let client = client.clone();
-            let messages_for_usage = messages_for_usage.clone();
+
+            // Fallback tool call markup is hidden, the calls come with the done item
+            let (event, fallback_done) = match (event, fallback.as_mut()) {
+                (Ok(StreamEvent::Content(content)), Some(fallback)) => (Ok(StreamEvent::Content(fallback.content(&content))), None),
+                (Ok(done @ StreamEvent::Done { .. }), Some(fallback)) => (Ok(done), Some(fallback.finish(&debug))),
+                (event, _) => (event, None),
+            };
             
             async move {
                 match event {
                     Ok(StreamEvent::Content(content)) => Ok(ChatStreamItem {
                         content,
-                        tool_calls: None,
-                        done: false,
-                        usage: None,
+                        ..Default::default()
+                    }),
+                    Ok(StreamEvent::Reasoning(thinking)) => Ok(ChatStreamItem {
+                        thinking: Some(thinking),
+                        ..Default::default()
                     }),
                     Ok(StreamEvent::ToolCall { id, name, arguments }) => {
                         Ok(ChatStreamItem {
-                            content: String::new(),
                             tool_calls: Some(vec![ToolCall {
                                 id: Some(id),
                                 function: crate::core::Function { name, arguments: serde_json::from_str(&arguments).unwrap_or(serde_json::Value::Null) },
                             }]),
-                            done: false,
-                            usage: None,
+                            ..Default::default()
                         })
                     }
-                    Ok(StreamEvent::Usage(usage)) => Ok(ChatStreamItem {
-                        content: String::new(),
-                        tool_calls: None,
-                        done: false,
-                        usage: Some(usage),
-                    }),
-                    Ok(StreamEvent::Done) => {
-                        // Make a quick usage request when stream is done
-                        let usage = get_usage_estimate(&client, &api_key, &base_url, &model, &messages_for_usage).await;
+                    Ok(StreamEvent::Done { generation_id, usage, finish_reason }) => {
+                        let usage = complete_usage(&client, &api_key, &base_url, &model, generation_id, usage).await;
+                        let (content, tool_calls) = fallback_done.unwrap_or_default();
                         
                         Ok(ChatStreamItem {
-                            content: String::new(),
-                            tool_calls: None,
+                            content,
+                            tool_calls,
                             done: true,
                             usage,
+                            finish_reason,
+                            ..Default::default()
                         })
                     },
                     Err(e) => Err(e),
@@ -620,58 +714,121 @@ impl OpenRouterClient {
         Ok(Box::pin(mapped_stream))
     }
 
-    pub async fn send_chat_request_no_stream(
-        &self,
-        messages: &[Message],
-    ) -> Result<(String, Option<Vec<ToolCall>>), Box<dyn std::error::Error>> {
+    /// The body `send_chat_request` would post to `/chat/completions`, without sending it
+    pub async fn build_request(&self, messages: &[Message]) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+        self.build_request_with_options(messages, &GenerationOptions::default()).await
+    }
+
+    pub async fn build_request_with_options(&self, messages: &[Message], options: &GenerationOptions) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+        let (messages_to_send, tools, images) = self.prepare_messages(messages).await?;
+        Ok(request_body(&self.completion_request(&messages_to_send, tools, &images, options), options.extensions.as_ref())?)
+    }
+
+    // Messages with the fallback tool context, the native tools and the images to forward
+    async fn prepare_messages(&self, messages: &[Message]) -> Result<(Vec<Message>, Option<Vec<Arc<Tool>>>, Vec<String>), Box<dyn std::error::Error>> {
+        validate_images(messages)?;
+        // In fallback mode tool results come back as user messages
+        let is_fallback = self.is_fallback_mode().await;
+        let rules = if is_fallback { HistoryRules { pair_tool_calls: false, ..HistoryRules::OPENROUTER } } else { HistoryRules::OPENROUTER };
+        let messages = &*normalize_history(messages, rules)?;
+        let messages = &*fit_images(messages, ImageLimits::OPENROUTER)?;
         let mut messages_to_send = messages.to_vec();
         
         // In fallback mode, inject tool context into the system message
-        let is_fallback = self.is_fallback_mode().await;
-        let tools = if !self.tools.is_empty() && !is_fallback {
-            Some(self.tools.iter().map(|tool| Tool {
-                name: tool.name.clone(),
-                description: tool.description.clone(),
-                parameters: tool.parameters.clone(),
-                function: Box::new(|_| "Not implemented".to_string()),
-            }).collect())
+        let enabled_tools = self.tools.enabled_tools();
+        let tools = if !enabled_tools.is_empty() && !is_fallback {
+            Some(enabled_tools.clone())
         } else {
             None
         };
         
-        if is_fallback && !self.tools.is_empty() {
-            let tool_context = FallbackToolHandler::generate_tool_context(&self.tools);
+        if is_fallback && !enabled_tools.is_empty() {
+            let tool_context = FallbackToolHandler::generate_tool_context_with_strategy(&enabled_tools, self.fallback_strategy);
             
             // Find existing system message or create one
             if let Some(system_msg) = messages_to_send.iter_mut().find(|msg| msg.role == "system") {
                 system_msg.content.push_str(&tool_context);
             } else {
                 // Insert system message at the beginning
-                messages_to_send.insert(0, Message {
-                    role: "system".to_string(),
-                    content: format!("You are a helpful assistant.{}", tool_context),
-                    images: None,
-                    tool_calls: None,
-                });
+                messages_to_send.insert(0, Message::system(format!("You are a helpful assistant.{}", tool_context)));
             }
         }
 
+        // Tool result images are forwarded separately after the tool messages
         let images: Vec<String> = messages
             .iter()
+            .filter(|m| m.role != "tool")
             .filter_map(|m| m.images.as_ref())
             .flatten()
             .cloned()
             .collect();
 
-        let response = self.chat_completion(messages_to_send, tools, images).await?;
-        
+
+        Ok((messages_to_send, tools, images))
+    }
+
+    /// Complete reply from a single JSON response, with the usage and cost of the request
+    pub async fn send_chat_request_no_stream(
+        &self,
+        messages: &[Message],
+    ) -> Result<ChatResponse, Box<dyn std::error::Error>> {
+        let started = Instant::now();
+        let (messages_to_send, tools, images) = self.prepare_messages(messages).await?;
+        let mut request = self.completion_request(&messages_to_send, tools, &images, &GenerationOptions::default());
+        request.stream = Some(false);
+        request.stream_options = None;
+
+        let response = self
+            .request(Method::POST, format!("{}/chat/completions", self.base_url))
+            .header("Content-Type", "application/json")
+            .json(&request)
+            .send_logged(&self.debug)
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(chat_api_error("OpenRouter", status, &error_text));
+        }
+
+        let response: OpenRouterResponse = response.json().await?;
+        let choice = response.choices.into_iter().next().ok_or("No choices in OpenRouter response")?;
+        let message = choice.message.ok_or("No message in OpenRouter response")?;
+        let text = message.content.as_str().unwrap_or_default().to_string();
+        let usage = response.usage.map(|usage| usage.token_usage());
+        let generation_id = Some(response.id).filter(|id| !id.is_empty());
+        let usage = complete_usage(&self.client, &self.api_key, &self.base_url, &self.model, generation_id, usage).await;
+
         // Check for fallback tool calls in the response
-        if is_fallback {
-            let (clean_response, tool_calls) = self.process_fallback_response(&response).await;
-            Ok((clean_response, tool_calls))
+        let (text, tool_calls) = if self.is_fallback_mode().await {
+            self.process_fallback_response(&text).await
         } else {
-            Ok((response, None))
-        }
+            let tool_calls = message.tool_calls.map(|tool_calls| {
+                tool_calls
+                    .into_iter()
+                    .filter_map(|tool_call| {
+                        let function = tool_call.function?;
+                        Some(ToolCall {
+                            id: tool_call.id,
+                            function: crate::core::Function {
+                                name: function.name.unwrap_or_default(),
+                                arguments: function.arguments.and_then(|args| serde_json::from_str(&args).ok()).unwrap_or(serde_json::Value::Null),
+                            },
+                        })
+                    })
+                    .collect()
+            });
+            (text, tool_calls)
+        };
+
+        Ok(ChatResponse {
+            text,
+            tool_calls,
+            usage,
+            finish_reason: choice.finish_reason,
+            latency: started.elapsed(),
+            choices: Vec::new(),
+        })
     }
 
     pub async fn send_chat_request_with_images(
@@ -684,10 +841,7 @@ impl OpenRouterClient {
         if let Some(last_message) = messages_with_images.last_mut() {
             let mut encoded_images = Vec::new();
             for image_path in image_paths {
-                let image_data = tokio::fs::read(&image_path).await
-                    .map_err(|e| format!("Failed to read image file {}: {}", image_path, e))?;
-                let encoded = base64::engine::general_purpose::STANDARD.encode(&image_data);
-                encoded_images.push(encoded);
+                encoded_images.push(encode_image_file(&image_path)?.data);
             }
             last_message.images = Some(encoded_images);
         }
@@ -698,16 +852,13 @@ impl OpenRouterClient {
         &self,
         messages: &[Message],
         image_paths: Vec<String>,
-    ) -> Result<(String, Option<Vec<ToolCall>>), Box<dyn std::error::Error>> {
+    ) -> Result<ChatResponse, Box<dyn std::error::Error>> {
         // For OpenRouter, encode images and add them to the messages
         let mut messages_with_images = messages.to_vec();
         if let Some(last_message) = messages_with_images.last_mut() {
             let mut encoded_images = Vec::new();
             for image_path in image_paths {
-                let image_data = tokio::fs::read(&image_path).await
-                    .map_err(|e| format!("Failed to read image file {}: {}", image_path, e))?;
-                let encoded = base64::engine::general_purpose::STANDARD.encode(&image_data);
-                encoded_images.push(encoded);
+                encoded_images.push(encode_image_file(&image_path)?.data);
             }
             last_message.images = Some(encoded_images);
         }
@@ -724,8 +875,7 @@ impl OpenRouterClient {
         if let Some(last_message) = messages_with_images.last_mut() {
             let mut encoded_images = Vec::new();
             for image_data in images_data {
-                let encoded = base64::engine::general_purpose::STANDARD.encode(&image_data);
-                encoded_images.push(encoded);
+                encoded_images.push(encode_image_bytes(&image_data)?.data);
             }
             last_message.images = Some(encoded_images);
         }
@@ -736,14 +886,13 @@ impl OpenRouterClient {
         &self,
         messages: &[Message],
         images_data: Vec<Vec<u8>>,
-    ) -> Result<(String, Option<Vec<ToolCall>>), Box<dyn std::error::Error>> {
+    ) -> Result<ChatResponse, Box<dyn std::error::Error>> {
         // For OpenRouter, encode images and add them to the messages
         let mut messages_with_images = messages.to_vec();
         if let Some(last_message) = messages_with_images.last_mut() {
             let mut encoded_images = Vec::new();
             for image_data in images_data {
-                let encoded = base64::engine::general_purpose::STANDARD.encode(&image_data);
-                encoded_images.push(encoded);
+                encoded_images.push(encode_image_bytes(&image_data)?.data);
             }
             last_message.images = Some(encoded_images);
         }
@@ -753,73 +902,93 @@ impl OpenRouterClient {
     pub async fn handle_tool_calls(&self, tool_calls: Vec<ToolCall>) -> Vec<Message> {
         let mut tool_responses = Vec::new();
         for tool_call in tool_calls {
-            if let Some(tool) = self
-                .tools
-                .iter()
-                .find(|t| t.name == tool_call.function.name)
-            {
-                let result = (tool.function)(tool_call.function.arguments.clone());
-                
-                // In fallback mode, format tool response as user message with tool context
-                let is_fallback = self.is_fallback_mode().await;
-                let (role, content) = if is_fallback {
-                    ("user".to_string(), format!("Tool response from {}: {}", tool_call.function.name, result))
-                } else {
-                    ("tool".to_string(), result)
-                };
-                
-                tool_responses.push(Message {
-                    role,
-                    content,
-                    images: None,
-                    tool_calls: None,
-                });
-            }
+            // Every call needs a result, even one naming an unknown or disabled tool
+            let output = match self.tools.get(&tool_call.function.name) {
+                Some(tool) => tool.invoke_with_attachments(tool_call.function.arguments.clone()),
+                None => ToolOutput::error(format!("Error: unknown tool '{}'", tool_call.function.name)),
+            };
+            tool_responses.push(self.tool_response_message(&tool_call, output).await);
         }
         tool_responses
     }
 
+    /// Format a tool result as a message for conversation continuation
+    pub async fn tool_response_message(&self, tool_call: &ToolCall, output: ToolOutput) -> Message {
+        // In fallback mode, format tool response as user message with tool context
+        if self.is_fallback_mode().await {
+            return Message {
+                role: "user".to_string(),
+                content: format!("Tool response from {}: {}", tool_call.function.name, output.content),
+                images: if output.images.is_empty() { None } else { Some(output.images) },
+                ..Default::default()
+            };
+        }
+
+        Message::tool_result(tool_call, output)
+    }
+
     pub async fn process_fallback_response(&self, content: &str) -> (String, Option<Vec<ToolCall>>) {
         let is_fallback = self.is_fallback_mode().await;
         if !is_fallback {
             return (content.to_string(), None);
         }
 
-        FallbackToolHandler::process_fallback_response(content)
+        self.debug.fallback_output(content);
+        FallbackToolHandler::process_fallback_response_with_strategy(content, self.fallback_strategy)
+    }
+
+}
+
+fn tool_images_message(images: Vec<String>, detail: Option<ImageDetail>) -> OpenRouterMessage {
+    let mut content_items = vec![json!({
+        "type": "text",
+        "text": "Images returned by the tool calls above:"
+    })];
+    for image in images {
+        content_items.push(image_url_content(&image, detail));
     }
 
+    OpenRouterMessage {
+        role: "user".to_string(),
+        content: json!(content_items),
+        name: None,
+        tool_calls: None,
+        tool_call_id: None,
+        reasoning: None,
+    }
 }
 
 // Helper function to get model pricing from OpenRouter API
 async fn get_model_pricing(
     client: &Client,
+    base_url: &str,
     model: &str,
 ) -> Option<(f64, f64)> {
+    if let Some(pricing) = lookup_pricing(model) {
+        return Some((pricing.input, pricing.output));
+    }
+
     let response = client
-        .get("https://openrouter.ai/api/v1/models")
+        .get(format!("{}/models", base_url))
         .send()
         .await;
 
-    if let Ok(response) = response {
-        if response.status().is_success() {
-            if let Ok(models_response) = response.json::<serde_json::Value>().await {
-                if let Some(data) = models_response["data"].as_array() {
-                    for model_data in data {
-                        if let Some(id) = model_data["id"].as_str() {
-                            if id == model {
-                                if let Some(pricing) = model_data["pricing"].as_object() {
-                                    let prompt_price = pricing["prompt"].as_str()
-                                        .and_then(|s| s.parse::<f64>().ok())
-                                        .unwrap_or(0.0);
-                                    let completion_price = pricing["completion"].as_str()
-                                        .and_then(|s| s.parse::<f64>().ok())
-                                        .unwrap_or(0.0);
-                                    return Some((prompt_price, completion_price));
-                                }
-                            }
-                        }
-                    }
-                }
+    if let Ok(response) = response
+        && response.status().is_success()
+        && let Ok(models_response) = response.json::<serde_json::Value>().await
+        && let Some(data) = models_response["data"].as_array()
+    {
+        for model_data in data {
+            if model_data["id"].as_str() == Some(model)
+                && let Some(pricing) = model_data["pricing"].as_object()
+            {
+                let prompt_price = pricing["prompt"].as_str()
+                    .and_then(|s| s.parse::<f64>().ok())
+                    .unwrap_or(0.0);
+                let completion_price = pricing["completion"].as_str()
+                    .and_then(|s| s.parse::<f64>().ok())
+                    .unwrap_or(0.0);
+                return Some((prompt_price, completion_price));
             }
         }
     }
@@ -827,68 +996,102 @@ async fn get_model_pricing(
     None
 }
 
-// Helper function to get usage information
-async fn get_usage_estimate(
+// Fill in the cost of a finished stream. The usage reported in the stream carries it when usage
+// accounting worked, otherwise it comes from the generation stats or the model's list price
+async fn complete_usage(
     client: &Client,
     api_key: &str,
     base_url: &str,
     model: &str,
-    messages: &[Message],
+    generation_id: Option<String>,
+    usage: Option<TokenUsage>,
 ) -> Option<TokenUsage> {
-    // Convert messages to OpenRouter format
-    let openrouter_messages: Vec<super::types::OpenRouterMessage> = messages
-        .iter()
-        .map(|msg| super::types::OpenRouterMessage {
-            role: msg.role.clone(),
-            content: serde_json::Value::String(msg.content.clone()),
-            name: None,
-            tool_calls: None,
-            tool_call_id: None,
-        })
-        .collect();
-
-    let request = super::types::OpenRouterRequest {
-        model: model.to_string(),
-        messages: openrouter_messages,
-        tools: None,
-        tool_choice: None,
-        stream: Some(false),
-        max_tokens: Some(1), // Minimal tokens since we just want usage
-        temperature: Some(0.7),
-        stream_options: None,
-    };
+    if usage.as_ref().is_some_and(|usage| usage.cost_usd.is_some()) {
+        return usage;
+    }
 
+    if let Some(id) = generation_id {
+        // Stats are recorded asynchronously and may briefly be missing after the stream ends
+        for attempt in 0..3 {
+            if attempt > 0 {
+                tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+            }
+            if let Ok(generation) = fetch_generation(client, api_key, base_url, &id).await && generation.total_cost.is_some() {
+                return Some(generation.usage());
+            }
+        }
+    }
+
+    let mut usage = usage?;
+    if let (Some((prompt_price, completion_price)), Some(prompt_tokens), Some(completion_tokens)) =
+        (get_model_pricing(client, base_url, model).await, usage.prompt_tokens, usage.completion_tokens)
+    {
+        usage.cost_usd = Some(prompt_tokens as f64 * prompt_price + completion_tokens as f64 * completion_price);
+    }
+    Some(usage)
+}
+
+async fn fetch_generation(client: &Client, api_key: &str, base_url: &str, id: &str) -> Result<OpenRouterGeneration, String> {
     let response = client
-        .post(&format!("{}/chat/completions", base_url))
-        .header("Authorization", &format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
-        .json(&request)
+        .get(format!("{}/generation", base_url))
+        .query(&[("id", id)])
+        .header("Authorization", format!("Bearer {}", api_key))
         .send()
-        .await;
+        .await
+        .map_err(|e| e.to_string())?;
 
-    if let Ok(response) = response {
-        if response.status().is_success() {
-            if let Ok(openrouter_response) = response.json::<super::types::OpenRouterResponse>().await {
-                if let Some(usage) = openrouter_response.usage {
-                    // Get pricing information for cost calculation
-                    let cost_usd = if let Some((prompt_price, completion_price)) = get_model_pricing(client, model).await {
-                        let prompt_cost = usage.prompt_tokens as f64 * prompt_price;
-                        let completion_cost = usage.completion_tokens as f64 * completion_price;
-                        Some(prompt_cost + completion_cost)
-                    } else {
-                        None
-                    };
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("OpenRouter generation stats error {}: {}", status, error_text));
+    }
 
-                    return Some(TokenUsage {
-                        prompt_tokens: Some(usage.prompt_tokens),
-                        completion_tokens: Some(usage.completion_tokens),
-                        total_tokens: Some(usage.total_tokens),
-                        cost_usd,
-                    });
+    let generation: OpenRouterGenerationResponse = response.json().await.map_err(|e| e.to_string())?;
+    Ok(generation.data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn dropped_connection_ends_the_stream_with_an_error() {
+        // One chunk of content, then the connection closes before the terminating chunk
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            // Read the whole request, closing with unread bytes resets the connection
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 8192];
+            while let Ok(read) = socket.read(&mut buffer).await {
+                request.extend_from_slice(&buffer[..read]);
+                let text = String::from_utf8_lossy(&request);
+                if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                    let length = head.lines()
+                        .find_map(|line| line.to_ascii_lowercase().strip_prefix("content-length: ").map(|n| n.parse::<usize>().unwrap()));
+                    if body.len() >= length.unwrap_or(0) {
+                        break;
+                    }
                 }
             }
-        }
+            let event = "data: {\"id\":\"gen-1\",\"object\":\"chat.completion.chunk\",\"created\":1718000000,\"model\":\"openai/gpt-4o\",\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"Hello\"},\"finish_reason\":null}]}\n\n";
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n{}\r\n",
+                event.len(),
+                event
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        let mut client = OpenRouterClient::new("key".to_string(), "openai/gpt-4o".to_string());
+        client.set_base_url(&format!("http://{}", address));
+        let items: Vec<_> = client.send_chat_request(&[Message::user("Hi".to_string())]).await.unwrap().collect().await;
+
+        assert_eq!(items.len(), 2, "{:?}", items);
+        assert_eq!(items[0].as_ref().unwrap().content, "Hello");
+        assert!(items[1].is_err(), "{:?}", items[1]);
     }
-    
-    None
-}
\ No newline at end of file
+}
@@ -1,5 +1,7 @@
 pub mod client;
+pub mod stream;
 pub mod types;
 
 pub use client::OpenRouterClient;
+pub use stream::{OpenRouterStreamParser, StreamEvent};
 pub use types::*;
\ No newline at end of file
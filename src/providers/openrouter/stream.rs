@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+
+use crate::core::{TokenUsage, SseParser, StreamParser, FallbackStrategy, FallbackStreamFilter, FallbackToolHandler, DebugLog, ToolCall};
+use super::types::*;
+
+#[derive(Debug, Clone)]
+pub enum StreamEvent {
+    Content(String),
+    Reasoning(String),
+    ToolCall { id: String, name: String, arguments: String },
+    Done { generation_id: Option<String>, usage: Option<TokenUsage>, finish_reason: Option<String> }, // Usage as reported in the stream
+}
+
+/// Parser for the OpenRouter chat completions event stream. Tool calls are reported once
+/// their accumulated arguments form valid JSON, usage with the `Done` event
+pub struct OpenRouterStreamParser {
+    sse: SseParser,
+    accumulating_tool_args: HashMap<usize, String>,
+    tool_call_info: HashMap<usize, (String, String)>,
+    generation_id: Option<String>,
+    usage: Option<TokenUsage>,
+    finish_reason: Option<String>,
+    done: bool,
+}
+
+impl OpenRouterStreamParser {
+    pub fn new() -> Self {
+        Self {
+            sse: SseParser::new(),
+            accumulating_tool_args: HashMap::new(),
+            tool_call_info: HashMap::new(),
+            generation_id: None,
+            usage: None,
+            finish_reason: None,
+            done: false,
+        }
+    }
+
+    fn handle_data(&mut self, data: &str, events: &mut Vec<StreamEvent>) {
+        if data == "[DONE]" {
+            self.done = true;
+            events.push(StreamEvent::Done {
+                generation_id: self.generation_id.take(),
+                usage: self.usage.take(),
+                finish_reason: self.finish_reason.take(),
+            });
+            return;
+        }
+
+        match serde_json::from_str::<OpenRouterResponse>(data) {
+            Ok(response) => {
+                if self.generation_id.is_none() && !response.id.is_empty() {
+                    self.generation_id = Some(response.id.clone());
+                }
+
+                // Usage arrives in the last chunk, reported with the done item
+                if let Some(usage) = &response.usage {
+                    self.usage = Some(usage.token_usage());
+                }
+
+                if let Some(choice) = response.choices.first() {
+                    if let Some(reason) = &choice.finish_reason {
+                        self.finish_reason = Some(reason.clone());
+                    }
+                    if let Some(delta) = &choice.delta {
+                        if let Some(reasoning) = delta.reasoning.as_ref().filter(|reasoning| !reasoning.is_empty()) {
+                            events.push(StreamEvent::Reasoning(reasoning.clone()));
+                        }
+
+                        // Check content
+                        if let Some(content_str) = delta.content.as_str() && !content_str.is_empty() {
+                            events.push(StreamEvent::Content(content_str.to_string()));
+                        }
+
+                        // Check tool calls
+                        match &delta.tool_calls {
+                            Some(tool_calls) => {
+                                for (index, tool_call) in tool_calls.iter().enumerate() {
+
+                                    // Store ID and name when we first see them
+                                    if let Some(id) = &tool_call.id && let Some(function) = &tool_call.function && let Some(name) = &function.name {
+                                        self.tool_call_info.insert(index, (id.clone(), name.clone()));
+                                    }
+
+                                    if let Some(function) = &tool_call.function && let Some(args) = &function.arguments {
+                                        let accumulated = self
+                                            .accumulating_tool_args
+                                            .entry(index)
+                                            .or_default();
+                                        accumulated.push_str(args);
+
+                                        // Try to parse as JSON once the object may be closed, parsing every
+                                        // fragment would make long arguments quadratic
+                                        if !accumulated.trim_end().ends_with('}') {
+                                            continue;
+                                        }
+                                        match serde_json::from_str::<serde_json::Value>(accumulated) {
+                                            Ok(_parsed) => {
+                                                // Use stored ID and name if available
+                                                if let Some((stored_id, stored_name)) = self.tool_call_info.get(&index) {
+                                                    events.push(StreamEvent::ToolCall {
+                                                        id: stored_id.clone(),
+                                                        name: stored_name.clone(),
+                                                        arguments: accumulated.clone(),
+                                                    });
+                                                    self.tool_call_info.remove(&index);
+                                                } else if let Some(id) = &tool_call.id {
+                                                    events.push(StreamEvent::ToolCall {
+                                                        id: id.clone(),
+                                                        name: function.name.clone().unwrap_or_default(),
+                                                        arguments: accumulated.clone(),
+                                                    });
+                                                }
+                                                self.accumulating_tool_args.remove(&index);
+                                            },
+                                            Err(_) => {
+                                                // JSON parsing failed, continue accumulating
+                                            }
+                                        }
+                                    }
+                                }
+                            },
+                            None => {
+                                // No tool calls in this chunk
+                            }
+                        }
+                    }
+                }
+            },
+            Err(_) => {
+                // Failed to parse JSON chunk, skip it
+            }
+        }
+    }
+}
+
+impl Default for OpenRouterStreamParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StreamParser for OpenRouterStreamParser {
+    type Item = StreamEvent;
+
+    fn push(&mut self, chunk: &[u8]) -> Vec<StreamEvent> {
+        let mut events = Vec::new();
+        for event in self.sse.push(chunk) {
+            if self.done {
+                break;
+            }
+            self.handle_data(&event.data, &mut events);
+        }
+        events
+    }
+
+    fn finish(&mut self) -> Vec<StreamEvent> {
+        let mut events = Vec::new();
+        for event in self.sse.finish() {
+            if self.done {
+                break;
+            }
+            self.handle_data(&event.data, &mut events);
+        }
+        events
+    }
+}
+
+/// Hides fallback tool call markup from the streamed content and parses the calls once the
+/// reply is complete
+pub(crate) struct FallbackCalls {
+    strategy: FallbackStrategy,
+    filter: FallbackStreamFilter,
+    raw: String,
+}
+
+impl FallbackCalls {
+    pub(crate) fn new(strategy: FallbackStrategy) -> Self {
+        Self {
+            strategy,
+            filter: FallbackStreamFilter::new(strategy),
+            raw: String::new(),
+        }
+    }
+
+    // The part of `chunk` to show
+    pub(crate) fn content(&mut self, chunk: &str) -> String {
+        self.raw.push_str(chunk);
+        self.filter.process_chunk(chunk)
+    }
+
+    // Held back text and the tool calls of the complete reply
+    pub(crate) fn finish(&mut self, debug: &DebugLog) -> (String, Option<Vec<ToolCall>>) {
+        debug.fallback_output(&self.raw);
+        let tool_calls = FallbackToolHandler::parse_fallback_tool_calls_with_strategy(&self.raw, self.strategy);
+        (self.filter.finish(&self.raw), tool_calls)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::stream::tests::{parse_chunks, parse_every_split};
+
+    // OpenRouter sends `: OPENROUTER PROCESSING` comments while the upstream provider starts
+    const BODY: &str = concat!(
+        ": OPENROUTER PROCESSING\n\n",
+        ": OPENROUTER PROCESSING\n\n",
+        "data: {\"id\":\"gen-1\",\"object\":\"chat.completion.chunk\",\"created\":1718000000,\"model\":\"openai/gpt-4o\",\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"\",\"reasoning\":\"Nutzer fragt…\"},\"finish_reason\":null}]}\n\n",
+        "data: {\"id\":\"gen-1\",\"object\":\"chat.completion.chunk\",\"created\":1718000000,\"model\":\"openai/gpt-4o\",\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"Bonjour, ça va ? 😊\"},\"finish_reason\":null}]}\n\n",
+        "data: {\"id\":\"gen-1\",\"object\":\"chat.completion.chunk\",\"created\":1718000000,\"model\":\"openai/gpt-4o\",\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":null,\"tool_calls\":[{\"id\":\"call_1\",\"type\":\"function\",\"function\":{\"name\":\"lookup\",\"arguments\":\"{\\\"term\\\":\"}}]},\"finish_reason\":null}]}\n\n",
+        "data: {\"id\":\"gen-1\",\"object\":\"chat.completion.chunk\",\"created\":1718000000,\"model\":\"openai/gpt-4o\",\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":null,\"tool_calls\":[{\"function\":{\"arguments\":\"\\\"café\\\"}\"}}]},\"finish_reason\":null}]}\n\n",
+        "data: {\"id\":\"gen-1\",\"object\":\"chat.completion.chunk\",\"created\":1718000000,\"model\":\"openai/gpt-4o\",\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"\"},\"finish_reason\":\"tool_calls\"}],\"usage\":{\"prompt_tokens\":30,\"completion_tokens\":12,\"total_tokens\":42,\"cost\":0.0004}}\n\n",
+        "data: [DONE]\n\n",
+    );
+
+    fn debug_events(events: Vec<StreamEvent>) -> Vec<String> {
+        events.iter().map(|event| format!("{:?}", event)).collect()
+    }
+
+    #[test]
+    fn events_survive_every_split() {
+        let events = parse_every_split(BODY.as_bytes(), OpenRouterStreamParser::new, debug_events);
+        assert_eq!(events.len(), 4, "{:?}", events);
+        assert_eq!(events[0], "Reasoning(\"Nutzer fragt…\")");
+        assert_eq!(events[1], "Content(\"Bonjour, ça va ? 😊\")");
+        assert!(events[2].starts_with("ToolCall { id: \"call_1\", name: \"lookup\""), "{}", events[2]);
+        assert!(events[3].starts_with("Done { generation_id: Some(\"gen-1\")"), "{}", events[3]);
+    }
+
+    #[test]
+    fn crlf_stream_without_done_marker_matches() {
+        let body = BODY.trim_end_matches("data: [DONE]\n\n").replace('\n', "\r\n");
+        let events = parse_every_split(body.as_bytes(), OpenRouterStreamParser::new, debug_events);
+        assert_eq!(events, debug_events(parse_chunks(OpenRouterStreamParser::new(), &[BODY.as_bytes()]))[..3]);
+    }
+}
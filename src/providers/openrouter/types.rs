@@ -1,5 +1,8 @@
 use serde::{Deserialize, Serialize};
 
+use crate::core::{ModelCapabilities, ModelPricing, TokenUsage};
+use crate::providers::openai::ReasoningEffort;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct OpenRouterRequest {
     pub model: String,
@@ -16,6 +19,51 @@ pub struct OpenRouterRequest {
     pub temperature: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stream_options: Option<OpenRouterStreamOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logit_bias: Option<std::collections::HashMap<u32, f32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<OpenRouterUsageOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<OpenRouterReasoning>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<serde_json::Value>,
+}
+
+/// Reasoning settings OpenRouter translates for each model, an effort for o-series and
+/// Grok models or a token budget for Claude and Gemini. Set one of `effort` and `max_tokens`
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct OpenRouterReasoning {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effort: Option<ReasoningEffort>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude: Option<bool>, // Reason without returning the reasoning
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>, // Reasoning at the model's default effort
+}
+
+impl OpenRouterReasoning {
+    pub fn effort(effort: ReasoningEffort) -> Self {
+        Self { effort: Some(effort), ..Default::default() }
+    }
+
+    pub fn max_tokens(max_tokens: u32) -> Self {
+        Self { max_tokens: Some(max_tokens), ..Default::default() }
+    }
+
+    pub fn excluded(mut self) -> Self {
+        self.exclude = Some(true);
+        self
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -23,6 +71,12 @@ pub struct OpenRouterStreamOptions {
     pub include_usage: bool,
 }
 
+/// Usage accounting, `include` adds the billed cost to the usage of the response
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OpenRouterUsageOptions {
+    pub include: bool,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct OpenRouterMessage {
     pub role: String,
@@ -33,6 +87,8 @@ pub struct OpenRouterMessage {
     pub tool_calls: Option<Vec<OpenRouterToolCall>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_call_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<String>, // Reasoning text of streamed deltas and responses
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -95,6 +151,94 @@ pub struct OpenRouterUsage {
     pub prompt_tokens: u32,
     pub completion_tokens: u32,
     pub total_tokens: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost: Option<f64>, // USD, present when usage accounting is requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_tokens_details: Option<OpenRouterPromptTokensDetails>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completion_tokens_details: Option<OpenRouterCompletionTokensDetails>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OpenRouterPromptTokensDetails {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio_tokens: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OpenRouterCompletionTokensDetails {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_tokens: Option<u32>,
+}
+
+impl OpenRouterUsage {
+    /// Token counts and the cost OpenRouter billed, which already has cache discounts applied
+    pub fn token_usage(&self) -> TokenUsage {
+        let prompt_details = self.prompt_tokens_details.as_ref();
+        TokenUsage {
+            prompt_tokens: Some(self.prompt_tokens),
+            completion_tokens: Some(self.completion_tokens),
+            total_tokens: Some(self.total_tokens),
+            cost_usd: self.cost,
+            cached_prompt_tokens: prompt_details.and_then(|details| details.cached_tokens),
+            reasoning_tokens: self.completion_tokens_details.as_ref().and_then(|details| details.reasoning_tokens),
+            audio_tokens: prompt_details.and_then(|details| details.audio_tokens),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct OpenRouterGenerationResponse {
+    pub data: OpenRouterGeneration,
+}
+
+/// Stats of one completion from `/api/v1/generation`, available shortly after it finishes
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OpenRouterGeneration {
+    pub id: String,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub provider_name: Option<String>,
+    #[serde(default)]
+    pub total_cost: Option<f64>, // USD
+    #[serde(default)]
+    pub tokens_prompt: Option<u32>, // Normalized token counts
+    #[serde(default)]
+    pub tokens_completion: Option<u32>,
+    #[serde(default)]
+    pub native_tokens_prompt: Option<u32>, // Counted by the model's own tokenizer, what is billed
+    #[serde(default)]
+    pub native_tokens_completion: Option<u32>,
+    #[serde(default)]
+    pub native_tokens_cached: Option<u32>,
+    #[serde(default)]
+    pub native_tokens_reasoning: Option<u32>,
+    #[serde(default)]
+    pub latency: Option<u64>, // Milliseconds to the first token
+    #[serde(default)]
+    pub generation_time: Option<u64>, // Milliseconds
+    #[serde(default)]
+    pub finish_reason: Option<String>,
+}
+
+impl OpenRouterGeneration {
+    /// Billed token counts and cost
+    pub fn usage(&self) -> TokenUsage {
+        let prompt_tokens = self.native_tokens_prompt.or(self.tokens_prompt);
+        let completion_tokens = self.native_tokens_completion.or(self.tokens_completion);
+        TokenUsage {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens.zip(completion_tokens).map(|(prompt, completion)| prompt + completion),
+            cost_usd: self.total_cost,
+            cached_prompt_tokens: self.native_tokens_cached,
+            reasoning_tokens: self.native_tokens_reasoning,
+            audio_tokens: None,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -120,12 +264,111 @@ pub struct OpenRouterModel {
     pub name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub supported_parameters: Option<Vec<String>>,
-    // We only need id and name, but include the rest as serde_json::Value to avoid parsing errors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_length: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub architecture: Option<OpenRouterArchitecture>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pricing: Option<OpenRouterModelPricing>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_provider: Option<OpenRouterTopProvider>,
+    // Fields we don't use are kept as serde_json::Value to avoid parsing errors
     #[serde(flatten)]
     pub _extra: serde_json::Value,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OpenRouterArchitecture {
+    #[serde(default)]
+    pub input_modalities: Vec<String>,
+    #[serde(default)]
+    pub output_modalities: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokenizer: Option<String>,
+}
+
+/// Prices in USD per token, as decimal strings
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OpenRouterModelPricing {
+    pub prompt: String,
+    pub completion: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_cache_read: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OpenRouterTopProvider {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_length: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_completion_tokens: Option<u32>,
+}
+
+impl OpenRouterModel {
+    pub fn capabilities(&self) -> ModelCapabilities {
+        // Routers such as openrouter/auto price per request with "-1"
+        let pricing = self.pricing.as_ref().and_then(|pricing| {
+            let prompt = pricing.prompt.parse::<f64>().ok().filter(|price| *price >= 0.0)?;
+            let completion = pricing.completion.parse::<f64>().ok().filter(|price| *price >= 0.0)?;
+            let cached_input = pricing.input_cache_read.as_ref().and_then(|price| price.parse::<f64>().ok()).filter(|price| *price >= 0.0);
+            Some(ModelPricing { input: prompt, output: completion, cached_input })
+        });
+
+        // Every model streams through OpenRouter, the listing doesn't mention it
+        let mut supported_parameters = self.supported_parameters.clone().unwrap_or_default();
+        if !supported_parameters.iter().any(|parameter| parameter == "stream") {
+            supported_parameters.push("stream".to_string());
+        }
+
+        ModelCapabilities {
+            context_length: self.context_length.or_else(|| self.top_provider.as_ref()?.context_length),
+            max_output_tokens: self.top_provider.as_ref().and_then(|provider| provider.max_completion_tokens),
+            input_modalities: self.architecture.as_ref().map(|arch| arch.input_modalities.clone()).unwrap_or_default(),
+            output_modalities: self.architecture.as_ref().map(|arch| arch.output_modalities.clone()).unwrap_or_default(),
+            supported_parameters,
+            pricing,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct OpenRouterModelsResponse {
     pub data: Vec<OpenRouterModel>,
-}
\ No newline at end of file
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct OpenRouterCreditsResponse {
+    pub data: OpenRouterCredits,
+}
+
+/// Account balance from `/api/v1/credits`, in USD
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct OpenRouterCredits {
+    pub total_credits: f64,
+    pub total_usage: f64,
+}
+
+impl OpenRouterCredits {
+    pub fn remaining(&self) -> f64 {
+        self.total_credits - self.total_usage
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct OpenRouterKeyResponse {
+    pub data: OpenRouterKey,
+}
+
+/// The API key's limits from `/api/v1/key`, credits in USD
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OpenRouterKey {
+    pub label: Option<String>,
+    pub limit: Option<f64>, // None for keys without a credit limit
+    pub limit_remaining: Option<f64>,
+    #[serde(default)]
+    pub usage: f64,
+    #[serde(default)]
+    pub is_free_tier: bool,
+}
@@ -0,0 +1,56 @@
+use std::error::Error;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// One request a provider client made and the response it got. API keys and other request
+/// headers are never stored
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedExchange {
+    pub method: String,
+    pub path: String, // Relative to the base URL, with the query string
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_body: Option<Value>, // JSON bodies parsed, anything else as a string
+    pub status: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    pub chunks: Vec<String>, // Response body split where the network split it, SSE and NDJSON replay the same way
+}
+
+impl RecordedExchange {
+    pub fn body(&self) -> String {
+        self.chunks.concat()
+    }
+}
+
+/// Exchanges recorded against a provider, in the order they happened
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReplayFixture {
+    pub exchanges: Vec<RecordedExchange>,
+}
+
+impl ReplayFixture {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read fixture {}: {}", path.display(), e))?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+pub(crate) fn parse_body(body: &[u8]) -> Option<Value> {
+    if body.is_empty() {
+        return None;
+    }
+    Some(serde_json::from_slice(body).unwrap_or_else(|_| Value::String(String::from_utf8_lossy(body).into_owned())))
+}
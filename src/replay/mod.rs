@@ -0,0 +1,5 @@
+pub mod fixture;
+pub mod server;
+
+pub use fixture::*;
+pub use server::*;
\ No newline at end of file
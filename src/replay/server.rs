@@ -0,0 +1,316 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use futures_util::StreamExt;
+use reqwest::{Client, Method};
+use serde_json::Value;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+use super::fixture::{parse_body, RecordedExchange, ReplayFixture};
+use crate::core::{StreamParser, Utf8Decoder};
+
+// Hop-by-hop headers and the ones reqwest sets itself when forwarding
+const SKIPPED_HEADERS: &[&str] = &["host", "content-length", "connection", "transfer-encoding", "accept-encoding"];
+
+enum Mode {
+    Record { upstream: String, fixture: PathBuf, client: Client },
+    Replay { pending: VecDeque<RecordedExchange> },
+}
+
+struct State {
+    mode: Mode,
+    exchanges: Vec<RecordedExchange>,
+    mismatches: Vec<String>,
+}
+
+/// Local HTTP server standing in for a provider API. Point a client's base URL at it to record
+/// the provider's real responses into a fixture file, then replay them offline in tests
+///
+/// ```no_run
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// use mono_ai::replay::ReplayServer;
+///
+/// let server = ReplayServer::replay("tests/fixtures/openai_chat.json").await?;
+/// let mut client = mono_ai::MonoAI::openai("unused".to_string(), "gpt-4.1-mini".to_string());
+/// client.set_base_url(&server.base_url());
+/// # Ok(())
+/// # }
+/// ```
+pub struct ReplayServer {
+    base_url: String,
+    state: Arc<Mutex<State>>,
+    task: JoinHandle<()>,
+}
+
+impl ReplayServer {
+    /// Forward every request to `upstream` (e.g. `https://api.openai.com/v1`) and write the
+    /// exchanges to `fixture` as they complete. Responses stream through unchanged
+    pub async fn record(upstream: &str, fixture: impl Into<PathBuf>) -> Result<Self, Box<dyn Error>> {
+        let mode = Mode::Record {
+            upstream: upstream.trim_end_matches('/').to_string(),
+            fixture: fixture.into(),
+            client: Client::new(),
+        };
+        Self::start(mode).await
+    }
+
+    /// Answer requests from a recorded fixture, in order. A request whose method, path or JSON
+    /// body differs from the recording gets a 500 and is listed in `mismatches()`
+    pub async fn replay(fixture: impl Into<PathBuf>) -> Result<Self, Box<dyn Error>> {
+        let fixture = ReplayFixture::load(fixture.into())?;
+        Self::start(Mode::Replay { pending: fixture.exchanges.into() }).await
+    }
+
+    async fn start(mode: Mode) -> Result<Self, Box<dyn Error>> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let base_url = format!("http://{}", listener.local_addr()?);
+        let state = Arc::new(Mutex::new(State { mode, exchanges: Vec::new(), mismatches: Vec::new() }));
+
+        let task_state = state.clone();
+        let task = tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                let state = task_state.clone();
+                tokio::spawn(async move {
+                    let _ = handle_connection(socket, state).await;
+                });
+            }
+        });
+
+        Ok(Self { base_url, state, task })
+    }
+
+    /// URL to pass to `set_base_url`, e.g. `http://127.0.0.1:41234`
+    pub fn base_url(&self) -> String {
+        self.base_url.clone()
+    }
+
+    /// Exchanges recorded or replayed so far, for asserting on the requests a client sent
+    pub fn exchanges(&self) -> Vec<RecordedExchange> {
+        self.state.lock().unwrap().exchanges.clone()
+    }
+
+    /// Requests that did not match the fixture when replaying
+    pub fn mismatches(&self) -> Vec<String> {
+        self.state.lock().unwrap().mismatches.clone()
+    }
+
+    /// Recorded exchanges no request has replayed yet
+    pub fn remaining(&self) -> usize {
+        match &self.state.lock().unwrap().mode {
+            Mode::Replay { pending } => pending.len(),
+            Mode::Record { .. } => 0,
+        }
+    }
+}
+
+impl Drop for ReplayServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+struct IncomingRequest {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+async fn handle_connection(socket: TcpStream, state: Arc<Mutex<State>>) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut reader = BufReader::new(socket);
+    let request = read_request(&mut reader).await?;
+    let mut socket = reader.into_inner();
+
+    let upstream = match &state.lock().unwrap().mode {
+        Mode::Record { upstream, client, .. } => Some((upstream.clone(), client.clone())),
+        Mode::Replay { .. } => None,
+    };
+
+    match upstream {
+        Some((upstream, client)) => record_exchange(&mut socket, request, &upstream, &client, &state).await,
+        None => {
+            let exchange = next_replay(request, &state);
+            match exchange {
+                Ok(exchange) => {
+                    write_head(&mut socket, exchange.status, exchange.content_type.as_deref()).await?;
+                    for chunk in &exchange.chunks {
+                        socket.write_all(chunk.as_bytes()).await?;
+                        socket.flush().await?;
+                    }
+                    Ok(())
+                }
+                Err(message) => {
+                    write_head(&mut socket, 500, Some("text/plain")).await?;
+                    socket.write_all(message.as_bytes()).await?;
+                    Ok(())
+                }
+            }
+        }
+    }
+}
+
+async fn read_request(reader: &mut BufReader<TcpStream>) -> Result<IncomingRequest, Box<dyn Error + Send + Sync>> {
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("").to_string();
+    let path = parts.next().unwrap_or("").to_string();
+
+    let mut headers = Vec::new();
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).await? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((key, value)) = header.split_once(':') {
+            headers.push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    let header = |name: &str| headers.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str());
+    let body = if header("transfer-encoding").is_some_and(|value| value.eq_ignore_ascii_case("chunked")) {
+        read_chunked_body(reader).await?
+    } else {
+        let content_length = header("content-length").and_then(|value| value.parse().ok()).unwrap_or(0);
+        let mut body = vec![0u8; content_length];
+        reader.read_exact(&mut body).await?;
+        body
+    };
+
+    Ok(IncomingRequest { method, path, headers, body })
+}
+
+async fn read_chunked_body(reader: &mut BufReader<TcpStream>) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    let mut body = Vec::new();
+    loop {
+        let mut size_line = String::new();
+        reader.read_line(&mut size_line).await?;
+        let size_hex = size_line.trim().split(';').next().unwrap_or("");
+        let size = usize::from_str_radix(size_hex, 16)?;
+        if size == 0 {
+            // Skip trailers up to the blank line ending the body
+            loop {
+                let mut trailer = String::new();
+                if reader.read_line(&mut trailer).await? == 0 || trailer.trim().is_empty() {
+                    return Ok(body);
+                }
+            }
+        }
+        let start = body.len();
+        body.resize(start + size, 0);
+        reader.read_exact(&mut body[start..]).await?;
+        let mut crlf = [0u8; 2];
+        reader.read_exact(&mut crlf).await?;
+    }
+}
+
+async fn record_exchange(
+    socket: &mut TcpStream,
+    request: IncomingRequest,
+    upstream: &str,
+    client: &Client,
+    state: &Arc<Mutex<State>>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let method = Method::from_bytes(request.method.as_bytes())?;
+    let mut builder = client.request(method, format!("{}{}", upstream, request.path));
+    for (name, value) in &request.headers {
+        if !SKIPPED_HEADERS.contains(&name.to_ascii_lowercase().as_str()) {
+            builder = builder.header(name, value);
+        }
+    }
+
+    let response = match builder.body(request.body.clone()).send().await {
+        Ok(response) => response,
+        Err(e) => {
+            write_head(socket, 502, Some("text/plain")).await?;
+            socket.write_all(format!("Upstream request failed: {}", e).as_bytes()).await?;
+            return Ok(());
+        }
+    };
+
+    let status = response.status().as_u16();
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    write_head(socket, status, content_type.as_deref()).await?;
+
+    let mut chunks = Vec::new();
+    let mut decoder = Utf8Decoder::new();
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        socket.write_all(&chunk).await?;
+        socket.flush().await?;
+        chunks.extend(decoder.push(&chunk));
+    }
+    chunks.extend(decoder.finish());
+
+    let exchange = RecordedExchange {
+        method: request.method,
+        path: request.path,
+        request_body: parse_body(&request.body),
+        status,
+        content_type,
+        chunks,
+    };
+
+    let mut state = state.lock().unwrap();
+    state.exchanges.push(exchange);
+    if let Mode::Record { fixture, .. } = &state.mode {
+        let recorded = ReplayFixture { exchanges: state.exchanges.clone() };
+        recorded.save(fixture).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn next_replay(request: IncomingRequest, state: &Arc<Mutex<State>>) -> Result<RecordedExchange, String> {
+    let mut state = state.lock().unwrap();
+    let expected = match &mut state.mode {
+        Mode::Replay { pending } => pending.pop_front(),
+        Mode::Record { .. } => None,
+    };
+    let body = parse_body(&request.body);
+
+    let result = match expected {
+        None => Err(format!("Replay fixture has no exchange left for {} {}", request.method, request.path)),
+        Some(expected) if expected.method != request.method || expected.path != request.path => Err(format!(
+            "Replay expected {} {} but got {} {}",
+            expected.method, expected.path, request.method, request.path
+        )),
+        // Only JSON bodies are compared, multipart boundaries change on every request
+        Some(expected) if matches!(expected.request_body, Some(Value::Object(_)) | Some(Value::Array(_))) && expected.request_body != body => {
+            Err(format!(
+                "Replay request body for {} {} differs from the recording\nexpected: {}\nactual: {}",
+                request.method,
+                request.path,
+                expected.request_body.unwrap_or(Value::Null),
+                body.unwrap_or(Value::Null)
+            ))
+        }
+        Some(expected) => Ok(expected),
+    };
+
+    match &result {
+        Ok(exchange) => state.exchanges.push(exchange.clone()),
+        Err(message) => state.mismatches.push(message.clone()),
+    }
+    result
+}
+
+async fn write_head(socket: &mut TcpStream, status: u16, content_type: Option<&str>) -> std::io::Result<()> {
+    let reason = reqwest::StatusCode::from_u16(status).ok().and_then(|s| s.canonical_reason()).unwrap_or("");
+    let mut head = format!("HTTP/1.1 {} {}\r\n", status, reason);
+    if let Some(content_type) = content_type {
+        head.push_str(&format!("Content-Type: {}\r\n", content_type));
+    }
+    // The body runs until the connection closes, so chunks reach the client as recorded
+    head.push_str("Cache-Control: no-cache\r\nConnection: close\r\n\r\n");
+    socket.write_all(head.as_bytes()).await?;
+    socket.flush().await
+}
@@ -0,0 +1,168 @@
+use base64::{Engine as _, engine::general_purpose};
+use serde_json::{Value, json};
+
+use crate::core::{Function, TokenUsage, ToolCall};
+use super::session::{VoiceCommand, VoiceConfig, VoiceEvent, VoiceProtocol};
+
+// Messages of the Gemini Live API. Transcripts only arrive in pieces, the final ones are
+// put together here
+pub(crate) struct GeminiLive {
+    model: String,
+    manual_turns: bool,
+    activity_open: bool, // With manual turns, the user's turn has started
+    user_transcript: String,
+    model_transcript: String,
+    usage: Option<TokenUsage>, // Reported before the turn completes
+}
+
+impl GeminiLive {
+    pub(crate) fn new(model: &str) -> Self {
+        let model = if model.starts_with("models/") { model.to_string() } else { format!("models/{}", model) };
+        Self {
+            model,
+            manual_turns: false,
+            activity_open: false,
+            user_transcript: String::new(),
+            model_transcript: String::new(),
+            usage: None,
+        }
+    }
+
+    fn finish_user_transcript(&mut self, events: &mut Vec<VoiceEvent>) {
+        if !self.user_transcript.is_empty() {
+            events.push(VoiceEvent::UserTranscript { text: std::mem::take(&mut self.user_transcript), is_final: true });
+        }
+    }
+
+    fn finish_model_transcript(&mut self, events: &mut Vec<VoiceEvent>) {
+        if !self.model_transcript.is_empty() {
+            events.push(VoiceEvent::ModelTranscript { text: std::mem::take(&mut self.model_transcript), is_final: true });
+        }
+    }
+}
+
+impl VoiceProtocol for GeminiLive {
+    fn setup(&mut self, config: &VoiceConfig) -> Vec<String> {
+        self.manual_turns = config.manual_turns;
+        let mut generation_config = json!({"responseModalities": ["AUDIO"]});
+        if let Some(voice) = &config.voice {
+            generation_config["speechConfig"] = json!({"voiceConfig": {"prebuiltVoiceConfig": {"voiceName": voice}}});
+        }
+
+        let mut setup = json!({
+            "model": self.model,
+            "generationConfig": generation_config,
+            "outputAudioTranscription": {},
+        });
+        if config.transcribe {
+            setup["inputAudioTranscription"] = json!({});
+        }
+        if config.manual_turns {
+            setup["realtimeInputConfig"] = json!({"automaticActivityDetection": {"disabled": true}});
+        }
+        if let Some(instructions) = &config.instructions {
+            setup["systemInstruction"] = json!({"parts": [{"text": instructions}]});
+        }
+        if let Some(tools) = &config.tools {
+            let declarations: Vec<Value> = tools
+                .enabled_tools()
+                .iter()
+                .map(|tool| json!({"name": tool.name, "description": tool.description, "parameters": tool.parameters}))
+                .collect();
+            if !declarations.is_empty() {
+                setup["tools"] = json!([{"functionDeclarations": declarations}]);
+            }
+        }
+        vec![json!({"setup": setup}).to_string()]
+    }
+
+    fn encode(&mut self, command: VoiceCommand) -> Vec<String> {
+        match command {
+            VoiceCommand::Audio(pcm) => {
+                let mut frames = Vec::new();
+                if self.manual_turns && !std::mem::replace(&mut self.activity_open, true) {
+                    frames.push(json!({"realtimeInput": {"activityStart": {}}}).to_string());
+                }
+                let audio = json!({"data": general_purpose::STANDARD.encode(pcm), "mimeType": "audio/pcm;rate=16000"});
+                frames.push(json!({"realtimeInput": {"audio": audio}}).to_string());
+                frames
+            }
+            VoiceCommand::CommitAudio if self.manual_turns => match std::mem::take(&mut self.activity_open) {
+                true => vec![json!({"realtimeInput": {"activityEnd": {}}}).to_string()],
+                false => Vec::new(),
+            },
+            VoiceCommand::CommitAudio => vec![json!({"realtimeInput": {"audioStreamEnd": true}}).to_string()],
+            VoiceCommand::Text(text) => {
+                vec![json!({"clientContent": {"turns": [{"role": "user", "parts": [{"text": text}]}], "turnComplete": true}}).to_string()]
+            }
+            VoiceCommand::ToolResult { call, output } => {
+                let response = json!({"id": call.id, "name": call.function.name, "response": {"output": output}});
+                vec![json!({"toolResponse": {"functionResponses": [response]}}).to_string()]
+            }
+            // No cancel message, replies stop when the user speaks over them
+            VoiceCommand::Cancel => Vec::new(),
+        }
+    }
+
+    fn decode(&mut self, frame: &str) -> Vec<VoiceEvent> {
+        let Ok(message) = serde_json::from_str::<Value>(frame) else {
+            return vec![VoiceEvent::Error(format!("Invalid live message: {}", frame))];
+        };
+
+        let mut events = Vec::new();
+        if message.get("setupComplete").is_some() {
+            events.push(VoiceEvent::Ready);
+        }
+        let usage = &message["usageMetadata"];
+        if usage.is_object() {
+            let count = |field: &str| usage[field].as_u64().map(|tokens| tokens as u32);
+            self.usage = Some(TokenUsage {
+                prompt_tokens: count("promptTokenCount"),
+                completion_tokens: count("responseTokenCount"),
+                total_tokens: count("totalTokenCount"),
+                cached_prompt_tokens: count("cachedContentTokenCount"),
+                reasoning_tokens: count("thoughtsTokenCount"),
+                ..TokenUsage::new()
+            });
+        }
+
+        let content = &message["serverContent"];
+        if let Some(text) = content["inputTranscription"]["text"].as_str().filter(|text| !text.is_empty()) {
+            self.user_transcript.push_str(text);
+            events.push(VoiceEvent::UserTranscript { text: text.to_string(), is_final: false });
+        }
+        // The user's turn is over once the model answers
+        let parts = content["modelTurn"]["parts"].as_array();
+        if parts.is_some() || content["outputTranscription"].is_object() {
+            self.finish_user_transcript(&mut events);
+        }
+        for data in parts.into_iter().flatten().filter_map(|part| part["inlineData"]["data"].as_str()) {
+            match general_purpose::STANDARD.decode(data) {
+                Ok(pcm) => events.push(VoiceEvent::Audio(pcm)),
+                Err(e) => events.push(VoiceEvent::Error(format!("Invalid audio in live message: {}", e))),
+            }
+        }
+        if let Some(text) = content["outputTranscription"]["text"].as_str().filter(|text| !text.is_empty()) {
+            self.model_transcript.push_str(text);
+            events.push(VoiceEvent::ModelTranscript { text: text.to_string(), is_final: false });
+        }
+        if content["interrupted"] == true {
+            self.finish_model_transcript(&mut events);
+            events.push(VoiceEvent::Interrupted);
+        }
+        if content["turnComplete"] == true {
+            self.finish_user_transcript(&mut events);
+            self.finish_model_transcript(&mut events);
+            events.push(VoiceEvent::TurnComplete { usage: self.usage.take() });
+        }
+
+        for call in message["toolCall"]["functionCalls"].as_array().into_iter().flatten() {
+            self.finish_user_transcript(&mut events);
+            events.push(VoiceEvent::ToolCall(ToolCall {
+                id: call["id"].as_str().map(String::from),
+                function: Function { name: call["name"].as_str().unwrap_or_default().to_string(), arguments: call["args"].clone() },
+            }));
+        }
+        events
+    }
+}
@@ -0,0 +1,5 @@
+pub mod session;
+mod openai;
+mod gemini;
+
+pub use session::*;
@@ -0,0 +1,124 @@
+use base64::{Engine as _, engine::general_purpose};
+use serde_json::{Value, json};
+
+use crate::core::{Function, TokenUsage, ToolCall};
+use super::session::{VoiceCommand, VoiceConfig, VoiceEvent, VoiceProtocol};
+
+// Events of the OpenAI Realtime API. Beta event names are accepted alongside the current ones
+#[derive(Default)]
+pub(crate) struct OpenAIRealtime {
+    responding: bool, // A reply is being generated, speech now interrupts it
+}
+
+impl VoiceProtocol for OpenAIRealtime {
+    fn setup(&mut self, config: &VoiceConfig) -> Vec<String> {
+        let format = json!({"type": "audio/pcm", "rate": 24000});
+        let mut input = json!({
+            "format": format,
+            "turn_detection": if config.manual_turns { Value::Null } else { json!({"type": "server_vad"}) },
+        });
+        if config.transcribe {
+            input["transcription"] = json!({"model": "gpt-4o-mini-transcribe"});
+        }
+        let mut output = json!({"format": format});
+        if let Some(voice) = &config.voice {
+            output["voice"] = json!(voice);
+        }
+
+        let mut session = json!({"type": "realtime", "audio": {"input": input, "output": output}});
+        if let Some(instructions) = &config.instructions {
+            session["instructions"] = json!(instructions);
+        }
+        if let Some(tools) = &config.tools {
+            let tools: Vec<Value> = tools
+                .enabled_tools()
+                .iter()
+                .map(|tool| json!({"type": "function", "name": tool.name, "description": tool.description, "parameters": tool.parameters}))
+                .collect();
+            session["tools"] = json!(tools);
+        }
+        vec![json!({"type": "session.update", "session": session}).to_string()]
+    }
+
+    fn encode(&mut self, command: VoiceCommand) -> Vec<String> {
+        let respond = json!({"type": "response.create"}).to_string();
+        match command {
+            VoiceCommand::Audio(pcm) => {
+                vec![json!({"type": "input_audio_buffer.append", "audio": general_purpose::STANDARD.encode(pcm)}).to_string()]
+            }
+            VoiceCommand::CommitAudio => vec![json!({"type": "input_audio_buffer.commit"}).to_string(), respond],
+            VoiceCommand::Text(text) => {
+                let item = json!({"type": "message", "role": "user", "content": [{"type": "input_text", "text": text}]});
+                vec![json!({"type": "conversation.item.create", "item": item}).to_string(), respond]
+            }
+            VoiceCommand::ToolResult { call, output } => {
+                let item = json!({"type": "function_call_output", "call_id": call.id, "output": output});
+                vec![json!({"type": "conversation.item.create", "item": item}).to_string(), respond]
+            }
+            VoiceCommand::Cancel => vec![json!({"type": "response.cancel"}).to_string()],
+        }
+    }
+
+    fn decode(&mut self, frame: &str) -> Vec<VoiceEvent> {
+        let Ok(event) = serde_json::from_str::<Value>(frame) else {
+            return vec![VoiceEvent::Error(format!("Invalid realtime event: {}", frame))];
+        };
+        let text = |field: &str| event[field].as_str().unwrap_or_default().to_string();
+
+        match event["type"].as_str().unwrap_or_default() {
+            "session.updated" => vec![VoiceEvent::Ready],
+            "input_audio_buffer.speech_started" => {
+                // Server turn detection cancels the reply on its own
+                let mut events = vec![VoiceEvent::SpeechStarted];
+                if std::mem::take(&mut self.responding) {
+                    events.push(VoiceEvent::Interrupted);
+                }
+                events
+            }
+            "conversation.item.input_audio_transcription.delta" => vec![VoiceEvent::UserTranscript { text: text("delta"), is_final: false }],
+            "conversation.item.input_audio_transcription.completed" => {
+                vec![VoiceEvent::UserTranscript { text: text("transcript"), is_final: true }]
+            }
+            "response.created" => {
+                self.responding = true;
+                Vec::new()
+            }
+            "response.output_audio.delta" | "response.audio.delta" => match general_purpose::STANDARD.decode(text("delta")) {
+                Ok(pcm) => vec![VoiceEvent::Audio(pcm)],
+                Err(e) => vec![VoiceEvent::Error(format!("Invalid audio in realtime event: {}", e))],
+            },
+            "response.output_audio_transcript.delta" | "response.audio_transcript.delta" => {
+                vec![VoiceEvent::ModelTranscript { text: text("delta"), is_final: false }]
+            }
+            "response.output_audio_transcript.done" | "response.audio_transcript.done" => {
+                vec![VoiceEvent::ModelTranscript { text: text("transcript"), is_final: true }]
+            }
+            "response.output_item.done" if event["item"]["type"] == "function_call" => {
+                let item = &event["item"];
+                let arguments = item["arguments"].as_str().and_then(|arguments| serde_json::from_str(arguments).ok()).unwrap_or(Value::Null);
+                vec![VoiceEvent::ToolCall(ToolCall {
+                    id: item["call_id"].as_str().map(String::from),
+                    function: Function { name: item["name"].as_str().unwrap_or_default().to_string(), arguments },
+                })]
+            }
+            "response.done" => {
+                self.responding = false;
+                let usage = &event["response"]["usage"];
+                let count = |value: &Value| value.as_u64().map(|tokens| tokens as u32);
+                let usage = usage.is_object().then(|| TokenUsage {
+                    prompt_tokens: count(&usage["input_tokens"]),
+                    completion_tokens: count(&usage["output_tokens"]),
+                    total_tokens: count(&usage["total_tokens"]),
+                    cached_prompt_tokens: count(&usage["input_token_details"]["cached_tokens"]),
+                    audio_tokens: count(&usage["input_token_details"]["audio_tokens"])
+                        .zip(count(&usage["output_token_details"]["audio_tokens"]))
+                        .map(|(input, output)| input + output),
+                    ..TokenUsage::new()
+                });
+                vec![VoiceEvent::TurnComplete { usage }]
+            }
+            "error" => vec![VoiceEvent::Error(event["error"]["message"].as_str().unwrap_or(frame).to_string())],
+            _ => Vec::new(),
+        }
+    }
+}
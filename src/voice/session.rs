@@ -0,0 +1,274 @@
+use std::error::Error;
+
+use futures_util::{SinkExt, StreamExt};
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message as Frame;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::handshake::client::Request;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+
+use crate::core::{TokenUsage, ToolCall, ToolRegistry};
+use super::gemini::GeminiLive;
+use super::openai::OpenAIRealtime;
+
+/// What happens in a voice session, in the order the provider reports it
+#[derive(Debug, Clone)]
+pub enum VoiceEvent {
+    /// The provider accepted the configuration
+    Ready,
+    /// The provider heard the user start speaking. Gemini doesn't report it
+    SpeechStarted,
+    /// The user's speech as text. Partial transcripts carry the newly recognized text, the
+    /// final one the whole utterance
+    UserTranscript { text: String, is_final: bool },
+    /// The spoken reply as text, partial and final as for the user
+    ModelTranscript { text: String, is_final: bool },
+    /// PCM16 mono audio of the reply at `VoiceSession::output_sample_rate`
+    Audio(Vec<u8>),
+    /// The user spoke over the reply and the provider stopped it. Drop the reply audio that
+    /// hasn't been played yet
+    Interrupted,
+    /// The model wants a tool run, answer with `VoiceSender::send_tool_result` or
+    /// `VoiceSession::run_tool`
+    ToolCall(ToolCall),
+    /// The model finished its reply
+    TurnComplete { usage: Option<TokenUsage> },
+    /// Error reported by the provider or the connection
+    Error(String),
+}
+
+/// Setup of a voice session, the same for every provider
+#[derive(Clone)]
+pub struct VoiceConfig {
+    pub instructions: Option<String>,
+    pub voice: Option<String>, // Provider voice name, e.g. "alloy" for OpenAI or "Puck" for Gemini
+    pub tools: Option<ToolRegistry>, // Enabled tools are offered to the model
+    pub transcribe: bool, // Send `UserTranscript` events
+    pub manual_turns: bool, // The user's turn ends with `commit_audio` instead of when the provider hears silence
+}
+
+impl Default for VoiceConfig {
+    fn default() -> Self {
+        Self {
+            instructions: None,
+            voice: None,
+            tools: None,
+            transcribe: true,
+            manual_turns: false,
+        }
+    }
+}
+
+impl VoiceConfig {
+    pub fn with_instructions(mut self, instructions: &str) -> Self {
+        self.instructions = Some(instructions.to_string());
+        self
+    }
+
+    pub fn with_voice(mut self, voice: &str) -> Self {
+        self.voice = Some(voice.to_string());
+        self
+    }
+
+    pub fn with_tools(mut self, tools: ToolRegistry) -> Self {
+        self.tools = Some(tools);
+        self
+    }
+
+    pub fn with_transcription(mut self, transcribe: bool) -> Self {
+        self.transcribe = transcribe;
+        self
+    }
+
+    pub fn with_manual_turns(mut self, manual_turns: bool) -> Self {
+        self.manual_turns = manual_turns;
+        self
+    }
+}
+
+// Input of the session, turned into provider messages by the protocol
+pub(crate) enum VoiceCommand {
+    Audio(Vec<u8>),
+    CommitAudio,
+    Text(String),
+    ToolResult { call: ToolCall, output: String },
+    Cancel,
+}
+
+// Translation between the session and one provider's WebSocket messages
+pub(crate) trait VoiceProtocol: Send + 'static {
+    fn setup(&mut self, config: &VoiceConfig) -> Vec<String>;
+    fn encode(&mut self, command: VoiceCommand) -> Vec<String>;
+    fn decode(&mut self, frame: &str) -> Vec<VoiceEvent>;
+}
+
+/// Sends audio, text and tool results into a voice session. Clones share the session, e.g.
+/// to stream the microphone from another task
+#[derive(Clone)]
+pub struct VoiceSender {
+    commands: mpsc::UnboundedSender<VoiceCommand>,
+}
+
+impl VoiceSender {
+    /// PCM16 mono audio at `VoiceSession::input_sample_rate`, in chunks of any size
+    pub fn send_audio(&self, pcm: &[u8]) -> Result<(), Box<dyn Error>> {
+        self.send(VoiceCommand::Audio(pcm.to_vec()))
+    }
+
+    /// End the user's turn with manual turns, the model replies to the audio sent so far
+    pub fn commit_audio(&self) -> Result<(), Box<dyn Error>> {
+        self.send(VoiceCommand::CommitAudio)
+    }
+
+    /// A typed user message, the model replies to it
+    pub fn send_text(&self, text: &str) -> Result<(), Box<dyn Error>> {
+        self.send(VoiceCommand::Text(text.to_string()))
+    }
+
+    pub fn send_tool_result(&self, call: &ToolCall, output: &str) -> Result<(), Box<dyn Error>> {
+        self.send(VoiceCommand::ToolResult { call: call.clone(), output: output.to_string() })
+    }
+
+    /// Stop the reply in progress, e.g. on a push-to-talk button. Gemini only stops when the
+    /// user speaks over it
+    pub fn interrupt(&self) -> Result<(), Box<dyn Error>> {
+        self.send(VoiceCommand::Cancel)
+    }
+
+    fn send(&self, command: VoiceCommand) -> Result<(), Box<dyn Error>> {
+        self.commands.send(command).map_err(|_| "Voice session is closed".into())
+    }
+}
+
+/// Live spoken conversation with a model over a WebSocket: audio goes in as it is recorded,
+/// the reply comes back as audio chunks and transcripts while the user may interrupt it.
+/// Dropping the session closes the connection
+pub struct VoiceSession {
+    sender: VoiceSender,
+    events: mpsc::UnboundedReceiver<VoiceEvent>,
+    tools: Option<ToolRegistry>,
+    input_sample_rate: u32,
+    output_sample_rate: u32,
+}
+
+impl VoiceSession {
+    /// OpenAI Realtime API with a realtime model, e.g. "gpt-realtime". Audio is 24 kHz both ways
+    pub async fn openai(api_key: &str, model: &str, config: VoiceConfig) -> Result<Self, Box<dyn Error>> {
+        Self::openai_at("https://api.openai.com/v1", api_key, model, config).await
+    }
+
+    pub(crate) async fn openai_at(base_url: &str, api_key: &str, model: &str, config: VoiceConfig) -> Result<Self, Box<dyn Error>> {
+        let url = format!("{}/realtime?model={}", base_url.replacen("http", "ws", 1), model);
+        let mut request = url.into_client_request()?;
+        request.headers_mut().insert("Authorization", HeaderValue::from_str(&format!("Bearer {}", api_key))?);
+        Self::connect(request, OpenAIRealtime::default(), config, 24_000, 24_000).await
+    }
+
+    /// Gemini Live API with a live model, e.g. "gemini-live-2.5-flash-preview". Input audio is
+    /// 16 kHz, the reply 24 kHz
+    pub async fn gemini(api_key: &str, model: &str, config: VoiceConfig) -> Result<Self, Box<dyn Error>> {
+        let url = format!(
+            "wss://generativelanguage.googleapis.com/ws/google.ai.generativelanguage.v1beta.GenerativeService.BidiGenerateContent?key={}",
+            api_key
+        );
+        Self::connect(url.into_client_request()?, GeminiLive::new(model), config, 16_000, 24_000).await
+    }
+
+    async fn connect(
+        request: Request,
+        mut protocol: impl VoiceProtocol,
+        config: VoiceConfig,
+        input_sample_rate: u32,
+        output_sample_rate: u32,
+    ) -> Result<Self, Box<dyn Error>> {
+        let (socket, _) = tokio_tungstenite::connect_async(request).await?;
+        let (mut sink, mut stream) = socket.split();
+        for frame in protocol.setup(&config) {
+            sink.send(Frame::text(frame)).await?;
+        }
+
+        let (commands, mut command_receiver) = mpsc::unbounded_channel();
+        let (event_sender, events) = mpsc::unbounded_channel();
+        // Owns the connection until either side closes it or the session is dropped
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    command = command_receiver.recv() => {
+                        let Some(command) = command else {
+                            let _ = sink.close().await;
+                            return;
+                        };
+                        for frame in protocol.encode(command) {
+                            if let Err(e) = sink.send(Frame::text(frame)).await {
+                                let _ = event_sender.send(VoiceEvent::Error(e.to_string()));
+                                return;
+                            }
+                        }
+                    }
+                    frame = stream.next() => {
+                        let text = match frame {
+                            Some(Ok(Frame::Text(text))) => text.to_string(),
+                            // Gemini sends its JSON in binary frames
+                            Some(Ok(Frame::Binary(data))) => String::from_utf8_lossy(&data).to_string(),
+                            Some(Ok(Frame::Close(close))) => {
+                                if let Some(close) = close.filter(|close| !close.reason.is_empty()) {
+                                    let _ = event_sender.send(VoiceEvent::Error(close.reason.to_string()));
+                                }
+                                return;
+                            }
+                            Some(Ok(_)) => continue,
+                            Some(Err(e)) => {
+                                let _ = event_sender.send(VoiceEvent::Error(e.to_string()));
+                                return;
+                            }
+                            None => return,
+                        };
+                        for event in protocol.decode(&text) {
+                            if event_sender.send(event).is_err() {
+                                return;
+                            }
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            sender: VoiceSender { commands },
+            events,
+            tools: config.tools,
+            input_sample_rate,
+            output_sample_rate,
+        })
+    }
+
+    /// Next event, None once the connection is closed
+    pub async fn next_event(&mut self) -> Option<VoiceEvent> {
+        self.events.recv().await
+    }
+
+    pub fn sender(&self) -> &VoiceSender {
+        &self.sender
+    }
+
+    /// Run a tool call with the tools from the config and send the result, the model then
+    /// continues its reply
+    pub async fn run_tool(&self, call: &ToolCall) -> Result<(), Box<dyn Error>> {
+        let output = match self.tools.as_ref().and_then(|tools| tools.get(&call.function.name)) {
+            Some(tool) => {
+                let arguments = call.function.arguments.clone();
+                tokio::task::spawn_blocking(move || tool.invoke(arguments)).await?
+            }
+            None => format!("Unknown tool: {}", call.function.name),
+        };
+        self.sender.send_tool_result(call, &output)
+    }
+
+    pub fn input_sample_rate(&self) -> u32 {
+        self.input_sample_rate
+    }
+
+    pub fn output_sample_rate(&self) -> u32 {
+        self.output_sample_rate
+    }
+}
@@ -0,0 +1,110 @@
+use std::convert::Infallible;
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+use serde_json::json;
+
+use crate::core::ChatStreamItem;
+
+// Comment lines sent while the model is silent, so proxies don't time out the connection
+const DEFAULT_KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// Serve a chat stream as server-sent events from an axum handler, see `chat_event` for the
+/// events. When the browser disconnects axum drops the response, which drops the provider
+/// stream and cancels the upstream request
+pub fn chat_sse<S>(stream: S) -> Sse<impl Stream<Item = Result<Event, Infallible>> + Send>
+where
+    S: Stream<Item = Result<ChatStreamItem, String>> + Send + 'static,
+{
+    chat_sse_with_keep_alive(stream, DEFAULT_KEEP_ALIVE)
+}
+
+pub fn chat_sse_with_keep_alive<S>(stream: S, keep_alive: Duration) -> Sse<impl Stream<Item = Result<Event, Infallible>> + Send>
+where
+    S: Stream<Item = Result<ChatStreamItem, String>> + Send + 'static,
+{
+    let events = until_done(stream).flat_map(|item| futures_util::stream::iter(chat_events(item).into_iter().map(Ok)));
+    Sse::new(events).keep_alive(KeepAlive::new().interval(keep_alive))
+}
+
+/// The events of one stream item:
+/// - `content` `{"content": "...", "index": 1}`, the index only for extra completions
+/// - `thinking` `{"thinking": "..."}`
+/// - `tool_calls` with the `ToolCall`s as a JSON array
+/// - `done` `{"usage": {...}, "guardrail_flags": [...]}` on the final item
+/// - `error` `{"error": "..."}`, the stream ends after it
+pub fn chat_events(item: Result<ChatStreamItem, String>) -> Vec<Event> {
+    let item = match item {
+        Ok(item) => item,
+        Err(e) => return vec![json_event("error", json!({"error": e}))],
+    };
+
+    let mut events = Vec::new();
+    if let Some(thinking) = item.thinking.filter(|thinking| !thinking.is_empty()) {
+        events.push(json_event("thinking", json!({"thinking": thinking})));
+    }
+    if !item.content.is_empty() {
+        let mut data = json!({"content": item.content});
+        if let Some(index) = item.choice_index {
+            data["index"] = json!(index);
+        }
+        events.push(json_event("content", data));
+    }
+    if let Some(tool_calls) = item.tool_calls.filter(|tool_calls| !tool_calls.is_empty()) {
+        events.push(json_event("tool_calls", json!(tool_calls)));
+    }
+    if item.done && item.choice_index.is_none() {
+        let usage = item.usage.map(|usage| {
+            json!({
+                "prompt_tokens": usage.prompt_tokens,
+                "completion_tokens": usage.completion_tokens,
+                "total_tokens": usage.total_tokens,
+                "cost_usd": usage.cost_usd,
+            })
+        });
+        events.push(json_event("done", json!({"usage": usage, "guardrail_flags": item.guardrail_flags})));
+    }
+    events
+}
+
+/// Serve only the reply text as a chunked plain-text body, for clients that don't speak SSE.
+/// Errors end the body early
+pub fn chat_body<S>(stream: S) -> Body
+where
+    S: Stream<Item = Result<ChatStreamItem, String>> + Send + 'static,
+{
+    let chunks = until_done(stream).filter_map(|item| async move {
+        match item {
+            Ok(item) if item.choice_index.is_none() && !item.content.is_empty() => Some(Ok(Bytes::from(item.content))),
+            Ok(_) => None,
+            Err(e) => Some(Err(std::io::Error::other(e))),
+        }
+    });
+    Body::from_stream(chunks)
+}
+
+fn json_event(name: &str, data: serde_json::Value) -> Event {
+    Event::default().event(name).data(data.to_string())
+}
+
+// Ends after the final item or the first error, some providers keep the connection open
+// a little longer and the browser shouldn't wait for it
+fn until_done<S>(stream: S) -> impl Stream<Item = Result<ChatStreamItem, String>> + Send
+where
+    S: Stream<Item = Result<ChatStreamItem, String>> + Send + 'static,
+{
+    futures_util::stream::unfold((Box::pin(stream), false), |(mut stream, finished)| async move {
+        if finished {
+            return None;
+        }
+        let item = stream.next().await?;
+        let finished = match &item {
+            Ok(item) => item.done && item.choice_index.is_none(),
+            Err(_) => true,
+        };
+        Some((item, (stream, finished)))
+    })
+}
@@ -0,0 +1,19 @@
+// Live conformance battery against every provider configured in the environment, see
+// `clients_from_env`. Exits with an error when a check fails
+use mono_ai::eval::{clients_from_env, ConformanceSuite};
+use mono_ai::MonoAI;
+
+#[tokio::main]
+async fn main() {
+    let clients = clients_from_env();
+    if clients.is_empty() {
+        println!("No providers configured, set OPENAI_API_KEY, ANTHROPIC_API_KEY, OPENROUTER_API_KEY or OLLAMA_MODEL");
+        return;
+    }
+    let clients: Vec<&MonoAI> = clients.iter().collect();
+    let matrix = ConformanceSuite::new().run(&clients).await;
+    print!("{}", matrix);
+    if !matrix.all_passed() {
+        std::process::exit(1);
+    }
+}